@@ -0,0 +1,375 @@
+//! Reads options from the file specified with `--config` and merges them into the command line
+//! arguments.
+//!
+//! Only the subset of TOML needed to specify option values is understood: `key = value` pairs with
+//! strings, integers, floats, booleans and single line arrays as values, comments and tables named
+//! after subcommands.
+
+use std::{ffi::OsString, fs, path::PathBuf};
+
+use anyhow::{anyhow, bail, Context, Error};
+use clap::{parser::ValueSource, ArgAction, Command};
+
+/// Keys whose values should not be stored in plain text.
+const SECRET_KEYS: [&str; 2] = ["password", "access_token"];
+
+/// Command line arguments, with the options of the config file inserted right after the
+/// subcommand.
+pub struct MergedArgs {
+    pub args: Vec<OsString>,
+    /// Keys of options holding secrets, which have been read from the config file.
+    pub secrets: Vec<String>,
+}
+
+/// Inserts the options of the config file specified with `--config` into `args`, unless they are
+/// already specified on the command line or by an environment variable. Returns `args` unchanged
+/// if no config file is specified.
+pub fn merge_config_file(cli: Command, args: Vec<OsString>) -> Result<MergedArgs, Error> {
+    let unchanged = |args| MergedArgs {
+        args,
+        secrets: Vec::new(),
+    };
+    // Required arguments may be specified in the config file, so we must not fail on missing ones.
+    // Any other error is reported by the second pass over the merged arguments.
+    let Ok(matches) = cli.clone().ignore_errors(true).try_get_matches_from(&args) else {
+        return Ok(unchanged(args));
+    };
+    let Some(path) = matches.get_one::<PathBuf>("config") else {
+        return Ok(unchanged(args));
+    };
+    let Some((subcommand_name, sub_matches)) = matches.subcommand() else {
+        return Ok(unchanged(args));
+    };
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not read config file '{}'.", path.display()))?;
+    let entries = parse_config(&text)
+        .with_context(|| format!("Invalid config file '{}'.", path.display()))?;
+
+    let subcommand = cli.find_subcommand(subcommand_name).unwrap();
+    let mut injected = Vec::new();
+    let mut secrets = Vec::new();
+    for entry in &entries {
+        if let Some(section) = &entry.section {
+            if cli.find_subcommand(section).is_none() {
+                bail!(
+                    "Unknown table '[{section}]' in config file. Tables must be named after a \
+                    subcommand."
+                );
+            }
+            if section != subcommand_name {
+                continue;
+            }
+        }
+        let Some(arg) = subcommand
+            .get_arguments()
+            .find(|arg| arg.get_id() == entry.key.as_str())
+        else {
+            let known_elsewhere = cli.get_subcommands().any(|other| {
+                other
+                    .get_arguments()
+                    .any(|arg| arg.get_id() == entry.key.as_str())
+            });
+            // Keys outside of a table are shared by all subcommands, which know them.
+            if entry.section.is_none() && known_elsewhere {
+                continue;
+            }
+            bail!(
+                "Unknown key '{}' in config file for subcommand '{subcommand_name}'.",
+                entry.key
+            );
+        };
+        if matches!(
+            sub_matches.value_source(&entry.key),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        let Some(long) = arg.get_long() else {
+            bail!(
+                "'{}' is a positional argument and can not be specified in the config file.",
+                entry.key
+            );
+        };
+        match (arg.get_action(), &entry.value) {
+            (ArgAction::SetTrue, Value::Bool(true)) => injected.push(format!("--{long}")),
+            (ArgAction::SetTrue, Value::Bool(false)) => (),
+            (ArgAction::SetTrue, _) => {
+                bail!("Value of '{}' in config file must be a boolean.", entry.key)
+            }
+            (ArgAction::Append, Value::Array(values)) => {
+                for value in values {
+                    injected.push(format!("--{long}={}", value.to_arg(&entry.key)?));
+                }
+            }
+            (ArgAction::Set | ArgAction::Append, value) => {
+                injected.push(format!("--{long}={}", value.to_arg(&entry.key)?))
+            }
+            _ => bail!("'{}' can not be specified in the config file.", entry.key),
+        }
+        if SECRET_KEYS.contains(&entry.key.as_str()) {
+            secrets.push(entry.key.clone());
+        }
+    }
+
+    // The first argument is the binary, so the first match after it is the subcommand. Global
+    // options do not take values which are named like subcommands.
+    let position = args
+        .iter()
+        .skip(1)
+        .position(|arg| arg == subcommand_name)
+        .unwrap()
+        + 2;
+    let mut merged = args;
+    merged.splice(position..position, injected.into_iter().map(OsString::from));
+    Ok(MergedArgs {
+        args: merged,
+        secrets,
+    })
+}
+
+/// A single `key = value` pair of the config file.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    /// Name of the table the pair is part of. `None` for pairs before the first table.
+    section: Option<String>,
+    /// Name of the option, with dashes replaced by underscores.
+    key: String,
+    value: Value,
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    /// Strings and numbers. Numbers are kept in their textual representation.
+    Text(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn to_arg(&self, key: &str) -> Result<String, Error> {
+        match self {
+            Value::Text(text) => Ok(text.clone()),
+            Value::Bool(value) => Ok(value.to_string()),
+            Value::Array(_) => bail!("'{key}' in config file does not accept multiple values."),
+        }
+    }
+}
+
+fn parse_config(text: &str) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    let mut section = None;
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let Some((name, rest)) = name.split_once(']') else {
+                bail!("Unterminated table header in line {line_number}.");
+            };
+            if !is_comment_or_empty(rest) {
+                bail!("Unexpected characters after table header in line {line_number}.");
+            }
+            section = Some(name.trim().to_owned());
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("Expected `key = value` in line {line_number}.");
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!("Invalid key '{key}' in line {line_number}.");
+        }
+        let (value, rest) = parse_value(value.trim_start())
+            .ok_or_else(|| anyhow!("Invalid value for '{key}' in line {line_number}."))?;
+        if !is_comment_or_empty(rest) {
+            bail!("Unexpected characters after value of '{key}' in line {line_number}.");
+        }
+        entries.push(Entry {
+            section: section.clone(),
+            key: key.replace('-', "_"),
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+fn is_comment_or_empty(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+/// Parses the value at the start of `text` and returns it together with the remaining text.
+fn parse_value(text: &str) -> Option<(Value, &str)> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Some((Value::Text(value), &rest[index + 1..])),
+                '\\' => value.push(match chars.next()?.1 {
+                    '"' => '"',
+                    '\\' => '\\',
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    _ => return None,
+                }),
+                c => value.push(c),
+            }
+        }
+        None
+    } else if let Some(rest) = text.strip_prefix('\'') {
+        let (value, rest) = rest.split_once('\'')?;
+        Some((Value::Text(value.to_owned()), rest))
+    } else if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix(']') {
+                return Some((Value::Array(values), rest));
+            }
+            let (value, remaining) = parse_value(rest)?;
+            values.push(value);
+            rest = remaining.trim_start();
+            if let Some(remaining) = rest.strip_prefix(',') {
+                rest = remaining;
+            } else if !rest.starts_with(']') {
+                return None;
+            }
+        }
+    } else {
+        let end = text
+            .find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#')
+            .unwrap_or(text.len());
+        let (token, rest) = text.split_at(end);
+        let number = token.replace('_', "");
+        let value = match token {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ if token.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+')
+                && number.parse::<f64>().is_ok() =>
+            {
+                Value::Text(number)
+            }
+            _ => return None,
+        };
+        Some((value, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use clap::{value_parser, Arg, ArgAction, Command};
+
+    use super::{merge_config_file, parse_config, Entry, Value};
+
+    #[test]
+    fn command_line_takes_precedence_over_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "batch_size_row = 10\nformat = \"parquet\"\n[query]\nno_empty_file = true\n",
+        )
+        .unwrap();
+        let cli = Command::new("odbc2parquet")
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .subcommand(
+                Command::new("query")
+                    .arg(Arg::new("batch_size_row").long("batch-size-row"))
+                    .arg(Arg::new("format").long("format"))
+                    .arg(
+                        Arg::new("no_empty_file")
+                            .long("no-empty-file")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(Arg::new("query").required(true)),
+            );
+        let args = ["odbc2parquet", "--config", path.to_str().unwrap(), "query"]
+            .into_iter()
+            .chain(["--format", "ndjson", "SELECT 42"])
+            .map(OsString::from)
+            .collect();
+
+        let merged = merge_config_file(cli, args).unwrap();
+
+        let expected: Vec<OsString> = [
+            "odbc2parquet",
+            "--config",
+            path.to_str().unwrap(),
+            "query",
+            "--batch-size-row=10",
+            "--no-empty-file",
+            "--format",
+            "ndjson",
+            "SELECT 42",
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        assert_eq!(expected, merged.args);
+        assert!(merged.secrets.is_empty());
+    }
+
+    #[test]
+    fn parse_config_file() {
+        let text = r#"
+            # Shared by all subcommands
+            connection-string = "Driver={ODBC Driver 18 for SQL Server};Server=localhost;"
+            password = 'sec"ret' # Stored in plain text
+
+            [query]
+            batch_size_row = 10_000
+            column_compression = ["a:gzip", "b:zstd"]
+            no_empty_file = true
+        "#;
+
+        let entries = parse_config(text).unwrap();
+
+        let entry = |section: Option<&str>, key: &str, value| Entry {
+            section: section.map(str::to_owned),
+            key: key.to_owned(),
+            value,
+        };
+        let text = |text: &str| Value::Text(text.to_owned());
+        assert_eq!(
+            vec![
+                entry(
+                    None,
+                    "connection_string",
+                    text("Driver={ODBC Driver 18 for SQL Server};Server=localhost;")
+                ),
+                entry(None, "password", text("sec\"ret")),
+                entry(Some("query"), "batch_size_row", text("10000")),
+                entry(
+                    Some("query"),
+                    "column_compression",
+                    Value::Array(vec![text("a:gzip"), text("b:zstd")])
+                ),
+                entry(Some("query"), "no_empty_file", Value::Bool(true)),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn invalid_config_file() {
+        let error = parse_config("user = \"me\"\nbatch_size_row 10").unwrap_err();
+        assert_eq!("Expected `key = value` in line 2.", error.to_string());
+
+        let error = parse_config("user = me").unwrap_err();
+        assert_eq!("Invalid value for 'user' in line 1.", error.to_string());
+    }
+}
@@ -1,10 +1,56 @@
-use anyhow::{bail, Error};
+use std::{env, path::PathBuf, sync::OnceLock};
+
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Args;
+use log::{debug, warn};
 use odbc_api::{
-    environment, escape_attribute_value, handles::OutputStringBuffer, Connection,
-    ConnectionOptions, DriverCompleteOption,
+    escape_attribute_value,
+    handles::{OutputStringBuffer, State},
+    Connection, ConnectionOptions, DriverCompleteOption, Environment,
+};
+
+use crate::enum_args::{
+    AuthModeArgument, ConnectionPoolingArgument, DriverFamilyArgument, IsolationLevelArgument,
+    PoolMatchingArgument,
 };
 
+/// ODBC environment shared by all connections of the process. Takes the place of the singleton
+/// `odbc_api::environment`, which does not allow to set the matching of the connection pool.
+static ENVIRONMENT: OnceLock<Environment> = OnceLock::new();
+
+/// Creates the ODBC environment used for all connections. Must be called once in `main`, before
+/// any connection is opened. Connection pooling is global for the entire process, so it is enabled
+/// before the environment is allocated, and the matching of the pool is set on the environment
+/// before it is shared.
+pub fn init_environment(
+    pooling: ConnectionPoolingArgument,
+    matching: PoolMatchingArgument,
+) -> Result<&'static Environment, Error> {
+    if !matches!(pooling, ConnectionPoolingArgument::Off) {
+        // Safe, since we set connection pooling before creating the environment or any
+        // connection, and we do it only once.
+        unsafe { Environment::set_connection_pooling(pooling.to_attr())? };
+    }
+    let mut odbc_env = Environment::new()?;
+    if !matches!(pooling, ConnectionPoolingArgument::Off) {
+        odbc_env.set_connection_pooling_matching(matching.to_attr())?;
+    }
+    if ENVIRONMENT.set(odbc_env).is_err() {
+        panic!("ODBC environment must only be initialized once.")
+    }
+    Ok(environment())
+}
+
+/// The ODBC environment created by [`init_environment`].
+pub fn environment() -> &'static Environment {
+    ENVIRONMENT
+        .get()
+        .expect("Environment must already be initialized in main.")
+}
+
+/// Environment variable holding the connection string, if `--connection-string` is not specified.
+const CONNECTION_STRING_ENV: &str = "ODBC2PARQUET_CONNECTION_STRING";
+
 /// Command line arguments used to establish a connection with the ODBC data source
 #[derive(Args)]
 pub struct ConnectOpts {
@@ -13,7 +59,9 @@ pub struct ConnectOpts {
     /// windows platform.
     prompt: bool,
     /// The connection string used to connect to the ODBC data source. Alternatively you may specify
-    /// the ODBC dsn.
+    /// the ODBC dsn. If neither this option nor `ODBC_CONNECTION_STRING` is set, the connection
+    /// string is read from the `ODBC2PARQUET_CONNECTION_STRING` environment variable, which takes
+    /// precedence over the dsn. Passwords are never logged.
     #[arg(long, short = 'c', env = "ODBC_CONNECTION_STRING")]
     connection_string: Option<String>,
     /// ODBC Data Source Name. Either this or the connection string must be specified to identify
@@ -30,35 +78,185 @@ pub struct ConnectOpts {
     /// password is going to be appended at the end of it as the `PWD` attribute.
     #[arg(long, short = 'p', env = "ODBC_PASSWORD", hide_env_values = true)]
     password: Option<String>,
+    /// Read the password from the terminal, if `--user` is given, but no password. The password is
+    /// not echoed, and unlike `--password` it does not show up in the process list or the shell
+    /// history. Fails if standard input is not a terminal. Unlike `--prompt` this does not depend
+    /// on a dialog of the driver. Only supported on unix like platforms.
+    #[arg(long, requires = "user", conflicts_with = "prompt")]
+    prompt_password: bool,
+    /// Number of seconds to wait for the login to the data source to complete, before giving up.
+    /// `0` means no timeout, i.e. wait indefinitely. If not specified the default of the driver is
+    /// used.
+    #[arg(long, conflicts_with = "prompt")]
+    login_timeout_sec: Option<u32>,
+    /// Size of the network packets used to communicate with the data source in KiB. Not every
+    /// driver supports this. If the driver rejects it, a warning is logged and the default packet
+    /// size of the driver is used instead.
+    #[arg(long, conflicts_with = "prompt")]
+    packet_size_kb: Option<u32>,
+    /// Transaction isolation level of the connection, set with a `SET TRANSACTION ISOLATION LEVEL`
+    /// statement right after connecting. `read-uncommitted` has the same semantics as `WITH
+    /// (NOLOCK)` in SQL Server. Queries do not block behind concurrent writers, but may return
+    /// dirty reads, i.e. rows of transactions which are not committed and may still be rolled
+    /// back. If not specified the default of the data source is used.
+    #[arg(long, value_enum)]
+    isolation_level: Option<IsolationLevelArgument>,
+    /// How to authenticate with the data source. `access-token` is not supported yet and is
+    /// rejected. Drivers like the one for Microsoft SQL Server only accept a token as a connection
+    /// attribute (`SQL_COPT_SS_ACCESS_TOKEN`), which must be set before connecting. `odbc-api`
+    /// does not offer a way to do so. Use `Authentication=ActiveDirectoryMsi` in the connection
+    /// string to log in with a managed identity instead.
+    #[arg(long, value_enum, default_value = "password")]
+    auth_mode: AuthModeArgument,
+    /// OAuth access token used to log into the data source, if `--auth-mode` is `access-token`.
+    /// The token is never logged. Reserved for `--auth-mode access-token`, which is not supported
+    /// yet.
+    #[arg(long, env = "ODBC_ACCESS_TOKEN", hide_env_values = true)]
+    access_token: Option<String>,
+    /// Log in with the identity of the current user, rather than with user and password, by
+    /// appending `Trusted_Connection=yes` to the connection string. On Windows the Microsoft SQL
+    /// Server driver uses integrated Windows authentication. On Linux and OS-X it uses Kerberos
+    /// and requires a valid ticket, e.g. obtained with `kinit`. Other drivers likely ignore the
+    /// attribute, in which case a warning is logged and the login proceeds as without this flag.
+    #[arg(long, conflicts_with_all = ["user", "password"])]
+    integrated_security: bool,
+    /// Path to the certificate of the certificate authority (CA) used to verify the certificate of
+    /// the data source, for servers with self-signed or internally issued certificates. Appended to
+    /// the connection string using the attribute of the driver, e.g. `sslrootcert` for PostgreSQL
+    /// or `SSLCA` for MySQL. Whether the certificate is verified at all is still controlled by the
+    /// connection string, e.g. `SSLMode=verify-full` for PostgreSQL.
+    #[arg(long)]
+    tls_ca_cert: Option<PathBuf>,
+    /// Family of the ODBC driver, used to pick the connection string attribute for
+    /// `--tls-ca-cert`. If not specified, it is inferred from the `Driver` attribute of the
+    /// connection string.
+    #[arg(long, value_enum, requires = "tls_ca_cert")]
+    driver_family: Option<DriverFamilyArgument>,
 }
 
 /// Open a database connection using the options provided on the command line.
 pub fn open_connection<'e>(opt: &ConnectOpts) -> Result<Connection<'e>, Error> {
-    let odbc_env = environment().expect("Enviornment must already be initialized in main.");
+    let conn = connect(opt)?;
+    if let Some(isolation_level) = opt.isolation_level {
+        if matches!(isolation_level, IsolationLevelArgument::ReadUncommitted) {
+            warn!("Isolation level 'read-uncommitted' may return dirty reads.");
+        }
+        let statement = format!(
+            "SET TRANSACTION ISOLATION LEVEL {}",
+            isolation_level.to_sql()
+        );
+        conn.execute(&statement, (), None)?;
+    }
+    Ok(conn)
+}
+
+fn connect<'e>(opt: &ConnectOpts) -> Result<Connection<'e>, Error> {
+    let odbc_env = environment();
+    let options = ConnectionOptions {
+        login_timeout_sec: opt.login_timeout_sec,
+        packet_size: opt.packet_size_kb.map(|kb| kb * 1024),
+    };
+    let connection_string = opt.connection_string.clone().or_else(|| {
+        env::var(CONNECTION_STRING_ENV)
+            .ok()
+            .filter(|cs| !cs.is_empty())
+    });
+    let password = match (&opt.password, &opt.user) {
+        (None, Some(user)) if opt.prompt_password => Some(prompt_for_password(user)?),
+        (password, _) => password.clone(),
+    };
+    if let (Some(dsn), Some(_)) = (opt.dsn.as_deref(), &connection_string) {
+        // Clap already rejects `--dsn` together with `--connection-string`, so the connection
+        // string must stem from the environment variable.
+        warn!("Ignoring DSN '{dsn}', since {CONNECTION_STRING_ENV} is set.");
+    }
+    if matches!(opt.auth_mode, AuthModeArgument::AccessToken) {
+        bail!(
+            "`--auth-mode access-token` is not supported. The token must be set as the \
+            `SQL_COPT_SS_ACCESS_TOKEN` connection attribute before connecting, which `odbc-api` \
+            does not allow. Specify `Authentication=ActiveDirectoryMsi` in the connection string \
+            to log in with a managed identity instead."
+        )
+    }
+    if let Some(ca_cert) = &opt.tls_ca_cert {
+        if !ca_cert.is_file() {
+            bail!(
+                "The CA certificate '{}' specified with `--tls-ca-cert` does not exist.",
+                ca_cert.display()
+            )
+        }
+    }
+    // Integrated security and the CA certificate can only be passed in a connection string.
+    let requires_connection_string = opt.integrated_security || opt.tls_ca_cert.is_some();
     // If a data source name has been given, try connecting with that.
-    if let Some(dsn) = opt.dsn.as_deref() {
-        let conn = odbc_env.connect(
-            dsn,
-            opt.user.as_deref().unwrap_or(""),
-            opt.password.as_deref().unwrap_or(""),
-            ConnectionOptions::default(),
-        )?;
+    let connection_string = match (opt.dsn.as_deref(), connection_string) {
+        (Some(dsn), None) if requires_connection_string => {
+            Some(format!("DSN={};", escape_attribute_value(dsn)))
+        }
+        (_, connection_string) => connection_string,
+    };
+    if let (Some(dsn), None) = (opt.dsn.as_deref(), &connection_string) {
+        let conn = with_packet_size_fallback(options, |options| {
+            odbc_env.connect(
+                dsn,
+                opt.user.as_deref().unwrap_or(""),
+                password.as_deref().unwrap_or(""),
+                options,
+            )
+        })
+        .map_err(|e| translate_login_error(e, opt.login_timeout_sec))?;
         return Ok(conn);
     }
 
     // There is no data source name, so at least there must be prompt or a connection string
-    if !opt.prompt && opt.connection_string.is_none() {
+    if !opt.prompt && connection_string.is_none() {
         bail!("Either DSN, connection string or prompt must be specified.")
     }
 
     // Append user and or password to connection string
-    let mut cs = opt.connection_string.clone().unwrap_or_default();
+    let mut cs = connection_string.unwrap_or_default();
     if let Some(uid) = opt.user.as_deref() {
         cs = format!("{}UID={};", cs, &escape_attribute_value(uid));
     }
-    if let Some(pwd) = opt.password.as_deref() {
+    if let Some(pwd) = password.as_deref() {
         cs = format!("{}PWD={};", cs, &escape_attribute_value(pwd));
     }
+    if opt.integrated_security {
+        if !is_mssql_driver(&cs) {
+            warn!(
+                "`--integrated-security` is only known to be supported by the Microsoft SQL Server \
+                drivers. Other drivers likely ignore the `Trusted_Connection` attribute."
+            );
+        }
+        cs = format!("{cs}Trusted_Connection=yes;");
+    }
+    if let Some(ca_cert) = &opt.tls_ca_cert {
+        let Some(family) = opt.driver_family.or_else(|| infer_driver_family(&cs)) else {
+            bail!(
+                "Can not tell the attribute for the CA certificate from the connection string. \
+                Specify the driver with `--driver-family`."
+            )
+        };
+        let Some(ca_cert) = ca_cert.to_str() else {
+            bail!("Path to the CA certificate must be valid UTF-8.")
+        };
+        cs = format!(
+            "{cs}{}={};",
+            family.ca_cert_attribute(),
+            escape_attribute_value(ca_cert)
+        );
+    }
+    debug!("Connection string: {}", redact_password(&cs));
+
+    // Without a prompt we do not need a window handle and can pass connection options, like the
+    // login timeout.
+    if !opt.prompt {
+        let conn = with_packet_size_fallback(options, |options| {
+            odbc_env.connect_with_connection_string(&cs, options)
+        })
+        .map_err(|e| translate_login_error(e, opt.login_timeout_sec))?;
+        return Ok(conn);
+    }
 
     #[cfg(target_os = "windows")]
     let driver_completion = if opt.prompt {
@@ -86,3 +284,216 @@ pub fn open_connection<'e>(opt: &ConnectOpts) -> Result<Connection<'e>, Error> {
     let conn = odbc_env.driver_connect(&cs, &mut completed_connection_string, driver_completion)?;
     Ok(conn)
 }
+
+/// `true` if the `Driver` attribute of the connection string names a Microsoft SQL Server driver.
+fn is_mssql_driver(connection_string: &str) -> bool {
+    driver_name_contains(connection_string, "sql server")
+}
+
+/// Family of the driver named by the `Driver` attribute of the connection string. `None` if there
+/// is no such attribute or the driver is not known.
+fn infer_driver_family(connection_string: &str) -> Option<DriverFamilyArgument> {
+    if driver_name_contains(connection_string, "postgres") {
+        Some(DriverFamilyArgument::Postgres)
+    } else if driver_name_contains(connection_string, "mysql")
+        || driver_name_contains(connection_string, "mariadb")
+    {
+        Some(DriverFamilyArgument::Mysql)
+    } else {
+        None
+    }
+}
+
+/// `true` if the `Driver` attribute of the connection string contains `name`, which must be lower
+/// case. Case is ignored for the attribute.
+fn driver_name_contains(connection_string: &str, name: &str) -> bool {
+    connection_string.split(';').any(|attribute| {
+        attribute.split_once('=').is_some_and(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("Driver") && value.to_ascii_lowercase().contains(name)
+        })
+    })
+}
+
+/// Replaces the values of the `PWD`, `Password` and `Token` attributes of a connection string, so
+/// it can be logged without leaking credentials.
+fn redact_password(connection_string: &str) -> String {
+    let mut redacted = String::new();
+    let mut rest = connection_string;
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=').unwrap_or((rest, ""));
+        // Braced values may contain `;`. Escaped closing braces are doubled (`}}`).
+        let value_len = if value.starts_with('{') {
+            let mut chars = value.char_indices().skip(1).peekable();
+            let mut end = value.len();
+            while let Some((index, c)) = chars.next() {
+                if c == '}' {
+                    if chars.peek().is_some_and(|&(_, next)| next == '}') {
+                        chars.next();
+                    } else {
+                        end = index + 1;
+                        break;
+                    }
+                }
+            }
+            value[end..].find(';').map_or(value.len(), |pos| end + pos)
+        } else {
+            value.find(';').unwrap_or(value.len())
+        };
+        redacted.push_str(key);
+        if key.len() < rest.len() {
+            redacted.push('=');
+            let key = key.trim();
+            if ["PWD", "Password", "Token"]
+                .iter()
+                .any(|secret| key.eq_ignore_ascii_case(secret))
+            {
+                redacted.push_str("***");
+            } else {
+                redacted.push_str(&value[..value_len]);
+            }
+        }
+        rest = &value[value_len..];
+        if let Some(stripped) = rest.strip_prefix(';') {
+            redacted.push(';');
+            rest = stripped;
+        }
+    }
+    redacted
+}
+
+/// Connects with `options`. Should the driver reject the packet size, we connect again using the
+/// default packet size of the driver.
+fn with_packet_size_fallback<'e>(
+    options: ConnectionOptions,
+    connect: impl Fn(ConnectionOptions) -> Result<Connection<'e>, odbc_api::Error>,
+) -> Result<Connection<'e>, odbc_api::Error> {
+    match connect(options) {
+        Err(error) if options.packet_size.is_some() && is_unsupported_attribute(&error) => {
+            warn!("Driver does not support setting the packet size. Using its default.\n{error}");
+            connect(ConnectionOptions {
+                packet_size: None,
+                ..options
+            })
+        }
+        result => result,
+    }
+}
+
+/// `true` if the error indicates that the driver does not support setting an attribute, or does
+/// not support the value it is set to.
+pub fn is_unsupported_attribute(error: &odbc_api::Error) -> bool {
+    match error {
+        odbc_api::Error::Diagnostics { record, .. } => [
+            State(*b"HYC00"),
+            State(*b"HY092"),
+            State::INVALID_ATTRIBUTE_VALUE,
+        ]
+        .contains(&record.state),
+        _ => false,
+    }
+}
+
+/// Tell the user that the login timed out, rather than just forwarding the diagnostics of the
+/// driver.
+fn translate_login_error(error: odbc_api::Error, login_timeout_sec: Option<u32>) -> Error {
+    match (error, login_timeout_sec) {
+        (odbc_api::Error::Diagnostics { record, .. }, Some(timeout))
+            if record.state == State(*b"HYT00") =>
+        {
+            anyhow!(
+                "Login timed out after {timeout} seconds. You can use `--login-timeout-sec` to \
+                specify a larger timeout, or `0` to wait indefinitely.\n{record}"
+            )
+        }
+        (other, _) => other.into(),
+    }
+}
+
+/// Reads the password of `user` from the terminal connected to standard input, without echoing
+/// it.
+#[cfg(unix)]
+fn prompt_for_password(user: &str) -> Result<String, Error> {
+    use std::io::{stdin, IsTerminal};
+
+    if !stdin().is_terminal() {
+        bail!(
+            "`--prompt-password` requires standard input to be a terminal. Pass the password with \
+            the `ODBC_PASSWORD` environment variable instead."
+        )
+    }
+    // Safety: `termios` is a plain C struct, which is initialized by `tcgetattr`.
+    let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .context("Could not read the settings of the terminal.");
+    }
+    let mut silent = original;
+    silent.c_lflag &= !libc::ECHO;
+    // Still echo the newline, so further output starts in a new line.
+    silent.c_lflag |= libc::ECHONL;
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Could not turn off echoing input.");
+    }
+    eprint!("Password for {user}: ");
+    let mut line = String::new();
+    let read = stdin().read_line(&mut line);
+    // Restore echoing, even if reading the password failed.
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original) };
+    read.context("Could not read the password from the terminal.")?;
+    let password = line.strip_suffix('\n').unwrap_or(&line);
+    let password = password.strip_suffix('\r').unwrap_or(password);
+    Ok(password.to_owned())
+}
+
+#[cfg(not(unix))]
+fn prompt_for_password(_user: &str) -> Result<String, Error> {
+    bail!("`--prompt-password` is only supported on unix like platforms.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enum_args::DriverFamilyArgument;
+
+    use super::{infer_driver_family, is_mssql_driver, redact_password};
+
+    #[test]
+    fn redact_password_in_connection_string() {
+        assert_eq!(
+            "Driver={ODBC Driver 18 for SQL Server};Server=localhost;UID=SA;PWD=***;",
+            redact_password(
+                "Driver={ODBC Driver 18 for SQL Server};Server=localhost;UID=SA;PWD=secret;"
+            )
+        );
+        assert_eq!(
+            "DSN=db;password=***;Port=5432",
+            redact_password("DSN=db;password={se;c}}ret};Port=5432")
+        );
+        assert_eq!("DSN=db", redact_password("DSN=db"));
+        assert_eq!(
+            "DSN=db;Authenticator=oauth;Token=***;",
+            redact_password("DSN=db;Authenticator=oauth;Token=abc;")
+        );
+    }
+
+    #[test]
+    fn detect_mssql_driver() {
+        assert!(is_mssql_driver(
+            "Driver={ODBC Driver 18 for SQL Server};Server=localhost;"
+        ));
+        assert!(!is_mssql_driver("Driver={SnowflakeDSIIDriver};Server=x;"));
+        assert!(!is_mssql_driver("DSN=db;"));
+    }
+
+    #[test]
+    fn infer_driver_family_from_connection_string() {
+        assert_eq!(
+            Some(DriverFamilyArgument::Postgres),
+            infer_driver_family("Driver={PostgreSQL UNICODE};Server=localhost;")
+        );
+        assert_eq!(
+            Some(DriverFamilyArgument::Mysql),
+            infer_driver_family("Driver={MariaDB ODBC 3.1 Driver};Server=localhost;")
+        );
+        assert_eq!(None, infer_driver_family("DSN=db;"));
+    }
+}
@@ -1,8 +1,13 @@
 use anyhow::{anyhow, bail, Error};
 use clap::ValueEnum;
+use odbc_api::{
+    sys::{AttrConnectionPooling, AttrCpMatch},
+    DataType,
+};
 use parquet::{
     basic::{BrotliLevel, Compression, Encoding, GzipLevel, ZstdLevel},
     errors::ParquetError,
+    file::properties::WriterVersion,
 };
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -10,6 +15,9 @@ pub enum EncodingArgument {
     System,
     Utf16,
     Auto,
+    // Like `System`, but text which is not valid UTF-8 is expected and replaced silently.
+    #[value(alias = "utf8lossy")]
+    Utf8Lossy,
 }
 
 impl EncodingArgument {
@@ -17,7 +25,7 @@ impl EncodingArgument {
     /// buffers, should be bound.
     pub fn use_utf16(self) -> bool {
         match self {
-            EncodingArgument::System => false,
+            EncodingArgument::System | EncodingArgument::Utf8Lossy => false,
             EncodingArgument::Utf16 => true,
             // Most windows systems do not utilize UTF-8 as their default encoding, yet.
             #[cfg(target_os = "windows")]
@@ -29,6 +37,362 @@ impl EncodingArgument {
     }
 }
 
+/// Connection pooling scheme of the ODBC driver manager.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ConnectionPoolingArgument {
+    Off,
+    DriverAware,
+    OnePerDriver,
+}
+
+impl ConnectionPoolingArgument {
+    pub fn to_attr(self) -> AttrConnectionPooling {
+        match self {
+            ConnectionPoolingArgument::Off => AttrConnectionPooling::Off,
+            ConnectionPoolingArgument::DriverAware => AttrConnectionPooling::DriverAware,
+            ConnectionPoolingArgument::OnePerDriver => AttrConnectionPooling::OnePerDriver,
+        }
+    }
+}
+
+/// How the driver manager picks a connection from the pool.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PoolMatchingArgument {
+    Strict,
+    Relaxed,
+}
+
+impl PoolMatchingArgument {
+    pub fn to_attr(self) -> AttrCpMatch {
+        match self {
+            PoolMatchingArgument::Strict => AttrCpMatch::Strict,
+            PoolMatchingArgument::Relaxed => AttrCpMatch::Relaxed,
+        }
+    }
+}
+
+/// Transaction isolation level of the connection.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum IsolationLevelArgument {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevelArgument {
+    /// Name of the isolation level, as used in the `SET TRANSACTION ISOLATION LEVEL` statement.
+    pub fn to_sql(self) -> &'static str {
+        match self {
+            IsolationLevelArgument::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevelArgument::ReadCommitted => "READ COMMITTED",
+            IsolationLevelArgument::RepeatableRead => "REPEATABLE READ",
+            IsolationLevelArgument::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+/// How to authenticate with the data source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AuthModeArgument {
+    /// User and password, passed as `UID` and `PWD`.
+    Password,
+    /// An OAuth access token, passed with `--access-token`. Not supported yet.
+    AccessToken,
+}
+
+/// Family of the ODBC driver, which determines the names of driver specific connection string
+/// attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DriverFamilyArgument {
+    /// psqlODBC
+    Postgres,
+    /// MySQL and MariaDB Connector/ODBC
+    Mysql,
+}
+
+impl DriverFamilyArgument {
+    /// Connection string attribute holding the path to the certificate of a custom CA.
+    pub fn ca_cert_attribute(self) -> &'static str {
+        match self {
+            DriverFamilyArgument::Postgres => "sslrootcert",
+            DriverFamilyArgument::Mysql => "SSLCA",
+        }
+    }
+}
+
+/// What to do with decimals which do not fit into the target column, specified with
+/// `--numeric-overflow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NumericOverflowArgument {
+    /// Abort the insert.
+    Error,
+    /// Insert NULL instead.
+    Null,
+    /// Insert the largest, or smallest, value the column can hold instead.
+    Clamp,
+}
+
+/// What to do if an inserted row conflicts with an existing row, specified with `--on-conflict`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnConflictArgument {
+    /// Plain `INSERT`. The database reports an error for conflicting rows.
+    Error,
+    /// Keep the existing row.
+    Ignore,
+    /// Overwrite the existing row with the inserted one.
+    Update,
+}
+
+/// SQL dialect used to generate statements which are not covered by standard SQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SqlDialectArgument {
+    Postgresql,
+    Mssql,
+    Mysql,
+}
+
+impl SqlDialectArgument {
+    /// Infers the dialect from the name of the database management system reported by the driver.
+    pub fn from_dbms_name(dbms_name: &str) -> Option<Self> {
+        let dbms_name = dbms_name.to_ascii_lowercase();
+        if dbms_name.contains("postgres") {
+            Some(SqlDialectArgument::Postgresql)
+        } else if dbms_name.contains("sql server") {
+            Some(SqlDialectArgument::Mssql)
+        } else if dbms_name.contains("mysql") || dbms_name.contains("mariadb") {
+            Some(SqlDialectArgument::Mysql)
+        } else {
+            None
+        }
+    }
+}
+
+/// How identifiers are quoted in generated statements, specified with `--quote-identifiers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QuoteIdentifiersArgument {
+    /// Use identifiers as they are.
+    None,
+    /// `"identifier"`
+    Ansi,
+    /// `` `identifier` ``
+    Mysql,
+    /// `[identifier]`
+    Sqlserver,
+}
+
+impl QuoteIdentifiersArgument {
+    /// Quotes a single identifier. Closing quotes within the identifier are escaped by doubling
+    /// them.
+    pub fn quote(self, identifier: &str) -> String {
+        let (open, close) = match self {
+            QuoteIdentifiersArgument::None => return identifier.to_owned(),
+            QuoteIdentifiersArgument::Ansi => ('"', '"'),
+            QuoteIdentifiersArgument::Mysql => ('`', '`'),
+            QuoteIdentifiersArgument::Sqlserver => ('[', ']'),
+        };
+        let escaped = identifier.replace(close, &format!("{close}{close}"));
+        format!("{open}{escaped}{close}")
+    }
+
+    /// Quotes each part of a table name, which may be qualified with a schema. E.g.
+    /// `dbo.Table` becomes `[dbo].[Table]`.
+    pub fn quote_table(self, table: &str) -> String {
+        table
+            .split('.')
+            .map(|part| self.quote(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// Type a positional parameter is bound as, specified with `--param-type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ParamTypeArgument {
+    Text,
+    I32,
+    I64,
+    F64,
+    /// `true`, `false`, `1` or `0`
+    Bool,
+    /// `YYYY-MM-DD`
+    Date,
+    /// `YYYY-MM-DD HH:MM:SS[.FFFFFFFFF]`
+    Timestamp,
+}
+
+/// Granularity of the dates files are split by, specified with `--split-by-date`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DateGranularityArgument {
+    Hour,
+    Day,
+    Month,
+}
+
+impl DateGranularityArgument {
+    /// `chrono` format of the date, as it appears in the file names.
+    pub fn format(self) -> &'static str {
+        match self {
+            // Colons are not allowed in file names on windows.
+            DateGranularityArgument::Hour => "%Y-%m-%dT%H",
+            DateGranularityArgument::Day => "%Y-%m-%d",
+            DateGranularityArgument::Month => "%Y-%m",
+        }
+    }
+}
+
+/// Relational type of a column, as reported by the ODBC driver. Used by `--encoding-for-type` to
+/// select all columns of a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SqlTypeArgument {
+    Char,
+    Varchar,
+    #[value(name = "longvarchar")]
+    LongVarchar,
+    #[value(name = "wchar")]
+    WChar,
+    #[value(name = "wvarchar")]
+    WVarchar,
+    #[value(name = "wlongvarchar")]
+    WLongVarchar,
+    Binary,
+    Varbinary,
+    #[value(name = "longvarbinary")]
+    LongVarbinary,
+    Bit,
+    #[value(name = "tinyint")]
+    TinyInt,
+    #[value(name = "smallint")]
+    SmallInt,
+    Integer,
+    #[value(name = "bigint")]
+    BigInt,
+    Real,
+    Float,
+    Double,
+    Decimal,
+    Numeric,
+    Date,
+    Time,
+    Timestamp,
+}
+
+impl SqlTypeArgument {
+    /// `true` if a column reported with `data_type` is of this type. Lengths and precisions are
+    /// ignored.
+    pub fn matches(self, data_type: &DataType) -> bool {
+        matches!(
+            (self, data_type),
+            (SqlTypeArgument::Char, DataType::Char { .. })
+                | (SqlTypeArgument::Varchar, DataType::Varchar { .. })
+                | (SqlTypeArgument::LongVarchar, DataType::LongVarchar { .. })
+                | (SqlTypeArgument::WChar, DataType::WChar { .. })
+                | (SqlTypeArgument::WVarchar, DataType::WVarchar { .. })
+                | (SqlTypeArgument::WLongVarchar, DataType::WLongVarchar { .. })
+                | (SqlTypeArgument::Binary, DataType::Binary { .. })
+                | (SqlTypeArgument::Varbinary, DataType::Varbinary { .. })
+                | (
+                    SqlTypeArgument::LongVarbinary,
+                    DataType::LongVarbinary { .. }
+                )
+                | (SqlTypeArgument::Bit, DataType::Bit)
+                | (SqlTypeArgument::TinyInt, DataType::TinyInt)
+                | (SqlTypeArgument::SmallInt, DataType::SmallInt)
+                | (SqlTypeArgument::Integer, DataType::Integer)
+                | (SqlTypeArgument::BigInt, DataType::BigInt)
+                | (SqlTypeArgument::Real, DataType::Real)
+                | (SqlTypeArgument::Float, DataType::Float { .. })
+                | (SqlTypeArgument::Double, DataType::Double)
+                | (SqlTypeArgument::Decimal, DataType::Decimal { .. })
+                | (SqlTypeArgument::Numeric, DataType::Numeric { .. })
+                | (SqlTypeArgument::Date, DataType::Date)
+                | (SqlTypeArgument::Time, DataType::Time { .. })
+                | (SqlTypeArgument::Timestamp, DataType::Timestamp { .. })
+        )
+    }
+}
+
+/// Time unit of integers counting the time since the UNIX epoch.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum EpochUnitArgument {
+    Seconds,
+    Millis,
+    Micros,
+}
+
+/// Which whitespace is removed from text values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TrimArgument {
+    None,
+    Trailing,
+    Both,
+}
+
+impl TrimArgument {
+    pub fn apply(self, text: &str) -> &str {
+        match self {
+            TrimArgument::None => text,
+            TrimArgument::Trailing => text.trim_end(),
+            TrimArgument::Both => text.trim(),
+        }
+    }
+}
+
+/// What to do with columns of types, which are not mapped to a specific parquet type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum UnknownTypeArgument {
+    Error,
+    Text,
+    Skip,
+}
+
+/// How the progress of an export is reported.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ProgressArgument {
+    None,
+    Bar,
+    Log,
+}
+
+/// Format of the output written by the query subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormatArgument {
+    Parquet,
+    /// Newline delimited JSON
+    Ndjson,
+    /// Comma separated values
+    Csv,
+}
+
+/// Version of the parquet format used to write the output.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ParquetVersionArgument {
+    #[value(name = "1.0")]
+    V1,
+    #[value(name = "2.0")]
+    V2,
+}
+
+impl ParquetVersionArgument {
+    pub fn to_writer_version(self) -> WriterVersion {
+        match self {
+            ParquetVersionArgument::V1 => WriterVersion::PARQUET_1_0,
+            ParquetVersionArgument::V2 => WriterVersion::PARQUET_2_0,
+        }
+    }
+
+    /// `true` if readers need to support version 2.0 of the parquet format to read columns written
+    /// with this encoding.
+    pub fn requires_v2(encoding: Encoding) -> bool {
+        matches!(
+            encoding,
+            Encoding::DELTA_BINARY_PACKED
+                | Encoding::DELTA_BYTE_ARRAY
+                | Encoding::DELTA_LENGTH_BYTE_ARRAY
+        )
+    }
+}
+
 /// Mirrors parquets `Compression` enum in order to parse it from the command line
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum CompressionVariants {
@@ -95,3 +459,169 @@ pub fn column_encoding_from_str(source: &str) -> Result<(String, Encoding), Erro
     let (name, encoding) = source.split_at(pos);
     Ok((name.to_owned(), encoding_from_str(&encoding[1..])?))
 }
+
+/// Parses `SQLTYPE:ENCODING`. E.g. `varchar:delta-byte-array`.
+pub fn encoding_for_type_from_str(source: &str) -> Result<(SqlTypeArgument, Encoding), Error> {
+    let (sql_type, encoding) = source
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Encoding for type must be passed in format: 'SQLTYPE:ENCODING'"))?;
+    let sql_type = SqlTypeArgument::from_str(sql_type, true).map_err(|_| {
+        let valid: Vec<_> = SqlTypeArgument::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|value| value.get_name().to_owned())
+            .collect();
+        anyhow!(
+            "Sorry, I do not know a SQL type called '{sql_type}'. Valid types are: {}.",
+            valid.join(", ")
+        )
+    })?;
+    Ok((sql_type, encoding_from_str(encoding)?))
+}
+
+/// Parses either `BYTES` or `COLUMN:BYTES`. E.g. `4096` or `description:1000000`. The column name
+/// is `None` if the limit applies to all columns.
+pub fn column_length_limit_from_str(source: &str) -> Result<(Option<String>, usize), Error> {
+    let (name, limit) = match source.rsplit_once(':') {
+        Some((name, limit)) => (Some(name.to_owned()), limit),
+        None => (None, source),
+    };
+    let limit = limit.parse().map_err(|_| {
+        anyhow!("Column length limit must be passed in format: 'BYTES' or 'COLUMN_NAME:BYTES'")
+    })?;
+    Ok((name, limit))
+}
+
+/// Parses `COLUMN:SCALE`. E.g. `price:4`.
+pub fn decimal_rescale_from_str(source: &str) -> Result<(String, i32), Error> {
+    let parsed = source
+        .rsplit_once(':')
+        .filter(|(column, _)| !column.is_empty())
+        .and_then(|(column, scale)| Some((column.to_owned(), scale.parse::<u8>().ok()?.into())));
+    match parsed {
+        Some(rescale) => Ok(rescale),
+        None => bail!("Decimal rescale must be passed in format: 'COLUMN:SCALE'"),
+    }
+}
+
+/// Parses `OLD:NEW`. E.g. `FirstName:first_name`.
+pub fn column_rename_from_str(source: &str) -> Result<(String, String), Error> {
+    match source.rsplit_once(':') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_owned(), new.to_owned()))
+        }
+        _ => bail!("Column rename must be passed in format: 'OLD_NAME:NEW_NAME'"),
+    }
+}
+
+/// Parses `COLUMN:VALUE`. E.g. `source:import`. The value may contain further colons.
+pub fn column_default_from_str(source: &str) -> Result<(String, String), Error> {
+    match source.split_once(':') {
+        Some((column, value)) if !column.is_empty() => Ok((column.to_owned(), value.to_owned())),
+        _ => bail!("Column default must be passed in format: 'COLUMN:VALUE'"),
+    }
+}
+
+/// Parses `INDEX:TYPE`. E.g. `1:i64`. Indices are one based.
+pub fn param_type_from_str(source: &str) -> Result<(usize, ParamTypeArgument), Error> {
+    let format_error = || anyhow!("Parameter type must be passed in format: 'INDEX:TYPE'");
+    let (index, param_type) = source.split_once(':').ok_or_else(format_error)?;
+    let index: usize = index.parse().map_err(|_| format_error())?;
+    if index == 0 {
+        bail!("Parameter indices start at 1.")
+    }
+    let param_type = ParamTypeArgument::from_str(param_type, true).map_err(|_| {
+        let valid: Vec<_> = ParamTypeArgument::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|value| value.get_name().to_owned())
+            .collect();
+        anyhow!(
+            "Sorry, I do not know a parameter type called '{param_type}'. Valid types are: {}.",
+            valid.join(", ")
+        )
+    })?;
+    Ok((index, param_type))
+}
+
+/// Parses `COLUMN:GRANULARITY`. E.g. `created_at:day`.
+pub fn split_by_date_from_str(source: &str) -> Result<(String, DateGranularityArgument), Error> {
+    let format_error = || anyhow!("Split by date must be passed in format: 'COLUMN:GRANULARITY'");
+    let (column, granularity) = source.rsplit_once(':').ok_or_else(format_error)?;
+    if column.is_empty() {
+        return Err(format_error());
+    }
+    let granularity = DateGranularityArgument::from_str(granularity, true).map_err(|_| {
+        anyhow!(
+            "Sorry, I do not know a granularity called '{granularity}'. Valid granularities \
+            are: hour, day, month."
+        )
+    })?;
+    Ok((column.to_owned(), granularity))
+}
+
+/// Parses a single character, used to delimit or quote CSV fields. `\t` is accepted for tab, since
+/// it is hard to pass a literal tab in most shells.
+pub fn csv_char_from_str(source: &str) -> Result<char, Error> {
+    if source == "\\t" {
+        return Ok('\t');
+    }
+    let mut chars = source.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '\n' && c != '\r' => Ok(c),
+        _ => bail!("Expected a single character other than a line break, but got '{source}'."),
+    }
+}
+
+/// Parses file permissions in octal notation, e.g. `0600` or `640`.
+pub fn file_mode_from_str(source: &str) -> Result<u32, Error> {
+    match u32::from_str_radix(source.trim_start_matches("0o"), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => bail!("Expected file permissions in octal notation, e.g. '0600', but got '{source}'."),
+    }
+}
+
+/// Parses `COLUMN[:FPP]`. E.g. `id` or `id:0.01`. The false positive probability defaults to
+/// `0.05`.
+pub fn column_bloom_filter_from_str(source: &str) -> Result<(String, f64), Error> {
+    let (name, fpp) = match source.rsplit_once(':') {
+        Some((name, fpp)) => match fpp.parse::<f64>() {
+            Ok(fpp) => (name, fpp),
+            Err(_) => bail!(
+                "Bloom filter must be passed in format: 'COLUMN_NAME[:FPP]'. '{fpp}' is not a \
+                valid false positive probability."
+            ),
+        },
+        None => (source, 0.05),
+    };
+    if !(fpp > 0. && fpp < 1.) {
+        bail!("False positive probability of bloom filter must be between 0 and 1, got {fpp}.")
+    }
+    Ok((name.to_owned(), fpp))
+}
+
+/// Parses `COLUMN:CODEC[:LEVEL]`. E.g. `image:uncompressed` or `name:zstd:10`.
+pub fn column_compression_from_str(source: &str) -> Result<(String, Compression), Error> {
+    let format_error =
+        || anyhow!("Column compression must be passed in format: 'COLUMN_NAME:CODEC[:LEVEL]'");
+    let (rest, last) = source.rsplit_once(':').ok_or_else(format_error)?;
+    let (name, codec, level) = match last.parse::<u32>() {
+        Ok(level) => {
+            let (name, codec) = rest.rsplit_once(':').ok_or_else(format_error)?;
+            (name, codec, Some(level))
+        }
+        Err(_) => (rest, last, None),
+    };
+    let variant = CompressionVariants::from_str(codec, true).map_err(|_| {
+        let valid: Vec<_> = CompressionVariants::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|value| value.get_name().to_owned())
+            .collect();
+        anyhow!(
+            "Sorry, I do not know a compression codec called '{codec}'. Valid codecs are: {}.",
+            valid.join(", ")
+        )
+    })?;
+    Ok((name.to_owned(), variant.to_compression(level)?))
+}
@@ -26,7 +26,7 @@ pub fn execute(exec_opt: &ExecOpt) -> Result<(), Error> {
     let schema_desc = parquet_metadata.file_metadata().schema_descr();
     let num_columns = schema_desc.num_columns();
 
-    let (statement_text, mapping) = to_positional_arguments(&statement);
+    let (statement_text, mapping) = to_positional_arguments(statement);
     let statement = odbc_conn.prepare(&statement_text)?;
     let column_descriptions_by_name: HashMap<_, _> = (0..num_columns)
         .map(|index_pq| {
@@ -62,7 +62,7 @@ pub fn execute(exec_opt: &ExecOpt) -> Result<(), Error> {
         .into_iter()
         .map(|col_desc| {
             let (buf_desc, odbc_to_parquet) =
-                parquet_type_to_odbc_buffer_desc(col_desc, encoding.use_utf16())?;
+                parquet_type_to_odbc_buffer_desc(col_desc, encoding.use_utf16(), &[], None)?;
             Ok((buf_desc, odbc_to_parquet))
         })
         .collect::<Result<_, Error>>()?;
@@ -106,9 +106,9 @@ pub fn execute(exec_opt: &ExecOpt) -> Result<(), Error> {
         for (column_index, ( _, parquet_to_odbc_col)) in column_buf_desc.iter().enumerate()
         {
             let index_pq = index_mappings[column_index];
-            let column_reader = row_group_reader.get_column_reader(index_pq)?;
+            let mut column_reader = row_group_reader.get_column_reader(index_pq)?;
             let column_writer = odbc_buffer.column_mut(column_index);
-            parquet_to_odbc_col(num_rows, &mut pb, column_reader, column_writer)?;
+            parquet_to_odbc_col(num_rows, &mut pb, &mut column_reader, column_writer)?;
         }
 
         odbc_buffer.execute()?;
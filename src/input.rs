@@ -8,7 +8,7 @@ use std::{
     ops::{Add, DivAssign, MulAssign},
 };
 
-use anyhow::{bail, Error};
+use anyhow::{anyhow, bail, Error};
 use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike};
 use num_traits::{FromPrimitive, PrimInt, Signed, ToPrimitive};
 use odbc_api::{
@@ -18,7 +18,7 @@ use odbc_api::{
 };
 use parquet::{
     basic::{ConvertedType, Type as PhysicalType},
-    column::reader::ColumnReader,
+    column::reader::{ColumnReader, ColumnReaderImpl},
     data_type::{
         AsBytes, BoolType, ByteArrayType, DataType, DoubleType, FixedLenByteArrayType, FloatType,
         Int32Type, Int64Type,
@@ -26,7 +26,10 @@ use parquet::{
     schema::types::ColumnDescriptor,
 };
 
-use crate::parquet_buffer::{BufferedDataType, ParquetBuffer};
+use crate::{
+    enum_args::NumericOverflowArgument,
+    parquet_buffer::{BufferedDataType, ParquetBuffer},
+};
 
 /// Message we emmit if we hit a code path we expected to be unreachable.
 const BUG: &str = "This is not supposed to happen. Please open a Bug at \
@@ -34,17 +37,33 @@ const BUG: &str = "This is not supposed to happen. Please open a Bug at \
 
 /// Function extracting the contents of a single column out of the Parquet column reader and into an
 /// ODBC buffer.
-type FnParquetToOdbcCol =
-    dyn Fn(usize, &mut ParquetBuffer, ColumnReader, AnySliceMut) -> Result<(), Error>;
+pub type FnParquetToOdbcCol =
+    dyn Fn(usize, &mut ParquetBuffer, &mut ColumnReader, AnySliceMut) -> Result<(), Error>;
+
+/// Precision and scale of the column a decimal is inserted into, and what to do with values which
+/// do not fit.
+#[derive(Clone, Copy)]
+pub struct DecimalTarget {
+    pub precision: usize,
+    pub scale: usize,
+    pub overflow: NumericOverflowArgument,
+}
 
 /// Takes a parquet column descriptor and chooses a strategy for inserting the column into the
 /// database.
+///
+/// Values of text columns which are equal to any of the `null_strings` are inserted as NULL.
+/// Decimals are checked against `decimal_target`, if known.
 pub fn parquet_type_to_odbc_buffer_desc(
     col_desc: &ColumnDescriptor,
     use_utf16: bool,
+    null_strings: &[&str],
+    decimal_target: Option<DecimalTarget>,
 ) -> Result<(BufferDesc, Box<FnParquetToOdbcCol>), Error> {
     // Column name. Used in error messages.
     let name = col_desc.self_type().name();
+    let owned_name = name.to_owned();
+    let fit = move |n: i128, scale: usize| fit_decimal(n, scale, decimal_target, &owned_name);
     if !col_desc.self_type().is_primitive() {
         bail!(
             "Sorry, this tool is only able to insert primitive types. Column '{}' is not a \
@@ -117,6 +136,10 @@ pub fn parquet_type_to_odbc_buffer_desc(
                     BufferDesc::Text { max_str_len },
                     Int32Type::map_to_text(
                         move |&n, index, odbc_buf| {
+                            let Some(n) = fit(n.into(), scale)? else {
+                                odbc_buf.set_cell(index, None);
+                                return Ok(());
+                            };
                             let buf = odbc_buf.set_mut(index, max_str_len);
                             write_integer_as_decimal(n, precision, scale, buf);
                             Ok(())
@@ -204,6 +227,10 @@ pub fn parquet_type_to_odbc_buffer_desc(
                     BufferDesc::Text { max_str_len },
                     Int64Type::map_to_text(
                         move |&n, index, odbc_buf| {
+                            let Some(n) = fit(n.into(), scale)? else {
+                                odbc_buf.set_cell(index, None);
+                                return Ok(());
+                            };
                             let buf = odbc_buf.set_mut(index, max_str_len);
                             write_integer_as_decimal(n, precision, scale, buf);
                             Ok(())
@@ -239,11 +266,20 @@ pub fn parquet_type_to_odbc_buffer_desc(
                 ConvertedType::UTF8 | ConvertedType::JSON | ConvertedType::ENUM => {
                     // Start small. We rebind the buffer as we encounter larger values in the file.
                     let max_str_len = 1;
+                    let null_strings: Vec<Vec<u8>> = null_strings
+                        .iter()
+                        .map(|null| null.as_bytes().to_owned())
+                        .collect();
+                    let is_null = move |text: &[u8]| null_strings.iter().any(|null| null == text);
                     if use_utf16 {
                         (
                             BufferDesc::WText { max_str_len },
                             ByteArrayType::map_to_wtext(
                                 move |text, index, odbc_buf| {
+                                    if is_null(text.data()) {
+                                        odbc_buf.set_cell(index, None);
+                                        return Ok(());
+                                    }
                                     // This allocation is not strictly necessary, we could just as
                                     // write directly into the buffer or at least preallocate the
                                     // U16String.
@@ -262,7 +298,11 @@ pub fn parquet_type_to_odbc_buffer_desc(
                         (
                             BufferDesc::Text { max_str_len },
                             ByteArrayType::map_to_text(
-                                |text, index, odbc_buf| {
+                                move |text, index, odbc_buf| {
+                                    if is_null(text.data()) {
+                                        odbc_buf.set_cell(index, None);
+                                        return Ok(());
+                                    }
                                     odbc_buf.ensure_max_element_length(text.data().len(), index)?;
                                     odbc_buf.set_cell(index, Some(text.data()));
                                     Ok(())
@@ -302,6 +342,10 @@ pub fn parquet_type_to_odbc_buffer_desc(
                         ByteArrayType::map_to_text(
                             move |bytes, index, odbc_buf| {
                                 let n = i128_from_be_slice(bytes.as_bytes());
+                                let Some(n) = fit(n, scale)? else {
+                                    odbc_buf.set_cell(index, None);
+                                    return Ok(());
+                                };
                                 let text = odbc_buf.set_mut(index, max_str_len);
                                 write_integer_as_decimal(n, precision, scale, text);
                                 Ok(())
@@ -345,6 +389,10 @@ pub fn parquet_type_to_odbc_buffer_desc(
                         FixedLenByteArrayType::map_to_text(
                             move |bytes, index, odbc_buf| {
                                 let n = i128_from_be_slice(bytes.as_bytes());
+                                let Some(n) = fit(n, scale)? else {
+                                    odbc_buf.set_cell(index, None);
+                                    return Ok(());
+                                };
                                 let text = odbc_buf.set_mut(index, max_str_len);
                                 write_integer_as_decimal(n, precision, scale, text);
                                 Ok(())
@@ -368,7 +416,7 @@ pub fn parquet_type_to_odbc_buffer_desc(
 
 /// We extend the parquet `DataType` to start of our builder pattern. These builders constructs the
 /// functors we use to transfer data from Parquet to ODBC.
-trait InserterBuilderStart: DataType + Sized {
+trait InserterBuilderStart: DataType + ColumnReaderMut + Sized {
     fn map_to_text<F>(f: F, nullable: bool) -> Box<FnParquetToOdbcCol>
     where
         F: Fn(&Self::T, usize, &mut TextColumnSliceMut<u8>) -> Result<(), Error> + 'static,
@@ -378,11 +426,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = Text::unwrap_writer_optional(column_writer);
-                    let it = pb.read_optional(&mut cr, num_rows)?;
+                    let it = pb.read_optional(cr, num_rows)?;
                     for (index, opt) in it.enumerate() {
                         if let Some(value) = opt {
                             f(value, index, &mut cw)?;
@@ -397,11 +445,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = Text::unwrap_writer_optional(column_writer);
-                    let values = pb.read_required(&mut cr, num_rows)?;
+                    let values = pb.read_required(cr, num_rows)?;
                     for (index, value) in values.iter().enumerate() {
                         f(value, index, &mut cw)?;
                     }
@@ -420,11 +468,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = WText::unwrap_writer_optional(column_writer);
-                    let it = pb.read_optional(&mut cr, num_rows)?;
+                    let it = pb.read_optional(cr, num_rows)?;
                     for (index, opt) in it.enumerate() {
                         if let Some(value) = opt {
                             f(value, index, &mut cw)?;
@@ -439,11 +487,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = WText::unwrap_writer_optional(column_writer);
-                    let values = pb.read_required(&mut cr, num_rows)?;
+                    let values = pb.read_required(cr, num_rows)?;
                     for (index, value) in values.iter().enumerate() {
                         f(value, index, &mut cw)?;
                     }
@@ -462,11 +510,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = Binary::unwrap_writer_optional(column_writer);
-                    let it = pb.read_optional(&mut cr, num_rows)?;
+                    let it = pb.read_optional(cr, num_rows)?;
                     for (index, value) in it.enumerate() {
                         if let Some(bytes) = value {
                             f(bytes, index, &mut cw)?;
@@ -481,11 +529,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = Binary::unwrap_writer_optional(column_writer);
-                    let values = pb.read_required(&mut cr, num_rows)?;
+                    let values = pb.read_required(cr, num_rows)?;
                     for (index, value) in values.iter().enumerate() {
                         f(value, index, &mut cw)?;
                     }
@@ -510,11 +558,11 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 |num_rows: usize,
                  pb: &mut ParquetBuffer,
-                 column_reader: ColumnReader,
+                 column_reader: &mut ColumnReader,
                  column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = Self::unwrap_writer_optional(column_writer);
-                    let it = pb.read_optional(&mut cr, num_rows)?;
+                    let it = pb.read_optional(cr, num_rows)?;
                     cw.write(it.map(|opt| opt.copied()));
                     Ok(())
                 },
@@ -523,15 +571,15 @@ trait InserterBuilderStart: DataType + Sized {
             Box::new(
                 |num_rows: usize,
                  pb: &mut ParquetBuffer,
-                 column_reader: ColumnReader,
+                 column_reader: &mut ColumnReader,
                  column_writer: AnySliceMut| {
-                    let mut cr = Self::get_column_reader(column_reader).expect(BUG);
+                    let cr = Self::column_reader_mut(column_reader).expect(BUG);
                     let target = Self::unwrap_writer_required(column_writer);
 
                     // We could use the identity operation, but cr.records wants to borrow a Vec to
                     // eventually resize it. So we have to use the parquet buffer, even though this
                     // is an identity operation and no actual conversion is happening.
-                    let values = pb.read_required(&mut cr, num_rows)?;
+                    let values = pb.read_required(cr, num_rows)?;
 
                     // While parquet-rs does not fill the ODBC buffer directly we can still just
                     // copy the identical representations from one buffer to the other.
@@ -551,7 +599,7 @@ trait InserterBuilderStart: DataType + Sized {
     }
 }
 
-impl<T> InserterBuilderStart for T where T: DataType {}
+impl<T> InserterBuilderStart for T where T: DataType + ColumnReaderMut {}
 
 struct ParquetToOdbcBuilder<Pdt: ?Sized, Odt> {
     pdt: PhantomData<Pdt>,
@@ -567,17 +615,18 @@ impl<Pdt, Odt> ParquetToOdbcBuilder<Pdt, Odt> {
         Pdt: DataType,
         Odt: for<'a> OdbcDataType<'a, Required = &'a mut [E], Optional = NullableSliceMut<'a, E>>,
         F: Fn(&Pdt::T) -> E + 'static,
+        Pdt: ColumnReaderMut,
         Pdt::T: BufferedDataType,
     {
         if nullable {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Pdt::get_column_reader(column_reader).expect(BUG);
+                    let cr = Pdt::column_reader_mut(column_reader).expect(BUG);
                     let mut cw = Odt::unwrap_writer_optional(column_writer);
-                    let it = pb.read_optional(&mut cr, num_rows)?;
+                    let it = pb.read_optional(cr, num_rows)?;
                     cw.write(it.map(|opt| opt.map(&f)));
                     Ok(())
                 },
@@ -586,11 +635,11 @@ impl<Pdt, Odt> ParquetToOdbcBuilder<Pdt, Odt> {
             Box::new(
                 move |num_rows: usize,
                       pb: &mut ParquetBuffer,
-                      column_reader: ColumnReader,
+                      column_reader: &mut ColumnReader,
                       column_writer: AnySliceMut| {
-                    let mut cr = Pdt::get_column_reader(column_reader).expect(BUG);
+                    let cr = Pdt::column_reader_mut(column_reader).expect(BUG);
                     let dest = Odt::unwrap_writer_required(column_writer);
-                    let source = pb.read_required(&mut cr, num_rows)?;
+                    let source = pb.read_required(cr, num_rows)?;
                     for (index, value) in source.iter().enumerate() {
                         dest[index] = f(value)
                     }
@@ -601,6 +650,35 @@ impl<Pdt, Odt> ParquetToOdbcBuilder<Pdt, Odt> {
     }
 }
 
+/// Access to the typed column reader, without consuming the [`ColumnReader`]. This allows for
+/// reading the values of a row group in several chunks.
+trait ColumnReaderMut: DataType + Sized {
+    fn column_reader_mut(column_reader: &mut ColumnReader) -> Option<&mut ColumnReaderImpl<Self>>;
+}
+
+macro_rules! impl_column_reader_mut {
+    ($data_type:ty, $variant:ident) => {
+        impl ColumnReaderMut for $data_type {
+            fn column_reader_mut(
+                column_reader: &mut ColumnReader,
+            ) -> Option<&mut ColumnReaderImpl<Self>> {
+                match column_reader {
+                    ColumnReader::$variant(column_reader) => Some(column_reader),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_column_reader_mut!(BoolType, BoolColumnReader);
+impl_column_reader_mut!(Int32Type, Int32ColumnReader);
+impl_column_reader_mut!(Int64Type, Int64ColumnReader);
+impl_column_reader_mut!(FloatType, FloatColumnReader);
+impl_column_reader_mut!(DoubleType, DoubleColumnReader);
+impl_column_reader_mut!(ByteArrayType, ByteArrayColumnReader);
+impl_column_reader_mut!(FixedLenByteArrayType, FixedLenByteArrayColumnReader);
+
 trait OdbcDataType<'a> {
     type Required;
     type Optional;
@@ -657,6 +735,59 @@ fn write_as_time_us(mut microseconds_since_midnight: i64, mut text: &mut [u8]) {
     .unwrap()
 }
 
+/// Checks if the decimal `n`, with the `scale` of the parquet column, fits into the
+/// target column. Returns the value to insert, which is `None` for NULL.
+fn fit_decimal(
+    n: i128,
+    scale: usize,
+    target: Option<DecimalTarget>,
+    name: &str,
+) -> Result<Option<i128>, Error> {
+    let Some(target) = target else {
+        return Ok(Some(n));
+    };
+    // Surplus fractional digits are rounded by the data source, so only the integer digits can
+    // overflow. This is the smallest absolute value with too many of them, in the scale of the
+    // parquet column.
+    let integer_digits = target.precision.saturating_sub(target.scale);
+    let Some(limit) = u32::try_from(integer_digits + scale)
+        .ok()
+        .and_then(|exponent| 10i128.checked_pow(exponent))
+    else {
+        return Ok(Some(n));
+    };
+    if n.abs() < limit {
+        return Ok(Some(n));
+    }
+    match target.overflow {
+        NumericOverflowArgument::Error => {
+            let divisor = 10i128.pow(scale as u32);
+            let sign = if n.is_negative() { "-" } else { "" };
+            let (integer, fraction) = ((n / divisor).abs(), (n % divisor).abs());
+            let text = if scale == 0 {
+                n.to_string()
+            } else {
+                format!("{sign}{integer}.{fraction:0scale$}")
+            };
+            Err(anyhow!(
+                "Value {} of column '{name}' does not fit into the target column of type \
+                DECIMAL({},{}). Use `--numeric-overflow null` or `--numeric-overflow clamp` to \
+                insert NULL or the largest value of the column instead.",
+                text,
+                target.precision,
+                target.scale
+            ))
+        }
+        NumericOverflowArgument::Null => Ok(None),
+        NumericOverflowArgument::Clamp => {
+            // Largest value of the target column, in the scale of the parquet column.
+            let step = 10i128.pow(scale.saturating_sub(target.scale) as u32);
+            let max = limit - step;
+            Ok(Some(if n.is_negative() { -max } else { max }))
+        }
+    }
+}
+
 fn write_integer_as_decimal<I>(mut n: I, precision: usize, scale: usize, text: &mut [u8])
 where
     I: PrimInt + FromPrimitive + DivAssign + ToPrimitive + Signed + MulAssign,
@@ -776,7 +907,32 @@ impl_odbc_data_type!(Timestamp, Timestamp, Timestamp, NullableTimestamp);
 
 #[cfg(test)]
 mod tests {
-    use super::{i128_from_be_slice, write_integer_as_decimal};
+    use crate::enum_args::NumericOverflowArgument;
+
+    use super::{fit_decimal, i128_from_be_slice, write_integer_as_decimal, DecimalTarget};
+
+    #[test]
+    fn decimals_overflowing_target_column() {
+        let target = |overflow| {
+            Some(DecimalTarget {
+                precision: 5,
+                scale: 2,
+                overflow,
+            })
+        };
+        // 123.45 fits into DECIMAL(5,2), 1234.5 does not.
+        let fit = target(NumericOverflowArgument::Error);
+        assert_eq!(Some(12345), fit_decimal(12345, 2, fit, "a").unwrap());
+        assert_eq!(Some(12345), fit_decimal(12345, 2, None, "a").unwrap());
+        let error = fit_decimal(-12345, 1, fit, "a").unwrap_err();
+        assert!(error.to_string().starts_with("Value -1234.5 of column 'a'"));
+        let null = target(NumericOverflowArgument::Null);
+        assert_eq!(None, fit_decimal(12345, 1, null, "a").unwrap());
+        let clamp = target(NumericOverflowArgument::Clamp);
+        assert_eq!(Some(9999), fit_decimal(12345, 1, clamp, "a").unwrap());
+        assert_eq!(Some(-999990), fit_decimal(-1234567, 3, clamp, "a").unwrap());
+        assert_eq!(Some(-999), fit_decimal(-1234, 0, clamp, "a").unwrap());
+    }
 
     #[test]
     fn format_i32_to_decimal() {
@@ -1,12 +1,31 @@
-use std::fs::File;
+use std::{
+    cmp::min,
+    fs::{self, File},
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Error;
-use log::info;
-use parquet::file::reader::{FileReader, SerializedFileReader};
+use anyhow::{bail, Context, Error};
+use log::{debug, info, warn};
+use odbc_api::{
+    buffers::{AnyBuffer, AnySliceMut, BufferDesc},
+    handles::{AsStatementRef, ParameterDescription},
+    ColumnarBulkInserter, Connection, DataType, U16String,
+};
+use parquet::{
+    basic::{ConvertedType, LogicalType, TimeUnit, Type as PhysicalType},
+    column::reader::ColumnReader,
+    file::reader::{FileReader, SerializedFileReader},
+    schema::types::{ColumnDescPtr, ColumnDescriptor},
+};
 
 use crate::{
-    connection::open_connection, input::parquet_type_to_odbc_buffer_desc,
-    parquet_buffer::ParquetBuffer, InsertOpt,
+    connection::open_connection,
+    enum_args::{OnConflictArgument, SqlDialectArgument},
+    input::{parquet_type_to_odbc_buffer_desc, DecimalTarget, FnParquetToOdbcCol},
+    parquet_buffer::ParquetBuffer,
+    rejects::RejectsWriter,
+    InsertOpt,
 };
 
 /// Read the content of a parquet file and insert it into a table.
@@ -16,76 +35,599 @@ pub fn insert(insert_opt: &InsertOpt) -> Result<(), Error> {
         input,
         connect_opts,
         table,
+        create_table,
+        batch_size_row,
+        strip_prefix,
+        strip_suffix,
+        truncate_table,
+        transaction,
+        null_string,
+        empty_string_as_null,
+        empty_string_as_empty,
+        resume_from_row_group,
+        stop_after_row_group,
+        on_conflict,
+        conflict_key,
+        sql_dialect,
+        quote_identifiers,
+        column_default,
+        skip_columns,
+        numeric_overflow,
+        continue_on_error,
+        rejects_output,
     } = insert_opt;
 
     let odbc_conn = open_connection(connect_opts)?;
 
-    let file = File::open(input)?;
-    let reader = SerializedFileReader::new(file)?;
+    let input_files = input_files(input)?;
+    if input_files.len() > 1 && (resume_from_row_group.is_some() || stop_after_row_group.is_some())
+    {
+        bail!(
+            "`--resume-from-row-group` and `--stop-after-row-group` require a single input file, \
+            but {} files match '{}'.",
+            input_files.len(),
+            input.display()
+        )
+    }
 
+    // The first file determines the schema, and therefore the statement and the buffers.
+    let reader = open_reader(&input_files[0])?;
     let parquet_metadata = reader.metadata();
     let schema_desc = parquet_metadata.file_metadata().schema_descr();
     let num_columns = schema_desc.num_columns();
 
-    let column_descriptions: Vec<_> = (0..num_columns).map(|i| schema_desc.column(i)).collect();
+    let all_column_descriptions: Vec<_> = (0..num_columns).map(|i| schema_desc.column(i)).collect();
+    // Inspect the metadata of all files upfront, so a mismatching schema does not abort the insert
+    // halfway.
+    let mut max_row_group_rows = max_num_rows_in_row_group(&reader);
+    for path in &input_files[1..] {
+        let other = open_reader(path)?;
+        check_same_schema(&all_column_descriptions, &other, path)?;
+        max_row_group_rows = max_row_group_rows.max(max_num_rows_in_row_group(&other));
+    }
+    if let Some(unknown) = skip_columns.iter().find(|skipped| {
+        !all_column_descriptions
+            .iter()
+            .any(|col_desc| col_desc.name() == skipped.as_str())
+    }) {
+        bail!("Column '{unknown}' specified with `--skip-columns` is not part of the parquet file.")
+    }
+    // Indices of the parquet columns inserted into the table.
+    let inserted_columns: Vec<usize> = (0..num_columns)
+        .filter(|&index| {
+            !skip_columns
+                .iter()
+                .any(|skipped| skipped == schema_desc.column(index).name())
+        })
+        .collect();
+    if inserted_columns.is_empty() {
+        bail!(
+            "All columns of the parquet file are skipped with `--skip-columns`. At least one column \
+            must be inserted."
+        )
+    }
+    let column_descriptions: Vec<_> = inserted_columns
+        .iter()
+        .map(|&index| all_column_descriptions[index].clone())
+        .collect();
     let column_names: Vec<&str> = column_descriptions
         .iter()
         .map(|col_desc| col_desc.name())
         .collect();
-    let column_buf_desc: Vec<_> = column_descriptions
+    // Names of the columns in the database table. Does not affect how the columns are read from
+    // the parquet file, as we read them by index.
+    let mut column_names = strip_column_names(column_names, strip_prefix, strip_suffix)?;
+    if let Some((present, _)) = column_default
+        .iter()
+        .find(|(column, _)| column_names.contains(&column.as_str()))
+    {
+        bail!("Column '{present}' has a `--column-default`, but is present in the parquet file.")
+    }
+    column_names.extend(column_default.iter().map(|(column, _)| column.as_str()));
+    if *empty_string_as_empty && null_string.as_deref() == Some("") {
+        bail!("An empty `--null-string` contradicts `--empty-string-as-empty`.")
+    }
+    // Text values inserted as NULL
+    let mut null_strings: Vec<&str> = null_string.as_deref().into_iter().collect();
+    if *empty_string_as_null {
+        null_strings.push("");
+    }
+    // Precision and scale of the target columns are only known after preparing the statement, yet
+    // we want to reject unsupported parquet types before changing the table.
+    let mut column_buf_desc: Vec<_> = column_descriptions
         .iter()
-        .map(|col_desc| parquet_type_to_odbc_buffer_desc(col_desc, encoding.use_utf16()))
+        .map(|col_desc| {
+            parquet_type_to_odbc_buffer_desc(col_desc, encoding.use_utf16(), &null_strings, None)
+        })
         .collect::<Result<_, _>>()?;
-    let insert_statement = insert_statement_text(table, &column_names);
+    // Identifiers as they appear in the generated statements.
+    let table = &quote_identifiers.quote_table(table);
+    let quoted_column_names: Vec<String> = column_names
+        .iter()
+        .map(|name| quote_identifiers.quote(name))
+        .collect();
+    let column_names: Vec<&str> = quoted_column_names.iter().map(String::as_str).collect();
+    let conflict_key: Vec<String> = conflict_key
+        .iter()
+        .map(|key| quote_identifiers.quote(key))
+        .collect();
+    // Generate the statement before changing the table, so invalid conflict options do not leave
+    // it modified.
+    let insert_statement = match on_conflict {
+        OnConflictArgument::Error => {
+            if !conflict_key.is_empty() {
+                bail!("`--conflict-key` requires `--on-conflict ignore` or `--on-conflict update`.")
+            }
+            insert_statement_text(table, &column_names)
+        }
+        on_conflict => {
+            let dialect = match sql_dialect {
+                Some(dialect) => *dialect,
+                None => {
+                    let dbms_name = odbc_conn.database_management_system_name()?;
+                    let Some(dialect) = SqlDialectArgument::from_dbms_name(&dbms_name) else {
+                        bail!(
+                            "Can not infer the SQL dialect of '{dbms_name}' in order to handle \
+                            conflicts. Please specify it using `--sql-dialect`."
+                        )
+                    };
+                    dialect
+                }
+            };
+            upsert_statement_text(table, &column_names, *on_conflict, &conflict_key, dialect)?
+        }
+    };
+
+    if *create_table {
+        let create_table_statement =
+            create_table_statement_text(table, &column_names, &column_descriptions);
+        odbc_conn.execute(&create_table_statement, (), None)?;
+    }
+    // Rows are removed within the same transaction as the insert, so a failure mid insert rolls
+    // back the removal as well. This is why `truncate` uses `DELETE FROM` rather than `TRUNCATE
+    // TABLE`, which implicitly commits on some databases, e.g. MySQL or Oracle.
+    let single_transaction = *transaction || *truncate_table;
+    if single_transaction {
+        odbc_conn.set_autocommit(false)?;
+    }
+    if *truncate_table {
+        truncate(&odbc_conn, table)?;
+    }
+    // Each chunk is committed explicitly, so a chunk which fails partway can be rolled back before
+    // inserting its rows one by one. Otherwise the rows inserted before the error would be
+    // inserted twice.
+    if *continue_on_error {
+        odbc_conn.set_autocommit(false)?;
+    }
+    let mut rejects_writer = rejects_output
+        .as_deref()
+        .map(|path| RejectsWriter::new(path, &column_descriptions))
+        .transpose()?;
+    let mut num_rows_rejected = 0;
 
-    let statement = odbc_conn.prepare(&insert_statement)?;
+    let mut statement = odbc_conn.prepare(&insert_statement)?;
 
-    let num_row_groups = reader.num_row_groups();
+    for (index, col_desc) in column_descriptions.iter().enumerate() {
+        if col_desc.converted_type() != ConvertedType::DECIMAL {
+            continue;
+        }
+        let parameter_number = (index + 1).try_into().unwrap();
+        let decimal_target = match statement.describe_param(parameter_number) {
+            Ok(ParameterDescription {
+                data_type:
+                    DataType::Decimal { precision, scale } | DataType::Numeric { precision, scale },
+                ..
+            }) => DecimalTarget {
+                precision,
+                scale: scale.try_into().unwrap_or(0),
+                overflow: *numeric_overflow,
+            },
+            Ok(_) => continue,
+            Err(error) => {
+                debug!(
+                    "Can not describe the target column of '{}'. Its values are not checked for \
+                    overflow. {error}",
+                    col_desc.name()
+                );
+                continue;
+            }
+        };
+        column_buf_desc[index] = parquet_type_to_odbc_buffer_desc(
+            col_desc,
+            encoding.use_utf16(),
+            &null_strings,
+            Some(decimal_target),
+        )?;
+    }
 
-    // Start with a small initial batch size and reallocate as we encounter larger row groups.
-    let mut batch_size = 1;
+    // Unless specified otherwise, choose the batch size large enough to hold the largest row group.
+    // This way each row group can be inserted with a single roundtrip.
+    let batch_size = batch_size_row.unwrap_or(max_row_group_rows).max(1);
+    let default_buf_desc = column_default.iter().map(|(_, value)| {
+        if encoding.use_utf16() {
+            BufferDesc::WText {
+                max_str_len: value.encode_utf16().count(),
+            }
+        } else {
+            BufferDesc::Text {
+                max_str_len: value.len(),
+            }
+        }
+    });
     let mut odbc_buffer = statement.into_column_inserter(
         batch_size,
-        column_buf_desc.iter().map(|(desc, _copy_col)| *desc),
+        column_buf_desc
+            .iter()
+            .map(|(desc, _copy_col)| *desc)
+            .chain(default_buf_desc),
     )?;
+    // Constant values are bound once, since only the columns read from parquet are overwritten
+    // for each batch.
+    for (index, (_, value)) in (column_buf_desc.len()..).zip(column_default) {
+        match odbc_buffer.column_mut(index) {
+            AnySliceMut::Text(mut column) => {
+                for row in 0..batch_size {
+                    column.set_cell(row, Some(value.as_bytes()));
+                }
+            }
+            AnySliceMut::WText(mut column) => {
+                let value = U16String::from_str(value);
+                for row in 0..batch_size {
+                    column.set_cell(row, Some(value.as_slice()));
+                }
+            }
+            _ => unreachable!("Column defaults are bound as text."),
+        }
+    }
 
     let mut pb = ParquetBuffer::new(batch_size);
 
-    for row_group_index in 0..num_row_groups {
+    for path in &input_files {
+        let reader = open_reader(path)?;
+        let num_row_groups = reader.num_row_groups();
+        let row_groups = selected_row_groups(
+            num_row_groups,
+            *resume_from_row_group,
+            *stop_after_row_group,
+        )?;
         info!(
-            "Insert row group {} of {}.",
-            row_group_index, num_row_groups
+            "Start inserting '{}' at row group {}.",
+            path.display(),
+            row_groups.start
         );
-        let row_group_reader = reader.get_row_group(row_group_index)?;
-        let num_rows: usize = row_group_reader
-            .metadata()
-            .num_rows()
-            .try_into()
-            .expect("Number of rows in row group of parquet file must be non negative");
-        // Ensure that num rows is less than batch size of originally created buffers.
-        if num_rows > batch_size {
-            batch_size = num_rows;
-            let descs = column_buf_desc.iter().map(|(desc, _)| *desc);
-            // An inefficiency here: Currently `odbc-api`s interface forces us to prepare the
-            // statement again, in case we need to allocate more row groups.
-            odbc_buffer = odbc_conn
-                .prepare(&insert_statement)?
-                .into_column_inserter(batch_size, descs)?;
-        }
-        odbc_buffer.set_num_rows(num_rows);
-        pb.set_num_rows_fetched(num_rows);
-        for (column_index, (_, parquet_to_odbc_col)) in column_buf_desc.iter().enumerate() {
-            let column_reader = row_group_reader.get_column_reader(column_index)?;
-            let column_writer = odbc_buffer.column_mut(column_index);
-            parquet_to_odbc_col(num_rows, &mut pb, column_reader, column_writer)?;
-        }
-
-        odbc_buffer.execute()?;
+        let mut num_rows_file = 0;
+        for row_group_index in row_groups {
+            info!(
+                "Insert row group {} of {}.",
+                row_group_index, num_row_groups
+            );
+            let row_group_reader = reader.get_row_group(row_group_index)?;
+            let num_rows: usize = row_group_reader
+                .metadata()
+                .num_rows()
+                .try_into()
+                .expect("Number of rows in row group of parquet file must be non negative");
+            let mut column_readers = inserted_columns
+                .iter()
+                .map(|&column_index| row_group_reader.get_column_reader(column_index))
+                .collect::<Result<Vec<_>, _>>()?;
+            // Indices of the rows within the row group, which could not be inserted.
+            let mut rejected_rows = Vec::new();
+            // Row groups larger than the batch size are inserted in chunks.
+            let mut remaining_rows = num_rows;
+            while remaining_rows != 0 {
+                let num_rows_chunk = min(remaining_rows, batch_size);
+                let chunk_start = num_rows - remaining_rows;
+                remaining_rows -= num_rows_chunk;
+                fill_batch(
+                    &mut odbc_buffer,
+                    &mut pb,
+                    &column_buf_desc,
+                    &mut column_readers,
+                    num_rows_chunk,
+                )?;
+                let result = odbc_buffer.execute().map(|_cursor| ());
+                match result {
+                    Ok(()) => {
+                        if *continue_on_error {
+                            odbc_conn.commit()?;
+                        }
+                    }
+                    Err(error) if *continue_on_error => {
+                        warn!(
+                            "Inserting rows {chunk_start} to {} of row group {row_group_index} \
+                            failed. Inserting them one by one.\n{error}",
+                            chunk_start + num_rows_chunk - 1
+                        );
+                        odbc_conn.rollback()?;
+                        // The column readers already moved past the chunk, so we read it again
+                        // using fresh readers.
+                        let mut column_readers = inserted_columns
+                            .iter()
+                            .map(|&column_index| row_group_reader.get_column_reader(column_index))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let mut num_rows_to_skip = chunk_start;
+                        while num_rows_to_skip != 0 {
+                            let num_rows_skipped = min(num_rows_to_skip, batch_size);
+                            fill_batch(
+                                &mut odbc_buffer,
+                                &mut pb,
+                                &column_buf_desc,
+                                &mut column_readers,
+                                num_rows_skipped,
+                            )?;
+                            num_rows_to_skip -= num_rows_skipped;
+                        }
+                        for row in chunk_start..chunk_start + num_rows_chunk {
+                            fill_batch(
+                                &mut odbc_buffer,
+                                &mut pb,
+                                &column_buf_desc,
+                                &mut column_readers,
+                                1,
+                            )?;
+                            let result = odbc_buffer.execute().map(|_cursor| ());
+                            match result {
+                                Ok(()) => odbc_conn.commit()?,
+                                Err(error) => {
+                                    odbc_conn.rollback()?;
+                                    warn!(
+                                        "Skipping row {row} of row group {row_group_index} of \
+                                        '{}'.\n{error}",
+                                        path.display()
+                                    );
+                                    rejected_rows.push(row);
+                                }
+                            }
+                        }
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+            if let Some(rejects_writer) = &mut rejects_writer {
+                if !rejected_rows.is_empty() {
+                    rejects_writer.write(&*row_group_reader, &inserted_columns, &rejected_rows)?;
+                }
+            }
+            num_rows_rejected += rejected_rows.len();
+            num_rows_file += num_rows - rejected_rows.len();
+        }
+        info!("Inserted {num_rows_file} rows from '{}'.", path.display());
     }
 
+    if single_transaction {
+        // The transaction is rolled back if the connection is dropped without committing.
+        odbc_conn.commit()?;
+    }
+    if let Some(rejects_writer) = rejects_writer {
+        rejects_writer.close()?;
+    }
+    if *continue_on_error {
+        if num_rows_rejected == 0 {
+            info!("All rows have been inserted.");
+        } else {
+            warn!("Skipped {num_rows_rejected} rows, which could not be inserted.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the next `num_rows` values of each column into the parameter buffers of the statement.
+fn fill_batch<S: AsStatementRef>(
+    odbc_buffer: &mut ColumnarBulkInserter<S, AnyBuffer>,
+    pb: &mut ParquetBuffer,
+    column_buf_desc: &[(BufferDesc, Box<FnParquetToOdbcCol>)],
+    column_readers: &mut [ColumnReader],
+    num_rows: usize,
+) -> Result<(), Error> {
+    odbc_buffer.set_num_rows(num_rows);
+    pb.set_num_rows_fetched(num_rows);
+    for (column_index, ((_, parquet_to_odbc_col), column_reader)) in
+        column_buf_desc.iter().zip(column_readers).enumerate()
+    {
+        let column_writer = odbc_buffer.column_mut(column_index);
+        parquet_to_odbc_col(num_rows, pb, column_reader, column_writer)?;
+    }
+    Ok(())
+}
+
+/// Parquet files specified by `input`. Either the file itself, the parquet files in a directory, or
+/// the files matching a pattern with wildcards in its file name. Sorted by path.
+fn input_files(input: &Path) -> Result<Vec<PathBuf>, Error> {
+    // Without a pattern, all parquet files in the directory are matched.
+    let (dir, pattern) = if input.is_dir() {
+        (input, None)
+    } else {
+        match input.file_name().and_then(|name| name.to_str()) {
+            Some(pattern) if pattern.contains(['*', '?']) => {
+                let dir = input.parent().filter(|dir| !dir.as_os_str().is_empty());
+                (dir.unwrap_or(Path::new(".")), Some(pattern))
+            }
+            _ => return Ok(vec![input.to_owned()]),
+        }
+    };
+    let matches = |name: &str| match pattern {
+        Some(pattern) => wildcard_match(pattern, name),
+        None => name.ends_with(".parquet") || name.ends_with(".par"),
+    };
+    let mut files = Vec::new();
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Could not list the input files in '{}'.", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        if entry.file_type()?.is_file() && name.to_str().is_some_and(matches) {
+            files.push(entry.path());
+        }
+    }
+    if files.is_empty() {
+        bail!("No parquet files match '{}'.", input.display())
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `true` if `name` matches `pattern`, with `*` matching any sequence of characters and `?`
+/// matching exactly one character.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Position in the pattern after the last `*` and position in the name it has been matched to,
+    // so we can backtrack and let the star consume one more character.
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn open_reader(path: &Path) -> Result<SerializedFileReader<File>, Error> {
+    let file = File::open(path)
+        .with_context(|| format!("Could not open input file '{}'.", path.display()))?;
+    Ok(SerializedFileReader::new(file)?)
+}
+
+/// Number of rows in the largest row group of the file.
+fn max_num_rows_in_row_group(reader: &SerializedFileReader<File>) -> usize {
+    reader
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows().try_into().unwrap())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Fails if the columns of `reader` differ from the columns of the first input file.
+fn check_same_schema(
+    expected: &[ColumnDescPtr],
+    reader: &SerializedFileReader<File>,
+    path: &Path,
+) -> Result<(), Error> {
+    let schema_desc = reader.metadata().file_metadata().schema_descr();
+    if schema_desc.num_columns() != expected.len() {
+        bail!(
+            "The schema of '{}' does not match the schema of the first input file. It has {} \
+            columns instead of {}.",
+            path.display(),
+            schema_desc.num_columns(),
+            expected.len()
+        )
+    }
+    for (index, expected) in expected.iter().enumerate() {
+        let actual = schema_desc.column(index);
+        if actual.self_type() != expected.self_type() {
+            bail!(
+                "The schema of '{}' does not match the schema of the first input file. Column \
+                '{}' differs from column '{}' of the first file.",
+                path.display(),
+                actual.name(),
+                expected.name()
+            )
+        }
+    }
     Ok(())
 }
 
+/// Range of the row groups to insert, as specified by `--resume-from-row-group` and
+/// `--stop-after-row-group`.
+fn selected_row_groups(
+    num_row_groups: usize,
+    resume_from: Option<usize>,
+    stop_after: Option<usize>,
+) -> Result<Range<usize>, Error> {
+    let start = resume_from.unwrap_or(0);
+    if start != 0 && start >= num_row_groups {
+        bail!(
+            "Can not resume from row group {start}. The input file has only {num_row_groups} row \
+            groups, starting with index 0."
+        )
+    }
+    let end = match stop_after {
+        Some(stop) if stop >= num_row_groups => bail!(
+            "Can not stop after row group {stop}. The input file has only {num_row_groups} row \
+            groups, starting with index 0."
+        ),
+        Some(stop) if stop < start => bail!(
+            "The row group to stop after ({stop}) must not be smaller than the row group to resume \
+            from ({start})."
+        ),
+        Some(stop) => stop + 1,
+        None => num_row_groups,
+    };
+    Ok(start..end)
+}
+
+/// Removes all rows from the table. Uses `DELETE FROM` rather than `TRUNCATE TABLE`, so removing
+/// the rows is part of the current transaction and rolled back if the insert fails.
+fn truncate(odbc_conn: &Connection, table: &str) -> Result<(), Error> {
+    // Probe the table, to give a clear error message rather than failing on both the truncate and
+    // the delete statements.
+    if odbc_conn
+        .execute(&format!("SELECT * FROM {table} WHERE 1=0"), (), None)
+        .is_err()
+    {
+        bail!("Can not truncate table '{table}', since it does not exist.")
+    }
+    info!("Truncate table '{table}'.");
+    odbc_conn.execute(&format!("DELETE FROM {table}"), (), None)?;
+    Ok(())
+}
+
+/// Removes `prefix` and `suffix` from the column names, if present. Names which do not start with
+/// the prefix or end with the suffix are kept as they are.
+fn strip_column_names<'a>(
+    column_names: Vec<&'a str>,
+    prefix: &Option<String>,
+    suffix: &Option<String>,
+) -> Result<Vec<&'a str>, Error> {
+    let stripped: Vec<&str> = column_names
+        .iter()
+        .map(|&name| {
+            let name = prefix
+                .as_deref()
+                .and_then(|prefix| name.strip_prefix(prefix))
+                .unwrap_or(name);
+            suffix
+                .as_deref()
+                .and_then(|suffix| name.strip_suffix(suffix))
+                .unwrap_or(name)
+        })
+        .collect();
+    for (index, (original, name)) in column_names.iter().zip(&stripped).enumerate() {
+        if name.is_empty() {
+            bail!("Stripping prefix and suffix from column '{original}' leaves an empty name.")
+        }
+        if let Some(other) = stripped[..index].iter().position(|other| other == name) {
+            bail!(
+                "Stripping prefix and suffix from columns '{}' and '{original}' results in the \
+                same name '{name}'.",
+                column_names[other]
+            )
+        }
+    }
+    Ok(stripped)
+}
+
 fn insert_statement_text(table: &str, column_names: &[&str]) -> String {
     // Generate statement text from table name and headline
     let columns = column_names.join(", ");
@@ -98,3 +640,295 @@ fn insert_statement_text(table: &str, column_names: &[&str]) -> String {
     info!("Insert statement Text: {}", statement_text);
     statement_text
 }
+
+/// Insert statement which ignores or updates rows conflicting with existing rows of the table, in
+/// the syntax of the specified dialect.
+fn upsert_statement_text(
+    table: &str,
+    column_names: &[&str],
+    on_conflict: OnConflictArgument,
+    conflict_key: &[String],
+    dialect: SqlDialectArgument,
+) -> Result<String, Error> {
+    if conflict_key.is_empty() {
+        bail!("`--on-conflict ignore` and `--on-conflict update` require `--conflict-key`.")
+    }
+    if let Some(unknown) = conflict_key
+        .iter()
+        .find(|key| !column_names.contains(&key.as_str()))
+    {
+        bail!("Conflict key '{unknown}' is not a column of the input file.")
+    }
+    let columns = column_names.join(", ");
+    let values = column_names
+        .iter()
+        .map(|_| "?")
+        .collect::<Vec<_>>()
+        .join(", ");
+    // Columns of existing rows overwritten on conflict. If every column is part of the key,
+    // conflicting rows are identical and there is nothing to update.
+    let updated: Vec<&str> = match on_conflict {
+        OnConflictArgument::Update => column_names
+            .iter()
+            .copied()
+            .filter(|name| !conflict_key.iter().any(|key| key == name))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let set = |value: &dyn Fn(&str) -> String| {
+        updated
+            .iter()
+            .map(|name| format!("{name} = {}", value(name)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let statement_text = match dialect {
+        SqlDialectArgument::Postgresql => {
+            let keys = conflict_key.join(", ");
+            let action = if updated.is_empty() {
+                "DO NOTHING".to_owned()
+            } else {
+                format!("DO UPDATE SET {}", set(&|name| format!("EXCLUDED.{name}")))
+            };
+            format!(
+                "INSERT INTO {table} ({columns}) VALUES ({values}) ON CONFLICT ({keys}) {action};"
+            )
+        }
+        SqlDialectArgument::Mssql => {
+            let condition = conflict_key
+                .iter()
+                .map(|key| format!("target.{key} = source.{key}"))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let when_matched = if updated.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    " WHEN MATCHED THEN UPDATE SET {}",
+                    set(&|name| format!("source.{name}"))
+                )
+            };
+            let source_values = column_names
+                .iter()
+                .map(|name| format!("source.{name}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "MERGE INTO {table} AS target USING (VALUES ({values})) AS source ({columns}) ON \
+                {condition}{when_matched} WHEN NOT MATCHED THEN INSERT ({columns}) VALUES \
+                ({source_values});"
+            )
+        }
+        SqlDialectArgument::Mysql => {
+            // Assigning a key column to itself keeps the existing row. Unlike `INSERT IGNORE` this
+            // does not turn other errors into warnings.
+            let assignments = if updated.is_empty() {
+                format!("{key} = {key}", key = conflict_key[0])
+            } else {
+                set(&|name| format!("VALUES({name})"))
+            };
+            format!(
+                "INSERT INTO {table} ({columns}) VALUES ({values}) ON DUPLICATE KEY UPDATE \
+                {assignments};"
+            )
+        }
+    };
+    info!("Insert statement Text: {}", statement_text);
+    Ok(statement_text)
+}
+
+/// Generates a `CREATE TABLE IF NOT EXISTS` statement with a column for every column in the
+/// parquet file.
+fn create_table_statement_text(
+    table: &str,
+    column_names: &[&str],
+    column_descriptions: &[ColumnDescPtr],
+) -> String {
+    let columns = column_names
+        .iter()
+        .zip(column_descriptions)
+        .map(|(name, col_desc)| {
+            let nullability = if col_desc.self_type().is_optional() {
+                "NULL"
+            } else {
+                "NOT NULL"
+            };
+            format!("{name} {} {nullability}", sql_type(col_desc))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let statement_text = format!("CREATE TABLE IF NOT EXISTS {table} ({columns});");
+    info!("Create table statement Text: {}", statement_text);
+    statement_text
+}
+
+/// Relational type used for a column in a table created for the contents of a parquet file.
+fn sql_type(col_desc: &ColumnDescriptor) -> String {
+    let lt = col_desc.logical_type();
+    let ct = col_desc.converted_type();
+    let pt = col_desc.physical_type();
+    match (pt, lt, ct) {
+        (_, Some(LogicalType::Decimal { scale, precision }), _) => {
+            format!("DECIMAL({precision}, {scale})")
+        }
+        (_, None, ConvertedType::DECIMAL) => format!(
+            "DECIMAL({}, {})",
+            col_desc.type_precision(),
+            col_desc.type_scale()
+        ),
+        (PhysicalType::BOOLEAN, _, _) => "BOOLEAN".to_owned(),
+        (PhysicalType::INT32, Some(LogicalType::Date), _)
+        | (PhysicalType::INT32, None, ConvertedType::DATE) => "DATE".to_owned(),
+        (PhysicalType::INT32 | PhysicalType::INT64, Some(LogicalType::Time { unit, .. }), _) => {
+            format!("TIME({})", fractional_digits(&unit))
+        }
+        (PhysicalType::INT64, Some(LogicalType::Timestamp { unit, .. }), _) => {
+            format!("TIMESTAMP({})", fractional_digits(&unit))
+        }
+        (PhysicalType::INT64, None, ConvertedType::TIMESTAMP_MILLIS) => "TIMESTAMP(3)".to_owned(),
+        (PhysicalType::INT64, None, ConvertedType::TIMESTAMP_MICROS) => "TIMESTAMP(6)".to_owned(),
+        (PhysicalType::INT96, _, _) => "TIMESTAMP".to_owned(),
+        (
+            PhysicalType::INT32,
+            Some(LogicalType::Integer {
+                bit_width: 8 | 16, ..
+            }),
+            _,
+        ) => "SMALLINT".to_owned(),
+        (
+            PhysicalType::INT32,
+            Some(LogicalType::Integer {
+                is_signed: false, ..
+            }),
+            _,
+        ) => "BIGINT".to_owned(),
+        (
+            PhysicalType::INT64,
+            Some(LogicalType::Integer {
+                is_signed: false, ..
+            }),
+            _,
+        ) => "DECIMAL(20, 0)".to_owned(),
+        (PhysicalType::INT32, _, _) => "INTEGER".to_owned(),
+        (PhysicalType::INT64, _, _) => "BIGINT".to_owned(),
+        (PhysicalType::FLOAT, _, _) => "REAL".to_owned(),
+        (PhysicalType::DOUBLE, _, _) => "DOUBLE PRECISION".to_owned(),
+        (PhysicalType::BYTE_ARRAY, Some(LogicalType::String | LogicalType::Json), _)
+        | (PhysicalType::BYTE_ARRAY, None, ConvertedType::UTF8 | ConvertedType::JSON) => {
+            "VARCHAR".to_owned()
+        }
+        (PhysicalType::BYTE_ARRAY, _, _) => "VARBINARY".to_owned(),
+        (PhysicalType::FIXED_LEN_BYTE_ARRAY, _, _) => format!("BINARY({})", col_desc.type_length()),
+    }
+}
+
+/// Number of fractional second digits required to represent the time unit.
+fn fractional_digits(unit: &TimeUnit) -> u8 {
+    match unit {
+        TimeUnit::MILLIS(_) => 3,
+        TimeUnit::MICROS(_) => 6,
+        TimeUnit::NANOS(_) => 9,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enum_args::{OnConflictArgument, QuoteIdentifiersArgument, SqlDialectArgument};
+
+    use super::{selected_row_groups, strip_column_names, upsert_statement_text, wildcard_match};
+
+    #[test]
+    fn strip_prefix_and_suffix_from_column_names() {
+        let prefix = Some("src_".to_owned());
+        let suffix = Some("_x".to_owned());
+
+        let stripped =
+            strip_column_names(vec!["src_a_x", "src_b", "c_x", "d"], &prefix, &suffix).unwrap();
+
+        assert_eq!(vec!["a", "b", "c", "d"], stripped);
+        assert!(strip_column_names(vec!["src_"], &prefix, &None).is_err());
+        assert!(strip_column_names(vec!["src_a", "a"], &prefix, &None).is_err());
+    }
+
+    #[test]
+    fn select_row_groups_to_insert() {
+        assert_eq!(0..5, selected_row_groups(5, None, None).unwrap());
+        assert_eq!(2..5, selected_row_groups(5, Some(2), None).unwrap());
+        assert_eq!(2..4, selected_row_groups(5, Some(2), Some(3)).unwrap());
+        assert_eq!(0..0, selected_row_groups(0, None, None).unwrap());
+        assert!(selected_row_groups(5, Some(5), None).is_err());
+        assert!(selected_row_groups(5, None, Some(5)).is_err());
+        assert!(selected_row_groups(5, Some(3), Some(2)).is_err());
+    }
+
+    #[test]
+    fn upsert_statements() {
+        let columns = ["id", "a", "b"];
+        let key = ["id".to_owned()];
+        let upsert = |on_conflict, dialect| {
+            upsert_statement_text("t", &columns, on_conflict, &key, dialect).unwrap()
+        };
+
+        assert_eq!(
+            "INSERT INTO t (id, a, b) VALUES (?, ?, ?) ON CONFLICT (id) DO UPDATE SET \
+            a = EXCLUDED.a, b = EXCLUDED.b;",
+            upsert(OnConflictArgument::Update, SqlDialectArgument::Postgresql)
+        );
+        assert_eq!(
+            "INSERT INTO t (id, a, b) VALUES (?, ?, ?) ON CONFLICT (id) DO NOTHING;",
+            upsert(OnConflictArgument::Ignore, SqlDialectArgument::Postgresql)
+        );
+        assert_eq!(
+            "MERGE INTO t AS target USING (VALUES (?, ?, ?)) AS source (id, a, b) ON \
+            target.id = source.id WHEN MATCHED THEN UPDATE SET a = source.a, b = source.b WHEN \
+            NOT MATCHED THEN INSERT (id, a, b) VALUES (source.id, source.a, source.b);",
+            upsert(OnConflictArgument::Update, SqlDialectArgument::Mssql)
+        );
+        assert_eq!(
+            "MERGE INTO t AS target USING (VALUES (?, ?, ?)) AS source (id, a, b) ON \
+            target.id = source.id WHEN NOT MATCHED THEN INSERT (id, a, b) VALUES \
+            (source.id, source.a, source.b);",
+            upsert(OnConflictArgument::Ignore, SqlDialectArgument::Mssql)
+        );
+        assert_eq!(
+            "INSERT INTO t (id, a, b) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE a = VALUES(a), \
+            b = VALUES(b);",
+            upsert(OnConflictArgument::Update, SqlDialectArgument::Mysql)
+        );
+        assert_eq!(
+            "INSERT INTO t (id, a, b) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE id = id;",
+            upsert(OnConflictArgument::Ignore, SqlDialectArgument::Mysql)
+        );
+        assert!(upsert_statement_text(
+            "t",
+            &columns,
+            OnConflictArgument::Update,
+            &["c".to_owned()],
+            SqlDialectArgument::Postgresql
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn match_file_names_with_wildcards() {
+        assert!(wildcard_match("part-*.parquet", "part-0001.parquet"));
+        assert!(wildcard_match("part-*.parquet", "part-.parquet"));
+        assert!(wildcard_match("*a*b", "xaxxab"));
+        assert!(wildcard_match("part-?.par", "part-1.par"));
+        assert!(!wildcard_match("part-?.par", "part-12.par"));
+        assert!(!wildcard_match("part-*.parquet", "part-1.par"));
+        assert!(!wildcard_match("*.parquet", "a.parquet.crc"));
+    }
+
+    #[test]
+    fn quote_identifiers() {
+        assert_eq!("a b", QuoteIdentifiersArgument::None.quote("a b"));
+        assert_eq!("\"a\"\"b\"", QuoteIdentifiersArgument::Ansi.quote("a\"b"));
+        assert_eq!("`a b`", QuoteIdentifiersArgument::Mysql.quote("a b"));
+        assert_eq!("[a]]b]", QuoteIdentifiersArgument::Sqlserver.quote("a]b"));
+        assert_eq!(
+            "[dbo].[My Table]",
+            QuoteIdentifiersArgument::Sqlserver.quote_table("dbo.My Table")
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Implements the `list-drivers` and `list-data-sources` subcommands.
+
+use anyhow::Error;
+use odbc_api::Environment;
+
+use crate::{insert::wildcard_match, json::write_json_string, ListOpt};
+
+/// Prints the drivers whose description matches the filter, together with their attributes.
+pub fn list_drivers(odbc_env: &Environment, opt: &ListOpt) -> Result<(), Error> {
+    let drivers: Vec<_> = odbc_env
+        .drivers()?
+        .into_iter()
+        .filter(|driver_info| matches_filter(opt.filter.as_deref(), &driver_info.description))
+        .collect();
+    if opt.json {
+        let mut json = String::from("[");
+        for (index, driver_info) in drivers.iter().enumerate() {
+            if index != 0 {
+                json.push(',');
+            }
+            json.push_str("{\"description\":");
+            write_json_string(&mut json, &driver_info.description);
+            json.push_str(",\"attributes\":{");
+            for (index, (key, value)) in driver_info.attributes.iter().enumerate() {
+                if index != 0 {
+                    json.push(',');
+                }
+                write_json_string(&mut json, key);
+                json.push(':');
+                write_json_string(&mut json, value);
+            }
+            json.push_str("}}");
+        }
+        json.push(']');
+        println!("{json}");
+    } else {
+        for driver_info in drivers {
+            println!("{}", driver_info.description);
+            for (key, value) in &driver_info.attributes {
+                println!("\t{key}={value}");
+            }
+            println!()
+        }
+    }
+    Ok(())
+}
+
+/// Prints the data sources whose server name matches the filter.
+pub fn list_data_sources(odbc_env: &Environment, opt: &ListOpt) -> Result<(), Error> {
+    let data_sources: Vec<_> = odbc_env
+        .data_sources()?
+        .into_iter()
+        .filter(|data_source_info| {
+            matches_filter(opt.filter.as_deref(), &data_source_info.server_name)
+        })
+        .collect();
+    if opt.json {
+        let mut json = String::from("[");
+        for (index, data_source_info) in data_sources.iter().enumerate() {
+            if index != 0 {
+                json.push(',');
+            }
+            json.push_str("{\"server_name\":");
+            write_json_string(&mut json, &data_source_info.server_name);
+            json.push_str(",\"driver\":");
+            write_json_string(&mut json, &data_source_info.driver);
+            json.push('}');
+        }
+        json.push(']');
+        println!("{json}");
+    } else {
+        let mut first = true;
+        for data_source_info in data_sources {
+            // After first item, always place an additional newline in between.
+            if first {
+                first = false;
+            } else {
+                println!()
+            }
+            println!("Server name: {}", data_source_info.server_name);
+            println!("Driver: {}", data_source_info.driver);
+        }
+    }
+    Ok(())
+}
+
+/// `true` if there is no filter, or `name` matches it case-insensitively. Filters without wildcards
+/// match any name containing them.
+fn matches_filter(filter: Option<&str>, name: &str) -> bool {
+    let Some(filter) = filter else {
+        return true;
+    };
+    let filter = filter.to_lowercase();
+    let name = name.to_lowercase();
+    if filter.contains(['*', '?']) {
+        wildcard_match(&filter, &name)
+    } else {
+        name.contains(&filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches_filter;
+
+    #[test]
+    fn filter_drivers_and_data_sources() {
+        assert!(matches_filter(None, "ODBC Driver 18 for SQL Server"));
+        assert!(matches_filter(
+            Some("sql server"),
+            "ODBC Driver 18 for SQL Server"
+        ));
+        assert!(matches_filter(
+            Some("odbc*18*"),
+            "ODBC Driver 18 for SQL Server"
+        ));
+        assert!(!matches_filter(
+            Some("postgres*"),
+            "ODBC Driver 18 for SQL Server"
+        ));
+        assert!(!matches_filter(Some("mariadb"), "PostgreSQL Unicode"));
+    }
+}
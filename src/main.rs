@@ -1,19 +1,33 @@
+mod config;
 mod connection;
 mod enum_args;
 mod execute;
 mod input;
 mod insert;
+mod json;
+mod list;
 mod parquet_buffer;
 mod query;
+mod rejects;
+mod trace;
 
-use crate::enum_args::{column_encoding_from_str, EncodingArgument};
+use crate::enum_args::{
+    column_bloom_filter_from_str, column_compression_from_str, column_default_from_str,
+    column_encoding_from_str, column_length_limit_from_str, column_rename_from_str,
+    csv_char_from_str, decimal_rescale_from_str, encoding_for_type_from_str, file_mode_from_str,
+    param_type_from_str, split_by_date_from_str, ConnectionPoolingArgument,
+    DateGranularityArgument, EncodingArgument, EpochUnitArgument, NumericOverflowArgument,
+    OnConflictArgument, OutputFormatArgument, ParamTypeArgument, ParquetVersionArgument,
+    PoolMatchingArgument, ProgressArgument, QuoteIdentifiersArgument, SqlDialectArgument,
+    SqlTypeArgument, TrimArgument, UnknownTypeArgument,
+};
 use anyhow::{bail, Error};
 use bytesize::ByteSize;
-use connection::ConnectOpts;
+use connection::{init_environment, ConnectOpts};
 use enum_args::CompressionVariants;
 use io_arg::IoArg;
-use odbc_api::environment;
-use parquet::basic::Encoding;
+use log::{info, warn};
+use parquet::basic::{Compression, Encoding};
 use std::path::PathBuf;
 use stderrlog::ColorChoice;
 
@@ -42,6 +56,39 @@ struct Cli {
     /// If not specified the tool will try to emit Colors, but not force it. If `TERM=dumb` or
     /// `NO_COLOR` is defined, then colors will not be used.
     no_color: bool,
+    /// Enable connection pooling of the ODBC driver manager.
+    ///
+    /// The setting is global for the entire process and applied before the ODBC environment is
+    /// created, so it affects every connection odbc2parquet opens. odbc2parquet uses a single
+    /// environment for the whole process, so e.g. the workers of `--parallel` share one pool, too.
+    /// Pools are owned by the driver manager within the current process. The driver needs to be
+    /// thread safe to support connection pooling. Which pooled connections are reused is controlled
+    /// by `--connection-pooling-matching`.
+    #[arg(long, value_enum, default_value = "off")]
+    connection_pooling: ConnectionPoolingArgument,
+    /// How the driver manager picks a connection from the pool, if `--connection-pooling` is
+    /// enabled. `strict` only reuses connections whose connection string and connection attributes
+    /// match exactly. `relaxed` reuses connections whose connection string keywords match, even if
+    /// some connection attributes differ. Like the pooling itself, this is set once on the ODBC
+    /// environment of the process.
+    #[arg(long, value_enum, default_value = "strict")]
+    connection_pooling_matching: PoolMatchingArgument,
+    /// Read options of the subcommand from a TOML file. Keys are named like the long options,
+    /// e.g. `connection-string = "..."` or `batch_size_row = 10000`. Flags take booleans and options
+    /// which can be specified multiple times take arrays. Keys at the top of the file apply to all
+    /// subcommands which know them, keys in a table like `[query]` only to that subcommand. Options
+    /// specified on the command line or by environment variables take precedence over the file.
+    /// Passwords and access tokens are stored in plain text, so make sure only you can read the
+    /// file.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Log every ODBC call to the specified file, using the trace of the ODBC driver manager.
+    ///
+    /// Tracing is enabled only for this invocation and disabled again on exit, without changing
+    /// the configuration of the driver manager (e.g. `odbcinst.ini` or the registry). Useful for
+    /// bug reports against specific drivers. Slows down the transfer considerably.
+    #[arg(long)]
+    odbc_trace: Option<PathBuf>,
     #[command(subcommand)]
     command: Command,
 }
@@ -51,12 +98,29 @@ enum Command {
     /// Query a data source and write the result as parquet.
     Query {
         #[clap(flatten)]
-        query_opt: QueryOpt,
+        query_opt: Box<QueryOpt>,
+    },
+    /// Print how the columns of a query would be mapped to parquet, without fetching any rows.
+    Schema {
+        #[clap(flatten)]
+        schema_opt: SchemaOpt,
+    },
+    /// Count the rows in the result set of a query, without writing any parquet. The count is
+    /// printed to standard out.
+    Count {
+        #[clap(flatten)]
+        count_opt: CountOpt,
     },
     /// List available drivers and their attributes.
-    ListDrivers,
+    ListDrivers {
+        #[clap(flatten)]
+        list_opt: ListOpt,
+    },
     /// List preconfigured data sources. Useful to find data source name to connect to database.
-    ListDataSources,
+    ListDataSources {
+        #[clap(flatten)]
+        list_opt: ListOpt,
+    },
     /// Read the content of a parquet and insert it into a table.
     Insert {
         #[clap(flatten)]
@@ -97,12 +161,20 @@ pub struct QueryOpt {
     /// If both option are specified the batch size is the largest possible which satisfies both
     /// constraints. This option controls the size of the buffers of data in transit, and therefore
     /// the memory usage of this tool. It indirectly controls the size of the row groups written to
-    /// parquet (since each batch is written as one row group). It is hard to make a generic
-    /// statement about how much smaller the average row group will be.
+    /// parquet (since each batch is written as one row group, unless `--row-group-size-rows` is
+    /// specified). It is hard to make a generic statement about how much smaller the average row
+    /// group will be.
     /// This options allows you to specify the memory usage using SI units. So you can pass `2Gib`,
     /// `600Mb` and so on.
     #[arg(long)]
     batch_size_memory: Option<ByteSize>,
+    /// Fail before allocating any buffers, if the memory required for fetching exceeds this limit.
+    /// This accounts for all fetch buffers, e.g. two with the default concurrent fetching. With
+    /// `--parallel` the limit applies to each worker. The batch size, memory per row and total
+    /// allocation are logged at info level (`-v`), regardless of this option. Useful to avoid
+    /// running out of memory in small containers. E.g. `--max-total-memory 512MiB`.
+    #[arg(long)]
+    max_total_memory: Option<ByteSize>,
     /// Maximum number of batches in a single output parquet file. If this option is omitted or 0 a
     /// single output file is produces. Otherwise each output file is closed after the maximum
     /// number of batches have been written and a new one with the suffix `_n` is started. There n
@@ -118,9 +190,16 @@ pub struct QueryOpt {
     /// one have been written. This can slow down the creation of parquet up to a factor of two in
     /// in case writing to parquet takes just as much time as fetching from the database. Usually
     /// io to the database is the bottlneck so the actual slow down is likely lower, but often still
-    /// significant.
+    /// significant. Equivalent to `--fetch-buffer-count 1`.
     #[arg(long)]
     sequential_fetching: bool,
+    /// Number of fetch buffers allocated. While the contents of one buffer is written into parquet,
+    /// the others are filled with batches fetched ahead from the database. Deeper read-ahead can
+    /// help if the database delivers batches irregularly, but memory required for fetching is
+    /// multiplied by this count. `1` is equivalent to `--sequential-fetching`. Defaults to `2`. Only
+    /// applies to `--format parquet`.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "sequential_fetching")]
+    fetch_buffer_count: Option<u64>,
     /// Then the size of the currently written parquet files goes beyond this threshold the current
     /// row group will be finished and then the file will be closed. So the file will be somewhat
     /// larger than the threshold. All further row groups will be written into new files to which
@@ -134,28 +213,65 @@ pub struct QueryOpt {
     /// options to control the size of the row groups. Do not expect the `batch_size_memory` however
     /// to be equal to the row group size. The row group size depends on the actual data in the
     /// database, and is due to compression likely much smaller. Values of this option can be
-    /// specified in SI units. E.g. `--file-size-threshold 1GiB`.
+    /// specified in SI units. E.g. `--file-size-threshold 1GiB`. Use `--max-file-size` if you need
+    /// an upper bound.
     #[arg(long)]
     file_size_threshold: Option<ByteSize>,
-    /// You can use this to limit the transfer buffer size which is used for an individual variadic
-    /// sized column.
-    ///
-    /// This is useful in situations there ODBC would require us to allocate a ridiculous amount of
-    /// memory for a single element of a row. Usually this is the case because the Database schema
-    /// has been ill-defined (like choosing `TEXT` for a username, although a users name is
-    /// unlikely to be several GB long). Another situation is that the ODBC driver is not good at
-    /// reporting the maximum length and therefore reports a really large value. The third option is
-    /// of course that your values are actually large. In this case you just need a ton of memory.
-    /// You can use the batch size limit though to retrieve less at once. For binary columns this is
-    /// a maximum element length in bytes. For text columns it depends on whether UTF-8 or UTF-16
-    /// encoding is used. See documentation of the `encoding` option. In case of UTF-8 this is the
-    /// maximum length in bytes for an element. In case of UTF-16 the binary length is multiplied by
-    /// two. This allows domain experts to configure limits (roughly) in the domain of how many
-    /// letters do I expect in this column, rather than to care about whether the command is
-    /// executed on Linux or Windows. The encoding of the column on the Database does not matter for
-    /// this setting or determining buffer sizes.
-    #[arg(long, default_value = "4096")]
-    column_length_limit: usize,
+    /// Upper bound for the size of each output file. Each batch is encoded into memory, before it
+    /// is written. If the encoded row group would make the current file larger than this limit,
+    /// the file is closed and the rows are written to a new one. If need be, a batch is split into
+    /// smaller row groups across files. Room for the metadata written at the end of the file is
+    /// reserved, too, so files usually end up slightly smaller than the limit. Can be combined with
+    /// `--file-size-threshold`, in which case a file is also closed after the first row group
+    /// which reaches the threshold. Files are named like for `--file-size-threshold`, e.g.
+    /// `out_01.par`. Fails if a single row does not fit into a file. E.g.
+    /// `--max-file-size 100MiB`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "row_groups_per_file",
+            "rows_per_file",
+            "row_group_size_rows",
+            "bloom_filter",
+            "split_key",
+        ]
+    )]
+    max_file_size: Option<ByteSize>,
+    /// Maximum number of rows in a single output file. Each output file is closed after exactly
+    /// this many rows have been written, even if this means splitting a row group across two
+    /// files. Files are named like for `--row-groups-per-file`. Can be combined with
+    /// `--file-size-threshold`, in which case a new file is started at whichever limit is reached
+    /// first.
+    #[arg(long, conflicts_with = "row_groups_per_file")]
+    rows_per_file: Option<usize>,
+    /// Number of rows in each row group. By default each fetched batch is written as one row
+    /// group, so the size of the row groups is coupled to the size of the fetch buffers. With this
+    /// option, rows of consecutive batches are accumulated and a row group is written every time
+    /// it holds this many rows, regardless of batch boundaries. The last row group holds the
+    /// remaining rows. This allows for small fetch buffers, and still large, well compressed row
+    /// groups. The rows of the current row group are kept in memory in their encoded and
+    /// compressed form. If the output is split with `--file-size-threshold` files are only split
+    /// at the end of a row group. `--rows-per-file` ends the row group together with the file.
+    /// Only applies to parquet output.
+    #[arg(long, conflicts_with = "row_groups_per_file")]
+    row_group_size_rows: Option<usize>,
+    /// Size the fetch buffers of text columns by the longest value in the data, rather than by the
+    /// length reported by the driver. Before the export, a first pass selects the maximum length of
+    /// each text column, wrapping the query in `SELECT MAX(CHAR_LENGTH(col)), ... FROM (<query>)`.
+    /// Since the number of bytes per character is unknown, the buffers are sized for the worst
+    /// case. Takes precedence over the limit of `--column-length-limit` without a column name, but
+    /// not over the limits of individual columns. Only supported for simple queries of a single
+    /// table without joins, unions or subqueries. For other queries, or if the first pass fails,
+    /// a warning is logged and the reported lengths are used. Does not apply to `--format ndjson`
+    /// or `csv`.
+    #[arg(long)]
+    size_text_from_data: bool,
+    /// Maximum number of rows written to the output. Fetching stops as soon as the limit is
+    /// reached. Useful to sample the result of a query, without adapting the SQL to the dialect of
+    /// the data source. `0` creates an output file with only schema information, unless
+    /// `--no-empty-file` is also specified.
+    #[arg(long)]
+    limit: Option<usize>,
     /// Default compression used by the parquet file writer.
     #[arg(long, value_enum, default_value = "zstd")]
     column_compression_default: CompressionVariants,
@@ -166,6 +282,389 @@ pub struct QueryOpt {
     /// Default compression level for `zstd` is 3
     #[arg(long)]
     column_compression_level_default: Option<u32>,
+    /// Overwrite the default compression for an individual column. You can pass multiple values in
+    /// format `COLUMN:CODEC[:LEVEL]`. E.g. `--column-compression image:uncompressed` or
+    /// `--column-compression name:zstd:10`. `CODEC` takes the same values as
+    /// `--column-compression-default`.
+    #[arg(
+        long,
+        value_parser=column_compression_from_str,
+        action = ArgAction::Append
+    )]
+    column_compression: Vec<(String, Compression)>,
+    #[clap(flatten)]
+    mapping_opts: MappingOpts,
+    /// Specify the fallback encoding of the parquet output column. You can parse multiple values
+    /// in format `COLUMN:ENCODING`. `ENCODING` must be one of: `plain`, `delta-binary-packed`,
+    /// `delta-byte-array`, `delta-length-byte-array` or `rle`.
+    #[arg(
+        long,
+        value_parser=column_encoding_from_str,
+        action = ArgAction::Append
+    )]
+    parquet_column_encoding: Vec<(String, Encoding)>,
+    /// Specify the fallback encoding for all parquet output columns of a SQL type, in format
+    /// `SQLTYPE:ENCODING`, e.g. `varchar:delta-byte-array`. `SQLTYPE` is the relational type
+    /// reported by the driver, one of: `char`, `varchar`, `longvarchar`, `wchar`, `wvarchar`,
+    /// `wlongvarchar`, `binary`, `varbinary`, `longvarbinary`, `bit`, `tinyint`, `smallint`,
+    /// `integer`, `bigint`, `real`, `float`, `double`, `decimal`, `numeric`, `date`, `time` or
+    /// `timestamp`. `ENCODING` takes the same values as for `--parquet-column-encoding`, which
+    /// takes precedence for individual columns. Can be specified multiple times.
+    #[arg(
+        long,
+        value_parser=encoding_for_type_from_str,
+        action = ArgAction::Append
+    )]
+    encoding_for_type: Vec<(SqlTypeArgument, Encoding)>,
+    /// Write a bloom filter for a column, to speed up point lookups on it. You can pass multiple
+    /// values in format `COLUMN[:FPP]`, with `FPP` being the false positive probability of the
+    /// filter. It defaults to `0.05`. E.g. `--bloom-filter id` or `--bloom-filter id:0.01`.
+    #[arg(
+        long,
+        value_parser=column_bloom_filter_from_str,
+        action = ArgAction::Append
+    )]
+    bloom_filter: Vec<(String, f64)>,
+    /// Version of the parquet format used to write the output. Choose `1.0` if your readers do not
+    /// support version 2.0. Delta encodings require version 2.0 and can not be specified with
+    /// `--parquet-column-encoding` if `1.0` is chosen.
+    #[arg(long, value_enum, default_value = "2.0")]
+    parquet_version: ParquetVersionArgument,
+    /// Number of seconds to wait for the query to execute, before the driver cancels it. `0` means
+    /// no timeout. If not specified the default of the driver is used. Drivers which do not support
+    /// a query timeout log a warning and execute the query without one.
+    #[arg(long)]
+    query_timeout_sec: Option<usize>,
+    /// Ask the driver to return at most this many rows, by setting the statement attribute
+    /// `SQL_ATTR_MAX_ROWS` before executing the query. Drivers honoring it may save the database
+    /// work, yet not all drivers do. Unlike `LIMIT` or `TOP` in SQL, the query text is not changed,
+    /// so it does not influence the query plan in the same way. The limit is always enforced client
+    /// side as well, so drivers which ignore or reject the attribute still produce at most this
+    /// many rows.
+    #[arg(long, conflicts_with = "split_key")]
+    server_side_limit: Option<usize>,
+    /// Only export the columns with the specified names. Can be repeated to select multiple
+    /// columns. Columns are written in the order of the result set, not in the order of this
+    /// option. Not selected columns are not bound to fetch buffers, so they are not transferred
+    /// into the memory of this tool. Columns used to partition the output are always fetched.
+    #[arg(long, action = ArgAction::Append)]
+    column_select: Vec<String>,
+    /// Do not write statistics (e.g. min/max values) for the specified column. Can be specified
+    /// multiple times. Useful for e.g. wide binary columns, where statistics would bloat the file
+    /// footer, or leak data. The column name is matched case sensitive against the name of the
+    /// column in the parquet output.
+    #[arg(long, action = ArgAction::Append)]
+    no_statistics: Vec<String>,
+    /// Do not write statistics (e.g. min/max values) for any column.
+    #[arg(long)]
+    no_statistics_all: bool,
+    /// Do not write column indexes. Column indexes hold the statistics of each page and allow
+    /// readers to skip pages during predicate pushdown, but increase the size of the file. Useful
+    /// for tiny files, there the overhead dominates. Statistics are still written for each column
+    /// chunk, so readers can still skip entire row groups. Columns without statistics, due to
+    /// `--no-statistics` or `--no-statistics-all`, never have a column index. Offset indexes are
+    /// still written.
+    #[arg(long)]
+    no_column_index: bool,
+    /// Do not use dictionary encoding for the specified column. Can be specified multiple times.
+    /// Useful for columns with mostly unique values, e.g. timestamps, for which a dictionary only
+    /// costs space and time. Independent of `--parquet-column-encoding`, which controls the
+    /// encoding used in addition to, or, without a dictionary, instead of it. The column name is
+    /// matched case sensitive against the name of the column in the parquet output.
+    #[arg(long, action = ArgAction::Append)]
+    no_dictionary: Vec<String>,
+    /// Do not use dictionary encoding for any column.
+    #[arg(long)]
+    no_dictionary_all: bool,
+    /// Best effort maximum size of a data page in bytes. Larger pages mean fewer pages per column
+    /// chunk. If not specified, the default of the parquet library (1 MiB) is used.
+    #[arg(long)]
+    data_page_size_bytes: Option<usize>,
+    /// Best effort maximum size of a dictionary page in bytes. Should the dictionary become larger,
+    /// the column falls back to plain encoding. If not specified, the default of the parquet
+    /// library (1 MiB) is used.
+    #[arg(long)]
+    dictionary_page_size_bytes: Option<usize>,
+    /// Store the query text and the time of the export (UTC, RFC 3339) in the key value metadata
+    /// of the parquet file, under the keys `odbc2parquet.query` and `odbc2parquet.exported_at`. If
+    /// the output is split into multiple files, each file carries the same metadata.
+    #[clap(long)]
+    embed_query_metadata: bool,
+    /// Value of the `created_by` field in the footer of the parquet file. Defaults to the version
+    /// of the parquet library used to write the file.
+    #[arg(long)]
+    created_by: Option<String>,
+    /// Write byte identical files for identical result sets, by leaving out any wall clock time
+    /// from the metadata. Together with `--embed-query-metadata` the query text is stored, but not
+    /// the time of the export. Consider `--created-by` to become independent of the version of
+    /// odbc2parquet, too.
+    #[arg(long)]
+    deterministic_output: bool,
+    /// Only prepare the query and map its result set to a parquet schema, without fetching any rows
+    /// or writing any file. Run with `-v` to see the schema, output files, row group size and
+    /// compression an actual export would use. Exits with an error, if preparing the statement or
+    /// mapping the schema fails, so this can be used to validate queries.
+    #[clap(long)]
+    dry_run: bool,
+    /// Print the execution plan of the query to standard output, instead of exporting its result
+    /// set. The query is wrapped in `EXPLAIN` for PostgreSQL and MySQL. For Microsoft SQL Server
+    /// the plan is requested with `SET SHOWPLAN_TEXT ON`. The rows of the plan are printed as text,
+    /// with columns separated by tabs. The output argument is ignored.
+    #[arg(long, conflicts_with = "dry_run")]
+    explain: bool,
+    /// Like `--explain`, but the query is executed, using `EXPLAIN ANALYZE`, so the plan contains
+    /// the actual times and row counts. Not supported for Microsoft SQL Server.
+    #[arg(long, conflicts_with_all = ["dry_run", "explain"])]
+    explain_analyze: bool,
+    /// Dialect of the statement generated for `--explain` and `--explain-analyze`. Inferred from
+    /// the name of the database management system if not specified.
+    #[arg(long, value_enum)]
+    sql_dialect: Option<SqlDialectArgument>,
+    /// Number of times fetching a batch is retried, if it fails due to a connection level error
+    /// (SQLSTATE class 08 or HYT01). Errors caused by the query or the data are never retried.
+    /// Whether a cursor can continue fetching after such an error depends on the ODBC driver.
+    /// Retrying implies `--sequential-fetching`, since concurrent fetching stops after the first
+    /// error. Only applies to `--format parquet`.
+    #[arg(long, default_value = "0")]
+    max_retries: u32,
+    /// Time in milliseconds to wait before the first retry of a failed fetch. The time is doubled
+    /// for each further retry of the same batch.
+    #[arg(long, default_value = "1000")]
+    retry_backoff_ms: u64,
+    /// In case fetch results gets split into multiple files a suffix with a number will be appended
+    /// to each file name. Default suffix length is 2 leading to suffixes like e.g. `_03`. In case
+    /// you would expect thousands of files in your output you may want to set this to say `4` so
+    /// the zeros pad this to a 4 digit number in order to make the filenames more friendly for
+    /// lexical sorting.
+    #[clap(long, default_value = "2")]
+    suffix_length: usize,
+    /// Template for the names of the files, in case the output is split into multiple files. Takes
+    /// precedence over `--suffix-length`. Supports the placeholders `{index}` for the number of the
+    /// file, `{index:WIDTH}` for the number padded with zeroes to `WIDTH` digits, `{date}` for the
+    /// date the export started and `{uuid}` for a random UUID. E.g. `export_{date}_{index:04}.par`.
+    /// The files are placed in the directory of the output path. Must contain an `{index}`
+    /// placeholder. Ignored if the output is not split.
+    #[clap(long)]
+    output_template: Option<String>,
+    /// Number of connections used to export the query in parallel. Requires `--split-key`. The
+    /// range of the key column is split evenly between the connections and each connection writes
+    /// its rows into its own file, named like the files of a split output (e.g. `out_01.par`). If
+    /// one connection fails, the others are stopped. Defaults to one connection, if only
+    /// `--split-key` is specified.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..), requires = "split_key")]
+    parallel: Option<u64>,
+    /// Name of an integer column of the result set, used to split the export between the
+    /// connections specified with `--parallel`. Each connection executes the query wrapped in
+    /// `SELECT * FROM (<query>) WHERE <split_key> BETWEEN ? AND ?`.
+    #[clap(
+        long,
+        conflicts_with_all = ["partition_by", "limit", "row_groups_per_file", "file_size_threshold", "rows_per_file", "dry_run"]
+    )]
+    split_key: Option<String>,
+    /// Format of the output. `ndjson` writes one JSON object per row instead of parquet. Numbers
+    /// and booleans are written as JSON numbers and booleans, dates and timestamps as ISO 8601
+    /// strings and binary data as base64 encoded strings. `csv` writes one row of comma separated
+    /// values per row, formatted the same way, with `NULL` as an empty field. Options which are
+    /// specific to parquet, like compression or encodings, are ignored. `--file-size-threshold`
+    /// refers to the number of bytes written and `--row-groups-per-file` to the number of fetched
+    /// batches. Text is always fetched sequentially and without retries. Can not be combined with
+    /// `--partition-by`, `--split-key`, `--column-select`, `--dry-run`, `--max-retries` or
+    /// `--fetch-buffer-count`.
+    #[arg(long, value_enum, default_value = "parquet")]
+    format: OutputFormatArgument,
+    /// Character separating the fields of `--format csv`. Defaults to `,`. Pass `\t` for tab.
+    #[arg(long, value_parser = csv_char_from_str)]
+    csv_delimiter: Option<char>,
+    /// Character used to enclose fields of `--format csv`, which contain the delimiter, a line
+    /// break or the quote character itself. Quote characters within a field are doubled. Defaults to
+    /// `"`.
+    #[arg(long, value_parser = csv_char_from_str)]
+    csv_quote: Option<char>,
+    /// Do not start each file of `--format csv` with a row of column names.
+    #[arg(long)]
+    csv_no_header: bool,
+    /// Report the progress of the export on standard error. `bar` redraws a single status line
+    /// after each batch, `log` emits an info level log message (requires `-v`) at most every
+    /// `--progress-interval-sec` seconds. Both report rows fetched, bytes written, throughput and
+    /// the current file. Since the total number of rows is unknown, a percentage and estimated
+    /// remaining time are only shown if `--limit` is set.
+    #[arg(long, value_enum, default_value = "none")]
+    progress: ProgressArgument,
+    /// Minimum number of seconds between two progress messages in `--progress log` mode.
+    #[arg(long, default_value = "10")]
+    progress_interval_sec: u64,
+    /// Abort the export if a value does not fit into its fetch buffer, rather than truncating it.
+    /// The error names the column and the row of the value. By default truncated values are
+    /// counted and a warning is logged for each affected column at the end of the export. The size
+    /// of the fetch buffers is controlled by `--column-length-limit`.
+    #[arg(long)]
+    error_on_truncation: bool,
+    /// Abort the export if a text value fetched with the system encoding is not valid UTF-8. The
+    /// error names the column and the row of the value. By default invalid characters are replaced
+    /// and a warning is logged for each affected column at the end of the export. Values can only
+    /// be invalid with `--encoding System` on a system without a UTF-8 locale, in which case you
+    /// likely want to use `--encoding Utf16` instead.
+    #[arg(long)]
+    error_on_encoding_loss: bool,
+    /// Abort the export if this column contains NULL. The error names the row of the first NULL
+    /// and its offset within the fetched batch. Refers to the name of the column in the output,
+    /// i.e. after applying `--column-rename`. Can be specified multiple times. Useful to guard
+    /// against upstream problems, if consumers of the output rely on the column being present.
+    #[arg(long, action = ArgAction::Append)]
+    require_non_null: Vec<String>,
+    /// Print the query text and the value of each positional parameter to standard error, before
+    /// executing the query. Independent of the log level. Parameter values are not redacted.
+    #[arg(long)]
+    verbose_sql: bool,
+    /// Statement executed on the connection before the query, e.g. to adjust session settings like
+    /// `SET DATEFIRST 1`. You can pass multiple statements, which are executed in order. Prefix
+    /// the statement with `@` to read it from a file, e.g. `--pre-sql @setup.sql`. The content of
+    /// the file is executed as a single statement. Statements must not return a result set. In a
+    /// parallel export they are executed on the connection of every worker.
+    #[arg(long, action = ArgAction::Append)]
+    pre_sql: Vec<String>,
+    /// Write a profile of the exported data as JSON to this path, once the export is finished. For
+    /// each column it contains the number of NULLs, minimum and maximum of numeric columns, and the
+    /// distinct values of columns with at most 20 of them. The profile is computed from the fetched
+    /// batches without a second pass, but adds CPU overhead proportional to the number of rows.
+    /// Only supported for parquet output and can not be combined with `--split-key`.
+    #[arg(long, conflicts_with_all = ["split_key", "dry_run"])]
+    profile_output: Option<PathBuf>,
+    /// Print the number of exported rows and the number of NULLs of each column to standard error,
+    /// once the export is finished. NULLs are counted from the indicators of the fetched batches,
+    /// so this is much cheaper than `--profile-output`. Can not be combined with `--split-key`.
+    #[arg(long, conflicts_with_all = ["split_key", "dry_run"])]
+    summary: bool,
+    /// Write a UTF-8 byte order mark at the start of each output file. Some spreadsheet
+    /// applications require it to detect the encoding. Only applies to text output formats, i.e.
+    /// `--format ndjson` and `--format csv`. If the output is split, each file starts with a byte
+    /// order mark.
+    #[arg(long)]
+    bom: bool,
+    /// In case the query comes back with a result set, but now rows, by default a file with only
+    /// schema information is still created. If you do not want to create any file in case the
+    /// result set is empty you can set this flag.
+    #[clap(long)]
+    no_empty_file: bool,
+    /// If the output is split into multiple files, an empty `_SUCCESS` file is created in the
+    /// directory of the output, after all files have been written successfully. This is the
+    /// convention of tools like Spark to mark a multi file dataset as complete. Set this flag to
+    /// not create the marker file.
+    #[clap(long)]
+    no_success_file: bool,
+    /// Write each output file to a sibling with the suffix `.tmp` (e.g. `out.par.tmp`) and rename
+    /// it to its final name only after it has been written and closed successfully. Consumers
+    /// watching the output directory never pick up half written files. If the output is split,
+    /// each file is renamed as soon as it is complete, and the `_SUCCESS` marker is created after
+    /// all of them have been renamed. Has no effect if writing to standard output.
+    #[arg(long)]
+    atomic_output: bool,
+    /// Permissions of the created output files in octal notation, e.g. `0600` to make them only
+    /// readable by the owner. Applies to every split file and to the temporary files of
+    /// `--atomic-output`. Set when the file is created, so the data is never readable by others.
+    /// Without this option the permissions are determined by the umask. Only supported on Unix,
+    /// ignored with a warning on other platforms.
+    #[arg(long, value_parser = file_mode_from_str)]
+    file_mode: Option<u32>,
+    /// Add the files of this export to the files of previous exports in the output directory,
+    /// rather than overwriting them. Numbering continues after the highest number of the existing
+    /// files, e.g. with `out_04.par` if `out_03.par` exists. Requires the output to be split, e.g.
+    /// with `--row-groups-per-file`, and respects `--suffix-length` and `--output-template`. Fails
+    /// if the schema of the existing file with the highest number does not match the schema of the
+    /// query.
+    #[arg(long, conflicts_with_all = ["partition_by", "split_by_date", "split_key"])]
+    append_to_dataset: bool,
+    /// Write the output in hive style partitions. The output is treated as a directory, with one
+    /// subdirectory for each distinct value of the partition column, e.g. `out/country=Germany/`.
+    /// Can be specified multiple times to create nested partitions. The partition columns
+    /// themselves are not written into the parquet files, since their values are already encoded
+    /// in the directory names.
+    #[arg(long, action = ArgAction::Append)]
+    partition_by: Vec<String>,
+    /// Maximum number of partitions written to at the same time with `--partition-by`. Each
+    /// partition keeps an open file and buffers for its current row group until the end of the
+    /// export, so the export fails once more distinct partition values are encountered. Raise the
+    /// limit for partition columns with many distinct values, if enough memory and file
+    /// descriptors are available.
+    #[arg(long, default_value = "100", requires = "partition_by")]
+    max_open_partitions: usize,
+    /// Write one file per day, hour or month, determined by the value of a date or timestamp
+    /// column. Specified as `COLUMN:GRANULARITY`, e.g. `created:day`. Granularity is one of `hour`,
+    /// `day` or `month`. The date is appended to the file name, e.g. `out_2024-01-31.par`, and rows
+    /// with a `NULL` date are written to `out_null.par`. Rows need not be ordered by the column,
+    /// files are kept open until the end of the export. No file is created for an empty result.
+    #[arg(
+        long,
+        value_parser=split_by_date_from_str,
+        conflicts_with_all = ["partition_by", "split_key", "file_size_threshold", "row_groups_per_file", "rows_per_file", "output_template", "max_file_size"]
+    )]
+    split_by_date: Option<(String, DateGranularityArgument)>,
+    /// Name of the output parquet file. Use `-` to indicate that the output should be written to
+    /// standard out instead. This option does nothing if the output is written to standard out.
+    output: IoArg,
+    /// Query executed against the ODBC data source. Question marks (`?`) can be used as
+    /// placeholders for positional parameters. E.g. "SELECT Name FROM Employees WHERE salary > ?;".
+    /// Instead of passing a query verbatim, you may pass a plain dash (`-`), to indicate that the
+    /// query should be read from standard input. In this case the entire input until EOF will be
+    /// considered the query. Standard input may also contain the parameters of the query: Follow
+    /// the query with a line consisting only of `---`, and then one parameter per line. This keeps
+    /// secrets off the command line. Parameters passed at the end of the command line come first.
+    /// Pass a path prefixed with `@` (e.g. `@query.sql`) to read the query from a file.
+    query: String,
+    /// For each placeholder question mark (`?`) in the query text one parameter must be passed at
+    /// the end of the command line.
+    parameters: Vec<String>,
+    /// Path to a file containing additional parameters for the query. The file may either contain
+    /// one parameter per line, or a JSON array of strings, numbers and booleans. These parameters
+    /// are appended to the ones passed at the end of the command line. Use `-` to read the
+    /// parameters from standard input, in case the query is not read from there already.
+    #[arg(long)]
+    parameters_file: Option<IoArg>,
+    /// Execute the query once for each row of this parquet file, binding its columns as the
+    /// positional parameters of the query, in the order of the schema. The result sets of all
+    /// executions are appended to the same output, so every execution must return the same
+    /// columns. The statement is prepared once, yet each execution is a roundtrip to the database,
+    /// and batches do not span executions. So this is only fast for small parameter files, like a
+    /// list of ids. Only supported for `--format parquet`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "parameters",
+            "parameters_file",
+            "param_type",
+            "null_param",
+            "split_key",
+            "server_side_limit",
+            "explain",
+            "explain_analyze",
+        ]
+    )]
+    param_parquet: Option<PathBuf>,
+    /// Bind a positional parameter with a specific type rather than as text, in format
+    /// `INDEX:TYPE`. Indices start at 1. E.g. `--param-type 1:i64 --param-type 2:date`. Binding
+    /// parameters with the type of the column they are compared to avoids implicit conversions by
+    /// the database, which may prevent the use of indices. Valid types are `text`, `i32`, `i64`,
+    /// `f64`, `bool`, `date` (`YYYY-MM-DD`) and `timestamp` (`YYYY-MM-DD HH:MM:SS[.FFF]`).
+    /// Parameters without a type are bound as text.
+    #[arg(
+        long,
+        value_parser=param_type_from_str,
+        action = ArgAction::Append
+    )]
+    param_type: Vec<(usize, ParamTypeArgument)>,
+    /// Bind the positional parameter with this index as NULL. Indices start at 1. The value passed
+    /// for the parameter is ignored, but must still be specified, e.g. as an empty string, so the
+    /// following parameters keep their positions. The parameter is bound with the type specified
+    /// by `--param-type`, or as text. Useful for queries like `WHERE (? IS NULL OR col = ?)`. Can
+    /// be specified multiple times.
+    #[arg(long, action = ArgAction::Append)]
+    null_param: Vec<usize>,
+}
+
+/// Options controlling how the columns of a result set are mapped to parquet. Shared by the `query`
+/// and `schema` subcommands, so `schema` reports the schema `query` would write.
+#[derive(Args)]
+pub struct MappingOpts {
     /// Encoding used for character data requested from the data source.
     ///
     /// `Utf16`: The tool will use 16Bit characters for requesting text from the data source,
@@ -179,21 +678,44 @@ pub struct QueryOpt {
     /// `Auto`: Since on OS-X and Linux the default locales character set is always UTF-8 the
     /// default option is the same as `System` on non-windows platforms. On windows the default is
     /// `Utf16`.
+    ///
+    /// `Utf8Lossy`: Like `System`, but for sources which are known to contain text which is not
+    /// valid UTF-8 in the system encoding. Invalid sequences are replaced with `U+FFFD` without
+    /// logging a warning, so the output is always valid UTF-8 at the cost of garbled characters.
+    /// Can not be combined with `--error-on-encoding-loss`.
     #[arg(long, value_enum, default_value = "Auto", ignore_case = true)]
     encoding: EncodingArgument,
     /// Map `BINARY` SQL columns to `BYTE_ARRAY` instead of `FIXED_LEN_BYTE_ARRAY`. This flag has
     /// been introduced in an effort to increase the compatibility of the output with Apache Spark.
     #[clap(long)]
     prefer_varbinary: bool,
-    /// Specify the fallback encoding of the parquet output column. You can parse multiple values
-    /// in format `COLUMN:ENCODING`. `ENCODING` must be one of: `plain`, `delta-binary-packed`,
-    /// `delta-byte-array`, `delta-length-byte-array` or `rle`.
+    /// You can use this to limit the transfer buffer size which is used for an individual variadic
+    /// sized column.
+    ///
+    /// This is useful in situations there ODBC would require us to allocate a ridiculous amount of
+    /// memory for a single element of a row. Usually this is the case because the Database schema
+    /// has been ill-defined (like choosing `TEXT` for a username, although a users name is
+    /// unlikely to be several GB long). Another situation is that the ODBC driver is not good at
+    /// reporting the maximum length and therefore reports a really large value. The third option is
+    /// of course that your values are actually large. In this case you just need a ton of memory.
+    /// You can use the batch size limit though to retrieve less at once. For binary columns this is
+    /// a maximum element length in bytes. For text columns it depends on whether UTF-8 or UTF-16
+    /// encoding is used. See documentation of the `encoding` option. In case of UTF-8 this is the
+    /// maximum length in bytes for an element. In case of UTF-16 the binary length is multiplied by
+    /// two. This allows domain experts to configure limits (roughly) in the domain of how many
+    /// letters do I expect in this column, rather than to care about whether the command is
+    /// executed on Linux or Windows. The encoding of the column on the Database does not matter for
+    /// this setting or determining buffer sizes.
+    ///
+    /// You can also limit individual columns using the format `COLUMN:BYTES`. E.g.
+    /// `--column-length-limit 100 --column-length-limit description:1000000`. The limit without a
+    /// column name applies to all other columns and defaults to 4096.
     #[arg(
         long,
-        value_parser=column_encoding_from_str,
+        value_parser=column_length_limit_from_str,
         action = ArgAction::Append
     )]
-    parquet_column_encoding: Vec<(String, Encoding)>,
+    column_length_limit: Vec<(Option<String>, usize)>,
     /// Tells the odbc2parquet, that the ODBC driver does not support binding 64-Bit integers (aka
     /// S_C_BIGINT in ODBC speak). This will cause the odbc2parquet to query large integers as text
     /// instead and convert them to 64-Bit integers itself. Setting this flag will not affect the
@@ -206,28 +728,179 @@ pub struct QueryOpt {
     /// of the relational Decimal type is 0, the output will be mapped to either 32Bit or 64Bit
     /// Integeres with logical type none. If the scale is not 0 the Decimal column will be fetches
     /// as text.
-    #[clap(long)]
+    #[clap(long, conflicts_with = "decimal_as_double")]
     avoid_decimal: bool,
-    /// In case fetch results gets split into multiple files a suffix with a number will be appended
-    /// to each file name. Default suffix length is 2 leading to suffixes like e.g. `_03`. In case
-    /// you would expect thousands of files in your output you may want to set this to say `4` so
-    /// the zeros pad this to a 4 digit number in order to make the filenames more friendly for
-    /// lexical sorting.
-    #[clap(long, default_value = "2")]
-    suffix_length: usize,
-    /// In case the query comes back with a result set, but now rows, by default a file with only
-    /// schema information is still created. If you do not want to create any file in case the
-    /// result set is empty you can set this flag.
+    /// Map DECIMAL and NUMERIC columns to DOUBLE. The values are converted into double precision
+    /// floating point numbers by the ODBC driver. This is lossy: Doubles are only accurate to about
+    /// 15 significant decimal digits, and most decimal fractions (e.g. `0.1`) can not be
+    /// represented exactly. Only use this if you do not require exact values, e.g. for statistical
+    /// analysis.
     #[clap(long)]
-    no_empty_file: bool,
-    /// Name of the output parquet file. Use `-` to indicate that the output should be written to
-    /// standard out instead. This option does nothing if the output is written to standard out.
-    output: IoArg,
+    decimal_as_double: bool,
+    /// Store a DECIMAL or NUMERIC column with a fixed scale, rather than the scale reported by the
+    /// database. Pass values in format `COLUMN:SCALE`, e.g. `--decimal-rescale price:4`. This way
+    /// files exported from differently declared columns can be joined. The precision is adjusted,
+    /// so the integer digits still fit. Values are fetched as text. If a value has non-zero
+    /// fractional digits beyond the new scale, the export fails, unless
+    /// `--decimal-rescale-truncate` is set. `COLUMN` refers to the name in the result set, before
+    /// `--column-rename`. You may pass this option multiple times.
+    #[arg(
+        long,
+        value_parser = decimal_rescale_from_str,
+        action = ArgAction::Append,
+        conflicts_with_all = ["avoid_decimal", "decimal_as_double"]
+    )]
+    decimal_rescale: Vec<(String, i32)>,
+    /// Truncate fractional digits which do not fit into the scale specified with
+    /// `--decimal-rescale` towards zero, rather than failing. E.g. `1.995` becomes `1.99` and
+    /// `-1.995` becomes `-1.99`.
+    #[arg(long, requires = "decimal_rescale")]
+    decimal_rescale_truncate: bool,
+    /// Store SQL TIMESTAMP columns using the deprecated INT96 physical type (nanoseconds of the day
+    /// and julian day), instead of INT64 with logical type TIMESTAMP. Only use this if you need to
+    /// support legacy readers, like older versions of Hive or Impala, which do not understand the
+    /// logical timestamp type. Timestamps with time zone are not affected.
+    #[clap(long, conflicts_with = "timestamps_as_epoch")]
+    timestamp_int96: bool,
+    /// Store SQL TIMESTAMP and DATE columns as plain INT64 without logical type, counting the
+    /// seconds, milliseconds or microseconds since the UNIX epoch. Timestamps are interpreted as
+    /// UTC and dates as midnight at the beginning of the day. Fractions of the unit are truncated.
+    /// Timestamps with time zone are not affected.
+    #[clap(long, value_enum)]
+    timestamps_as_epoch: Option<EpochUnitArgument>,
+    /// Fetch GUIDs (e.g. `UNIQUEIDENTIFIER` on Microsoft SQL Server) as text, rather than storing
+    /// them as `FIXED_LEN_BYTE_ARRAY(16)` with logical type UUID. Use this if your driver reports
+    /// GUIDs oddly, or your readers expect the text representation.
+    #[clap(long)]
+    guid_as_text: bool,
+    /// Fetch `TIME` columns as text, rather than storing them with logical type TIME. By default
+    /// the unit (milliseconds, microseconds or nanoseconds) is chosen based on the fractional
+    /// seconds precision reported by the driver. Use this if your driver reports the precision
+    /// incorrectly.
+    #[clap(long)]
+    time_as_text: bool,
+    /// Store `BIT` columns as 32 Bit integers, rather than as booleans. By default columns are
+    /// stored as parquet BOOLEAN, if the driver either reports them as `BIT`, or names their type
+    /// `BIT` or `BOOLEAN`, even if it reports an integer type. Use this if your data source stores
+    /// values other than 0 and 1 in these columns.
+    #[clap(long)]
+    bit_as_int: bool,
+    /// Store SQL `INTERVAL` columns as text, rather than as `FIXED_LEN_BYTE_ARRAY(12)` with
+    /// converted type INTERVAL. By default intervals are fetched as text and parsed into months,
+    /// days and milliseconds. Both the format specified by ODBC (e.g. `3 04:05:06.789`) and the
+    /// one of PostgreSQL (e.g. `1 year 2 mons 3 days`) are understood. Use this if your driver
+    /// formats intervals differently, or your intervals are negative, since parquet intervals can
+    /// not represent negative values.
+    #[clap(long)]
+    interval_as_text: bool,
+    /// Store PostgreSQL arrays as text, rather than as parquet LIST. By default one dimensional
+    /// arrays of integers, floating point numbers, booleans and strings are fetched in their text
+    /// representation (e.g. `{1,2,NULL}`) and parsed into lists of optional elements. Use this if
+    /// your driver formats arrays differently, or for multidimensional arrays, which can not be
+    /// stored as lists.
+    #[clap(long)]
+    array_as_text: bool,
+    /// Store JSON columns as plain strings, rather than with the logical type JSON. By default
+    /// columns whose type is named `JSON` or `JSONB` by the data source, e.g. PostgreSQL `JSONB`
+    /// or MySQL `JSON`, are annotated with the logical type JSON, so readers like Arrow or DuckDB
+    /// can treat them as JSON documents. The documents themselves are written as they are. Use
+    /// this, if your reader does not support the logical type. `XML` columns of Microsoft SQL
+    /// Server are always stored as plain strings, since parquet has no logical type for them.
+    #[clap(long)]
+    json_as_text: bool,
+    /// Remove whitespace from the values of text columns before writing them. `trailing` strips
+    /// the padding of fixed width `CHAR(n)` columns, `both` additionally removes leading
+    /// whitespace. Trimming changes the exported data, so it is off by default. It has no effect on
+    /// the columns of other types, e.g. decimals fetched as text, and only applies to parquet
+    /// output.
+    #[arg(long, value_enum, default_value = "none")]
+    trim: TrimArgument,
+    /// What to do with columns whose SQL type is unknown to odbc2parquet, or not mapped to a
+    /// specific parquet type. `text` fetches them as text, using the default conversion of the
+    /// driver. `error` aborts the export before any rows are fetched. `skip` omits the column from
+    /// the output and logs a warning, so the remaining columns can still be exported.
+    #[arg(long, value_enum, default_value = "text")]
+    unknown_type: UnknownTypeArgument,
+    /// Replace every character in column names which is not an ASCII letter, digit or `_` with
+    /// `_`. Names which would collide are made unique by appending `_2`, `_3`, and so on. The
+    /// original names of renamed columns are stored as JSON in the key value metadata of the
+    /// parquet file, under the key `odbc2parquet.original_column_names`.
+    #[clap(long)]
+    sanitize_column_names: bool,
+    /// Write all column names in lower case. Applied after `--column-rename` and before
+    /// `--sanitize-column-names`. Fails if two columns would end up with the same name, e.g. `ID`
+    /// and `id`.
+    #[arg(long, conflicts_with = "uppercase_columns")]
+    lowercase_columns: bool,
+    /// Write all column names in upper case. Applied after `--column-rename` and before
+    /// `--sanitize-column-names`. Fails if two columns would end up with the same name, e.g. `ID`
+    /// and `id`.
+    #[arg(long)]
+    uppercase_columns: bool,
+    /// Rename a column of the result set in the output, without changing the query. You can pass
+    /// multiple values in format `OLD:NEW`. E.g. `--column-rename FirstName:first_name`. Renames
+    /// are applied before `--lowercase-columns`, `--uppercase-columns` and
+    /// `--sanitize-column-names`. The original names are stored in the metadata
+    /// of parquet files. Options referring to output columns, like `--bloom-filter`, use the new name.
+    #[arg(
+        long,
+        value_parser=column_rename_from_str,
+        action = ArgAction::Append
+    )]
+    column_rename: Vec<(String, String)>,
+}
+
+#[derive(Args)]
+pub struct SchemaOpt {
+    #[clap(flatten)]
+    connect_opts: ConnectOpts,
+    #[clap(flatten)]
+    mapping_opts: MappingOpts,
+    /// Print the schema as a JSON array of objects, rather than as a table.
+    #[clap(long)]
+    json: bool,
+    /// Query whose result set is inspected. The query is only prepared, not executed. Pass a plain
+    /// dash (`-`) to read the query from standard input, or a path prefixed with `@` to read it from
+    /// a file.
+    query: String,
+}
+
+#[derive(Args)]
+pub struct ListOpt {
+    /// Only list drivers whose description, or data sources whose server name, matches the filter.
+    /// Matched case-insensitively. Either a substring, or a pattern with the wildcards `*` and `?`
+    /// matching the entire name, e.g. `postgres*`.
+    filter: Option<String>,
+    /// Print the list as a JSON array of objects, rather than as human readable text.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct CountOpt {
+    #[clap(flatten)]
+    connect_opts: ConnectOpts,
+    /// Size of a single batch in rows. See the `query` subcommand for details.
+    #[arg(long)]
+    batch_size_row: Option<usize>,
+    /// Limits the size of a single batch. See the `query` subcommand for details.
+    #[arg(long)]
+    batch_size_memory: Option<ByteSize>,
+    /// Limits the transfer buffer size for an individual variadic sized column. See the `query`
+    /// subcommand for details.
+    #[arg(long, default_value = "4096")]
+    column_length_limit: usize,
+    /// Encoding used for character data requested from the data source. See the `query`
+    /// subcommand for details.
+    #[arg(long, value_enum, default_value = "Auto", ignore_case = true)]
+    encoding: EncodingArgument,
+    /// Tells the odbc2parquet, that the ODBC driver does not support binding 64-Bit integers. See
+    /// the `query` subcommand for details.
+    #[clap(long)]
+    driver_does_not_support_64bit_integers: bool,
     /// Query executed against the ODBC data source. Question marks (`?`) can be used as
-    /// placeholders for positional parameters. E.g. "SELECT Name FROM Employees WHERE salary > ?;".
-    /// Instead of passing a query verbatim, you may pass a plain dash (`-`), to indicate that the
-    /// query should be read from standard input. In this case the entire input until EOF will be
-    /// considered the query.
+    /// placeholders for positional parameters. Pass a plain dash (`-`) to read the query from
+    /// standard input, or a path prefixed with `@` to read it from a file.
     query: String,
     /// For each placeholder question mark (`?`) in the query text one parameter must be passed at
     /// the end of the command line.
@@ -251,14 +924,134 @@ pub struct InsertOpt {
     /// `Auto`: Since on OS-X and Linux the default locales character set is always UTF-8 the
     /// default option is the same as `System` on non-windows platforms. On windows the default is
     /// `Utf16`.
+    ///
+    /// `Utf8Lossy`: Same as `System`, since text in parquet is always valid UTF-8.
     #[arg(long, value_enum, default_value = "Auto", ignore_case = true)]
     encoding: EncodingArgument,
-    /// Path to the input parquet file which is used to fill the database table with values.
+    /// Path to the input parquet file which is used to fill the database table with values. May
+    /// also be a directory, in which case all files with the extension `.parquet` or `.par` in it
+    /// are inserted, or a pattern with the wildcards `*` and `?` in its file name, e.g.
+    /// `parts/part-*.parquet`. Multiple files are inserted in the order of their names, using the
+    /// same connection and prepared statement. All files must share the same schema.
     input: PathBuf,
     /// Name of the table to insert the values into. No precautions against SQL injection are
     /// taken. The insert statement is created by the tool. It will only work if the column names
     /// are the same in the parquet file and the database.
     table: String,
+    /// Create the table before inserting, if it does not exist yet. The relational types of the
+    /// columns are inferred from the parquet schema. Text columns are mapped to `VARCHAR` and
+    /// booleans to `BOOLEAN`, which not every database supports. You can inspect the generated
+    /// `CREATE TABLE` statement using `-v`. If you need more control over the relational types,
+    /// create the table yourself beforehand.
+    #[arg(long)]
+    create_table: bool,
+    /// Number of rows inserted into the database with a single roundtrip. Row groups with more rows
+    /// are inserted in several chunks. Larger batches are usually faster, but require more memory.
+    /// Defaults to the number of rows in the largest row group of the input file.
+    #[arg(long)]
+    batch_size_row: Option<usize>,
+    /// Remove this prefix from the names of the parquet columns, to obtain the names of the columns
+    /// in the database table. Names without the prefix are used as they are.
+    #[arg(long)]
+    strip_prefix: Option<String>,
+    /// Remove this suffix from the names of the parquet columns, to obtain the names of the columns
+    /// in the database table. Names without the suffix are used as they are.
+    #[arg(long)]
+    strip_suffix: Option<String>,
+    /// Remove all rows from the table before inserting. Removing the rows and the insert happen
+    /// within one transaction, so the table is not left empty if the insert fails. Since some
+    /// databases (e.g. MySQL or Oracle) treat `TRUNCATE TABLE` as DDL and commit it implicitly, the
+    /// rows are removed with `DELETE FROM`, which can be rolled back on every database. For large
+    /// tables this is slower than `TRUNCATE TABLE`. Fails if the table does not exist.
+    #[arg(long)]
+    truncate_table: bool,
+    /// Insert all row groups within a single transaction, which is only committed after the last
+    /// row group has been inserted. If any error occurs, the transaction is rolled back and the
+    /// table is left unchanged. Without this flag every chunk is committed as soon as it is
+    /// inserted. Be aware that for large files the database has to keep the entire transaction in
+    /// its log until the commit at the end.
+    #[arg(long)]
+    transaction: bool,
+    /// Insert values of text columns which are equal to this string as NULL. E.g. `\N` or `NULL`
+    /// for files which encode missing values as text. Empty strings are only inserted as NULL, if
+    /// this is explicitly set to an empty string.
+    #[arg(long)]
+    null_string: Option<String>,
+    /// Insert empty values of text columns as NULL. Values which are NULL in the parquet file are
+    /// always inserted as NULL.
+    #[arg(long, conflicts_with = "empty_string_as_empty")]
+    empty_string_as_null: bool,
+    /// Insert empty values of text columns as empty strings. This is the default behaviour. The flag
+    /// allows to state it explicitly.
+    #[arg(long)]
+    empty_string_as_empty: bool,
+    /// Skip the first `N` row groups of the input file. Row groups are counted starting with zero,
+    /// so this is the index of the first row group inserted. Allows to resume an insert which
+    /// failed partway, without inserting the rows of the already committed row groups twice. Only
+    /// supported for a single input file.
+    #[arg(long, conflicts_with = "truncate_table")]
+    resume_from_row_group: Option<usize>,
+    /// Index of the last row group inserted. The remaining row groups of the input file are
+    /// skipped. Together with `--resume-from-row-group` this allows to bisect problematic data.
+    /// Only supported for a single input file.
+    #[arg(long)]
+    stop_after_row_group: Option<usize>,
+    /// What to do with rows which conflict with an existing row of the table, e.g. because of a
+    /// primary key. `error` issues a plain `INSERT`. `ignore` keeps the existing row and `update`
+    /// overwrites all columns of the existing row, except the ones specified by `--conflict-key`.
+    /// Other than `error` requires `--conflict-key`.
+    #[arg(long, value_enum, default_value = "error")]
+    on_conflict: OnConflictArgument,
+    /// Column of the database table identifying conflicting rows. You can pass multiple columns for
+    /// composite keys. The columns must be part of a unique constraint or primary key. MySQL
+    /// detects conflicts using all unique keys of the table, independent of this option.
+    #[arg(long, action = ArgAction::Append)]
+    conflict_key: Vec<String>,
+    /// Dialect of the statement generated for `--on-conflict ignore` and `--on-conflict update`.
+    /// `postgresql` uses `ON CONFLICT`, `mssql` uses `MERGE` and `mysql` uses
+    /// `ON DUPLICATE KEY UPDATE`. Inferred from the name of the database management system if
+    /// not specified.
+    #[arg(long, value_enum)]
+    sql_dialect: Option<SqlDialectArgument>,
+    /// Quote the table and column names in the generated `INSERT`, `CREATE TABLE` and `DELETE`
+    /// statements. Required if column names are reserved words or contain spaces. `ansi` uses
+    /// `"column"`, `mysql` uses `` `column` `` and `sqlserver` uses `[column]`. Parts of a table
+    /// name qualified with a schema (e.g. `dbo.Table`) are quoted individually. Names are quoted
+    /// after applying `--strip-prefix` and `--strip-suffix`.
+    #[arg(long, value_enum, default_value = "none")]
+    quote_identifiers: QuoteIdentifiersArgument,
+    /// Insert a constant value into a column of the table, which is missing in the parquet file.
+    /// Specified as `COLUMN:VALUE`, e.g. `--column-default source:import`. The value is bound as
+    /// text for every row, so the database converts it to the type of the column. Can be specified
+    /// multiple times. It is an error to specify a default for a column present in the file.
+    #[arg(
+        long,
+        value_parser=column_default_from_str,
+        action = ArgAction::Append,
+        conflicts_with = "create_table"
+    )]
+    column_default: Vec<(String, String)>,
+    /// Do not insert this column of the parquet file, e.g. bookkeeping columns which do not exist
+    /// in the table. Refers to the name in the parquet file, before applying `--strip-prefix` and
+    /// `--strip-suffix`. Can be specified multiple times.
+    #[arg(long, action = ArgAction::Append)]
+    skip_columns: Vec<String>,
+    /// What to do with DECIMAL values which have more integer digits than the target column can
+    /// hold. `error` aborts the insert, `null` inserts NULL, and `clamp` inserts the largest, or
+    /// smallest, value of the target column. Precision and scale of the target column are
+    /// described by the driver. If it can not describe them, values are passed on unchecked.
+    #[arg(long, value_enum, default_value = "error")]
+    numeric_overflow: NumericOverflowArgument,
+    /// If inserting a chunk of rows fails, insert its rows one by one instead of aborting. Rows
+    /// which can still not be inserted are logged and skipped, and the total number of skipped rows
+    /// is reported at the end. Each chunk, or row, is committed on its own. Slow, if many chunks
+    /// contain invalid rows.
+    #[arg(long, conflicts_with_all = ["transaction", "truncate_table"])]
+    continue_on_error: bool,
+    /// Write the rows skipped by `--continue-on-error` into a parquet file at this path. It has the
+    /// schema of the inserted columns. The file is created, even if no rows are skipped.
+    #[arg(long, requires = "continue_on_error")]
+    rejects_output: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -278,6 +1071,8 @@ pub struct ExecOpt {
     /// `Auto`: Since on OS-X and Linux the default locales character set is always UTF-8 the
     /// default option is the same as `System` on non-windows platforms. On windows the default is
     /// `Utf16`.
+    ///
+    /// `Utf8Lossy`: Same as `System`, since text in parquet is always valid UTF-8.
     #[arg(long, value_enum, default_value = "Auto", ignore_case = true)]
     encoding: EncodingArgument,
     /// Path to the input parquet file which is used to fill the database table with values.
@@ -297,13 +1092,71 @@ impl Cli {
     /// clap.
     pub fn perform_extra_validation(&self) -> Result<(), Error> {
         if let Command::Query { query_opt } = &self.command {
+            if let ParquetVersionArgument::V1 = query_opt.parquet_version {
+                if let Some((column, encoding)) = query_opt
+                    .parquet_column_encoding
+                    .iter()
+                    .find(|(_, encoding)| ParquetVersionArgument::requires_v2(*encoding))
+                {
+                    bail!(
+                        "Encoding {encoding} for column '{column}' requires parquet version 2.0, \
+                        but --parquet-version 1.0 has been specified."
+                    )
+                }
+                if let Some((sql_type, encoding)) = query_opt
+                    .encoding_for_type
+                    .iter()
+                    .find(|(_, encoding)| ParquetVersionArgument::requires_v2(*encoding))
+                {
+                    bail!(
+                        "Encoding {encoding} for type {sql_type:?} requires parquet version 2.0, \
+                        but --parquet-version 1.0 has been specified."
+                    )
+                }
+            }
+            if query_opt.rows_per_file == Some(0) {
+                bail!("rows-per-file must be at least 1.")
+            }
+            if query_opt.row_group_size_rows == Some(0) {
+                bail!("row-group-size-rows must be at least 1.")
+            }
+            if query_opt.append_to_dataset {
+                if query_opt.row_groups_per_file == 0
+                    && query_opt.file_size_threshold.is_none()
+                    && query_opt.max_file_size.is_none()
+                    && query_opt.rows_per_file.is_none()
+                {
+                    bail!(
+                        "append-to-dataset requires the output to be split into numbered files, \
+                        e.g. with row-groups-per-file."
+                    )
+                }
+                if !matches!(query_opt.format, OutputFormatArgument::Parquet) {
+                    bail!("append-to-dataset is only supported for parquet output.")
+                }
+            }
             if !query_opt.output.is_file() {
                 if query_opt.file_size_threshold.is_some() {
                     bail!("file-size-threshold conflicts with specifying stdout ('-') as output.")
                 }
+                if query_opt.max_file_size.is_some() {
+                    bail!("max-file-size conflicts with specifying stdout ('-') as output.")
+                }
                 if query_opt.row_groups_per_file != 0 {
                     bail!("row-groups-per-file conflicts with specifying stdout ('-') as output.")
                 }
+                if query_opt.rows_per_file.is_some() {
+                    bail!("rows-per-file conflicts with specifying stdout ('-') as output.")
+                }
+                if query_opt.split_key.is_some() {
+                    bail!("split-key conflicts with specifying stdout ('-') as output.")
+                }
+                if !query_opt.partition_by.is_empty() {
+                    bail!("partition-by conflicts with specifying stdout ('-') as output.")
+                }
+                if query_opt.split_by_date.is_some() {
+                    bail!("split-by-date conflicts with specifying stdout ('-') as output.")
+                }
             }
         }
         Ok(())
@@ -311,7 +1164,8 @@ impl Cli {
 }
 
 fn main() -> Result<(), Error> {
-    let opt = Cli::parse();
+    let merged = config::merge_config_file(Cli::command(), std::env::args_os().collect())?;
+    let opt = Cli::parse_from(merged.args);
     opt.perform_extra_validation()?;
 
     let verbose = if opt.quiet {
@@ -339,41 +1193,50 @@ fn main() -> Result<(), Error> {
         .init()
         .unwrap();
 
+    if let Some(config) = &opt.config {
+        info!("Read options from config file '{}'.", config.display());
+        for key in &merged.secrets {
+            warn!(
+                "The config file '{}' contains the {} in plain text. Make sure only you can read \
+                it.",
+                config.display(),
+                key.replace('_', " ")
+            );
+        }
+    }
+
+    // Dropped at the end of `main`, which disables tracing again.
+    let _odbc_trace = opt
+        .odbc_trace
+        .as_deref()
+        .map(trace::OdbcTrace::enable)
+        .transpose()?;
+
     // Initialize ODBC environment used to create the connection to the Database. We now use the
     // singleton pattern with `environment`. This makes our life easier if using concurrent fetching
     // since it allows us to create an environment with a 'static lifetime. From this point forward
     // in the application, we may assume that calls to environment are succesful, since any error
     // creating the environment must occur now.
-    let odbc_env = environment()?;
+    let odbc_env = init_environment(opt.connection_pooling, opt.connection_pooling_matching)?;
 
     match opt.command {
         Command::Query { query_opt } => {
-            query::query(query_opt)?;
+            query::query(*query_opt)?;
+        }
+        Command::Schema { schema_opt } => {
+            query::schema(schema_opt)?;
+        }
+        Command::Count { count_opt } => {
+            query::count(count_opt)?;
         }
         Command::Insert { insert_opt } => {
             insert::insert(&insert_opt)?;
         }
-        Command::ListDrivers => {
-            for driver_info in odbc_env.drivers()? {
-                println!("{}", driver_info.description);
-                for (key, value) in &driver_info.attributes {
-                    println!("\t{key}={value}");
-                }
-                println!()
-            }
+        Command::ListDrivers { list_opt } => {
+            list::list_drivers(odbc_env, &list_opt)?;
         }
-        Command::ListDataSources => {
-            let mut first = true;
-            for data_source_info in odbc_env.data_sources()? {
-                // After first item, always place an additional newline in between.
-                if first {
-                    first = false;
-                } else {
-                    println!()
-                }
-                println!("Server name: {}", data_source_info.server_name);
-                println!("Driver: {}", data_source_info.driver);
-            }
+        Command::ListDataSources { list_opt } => {
+            list::list_data_sources(odbc_env, &list_opt)?;
         }
         Command::Completions { shell, output } => {
             let output = output.open_as_output()?;
@@ -1,9 +1,11 @@
-use anyhow::Error;
+use anyhow::{bail, Error};
+use chrono::{Datelike, NaiveDate};
+use odbc_api::sys::Timestamp;
 use parquet::{
     column::{reader::ColumnReaderImpl, writer::ColumnWriterImpl},
-    data_type::{ByteArray, DataType, FixedLenByteArray, FixedLenByteArrayType},
+    data_type::{ByteArray, DataType, FixedLenByteArray, FixedLenByteArrayType, Int96, Int96Type},
 };
-use std::mem::size_of;
+use std::mem::{size_of, take};
 
 /// Holds preallocated buffers for every possible physical parquet type. This way we do not need to
 /// reallocate them.
@@ -14,8 +16,13 @@ pub struct ParquetBuffer {
     pub values_f64: Vec<f64>,
     pub values_bytes_array: Vec<ByteArray>,
     pub values_fixed_bytes_array: Vec<FixedLenByteArray>,
+    pub values_int96: Vec<Int96>,
     pub values_bool: Vec<bool>,
     pub def_levels: Vec<i16>,
+    /// Indices of the rows in the fetch buffer which should be written into the parquet column.
+    /// `None` if all fetched rows are written. Used to write only a subset of a batch into a row
+    /// group, e.g. if the rows of a batch belong to different partitions.
+    row_selection: Option<Vec<usize>>,
 }
 
 impl ParquetBuffer {
@@ -27,6 +34,7 @@ impl ParquetBuffer {
         + size_of::<f64>()
         + size_of::<ByteArray>()
         + size_of::<FixedLenByteArrayType>()
+        + size_of::<Int96>()
         + size_of::<bool>()
         + size_of::<i16>();
 
@@ -38,8 +46,10 @@ impl ParquetBuffer {
             values_f64: Vec::with_capacity(batch_size),
             values_bytes_array: Vec::with_capacity(batch_size),
             values_fixed_bytes_array: Vec::with_capacity(batch_size),
+            values_int96: Vec::with_capacity(batch_size),
             values_bool: Vec::with_capacity(batch_size),
             def_levels: Vec::with_capacity(batch_size),
+            row_selection: None,
         }
     }
 
@@ -52,7 +62,23 @@ impl ParquetBuffer {
         self.values_bytes_array.resize(num_rows, ByteArray::new());
         self.values_fixed_bytes_array
             .resize(num_rows, ByteArray::new().into());
+        self.values_int96.resize(num_rows, Int96::new());
         self.values_bool.resize(num_rows, false);
+        self.row_selection = None;
+    }
+
+    /// Only write the rows with the specified indices into the parquet columns. Indices must be
+    /// sorted in ascending order. The selection is reset by the next call to
+    /// [`Self::set_num_rows_fetched`].
+    pub fn select_rows(&mut self, rows: Vec<usize>) {
+        self.set_num_rows_fetched(rows.len());
+        self.row_selection = Some(rows);
+    }
+
+    /// Indices of the rows selected with [`Self::select_rows`], or `None` if all rows fetched are
+    /// written.
+    pub fn row_selection(&self) -> Option<&[usize]> {
+        self.row_selection.as_deref()
     }
 
     /// Writes an i128 twos complement representation into a fixed sized byte array
@@ -70,7 +96,54 @@ impl ParquetBuffer {
         })
     }
 
+    /// Writes big endian twos complement representations, wider than 128 Bit, into a fixed sized
+    /// byte array. Only the last `length_in_bytes` bytes of each value are written.
+    pub fn write_twos_complement_bytes(
+        &mut self,
+        cw: &mut ColumnWriterImpl<FixedLenByteArrayType>,
+        source: impl Iterator<Item = Option<[u8; 32]>>,
+        length_in_bytes: usize,
+    ) -> Result<(), Error> {
+        self.write_optional_any_fallible(cw, source.map(Ok), |bytes| {
+            let out = bytes[(32 - length_in_bytes)..].to_owned();
+            // Vec<u8> -> ByteArray -> FixedLenByteArray
+            let out: ByteArray = out.into();
+            out.into()
+        })
+    }
+
+    /// Writes timestamps in the deprecated INT96 representation. The first eight bytes hold the
+    /// nanoseconds since midnight, the last four bytes the julian day.
+    pub fn write_timestamps_int96<'a>(
+        &mut self,
+        cw: &mut ColumnWriterImpl<Int96Type>,
+        source: impl Iterator<Item = Option<&'a Timestamp>>,
+    ) -> Result<(), Error> {
+        self.write_optional_any_fallible(cw, source.map(Ok), timestamp_to_int96)
+    }
+
     fn write_optional_any_fallible<T, S>(
+        &mut self,
+        cw: &mut ColumnWriterImpl<T>,
+        source: impl Iterator<Item = Result<Option<S>, Error>>,
+        into_physical: impl FnMut(S) -> T::T,
+    ) -> Result<(), Error>
+    where
+        T: DataType,
+        T::T: BufferedDataType,
+    {
+        // Take the selection out of the buffer, so we can borrow the value buffers mutably.
+        let row_selection = take(&mut self.row_selection);
+        let result = if let Some(rows) = &row_selection {
+            self.write_all_optional(cw, SelectedRows::new(source, rows), into_physical)
+        } else {
+            self.write_all_optional(cw, source, into_physical)
+        };
+        self.row_selection = row_selection;
+        result
+    }
+
+    fn write_all_optional<T, S>(
         &mut self,
         cw: &mut ColumnWriterImpl<T>,
         source: impl Iterator<Item = Result<Option<S>, Error>>,
@@ -125,6 +198,54 @@ impl ParquetBuffer {
         self.write_optional_any_fallible(cw, source.map(Ok), |s| s)
     }
 
+    /// Write a column of lists with optional elements, i.e. a group annotated with the `LIST`
+    /// logical type with a repeated group `list` containing an optional `element`. Each item of
+    /// `source` is one row. If `optional` is `false`, the lists themselves must not be `NULL`.
+    pub fn write_lists<T>(
+        &mut self,
+        cw: &mut ColumnWriterImpl<T>,
+        source: impl Iterator<Item = Result<Option<Vec<Option<T::T>>>, Error>>,
+        optional: bool,
+    ) -> Result<(), Error>
+    where
+        T: DataType,
+    {
+        let row_selection = take(&mut self.row_selection);
+        let result = if let Some(rows) = &row_selection {
+            write_all_lists(cw, SelectedRows::new(source, rows), optional)
+        } else {
+            write_all_lists(cw, source, optional)
+        };
+        self.row_selection = row_selection;
+        result
+    }
+
+    /// Write the values of a required column, which do not need any transformation. If all rows
+    /// are selected the values are passed directly to the column writer without any copy.
+    pub fn write_required<T>(
+        &mut self,
+        cw: &mut ColumnWriterImpl<T>,
+        values: &[T::T],
+    ) -> Result<(), Error>
+    where
+        T: DataType,
+        T::T: BufferedDataType,
+    {
+        let row_selection = take(&mut self.row_selection);
+        let result = if let Some(rows) = &row_selection {
+            let (buf, _def_levels) = T::T::mut_buf(self);
+            for (target, &row) in buf.iter_mut().zip(rows) {
+                *target = values[row].clone();
+            }
+            cw.write_batch(buf, None, None)
+        } else {
+            cw.write_batch(values, None, None)
+        };
+        self.row_selection = row_selection;
+        result?;
+        Ok(())
+    }
+
     /// Iterate over the elements of a column reader over an optional column.
     ///
     /// Be careful with calling this method on required columns as the bound definition buffer will
@@ -181,6 +302,81 @@ impl ParquetBuffer {
     }
 }
 
+/// The number of elements in a list varies, so the values and levels are not buffered in the
+/// preallocated buffers, which hold one value per row.
+fn write_all_lists<T>(
+    cw: &mut ColumnWriterImpl<T>,
+    source: impl Iterator<Item = Result<Option<Vec<Option<T::T>>>, Error>>,
+    optional: bool,
+) -> Result<(), Error>
+where
+    T: DataType,
+{
+    // Definition level of an empty list. Elements increase it by one if present in the repeated
+    // group and by another one if they are not NULL.
+    let empty = i16::from(optional);
+    let mut values = Vec::new();
+    let mut def_levels = Vec::new();
+    let mut rep_levels = Vec::new();
+    for item in source {
+        let Some(elements) = item? else {
+            if !optional {
+                bail!("NULL value for a list column, which is not nullable.")
+            }
+            def_levels.push(0);
+            rep_levels.push(0);
+            continue;
+        };
+        if elements.is_empty() {
+            def_levels.push(empty);
+            rep_levels.push(0);
+        }
+        for (index, element) in elements.into_iter().enumerate() {
+            rep_levels.push(if index == 0 { 0 } else { 1 });
+            if let Some(value) = element {
+                values.push(value);
+                def_levels.push(empty + 2);
+            } else {
+                def_levels.push(empty + 1);
+            }
+        }
+    }
+    cw.write_batch(&values, Some(&def_levels), Some(&rep_levels))?;
+    Ok(())
+}
+
+/// Adapts an iterator over all rows of a fetch buffer, so it only yields the selected rows.
+struct SelectedRows<'a, I> {
+    source: I,
+    rows: std::slice::Iter<'a, usize>,
+    /// Index of the row the next item of source belongs to.
+    next_row: usize,
+}
+
+impl<'a, I> SelectedRows<'a, I> {
+    fn new(source: I, rows: &'a [usize]) -> Self {
+        Self {
+            source,
+            rows: rows.iter(),
+            next_row: 0,
+        }
+    }
+}
+
+impl<I> Iterator for SelectedRows<'_, I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = *self.rows.next()?;
+        let item = self.source.nth(row - self.next_row);
+        self.next_row = row + 1;
+        item
+    }
+}
+
 pub trait BufferedDataType: Sized {
     /// The tuple returned is (Values, Definition levels)
     fn mut_buf(buffer: &mut ParquetBuffer) -> (&mut Vec<Self>, &mut Vec<i16>);
@@ -228,14 +424,115 @@ impl BufferedDataType for FixedLenByteArray {
     }
 }
 
+impl BufferedDataType for Int96 {
+    fn mut_buf(buffer: &mut ParquetBuffer) -> (&mut Vec<Self>, &mut Vec<i16>) {
+        (&mut buffer.values_int96, &mut buffer.def_levels)
+    }
+}
+
+/// Julian day of 0000-12-31, the day before the first day of the common era.
+const JULIAN_DAY_OF_CE: i32 = 1_721_425;
+
+fn timestamp_to_int96(ts: &Timestamp) -> Int96 {
+    let julian_day = NaiveDate::from_ymd_opt(ts.year as i32, ts.month as u32, ts.day as u32)
+        .unwrap()
+        .num_days_from_ce()
+        + JULIAN_DAY_OF_CE;
+    let nanos_of_day = (ts.hour as u64 * 3600 + ts.minute as u64 * 60 + ts.second as u64)
+        * 1_000_000_000
+        + ts.fraction as u64;
+    let mut int96 = Int96::new();
+    int96.set_data(
+        nanos_of_day as u32,
+        (nanos_of_day >> 32) as u32,
+        julian_day as u32,
+    );
+    int96
+}
+
 #[cfg(test)]
 mod test {
 
-    use super::ParquetBuffer;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use odbc_api::sys::Timestamp;
+    use parquet::{
+        data_type::Int32Type,
+        file::{
+            reader::{FileReader, SerializedFileReader},
+            writer::SerializedFileWriter,
+        },
+        schema::parser::parse_message_type,
+    };
+
+    use super::{timestamp_to_int96, ParquetBuffer, SelectedRows};
 
     #[test]
     #[cfg(target_pointer_width = "64")] // Memory usage is platform dependent
     fn memory_usage() {
-        assert_eq!(59, ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW);
+        assert_eq!(71, ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW);
+    }
+
+    #[test]
+    fn timestamp_as_int96() {
+        let ts = Timestamp {
+            year: 2021,
+            month: 3,
+            day: 4,
+            hour: 12,
+            minute: 34,
+            second: 56,
+            fraction: 123_456_789,
+        };
+        let int96 = timestamp_to_int96(&ts);
+        // 2021-03-04 is julian day 2459278
+        assert_eq!(2_459_278, int96.data()[2]);
+        assert_eq!(1_614_861_296_123_456_789, int96.to_nanos());
+    }
+
+    #[test]
+    fn write_list_column() {
+        let schema = parse_message_type(
+            "message schema {
+                OPTIONAL GROUP a (LIST) {
+                    REPEATED GROUP list {
+                        OPTIONAL INT32 element;
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let mut file = Vec::new();
+        let mut writer =
+            SerializedFileWriter::new(&mut file, Arc::new(schema), Default::default()).unwrap();
+        let mut row_group_writer = writer.next_row_group().unwrap();
+        let mut column_writer = row_group_writer.next_column().unwrap().unwrap();
+        let lists = [Some(vec![Some(1), None, Some(3)]), Some(vec![]), None];
+        ParquetBuffer::new(3)
+            .write_lists(
+                column_writer.typed::<Int32Type>(),
+                lists.into_iter().map(Ok),
+                true,
+            )
+            .unwrap();
+        column_writer.close().unwrap();
+        row_group_writer.close().unwrap();
+        writer.close().unwrap();
+
+        let reader = SerializedFileReader::new(Bytes::from(file)).unwrap();
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        assert_eq!(vec!["{a: [1, null, 3]}", "{a: []}", "{a: null}"], rows);
+    }
+
+    #[test]
+    fn only_iterate_selected_rows() {
+        let rows = [0, 2, 3, 6];
+        let selected: Vec<_> = SelectedRows::new("abcdefg".chars(), &rows).collect();
+        assert_eq!(vec!['a', 'c', 'd', 'g'], selected);
     }
 }
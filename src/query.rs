@@ -1,109 +1,610 @@
+mod array;
 mod batch_size_limit;
 mod binary;
 mod boolean;
+mod column_names;
 mod column_strategy;
 mod conversion_strategy;
+mod count;
+mod csv;
 mod current_file;
 mod date;
 mod decimal;
+mod encoding_loss;
+mod epoch;
+mod explain;
 mod fetch_batch;
+mod guid;
 mod identical;
+mod interrupt;
+mod interval;
+mod ndjson;
+mod non_null;
+mod output_template;
+mod parallel;
+mod param_parquet;
+mod parameters_file;
 mod parquet_writer;
+mod partition;
+mod profile;
+mod progress;
+mod row_group_buffer;
+mod schema;
+mod summary;
 mod text;
+mod text_length;
 mod time;
 mod timestamp;
 mod timestamp_precision;
 mod timestamp_tz;
+mod truncation;
 
-use anyhow::Error;
-use fetch_batch::{fetch_strategy, FetchBatch};
+use anyhow::{bail, Context, Error};
+use chrono::Utc;
+use fetch_batch::{fetch_strategy, FetchBatch, FetchRetries};
 use io_arg::IoArg;
-use log::info;
-use odbc_api::{Cursor, IntoParameter};
-use std::io::{stdin, Read};
+use log::{info, warn};
+use odbc_api::{
+    handles::{AsStatementRef, Statement},
+    sys::{Pointer, SQLGetStmtAttr, SQLSetStmtAttr, SqlReturn, StatementAttribute},
+    Connection, CursorImpl, ParameterCollectionRef, ResultSetMetadata, StatementConnection,
+};
+use parquet::{
+    file::metadata::KeyValue,
+    schema::{printer::print_schema, types::ColumnPath},
+};
+use std::{
+    fs::{read_to_string, File},
+    io::{stdin, Read},
+    path::{Path, PathBuf},
+    ptr::null_mut,
+    time::Duration,
+};
 
 use self::{
     batch_size_limit::{BatchSizeLimit, FileSizeLimit},
-    column_strategy::{ColumnStrategy, MappingOptions},
+    column_names::{original_names_to_json, ORIGINAL_COLUMN_NAMES_KEY},
+    column_strategy::{individual_column_length_limits, ColumnStrategy, MappingOptions},
     conversion_strategy::ConversionStrategy,
-    parquet_writer::{parquet_output, ParquetWriterOptions},
+    csv::CsvOptions,
+    encoding_loss::OnEncodingLoss,
+    explain::explain_query,
+    interrupt::install_interrupt_handler,
+    ndjson::{cursor_to_text, TextFormat},
+    output_template::OutputTemplate,
+    parallel::ParallelExport,
+    param_parquet::{read_parameter_rows, RepeatedFetch},
+    parameters_file::{
+        bind_parameters, count_placeholders, describe_bound_query, read_parameters_file,
+        split_stdin_parameters,
+    },
+    parquet_writer::{describe_output, parquet_output, writer_properties, ParquetWriterOptions},
+    progress::Progress,
+    text_length::text_lengths_from_data,
 };
 
-use crate::{connection::open_connection, QueryOpt};
+use crate::{
+    connection::{is_unsupported_attribute, open_connection},
+    enum_args::{DateGranularityArgument, OutputFormatArgument, SqlDialectArgument},
+    parquet_buffer::ParquetBuffer,
+    QueryOpt,
+};
+
+pub use self::{count::count, schema::schema};
 
 /// Execute a query and writes the result to parquet.
 pub fn query(opt: QueryOpt) -> Result<(), Error> {
     let QueryOpt {
         connect_opts,
         output,
-        parameters,
+        mut parameters,
+        parameters_file,
         query,
         batch_size_row,
         batch_size_memory,
+        max_total_memory,
         row_groups_per_file,
         sequential_fetching,
+        fetch_buffer_count,
         file_size_threshold,
-        encoding,
-        prefer_varbinary,
+        max_file_size,
+        rows_per_file,
+        row_group_size_rows,
         column_compression_default,
         column_compression_level_default,
+        column_compression,
         parquet_column_encoding,
-        avoid_decimal,
-        driver_does_not_support_64bit_integers,
+        encoding_for_type,
+        bloom_filter,
+        no_statistics,
+        no_statistics_all,
+        no_column_index,
+        no_dictionary,
+        no_dictionary_all,
+        data_page_size_bytes,
+        dictionary_page_size_bytes,
         suffix_length,
         no_empty_file,
-        column_length_limit,
+        no_success_file,
+        atomic_output,
+        file_mode,
+        append_to_dataset,
+        partition_by,
+        max_open_partitions,
+        split_by_date,
+        mapping_opts,
+        size_text_from_data,
+        limit,
+        embed_query_metadata,
+        created_by,
+        deterministic_output,
+        dry_run,
+        explain,
+        explain_analyze,
+        sql_dialect,
+        max_retries,
+        retry_backoff_ms,
+        parquet_version,
+        query_timeout_sec,
+        server_side_limit,
+        column_select,
+        output_template,
+        parallel,
+        split_key,
+        format,
+        csv_delimiter,
+        csv_quote,
+        csv_no_header,
+        progress,
+        progress_interval_sec,
+        error_on_truncation,
+        error_on_encoding_loss,
+        require_non_null,
+        verbose_sql,
+        bom,
+        pre_sql,
+        profile_output,
+        summary,
+        param_type,
+        null_param,
+        param_parquet,
     } = opt;
 
+    if format != OutputFormatArgument::Parquet
+        && (!partition_by.is_empty()
+            || split_by_date.is_some()
+            || split_key.is_some()
+            || !column_select.is_empty()
+            || dry_run)
+    {
+        bail!(
+            "Text output formats can not be combined with `--partition-by`, `--split-by-date`, \
+            `--split-key`, `--column-select` or `--dry-run`."
+        )
+    }
+    if format != OutputFormatArgument::Parquet && (max_retries != 0 || fetch_buffer_count.is_some())
+    {
+        bail!(
+            "Text output formats always fetch sequentially and without retries. `--max-retries`, \
+            `--retry-backoff-ms` and `--fetch-buffer-count` only apply to `--format parquet`."
+        )
+    }
+
+    let on_encoding_loss = OnEncodingLoss::new(mapping_opts.encoding, error_on_encoding_loss)?;
+
+    if format != OutputFormatArgument::Csv
+        && (csv_delimiter.is_some() || csv_quote.is_some() || csv_no_header)
+    {
+        bail!(
+            "`--csv-delimiter`, `--csv-quote` and `--csv-no-header` only apply to `--format csv`."
+        )
+    }
+    let csv = CsvOptions {
+        delimiter: csv_delimiter.unwrap_or(','),
+        quote: csv_quote.unwrap_or('"'),
+        header: !csv_no_header,
+    };
+    if csv.delimiter == csv.quote {
+        bail!("The CSV delimiter and quote character must be different.")
+    }
+
+    if param_parquet.is_some() && format != OutputFormatArgument::Parquet {
+        bail!("`--param-parquet` is only supported for `--format parquet`.")
+    }
+
+    if profile_output.is_some() && format != OutputFormatArgument::Parquet {
+        bail!("`--profile-output` is only supported for `--format parquet`.")
+    }
+
+    if bom && format == OutputFormatArgument::Parquet {
+        bail!("`--bom` only applies to text output formats, like `--format ndjson`.")
+    }
+
+    if mapping_opts.timestamp_int96 {
+        warn!(
+            "INT96 timestamps are deprecated. Only use `--timestamp-int96` if your reader does not \
+            support the logical TIMESTAMP type."
+        );
+    }
+    for (option, page_size) in [
+        ("data-page-size-bytes", data_page_size_bytes),
+        ("dictionary-page-size-bytes", dictionary_page_size_bytes),
+    ] {
+        match (page_size, batch_size_memory) {
+            (Some(0), _) => bail!("`--{option}` must be larger than 0."),
+            (Some(page_size), Some(memory)) if page_size as u64 > memory.as_u64() => warn!(
+                "`--{option}` is larger than `--batch-size-memory`. Pages will never be split."
+            ),
+            _ => (),
+        }
+    }
+    // Drivers may ignore the server side limit, so it is enforced client side, too.
+    let limit = match (limit, server_side_limit) {
+        (Some(limit), Some(max_rows)) => Some(limit.min(max_rows)),
+        (limit, max_rows) => limit.or(max_rows),
+    };
     let batch_size = BatchSizeLimit::new(batch_size_row, batch_size_memory);
-    let file_size = FileSizeLimit::new(row_groups_per_file, file_size_threshold);
-    let query = query_statement_text(query)?;
+    let fetch_buffer_count = match fetch_buffer_count {
+        _ if sequential_fetching => 1,
+        Some(count) => count as usize,
+        // Double buffering
+        None => 2,
+    };
+    let retries = FetchRetries {
+        max_retries,
+        backoff: Duration::from_millis(retry_backoff_ms),
+    };
+    if max_file_size.is_some() && format != OutputFormatArgument::Parquet {
+        bail!("`--max-file-size` is only supported for `--format parquet`.")
+    }
+    let file_size = FileSizeLimit::new(
+        row_groups_per_file,
+        file_size_threshold,
+        rows_per_file,
+        max_file_size,
+    );
+    let output_template = output_template
+        .as_deref()
+        .map(OutputTemplate::parse)
+        .transpose()?;
+    // A parallel export writes one file per worker.
+    let output_is_splitted =
+        file_size.output_is_splitted() || split_key.is_some() || split_by_date.is_some();
+    if output_is_splitted
+        && output_template
+            .as_ref()
+            .is_some_and(|template| !template.has_index())
+    {
+        bail!(
+            "The output template must contain an '{{index}}' placeholder, if the output is split \
+            into multiple files. Otherwise the files would overwrite each other."
+        )
+    }
+    if query == "-" && parameters_file.as_ref().is_some_and(|file| !file.is_file()) {
+        bail!("Query and parameters can not both be read from standard input.")
+    }
+    let query_from_stdin = query == "-";
+    let mut query = query_statement_text(query)?;
+    let pre_sql = pre_sql_statements(pre_sql)?;
+    let mut has_additional_parameters = false;
+    if query_from_stdin {
+        if let Some((text, stdin_parameters)) = split_stdin_parameters(&query) {
+            parameters.extend(stdin_parameters);
+            query = text.to_owned();
+            has_additional_parameters = true;
+        }
+    }
+    if let Some(parameters_file) = parameters_file {
+        parameters.extend(read_parameters_file(parameters_file)?);
+        has_additional_parameters = true;
+    }
+    if has_additional_parameters {
+        let num_placeholders = count_placeholders(&query);
+        if num_placeholders != parameters.len() {
+            bail!(
+                "The query contains {num_placeholders} placeholders, but {} parameters have been \
+                specified.",
+                parameters.len()
+            )
+        }
+    }
 
     // Convert the input strings into parameters suitable for use with ODBC.
-    let params: Vec<_> = parameters
-        .iter()
-        .map(|param| param.as_str().into_parameter())
-        .collect();
+    let params = bind_parameters(&parameters, &param_type, &null_param)?;
+    let parameter_rows = param_parquet
+        .as_deref()
+        .map(read_parameter_rows)
+        .transpose()?;
+    if let Some(first_row) = parameter_rows.as_ref().and_then(|rows| rows.first()) {
+        let num_placeholders = count_placeholders(&query);
+        if num_placeholders != first_row.len() {
+            bail!(
+                "The query contains {num_placeholders} placeholders, but the parameter file has {} \
+                columns.",
+                first_row.len()
+            )
+        }
+    }
 
     let odbc_conn = open_connection(&connect_opts)?;
+    execute_pre_sql(&odbc_conn, &pre_sql)?;
+    install_interrupt_handler();
     let db_name = odbc_conn.database_management_system_name()?;
     info!("Database Management System Name: {db_name}");
 
+    if explain || explain_analyze {
+        let dialect = match sql_dialect {
+            Some(dialect) => dialect,
+            None => {
+                let Some(dialect) = SqlDialectArgument::from_dbms_name(&db_name) else {
+                    bail!(
+                        "Can not infer the SQL dialect of '{db_name}' in order to explain the \
+                        query. Please specify it using `--sql-dialect`."
+                    )
+                };
+                dialect
+            }
+        };
+        return explain_query(
+            &odbc_conn,
+            &query,
+            params.as_slice(),
+            dialect,
+            explain_analyze,
+        );
+    }
+
+    let mut key_value_metadata = Vec::new();
+    if embed_query_metadata {
+        key_value_metadata.push(KeyValue::new(QUERY_KEY.to_owned(), query.clone()));
+        if !deterministic_output {
+            key_value_metadata.push(KeyValue::new(
+                EXPORTED_AT_KEY.to_owned(),
+                Utc::now().to_rfc3339(),
+            ));
+        }
+    }
+
     let parquet_format_options = ParquetWriterOptions {
         column_compression_default: column_compression_default
             .to_compression(column_compression_level_default)?,
+        column_compressions: column_compression,
         column_encodings: parquet_column_encoding,
+        encodings_for_type: encoding_for_type,
+        bloom_filters: bloom_filter,
+        no_statistics,
+        no_statistics_all,
+        no_column_index,
+        no_dictionary,
+        no_dictionary_all,
+        data_page_size: data_page_size_bytes,
+        dictionary_page_size: dictionary_page_size_bytes,
         file_size,
         suffix_length,
+        output_template,
+        row_group_size: row_group_size_rows,
         no_empty_file,
+        partitioned: !partition_by.is_empty(),
+        max_open_partitions,
+        split_by_date: split_by_date.is_some(),
+        key_value_metadata,
+        writer_version: parquet_version.to_writer_version(),
+        created_by,
+        atomic_output,
+        file_mode,
+        append_to_dataset,
+    };
+
+    let column_length_limits = individual_column_length_limits(&mapping_opts);
+    let text_lengths = if size_text_from_data {
+        text_lengths_from_data(
+            &odbc_conn,
+            &query,
+            params.as_slice(),
+            &db_name,
+            mapping_opts.encoding.use_utf16(),
+        )?
+    } else {
+        Vec::new()
     };
 
-    let mapping_options = MappingOptions {
-        db_name: &db_name,
-        use_utf16: encoding.use_utf16(),
-        prefer_varbinary,
-        avoid_decimal,
-        driver_does_support_i64: !driver_does_not_support_64bit_integers,
-        column_length_limit,
+    let mapping_options = MappingOptions::from_opts(
+        &mapping_opts,
+        &db_name,
+        &column_length_limits,
+        &text_lengths,
+    );
+
+    if dry_run {
+        return dry_run_query(
+            &odbc_conn,
+            &query,
+            mapping_options,
+            &partition_by,
+            split_by_date.as_ref(),
+            &column_select,
+            batch_size,
+            &output,
+            &parquet_format_options,
+        );
+    }
+
+    if verbose_sql {
+        // Printed directly, so it is visible independent of the log level.
+        eprintln!("{}", describe_bound_query(&query, &parameters, &null_param));
+    }
+
+    if cfg!(not(unix)) && file_mode.is_some() {
+        warn!("`--file-mode` is only supported on Unix. The permissions of the files are not set.");
+    }
+
+    if atomic_output && matches!(output, IoArg::StdStream) {
+        warn!("`--atomic-output` has no effect, since the output is written to standard output.");
+    }
+
+    // Spark and friends expect a marker file next to the parts of a multi file dataset.
+    let success_file = match &output {
+        IoArg::File(path) if output_is_splitted && !no_success_file => {
+            Some(path.with_file_name("_SUCCESS"))
+        }
+        _ => None,
     };
 
-    if let Some(cursor) = odbc_conn
-        .into_cursor(&query, params.as_slice(), None)
+    if let (Some(split_key), IoArg::File(path)) = (&split_key, &output) {
+        let parallel_export = ParallelExport {
+            num_workers: parallel.unwrap_or(1) as usize,
+            split_key,
+            query: &query,
+            parameters: &parameters,
+            param_types: &param_type,
+            null_params: &null_param,
+            batch_size,
+            fetch_buffer_count,
+            retries,
+            column_select: &column_select,
+            error_on_truncation,
+            on_encoding_loss,
+            require_non_null: &require_non_null,
+            pre_sql: &pre_sql,
+            max_total_memory,
+            query_timeout_sec,
+        };
+        parallel_export.export(
+            &odbc_conn,
+            &connect_opts,
+            mapping_options,
+            path,
+            parquet_format_options,
+        )?;
+        return create_success_file(success_file);
+    }
+
+    if let Some(parameter_rows) = parameter_rows {
+        info!(
+            "Executing the query once for each of the {} rows of the parameter file.",
+            parameter_rows.len()
+        );
+        let mut prepared = odbc_conn.into_prepared(&query)?;
+        if let Some(timeout_sec) = query_timeout_sec {
+            match prepared.set_query_timeout_sec(timeout_sec) {
+                Err(error) if is_unsupported_attribute(&error) => warn!(
+                    "Driver does not support a query timeout. Executing query without one.\n{error}"
+                ),
+                result => result?,
+            }
+        }
+        cursor_to_parquet(
+            prepared,
+            |prepared, table_strategy| {
+                let fetch_strategy: Box<dyn FetchBatch> = Box::new(RepeatedFetch::new(
+                    prepared,
+                    parameter_rows,
+                    table_strategy,
+                    batch_size,
+                    max_total_memory,
+                )?);
+                Ok(fetch_strategy)
+            },
+            output,
+            mapping_options,
+            &partition_by,
+            split_by_date.as_ref(),
+            &column_select,
+            parquet_format_options,
+            limit,
+            Progress::new(progress, Duration::from_secs(progress_interval_sec), limit),
+            error_on_truncation,
+            on_encoding_loss,
+            &require_non_null,
+            profile_output.as_deref(),
+            summary,
+        )?;
+        return create_success_file(success_file);
+    }
+
+    let cursor = if let Some(max_rows) = server_side_limit {
+        into_cursor_with_max_rows(
+            odbc_conn,
+            &query,
+            params.as_slice(),
+            query_timeout_sec,
+            max_rows,
+        )?
+    } else {
+        match odbc_conn.into_cursor(&query, params.as_slice(), query_timeout_sec) {
+            Err(failed)
+                if query_timeout_sec.is_some() && is_unsupported_attribute(&failed.error) =>
+            {
+                warn!(
+                    "Driver does not support a query timeout. Executing query without one.\n{}",
+                    failed.error
+                );
+                failed
+                    .connection
+                    .into_cursor(&query, params.as_slice(), None)
+            }
+            result => result,
+        }
         // Drop the connection for odbc_api::ConnectionAndError in order to make the error
         // convertible into an anyhow error. The connection is offered by odbc_api in the error type
         // to allow reusing the same connection, even after conversion into cursor failed. However
         // within the context of `odbc2parquet`, we just want to shutdown the application and
         // present an error to the user.
         .map_err(odbc_api::Error::from)?
-    {
+    };
+
+    if let Some(cursor) = cursor {
+        let text_format = match format {
+            OutputFormatArgument::Parquet => None,
+            OutputFormatArgument::Ndjson => Some(TextFormat::Ndjson),
+            OutputFormatArgument::Csv => Some(TextFormat::Csv(csv)),
+        };
+        if let Some(text_format) = text_format {
+            cursor_to_text(
+                cursor,
+                output,
+                text_format,
+                batch_size,
+                mapping_options,
+                &parquet_format_options,
+                limit,
+                error_on_truncation,
+                on_encoding_loss,
+                &require_non_null,
+                summary,
+                bom,
+                max_total_memory,
+                Progress::new(progress, Duration::from_secs(progress_interval_sec), limit),
+            )?;
+            return create_success_file(success_file);
+        }
         cursor_to_parquet(
             cursor,
+            |cursor, table_strategy| {
+                fetch_strategy(
+                    fetch_buffer_count,
+                    cursor,
+                    table_strategy,
+                    batch_size,
+                    retries,
+                    max_total_memory,
+                )
+            },
             output,
-            batch_size,
-            !sequential_fetching,
             mapping_options,
+            &partition_by,
+            split_by_date.as_ref(),
+            &column_select,
             parquet_format_options,
+            limit,
+            Progress::new(progress, Duration::from_secs(progress_interval_sec), limit),
+            error_on_truncation,
+            on_encoding_loss,
+            &require_non_null,
+            profile_output.as_deref(),
+            summary,
         )?;
+        create_success_file(success_file)?;
     } else {
         eprintln!(
             "Query came back empty (not even a schema has been returned). No file has been created"
@@ -112,32 +613,298 @@ pub fn query(opt: QueryOpt) -> Result<(), Error> {
     Ok(())
 }
 
-/// The query statement is either passed verbatim at the command line, or via stdin. The latter is
-/// indicated by passing `-` at the command line instead of the string. This method reads stdin
-/// until EOF if required and always returns the statement text.
+/// Executes the query with `SQL_ATTR_MAX_ROWS` set to `max_rows`. Drivers rejecting the attribute
+/// execute the query without it.
+fn into_cursor_with_max_rows(
+    odbc_conn: Connection<'static>,
+    query: &str,
+    params: impl ParameterCollectionRef,
+    query_timeout_sec: Option<usize>,
+    max_rows: usize,
+) -> Result<Option<CursorImpl<StatementConnection<'static>>>, Error> {
+    let mut preallocated = odbc_conn.into_preallocated()?;
+    if let Some(timeout_sec) = query_timeout_sec {
+        match preallocated.set_query_timeout_sec(timeout_sec) {
+            Err(error) if is_unsupported_attribute(&error) => warn!(
+                "Driver does not support a query timeout. Executing query without one.\n{error}"
+            ),
+            result => result?,
+        }
+    }
+    let stmt = preallocated.as_stmt_ref().as_sys();
+    // `odbc-api` does not offer a safe wrapper for this attribute. The value is passed as integer
+    // in place of the pointer.
+    let ret = unsafe { SQLSetStmtAttr(stmt, StatementAttribute::MaxRows, max_rows as Pointer, 0) };
+    match ret {
+        SqlReturn::SUCCESS => (),
+        SqlReturn::SUCCESS_WITH_INFO => {
+            // The driver substituted a different value, e.g. its own maximum.
+            let mut substituted: usize = 0;
+            let ret = unsafe {
+                SQLGetStmtAttr(
+                    stmt,
+                    StatementAttribute::MaxRows,
+                    &mut substituted as *mut usize as Pointer,
+                    0,
+                    null_mut(),
+                )
+            };
+            if ret == SqlReturn::SUCCESS {
+                info!(
+                    "Driver substituted {substituted} for `--server-side-limit` {max_rows}. The \
+                    limit is still applied client side."
+                );
+            }
+        }
+        _ => warn!(
+            "Driver did not accept `--server-side-limit`. All rows are fetched and the limit is \
+            applied client side."
+        ),
+    }
+    let has_result_set = preallocated
+        .execute(query, params)?
+        // Take the statement of the cursor, rather than dropping the cursor, which would close it.
+        .map(CursorImpl::into_stmt)
+        .is_some();
+    // Safety: The statement is in cursor state, if execution returned a result set.
+    Ok(has_result_set.then(|| unsafe { CursorImpl::new(preallocated.into_statement()) }))
+}
+
+/// Only called after all files have been written and closed without error.
+fn create_success_file(success_file: Option<PathBuf>) -> Result<(), Error> {
+    if let Some(success_file) = success_file {
+        File::create(&success_file).with_context(|| {
+            format!(
+                "Could not create marker file '{}'.",
+                success_file.to_string_lossy()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Prepares the query and maps its result set to a parquet schema, without fetching any rows or
+/// writing any file. Logs what an actual export would do.
+#[allow(clippy::too_many_arguments)]
+fn dry_run_query(
+    odbc_conn: &Connection,
+    query: &str,
+    mapping_options: MappingOptions,
+    partition_by: &[String],
+    split_by_date: Option<&(String, DateGranularityArgument)>,
+    column_select: &[String],
+    batch_size: BatchSizeLimit,
+    output: &IoArg,
+    parquet_format_options: &ParquetWriterOptions,
+) -> Result<(), Error> {
+    let mut prepared = odbc_conn.prepare(query)?;
+    let mut table_strategy =
+        ConversionStrategy::new(&mut prepared, mapping_options, partition_by, column_select)?;
+    if let Some((column, granularity)) = split_by_date {
+        table_strategy.split_by_date(column, *granularity)?;
+    }
+    let total_mem_usage_per_row =
+        table_strategy.fetch_buffer_size_per_row() + ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW;
+    let batch_size_row = batch_size.batch_size_in_rows(total_mem_usage_per_row)?;
+    let properties = writer_properties(parquet_format_options);
+
+    info!("Dry run. No rows are fetched and no file is written.");
+    info!(
+        "Output would be written to {}.",
+        describe_output(output, parquet_format_options)?
+    );
+    let mut schema_text = Vec::new();
+    print_schema(&mut schema_text, &table_strategy.parquet_schema());
+    info!("Parquet schema:\n{}", String::from_utf8_lossy(&schema_text));
+    info!("Row groups would contain up to {batch_size_row} rows.");
+    match parquet_format_options.file_size {
+        FileSizeLimit::None => (),
+        FileSizeLimit::RowGroups(row_groups) => {
+            info!("A new file would be started after {row_groups} row groups.")
+        }
+        FileSizeLimit::Size(size) => info!("A new file would be started after {size}."),
+        FileSizeLimit::Both { row_groups, size } => {
+            info!("A new file would be started after {row_groups} row groups or {size}.")
+        }
+        FileSizeLimit::Rows { rows, size: None } => {
+            info!("A new file would be started after {rows} rows.")
+        }
+        FileSizeLimit::Rows {
+            rows,
+            size: Some(size),
+        } => info!("A new file would be started after {rows} rows or {size}."),
+        FileSizeLimit::Ceiling {
+            max,
+            threshold: None,
+        } => info!("No file would be larger than {max}."),
+        FileSizeLimit::Ceiling {
+            max,
+            threshold: Some(threshold),
+        } => info!(
+            "A new file would be started after {threshold}. No file would be larger than {max}."
+        ),
+    }
+    for field in table_strategy.parquet_schema().get_fields() {
+        let column = ColumnPath::new(vec![field.name().to_owned()]);
+        info!(
+            "Column '{}' would be compressed with {}.",
+            field.name(),
+            properties.compression(&column)
+        );
+    }
+    Ok(())
+}
+
+/// Key in the key value metadata of the parquet file, under which the query text is stored if
+/// `--embed-query-metadata` is set.
+const QUERY_KEY: &str = "odbc2parquet.query";
+
+/// Key in the key value metadata of the parquet file, under which the time of the export is stored
+/// if `--embed-query-metadata` is set.
+const EXPORTED_AT_KEY: &str = "odbc2parquet.exported_at";
+
+/// The query statement is either passed verbatim at the command line, via stdin or read from a
+/// file. Reading from stdin is indicated by passing `-` at the command line instead of the string,
+/// reading from a file by passing its path prefixed with `@`. This method reads stdin until EOF if
+/// required and always returns the statement text.
 fn query_statement_text(query: String) -> Result<String, Error> {
     Ok(if query == "-" {
         let mut buf = String::new();
         stdin().lock().read_to_string(&mut buf)?;
         buf
+    } else if let Some(path) = query.strip_prefix('@') {
+        let text = read_to_string(path)
+            .with_context(|| format!("Could not read query from file '{path}'."))?;
+        if text.trim().is_empty() {
+            bail!("Query file '{path}' is empty.")
+        }
+        text
     } else {
         query
     })
 }
 
-fn cursor_to_parquet(
-    mut cursor: impl Cursor + Send + 'static,
+/// Statements specified with `--pre-sql`. Statements starting with `@` are read from a file.
+fn pre_sql_statements(pre_sql: Vec<String>) -> Result<Vec<String>, Error> {
+    pre_sql
+        .into_iter()
+        .map(|statement| {
+            let Some(path) = statement.strip_prefix('@') else {
+                return Ok(statement);
+            };
+            let text = read_to_string(path)
+                .with_context(|| format!("Could not read pre SQL statement from file '{path}'."))?;
+            if text.trim().is_empty() {
+                bail!("Pre SQL file '{path}' is empty.")
+            }
+            Ok(text)
+        })
+        .collect()
+}
+
+/// Executes the statements specified with `--pre-sql` in order.
+fn execute_pre_sql(odbc_conn: &Connection, statements: &[String]) -> Result<(), Error> {
+    for statement in statements {
+        info!("Execute pre SQL statement: {statement}");
+        let cursor = odbc_conn
+            .execute(statement, (), None)
+            .with_context(|| format!("Pre SQL statement '{statement}' failed."))?;
+        if cursor.is_some() {
+            bail!(
+                "Pre SQL statement '{statement}' returned a result set. Only statements without a \
+                result set, like session settings, are supported."
+            )
+        }
+    }
+    Ok(())
+}
+
+/// Writes the result set into `path`. `into_fetch_strategy` decides how its batches are fetched,
+/// once the columns are mapped to parquet.
+#[allow(clippy::too_many_arguments)]
+fn cursor_to_parquet<R: ResultSetMetadata>(
+    mut result_set: R,
+    into_fetch_strategy: impl FnOnce(R, &ConversionStrategy) -> Result<Box<dyn FetchBatch>, Error>,
     path: IoArg,
-    batch_size: BatchSizeLimit,
-    concurrent_fetching: bool,
     mapping_options: MappingOptions,
+    partition_by: &[String],
+    split_by_date: Option<&(String, DateGranularityArgument)>,
+    column_select: &[String],
     parquet_format_options: ParquetWriterOptions,
+    limit: Option<usize>,
+    mut progress: Progress,
+    error_on_truncation: bool,
+    on_encoding_loss: OnEncodingLoss,
+    require_non_null: &[String],
+    profile_output: Option<&Path>,
+    summary: bool,
 ) -> Result<(), Error> {
-    let table_strategy = ConversionStrategy::new(&mut cursor, mapping_options)?;
+    let mut table_strategy = ConversionStrategy::new(
+        &mut result_set,
+        mapping_options,
+        partition_by,
+        column_select,
+    )?;
+    if let Some((column, granularity)) = split_by_date {
+        table_strategy.split_by_date(column, *granularity)?;
+    }
+    let parquet_format_options =
+        with_original_column_names(parquet_format_options, &table_strategy);
+    let parquet_format_options = with_encodings_for_type(parquet_format_options, &table_strategy);
     let parquet_schema = table_strategy.parquet_schema();
     let writer = parquet_output(path, parquet_schema.clone(), parquet_format_options)?;
-    let fetch_strategy: Box<dyn FetchBatch> =
-        fetch_strategy(concurrent_fetching, cursor, &table_strategy, batch_size)?;
-    table_strategy.block_cursor_to_parquet(fetch_strategy, writer)?;
+    let fetch_strategy = into_fetch_strategy(result_set, &table_strategy)?;
+    table_strategy.block_cursor_to_parquet(
+        fetch_strategy,
+        writer,
+        limit,
+        &mut progress,
+        error_on_truncation,
+        on_encoding_loss,
+        require_non_null,
+        profile_output,
+        summary,
+    )?;
     Ok(())
 }
+
+/// Resolve `--encoding-for-type` into encodings of the individual columns. Encodings specified for
+/// individual columns take precedence, since they are applied afterwards.
+fn with_encodings_for_type(
+    mut parquet_format_options: ParquetWriterOptions,
+    table_strategy: &ConversionStrategy,
+) -> ParquetWriterOptions {
+    let mut column_encodings = Vec::new();
+    for (name, data_type) in table_strategy.exported_column_types() {
+        // Like for individual columns, the last encoding specified for a type wins.
+        if let Some((_, encoding)) = parquet_format_options
+            .encodings_for_type
+            .iter()
+            .rev()
+            .find(|(sql_type, _)| sql_type.matches(data_type))
+        {
+            column_encodings.push((name.to_owned(), *encoding));
+        }
+    }
+    column_encodings.append(&mut parquet_format_options.column_encodings);
+    parquet_format_options.column_encodings = column_encodings;
+    parquet_format_options
+}
+
+/// Preserve the original names of renamed columns in the metadata, so they are not lost to readers
+/// of the file.
+fn with_original_column_names(
+    mut parquet_format_options: ParquetWriterOptions,
+    table_strategy: &ConversionStrategy,
+) -> ParquetWriterOptions {
+    let renamed_columns = table_strategy.renamed_columns();
+    if !renamed_columns.is_empty() {
+        parquet_format_options
+            .key_value_metadata
+            .push(KeyValue::new(
+                ORIGINAL_COLUMN_NAMES_KEY.to_owned(),
+                original_names_to_json(renamed_columns),
+            ));
+    }
+    parquet_format_options
+}
@@ -0,0 +1,308 @@
+//! Stores one dimensional PostgreSQL arrays, e.g. `integer[]` or `text[]`, with the logical type
+//! `LIST`.
+
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::{anyhow, bail, Error};
+use odbc_api::buffers::{AnySlice, BufferDesc};
+use parquet::{
+    basic::{ConvertedType, LogicalType, Repetition, Type as PhysicalType},
+    column::writer::{get_typed_column_writer_mut, ColumnWriter},
+    data_type::{
+        BoolType, ByteArray, ByteArrayType, DataType, DoubleType, FloatType, Int32Type, Int64Type,
+    },
+    schema::types::Type,
+};
+
+use crate::parquet_buffer::ParquetBuffer;
+
+use super::column_strategy::ColumnStrategy;
+
+/// Type of the elements of an array.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArrayElement {
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    Boolean,
+    Text,
+}
+
+impl ArrayElement {
+    /// PostgreSQL names array types after their element type, prefixed with `_`, e.g. `_int4`.
+    /// `None` for other types and arrays of elements we do not map to parquet types.
+    pub fn from_type_name(type_name: &str) -> Option<Self> {
+        let element = match type_name.strip_prefix('_')? {
+            "int2" => ArrayElement::Int16,
+            "int4" => ArrayElement::Int32,
+            "int8" => ArrayElement::Int64,
+            "float4" => ArrayElement::Float32,
+            "float8" => ArrayElement::Float64,
+            "bool" => ArrayElement::Boolean,
+            "text" | "varchar" | "bpchar" | "name" => ArrayElement::Text,
+            _ => return None,
+        };
+        Some(element)
+    }
+
+    fn parquet_type(self) -> Type {
+        let physical_type = match self {
+            ArrayElement::Int16 | ArrayElement::Int32 => PhysicalType::INT32,
+            ArrayElement::Int64 => PhysicalType::INT64,
+            ArrayElement::Float32 => PhysicalType::FLOAT,
+            ArrayElement::Float64 => PhysicalType::DOUBLE,
+            ArrayElement::Boolean => PhysicalType::BOOLEAN,
+            ArrayElement::Text => PhysicalType::BYTE_ARRAY,
+        };
+        let builder = Type::primitive_type_builder("element", physical_type)
+            .with_repetition(Repetition::OPTIONAL);
+        let builder = match self {
+            ArrayElement::Int16 => builder.with_logical_type(Some(LogicalType::Integer {
+                bit_width: 16,
+                is_signed: true,
+            })),
+            ArrayElement::Text => builder.with_converted_type(ConvertedType::UTF8),
+            _ => builder,
+        };
+        builder.build().unwrap()
+    }
+}
+
+/// Fetches arrays in their text representation, e.g. `{1,2,NULL}`, and parses their elements. The
+/// elements are written as the optional `element` of the repeated group `list`, following the
+/// backward compatibility rules of the parquet format for lists.
+pub struct Array {
+    repetition: Repetition,
+    element: ArrayElement,
+    use_utf16: bool,
+    /// Maximum length of the text representation of the array in characters.
+    length: usize,
+}
+
+impl Array {
+    pub fn new(
+        repetition: Repetition,
+        element: ArrayElement,
+        use_utf16: bool,
+        length: usize,
+    ) -> Self {
+        Self {
+            repetition,
+            element,
+            use_utf16,
+            length,
+        }
+    }
+
+    fn write_arrays<T>(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+        parse: impl Fn(&str) -> Option<T::T>,
+    ) -> Result<(), Error>
+    where
+        T: DataType,
+    {
+        let cw = get_typed_column_writer_mut::<T>(column_writer);
+        let texts: Box<dyn Iterator<Item = Option<Cow<str>>>> = match column_view {
+            AnySlice::Text(view) => {
+                Box::new(view.iter().map(|item| item.map(String::from_utf8_lossy)))
+            }
+            AnySlice::WText(view) => Box::new(
+                view.iter()
+                    .map(|item| item.map(|text| Cow::Owned(text.to_string_lossy()))),
+            ),
+            _ => panic!("Arrays must be fetched into text buffers"),
+        };
+        let lists = texts.map(|text| {
+            let Some(text) = text else {
+                return Ok(None);
+            };
+            let elements = parse_array_literal(&text)?
+                .into_iter()
+                .map(|element| {
+                    element
+                        .map(|element| {
+                            parse(&element).ok_or_else(|| {
+                                anyhow!(
+                                    "Can not parse element '{element}' of array '{text}' as \
+                                    {:?}. Use `--array-as-text` to store arrays as text.",
+                                    self.element
+                                )
+                            })
+                        })
+                        .transpose()
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Some(elements))
+        });
+        parquet_buffer.write_lists(cw, lists, self.repetition == Repetition::OPTIONAL)
+    }
+}
+
+impl ColumnStrategy for Array {
+    fn parquet_type(&self, name: &str) -> Type {
+        let list = Type::group_type_builder("list")
+            .with_repetition(Repetition::REPEATED)
+            .with_fields(vec![Arc::new(self.element.parquet_type())])
+            .build()
+            .unwrap();
+        Type::group_type_builder(name)
+            .with_logical_type(Some(LogicalType::List))
+            .with_repetition(self.repetition)
+            .with_fields(vec![Arc::new(list)])
+            .build()
+            .unwrap()
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        if self.use_utf16 {
+            BufferDesc::WText {
+                max_str_len: self.length,
+            }
+        } else {
+            BufferDesc::Text {
+                max_str_len: self.length,
+            }
+        }
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        let pb = parquet_buffer;
+        let cw = column_writer;
+        let cv = column_view;
+        match self.element {
+            ArrayElement::Int16 | ArrayElement::Int32 => {
+                self.write_arrays::<Int32Type>(pb, cw, cv, |text| text.parse().ok())
+            }
+            ArrayElement::Int64 => {
+                self.write_arrays::<Int64Type>(pb, cw, cv, |text| text.parse().ok())
+            }
+            ArrayElement::Float32 => {
+                self.write_arrays::<FloatType>(pb, cw, cv, |text| text.parse().ok())
+            }
+            ArrayElement::Float64 => {
+                self.write_arrays::<DoubleType>(pb, cw, cv, |text| text.parse().ok())
+            }
+            ArrayElement::Boolean => self.write_arrays::<BoolType>(pb, cw, cv, |text| match text {
+                "t" | "true" => Some(true),
+                "f" | "false" => Some(false),
+                _ => None,
+            }),
+            ArrayElement::Text => {
+                self.write_arrays::<ByteArrayType>(pb, cw, cv, |text| Some(ByteArray::from(text)))
+            }
+        }
+    }
+}
+
+/// Splits the text representation of a one dimensional PostgreSQL array, e.g. `{1,"a b",NULL}`,
+/// into its elements. Unquoted `NULL` elements are `None`.
+fn parse_array_literal(text: &str) -> Result<Vec<Option<String>>, Error> {
+    let invalid =
+        || anyhow!("Invalid array '{text}'. Use `--array-as-text` to store arrays as text.");
+    let mut literal = text.trim();
+    // Arrays with lower bounds other than one are prefixed with their dimensions, e.g. `[0:1]=`.
+    if literal.starts_with('[') {
+        literal = literal.split_once('=').ok_or_else(invalid)?.1;
+    }
+    let inner = literal
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .ok_or_else(invalid)?;
+    let mut elements = Vec::new();
+    if inner.trim().is_empty() {
+        return Ok(elements);
+    }
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let element = match chars.peek() {
+            Some('{') => bail!(
+                "Multidimensional array '{text}' can not be stored as a list. Use \
+                `--array-as-text` to store arrays as text."
+            ),
+            Some('"') => {
+                chars.next();
+                let mut element = String::new();
+                loop {
+                    match chars.next().ok_or_else(invalid)? {
+                        '"' => break,
+                        '\\' => element.push(chars.next().ok_or_else(invalid)?),
+                        c => element.push(c),
+                    }
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                Some(element)
+            }
+            _ => {
+                let mut element = String::new();
+                while let Some(c) = chars.next_if(|&c| c != ',') {
+                    element.push(c);
+                }
+                let element = element.trim_end();
+                if element.is_empty() {
+                    return Err(invalid());
+                }
+                (!element.eq_ignore_ascii_case("NULL")).then(|| element.to_owned())
+            }
+        };
+        elements.push(element);
+        match chars.next() {
+            Some(',') => (),
+            None => break,
+            Some(_) => return Err(invalid()),
+        }
+    }
+    Ok(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_array_literal, ArrayElement};
+
+    #[test]
+    fn parse_arrays() {
+        let owned = |elements: &[Option<&str>]| -> Vec<Option<String>> {
+            elements.iter().map(|e| e.map(str::to_owned)).collect()
+        };
+        assert_eq!(owned(&[]), parse_array_literal("{}").unwrap());
+        assert_eq!(
+            owned(&[Some("1"), None, Some("3")]),
+            parse_array_literal("{1,NULL,3}").unwrap()
+        );
+        assert_eq!(
+            owned(&[Some("a b"), Some("NULL"), Some("c,\"d\"")]),
+            parse_array_literal(r#"{"a b","NULL","c,\"d\""}"#).unwrap()
+        );
+        assert_eq!(
+            owned(&[Some("7"), Some("8")]),
+            parse_array_literal("[0:1]={7,8}").unwrap()
+        );
+        assert!(parse_array_literal("{{1,2},{3,4}}").is_err());
+        assert!(parse_array_literal("{1,}").is_err());
+        assert!(parse_array_literal("1,2").is_err());
+    }
+
+    #[test]
+    fn element_from_type_name() {
+        assert_eq!(
+            Some(ArrayElement::Int32),
+            ArrayElement::from_type_name("_int4")
+        );
+        assert_eq!(
+            Some(ArrayElement::Text),
+            ArrayElement::from_type_name("_text")
+        );
+        assert_eq!(None, ArrayElement::from_type_name("int4"));
+        assert_eq!(None, ArrayElement::from_type_name("_numeric"));
+    }
+}
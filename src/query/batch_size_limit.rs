@@ -2,6 +2,7 @@ use std::cmp::min;
 
 use anyhow::bail;
 use bytesize::ByteSize;
+use log::info;
 
 #[cfg(target_pointer_width = "64")]
 const DEFAULT_BATCH_SIZE_BYTES: ByteSize = ByteSize::gib(2); // 2GB
@@ -15,6 +16,7 @@ const DEFAULT_BATCH_SIZE_BYTES: ByteSize = ByteSize::gib(1); // 1GB
 const DEFAULT_BATCH_SIZE_ROWS: usize = u16::MAX as usize; // 65535 rows
 
 /// Describes how we limit the size of individual parquet files.
+#[derive(Clone, Copy)]
 pub enum FileSizeLimit {
     /// No file size limit is applied. The entire output is written to one parquet file.
     None,
@@ -25,15 +27,59 @@ pub enum FileSizeLimit {
         row_groups: u32,
         size: ByteSize,
     },
+    /// Limits the number of rows in an individual file, regardless of row group boundaries.
+    /// Optionally a new file is also started if the size threshold is reached.
+    Rows {
+        rows: usize,
+        size: Option<ByteSize>,
+    },
+    /// No file becomes larger than `max`. Row groups are encoded before they are written, and a new
+    /// file is started as soon as the next one would not fit, splitting batches if necessary.
+    /// Optionally a new file is also started after a row group, once the size threshold is
+    /// reached.
+    Ceiling {
+        max: ByteSize,
+        threshold: Option<ByteSize>,
+    },
 }
 
 impl FileSizeLimit {
-    pub fn new(num_row_groups: u32, file_size_threshold: Option<ByteSize>) -> Self {
-        match (num_row_groups, file_size_threshold) {
-            (0, None) => Self::None,
-            (0, Some(size)) => Self::Size(size),
-            (row_groups, None) => Self::RowGroups(row_groups),
-            (row_groups, Some(size)) => Self::Both { row_groups, size },
+    pub fn new(
+        num_row_groups: u32,
+        file_size_threshold: Option<ByteSize>,
+        rows_per_file: Option<usize>,
+        max_file_size: Option<ByteSize>,
+    ) -> Self {
+        // `--max-file-size` conflicts with `--row-groups-per-file` and `--rows-per-file`
+        if let Some(max) = max_file_size {
+            return Self::Ceiling {
+                max,
+                threshold: file_size_threshold,
+            };
+        }
+        match (num_row_groups, file_size_threshold, rows_per_file) {
+            // `--rows-per-file` conflicts with `--row-groups-per-file`
+            (_, size, Some(rows)) => Self::Rows { rows, size },
+            (0, None, None) => Self::None,
+            (0, Some(size), None) => Self::Size(size),
+            (row_groups, None, None) => Self::RowGroups(row_groups),
+            (row_groups, Some(size), None) => Self::Both { row_groups, size },
+        }
+    }
+
+    /// Maximum number of rows in a single file, if the output is split by rows.
+    pub fn rows_per_file(&self) -> Option<usize> {
+        match self {
+            FileSizeLimit::Rows { rows, .. } => Some(*rows),
+            _ => None,
+        }
+    }
+
+    /// Upper bound for the size of a single file, if specified with `--max-file-size`.
+    pub fn max_file_size(&self) -> Option<ByteSize> {
+        match self {
+            FileSizeLimit::Ceiling { max, .. } => Some(*max),
+            _ => None,
         }
     }
 
@@ -42,13 +88,28 @@ impl FileSizeLimit {
         !matches!(self, FileSizeLimit::None)
     }
 
-    pub fn should_start_new_file(&self, num_batch: u32, current_file_size: ByteSize) -> bool {
+    pub fn should_start_new_file(
+        &self,
+        num_batch: u32,
+        current_file_size: ByteSize,
+        rows_in_file: usize,
+    ) -> bool {
         match self {
             FileSizeLimit::None => false,
-            FileSizeLimit::RowGroups(row_groups) => num_batch != 0 && num_batch % row_groups == 0,
+            FileSizeLimit::RowGroups(row_groups) => {
+                num_batch != 0 && num_batch.is_multiple_of(*row_groups)
+            }
             FileSizeLimit::Size(size) => &current_file_size >= size,
             FileSizeLimit::Both { row_groups, size } => {
-                (num_batch != 0 && num_batch % row_groups == 0) || &current_file_size >= size
+                (num_batch != 0 && num_batch.is_multiple_of(*row_groups))
+                    || &current_file_size >= size
+            }
+            FileSizeLimit::Rows { rows, size } => {
+                rows_in_file >= *rows || size.is_some_and(|size| current_file_size >= size)
+            }
+            FileSizeLimit::Ceiling { max, threshold } => {
+                &current_file_size >= max
+                    || threshold.is_some_and(|threshold| current_file_size >= threshold)
             }
         }
     }
@@ -56,6 +117,7 @@ impl FileSizeLimit {
 
 /// Batches can be limited by either number of rows or the total size of the rows in the batch in
 /// bytes.
+#[derive(Clone, Copy)]
 pub enum BatchSizeLimit {
     Rows(usize),
     Bytes(ByteSize),
@@ -107,3 +169,27 @@ impl BatchSizeLimit {
         }
     }
 }
+
+/// Logs the memory allocated for `num_buffers` fetch buffers of `batch_size_row` rows. Fails before
+/// anything is allocated, if it exceeds `max_total_memory`, specified with `--max-total-memory`.
+pub fn check_total_memory(
+    batch_size_row: usize,
+    mem_usage_per_row: usize,
+    num_buffers: usize,
+    max_total_memory: Option<ByteSize>,
+) -> Result<(), anyhow::Error> {
+    let total = ByteSize::b((batch_size_row * mem_usage_per_row * num_buffers) as u64);
+    info!(
+        "Batch size set to {batch_size_row} rows. Memory usage per row is {mem_usage_per_row} \
+        bytes. Allocating {num_buffers} buffer(s) with {total} in total. This excludes memory \
+        directly allocated by the ODBC driver."
+    );
+    if let Some(max_total_memory) = max_total_memory.filter(|&max| total > max) {
+        bail!(
+            "Buffers would require {total} of memory, which exceeds `--max-total-memory` of \
+            {max_total_memory}. Use `--batch-size-memory` or `--batch-size-row` to fetch smaller \
+            batches, or `--sequential-fetching` to allocate only one buffer."
+        )
+    }
+    Ok(())
+}
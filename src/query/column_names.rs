@@ -0,0 +1,255 @@
+//! Transformations applied to the column names of the result set, before they are used as names
+//! of the parquet columns.
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem::replace,
+};
+
+use anyhow::{bail, Error};
+use log::info;
+
+use crate::json::write_json_string;
+
+/// Key in the key value metadata of the parquet file, under which the original names of renamed
+/// columns are stored.
+pub const ORIGINAL_COLUMN_NAMES_KEY: &str = "odbc2parquet.original_column_names";
+
+/// Replaces every character outside of `[A-Za-z0-9_]` with `_`. Names which would collide with a
+/// previous name are made unique by appending `_2`, `_3`, and so on.
+///
+/// Returns tuples of original and sanitized name for each column which has been renamed.
+pub fn sanitize_column_names<'a>(
+    names: impl IntoIterator<Item = &'a mut String>,
+) -> Vec<(String, String)> {
+    let mut names: Vec<_> = names.into_iter().collect();
+    let sanitized: Vec<String> = names
+        .iter()
+        .map(|name| {
+            name.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect()
+        })
+        .collect();
+    // Names which are valid already. We do not want to rename them, just because a sanitized name
+    // of a preceding column would collide with them.
+    let valid: HashSet<String> = names
+        .iter()
+        .zip(&sanitized)
+        .filter(|(name, candidate)| **name == *candidate)
+        .map(|(_, candidate)| candidate.clone())
+        .collect();
+    let mut taken = HashSet::new();
+    let mut renamed = Vec::new();
+    for (name, candidate) in names.iter_mut().zip(sanitized) {
+        if **name == candidate && !taken.contains(&candidate) {
+            taken.insert(candidate);
+            continue;
+        }
+        let mut unique = candidate.clone();
+        let mut suffix = 2;
+        while taken.contains(&unique) || valid.contains(&unique) {
+            unique = format!("{candidate}_{suffix}");
+            suffix += 1;
+        }
+        taken.insert(unique.clone());
+        renamed.push((replace(*name, unique.clone()), unique));
+    }
+    renamed
+}
+
+/// Applies the renames specified with `--column-rename`, given as tuples of old and new name.
+/// `available` are the names of all columns in the result set, including the ones not selected.
+///
+/// Returns tuples of original and new name for each column which has been renamed.
+pub fn rename_columns<'a>(
+    names: impl IntoIterator<Item = &'a mut String>,
+    renames: &[(String, String)],
+    available: &[String],
+) -> Result<Vec<(String, String)>, Error> {
+    if let Some((unknown, _)) = renames.iter().find(|(old, _)| !available.contains(old)) {
+        bail!(
+            "Can not rename column '{unknown}', since it is not part of the result set. Available \
+            columns are: {}",
+            available.join(", ")
+        )
+    }
+    let mut names: Vec<_> = names.into_iter().collect();
+    let mut renamed = Vec::new();
+    for name in names.iter_mut() {
+        if let Some((_, new)) = renames.iter().find(|(old, _)| old == &**name) {
+            renamed.push((replace(*name, new.clone()), new.clone()));
+        }
+    }
+    let mut taken = HashSet::new();
+    for name in &names {
+        if !taken.insert(name.as_str()) {
+            bail!("Multiple columns would be written as '{name}'. Column names must be unique.")
+        }
+    }
+    Ok(renamed)
+}
+
+/// Case the column names are folded to, specified with `--lowercase-columns` or
+/// `--uppercase-columns`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnCase {
+    Lower,
+    Upper,
+}
+
+/// Folds every name to `case`. Fails if two columns would share the same name afterwards, e.g. `ID`
+/// and `id`.
+///
+/// Returns tuples of previous and folded name for each column which has been renamed.
+pub fn fold_column_case<'a>(
+    names: impl IntoIterator<Item = &'a mut String>,
+    case: ColumnCase,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut previous_names = HashMap::new();
+    let mut renamed = Vec::new();
+    for name in names {
+        let folded = match case {
+            ColumnCase::Lower => name.to_lowercase(),
+            ColumnCase::Upper => name.to_uppercase(),
+        };
+        if let Some(other) = previous_names.insert(folded.clone(), name.clone()) {
+            bail!(
+                "Columns '{other}' and '{name}' would both be written as '{folded}' after folding \
+                their case. Rename one of them with `--column-rename`."
+            )
+        }
+        if *name != folded {
+            renamed.push((replace(name, folded.clone()), folded));
+        }
+    }
+    Ok(renamed)
+}
+
+/// Applies `--column-rename`, folds the case of the resulting names and sanitizes them, in this
+/// order. Used for parquet and text output alike, so both write the same column names.
+///
+/// Returns tuples of original and final name for each column which has been renamed.
+pub fn name_columns(
+    mut names: Vec<&mut String>,
+    renames: &[(String, String)],
+    case: Option<ColumnCase>,
+    sanitize: bool,
+    available: &[String],
+) -> Result<Vec<(String, String)>, Error> {
+    let mut renamed = rename_columns(names.iter_mut().map(|name| &mut **name), renames, available)?;
+    // Fold the case and sanitize the names the columns have after renaming them.
+    if let Some(case) = case {
+        let folded = fold_column_case(names.iter_mut().map(|name| &mut **name), case)?;
+        chain_renames(&mut renamed, folded);
+    }
+    if sanitize {
+        let sanitized = sanitize_column_names(names.iter_mut().map(|name| &mut **name));
+        chain_renames(&mut renamed, sanitized);
+    }
+    for (original, new) in &renamed {
+        info!("Column '{original}' is written as '{new}'.");
+    }
+    Ok(renamed)
+}
+
+/// Adds the renames of a later transformation to the ones of the previous transformations.
+/// `renamed` and `further` are tuples of previous and new name, so a column renamed by both ends
+/// up with a single tuple of its original and final name.
+pub fn chain_renames(renamed: &mut Vec<(String, String)>, further: Vec<(String, String)>) {
+    for (previous, new_name) in further {
+        match renamed
+            .iter_mut()
+            .find(|(_original, name)| *name == previous)
+        {
+            Some((_original, name)) => *name = new_name,
+            None => renamed.push((previous, new_name)),
+        }
+    }
+}
+
+/// Renders the renamed columns as a JSON object, mapping the new names to the original ones.
+pub fn original_names_to_json(renamed: &[(String, String)]) -> String {
+    let mut json = String::from("{");
+    for (index, (original, new)) in renamed.iter().enumerate() {
+        if index != 0 {
+            json.push(',');
+        }
+        write_json_string(&mut json, new);
+        json.push(':');
+        write_json_string(&mut json, original);
+    }
+    json.push('}');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        chain_renames, fold_column_case, original_names_to_json, rename_columns,
+        sanitize_column_names, ColumnCase,
+    };
+
+    #[test]
+    fn sanitize_and_deduplicate_names() {
+        let mut names = vec![
+            "id".to_owned(),
+            "first name".to_owned(),
+            "first.name".to_owned(),
+            "first_name".to_owned(),
+            "SUM(a)".to_owned(),
+        ];
+
+        let renamed = sanitize_column_names(&mut names);
+
+        assert_eq!(
+            ["id", "first_name_2", "first_name_3", "first_name", "SUM_a_"].as_slice(),
+            names
+        );
+        assert_eq!(
+            r#"{"first_name_2":"first name","first_name_3":"first.name","SUM_a_":"SUM(a)"}"#,
+            original_names_to_json(&renamed)
+        );
+    }
+
+    #[test]
+    fn rename_columns_and_detect_collisions() {
+        let available = ["Id".to_owned(), "FirstName".to_owned()];
+        let renames = [("FirstName".to_owned(), "first_name".to_owned())];
+        let mut names = available.to_vec();
+
+        let renamed = rename_columns(&mut names, &renames, &available).unwrap();
+
+        assert_eq!(["Id", "first_name"].as_slice(), names);
+        assert_eq!(
+            vec![("FirstName".to_owned(), "first_name".to_owned())],
+            renamed
+        );
+
+        let collision = [("FirstName".to_owned(), "Id".to_owned())];
+        assert!(rename_columns(&mut available.to_vec(), &collision, &available).is_err());
+        let unknown = [("LastName".to_owned(), "last_name".to_owned())];
+        assert!(rename_columns(&mut available.to_vec(), &unknown, &available).is_err());
+    }
+
+    #[test]
+    fn fold_case_after_renaming() {
+        let mut names = vec!["Id".to_owned(), "first_name".to_owned()];
+        let mut renamed = vec![("FirstName".to_owned(), "first_name".to_owned())];
+
+        let folded = fold_column_case(&mut names, ColumnCase::Upper).unwrap();
+        chain_renames(&mut renamed, folded);
+
+        assert_eq!(["ID", "FIRST_NAME"].as_slice(), names);
+        assert_eq!(
+            vec![
+                ("FirstName".to_owned(), "FIRST_NAME".to_owned()),
+                ("Id".to_owned(), "ID".to_owned())
+            ],
+            renamed
+        );
+
+        let mut colliding = vec!["ID".to_owned(), "id".to_owned()];
+        assert!(fold_column_case(&mut colliding, ColumnCase::Lower).is_err());
+    }
+}
@@ -1,10 +1,11 @@
-use std::{cmp::min, convert::TryInto, num::NonZeroUsize};
+use std::{cmp::min, convert::TryInto, num::NonZeroUsize, ptr::null_mut};
 
-use anyhow::Error;
-use log::{debug, info};
+use anyhow::{bail, Error};
+use log::{debug, info, warn};
 use odbc_api::{
     buffers::{AnySlice, BufferDesc},
-    sys::SqlDataType,
+    handles::Statement,
+    sys::{Desc, SQLColAttribute, SqlDataType, SqlReturn},
     DataType, Nullability, ResultSetMetadata,
 };
 use parquet::{
@@ -17,18 +18,25 @@ use parquet::{
 };
 
 use crate::{
+    enum_args::{EpochUnitArgument, TrimArgument, UnknownTypeArgument},
     parquet_buffer::ParquetBuffer,
     query::{
+        array::{Array, ArrayElement},
         binary::Binary,
         boolean::Boolean,
+        column_names::ColumnCase,
         date::Date,
-        decimal::decimal_fetch_strategy,
+        decimal::{decimal_fetch_strategy, rescaled_decimal_strategy},
+        epoch::{date_as_epoch, timestamp_as_epoch},
+        guid::Guid,
         identical::{fetch_identical, fetch_identical_with_logical_type},
-        text::text_strategy,
+        interval::{Interval, IntervalKind},
+        text::{json_strategy, text_strategy},
         time::time_from_text,
-        timestamp::timestamp_without_tz,
+        timestamp::{timestamp_without_tz, timestamp_without_tz_int96},
         timestamp_tz::timestamp_tz,
     },
+    MappingOpts,
 };
 
 /// Decisions on how to handle a particular column of the ODBC result set. What buffer to bind to it
@@ -55,8 +63,117 @@ pub struct MappingOptions<'a> {
     pub use_utf16: bool,
     pub prefer_varbinary: bool,
     pub avoid_decimal: bool,
+    /// Fetch DECIMAL and NUMERIC columns as double precision floating point numbers.
+    pub decimal_as_double: bool,
+    /// Tuples of column name and scale, for DECIMAL and NUMERIC columns which are stored with a
+    /// different scale than the one reported by the database.
+    pub decimal_rescales: &'a [(String, i32)],
+    /// Cut off fractional digits, which do not fit into the new scale of a rescaled column, rather
+    /// than failing.
+    pub decimal_rescale_truncate: bool,
     pub driver_does_support_i64: bool,
     pub column_length_limit: usize,
+    /// Tuples of column name and length limit, which overwrite `column_length_limit` for
+    /// individual columns.
+    pub column_length_limits: &'a [(String, usize)],
+    /// Tuples of column name and buffer length of text columns, determined from the data with
+    /// `--size-text-from-data`. Take precedence over the reported length and `column_length_limit`,
+    /// but not over `column_length_limits`.
+    pub text_lengths: &'a [(String, usize)],
+    /// Store timestamps without time zone as INT96 instead of INT64.
+    pub timestamp_int96: bool,
+    /// Store timestamps without time zone and dates as plain INT64, counting the units since the
+    /// UNIX epoch.
+    pub timestamps_as_epoch: Option<EpochUnitArgument>,
+    /// Tuples of old and new name of columns, which are renamed in the parquet schema. Applied
+    /// before folding the case of the column names.
+    pub column_renames: &'a [(String, String)],
+    /// Case the column names are folded to, after renaming and before sanitizing them.
+    pub column_case: Option<ColumnCase>,
+    /// Replace characters in column names which are not alphanumeric or `_`.
+    pub sanitize_column_names: bool,
+    /// Fetch GUIDs as text, rather than mapping them to the logical type UUID.
+    pub guid_as_text: bool,
+    /// Fetch TIME columns as text, rather than mapping them to the logical type TIME.
+    pub time_as_text: bool,
+    /// Store BIT columns as INT32, rather than as BOOLEAN.
+    pub bit_as_int: bool,
+    /// Fetch INTERVAL columns as text, rather than mapping them to the converted type INTERVAL.
+    pub interval_as_text: bool,
+    /// Fetch PostgreSQL arrays as text, rather than mapping them to the logical type LIST.
+    pub array_as_text: bool,
+    /// Store JSON columns as plain strings, rather than with the logical type JSON.
+    pub json_as_text: bool,
+    /// Whitespace removed from the values of character columns.
+    pub trim: TrimArgument,
+    /// What to do with columns of types, which are not mapped to a specific parquet type.
+    pub unknown_type: UnknownTypeArgument,
+}
+
+impl<'a> MappingOptions<'a> {
+    /// Mapping specified by the command line options shared by the `query` and `schema`
+    /// subcommands. `column_length_limits` is obtained by [`individual_column_length_limits`].
+    /// `text_lengths` are determined from the data, for `--size-text-from-data`.
+    pub fn from_opts(
+        opts: &'a MappingOpts,
+        db_name: &'a str,
+        column_length_limits: &'a [(String, usize)],
+        text_lengths: &'a [(String, usize)],
+    ) -> Self {
+        // The last limit specified without a column name applies to all columns, which are not
+        // limited individually.
+        let column_length_limit = opts
+            .column_length_limit
+            .iter()
+            .rev()
+            .find_map(|(column, limit)| column.is_none().then_some(*limit))
+            .unwrap_or(DEFAULT_COLUMN_LENGTH_LIMIT);
+        let column_case = if opts.lowercase_columns {
+            Some(ColumnCase::Lower)
+        } else if opts.uppercase_columns {
+            Some(ColumnCase::Upper)
+        } else {
+            None
+        };
+        MappingOptions {
+            db_name,
+            use_utf16: opts.encoding.use_utf16(),
+            prefer_varbinary: opts.prefer_varbinary,
+            avoid_decimal: opts.avoid_decimal,
+            decimal_as_double: opts.decimal_as_double,
+            decimal_rescales: &opts.decimal_rescale,
+            decimal_rescale_truncate: opts.decimal_rescale_truncate,
+            driver_does_support_i64: !opts.driver_does_not_support_64bit_integers,
+            column_length_limit,
+            column_length_limits,
+            text_lengths,
+            timestamp_int96: opts.timestamp_int96,
+            timestamps_as_epoch: opts.timestamps_as_epoch,
+            column_renames: &opts.column_rename,
+            column_case,
+            sanitize_column_names: opts.sanitize_column_names,
+            guid_as_text: opts.guid_as_text,
+            time_as_text: opts.time_as_text,
+            bit_as_int: opts.bit_as_int,
+            interval_as_text: opts.interval_as_text,
+            array_as_text: opts.array_as_text,
+            json_as_text: opts.json_as_text,
+            trim: opts.trim,
+            unknown_type: opts.unknown_type,
+        }
+    }
+}
+
+/// Maximum element length of variadic sized columns, unless specified by `--column-length-limit`.
+const DEFAULT_COLUMN_LENGTH_LIMIT: usize = 4096;
+
+/// Limits of `--column-length-limit` which are specified for individual columns, as tuples of
+/// column name and limit.
+pub fn individual_column_length_limits(opts: &MappingOpts) -> Vec<(String, usize)> {
+    opts.column_length_limit
+        .iter()
+        .filter_map(|(column, limit)| column.clone().map(|column| (column, *limit)))
+        .collect()
 }
 
 /// Fetch strategies based on column description and environment arguments `MappingOptions`.
@@ -70,6 +187,8 @@ pub struct MappingOptions<'a> {
 ///   within the runtime of the odbc2parquet command line tool.
 /// * `index`: One based column index. Useful if additional meta-information needs to be acquired
 ///   using `cursor`
+///
+/// `None` if the column should be omitted from the output, due to `--unknown-type skip`.
 pub fn strategy_from_column_description(
     name: &str,
     data_type: DataType,
@@ -77,14 +196,33 @@ pub fn strategy_from_column_description(
     mapping_options: MappingOptions,
     cursor: &mut impl ResultSetMetadata,
     index: i16,
-) -> Result<Box<dyn ColumnStrategy>, Error> {
+) -> Result<Option<Box<dyn ColumnStrategy>>, Error> {
     let MappingOptions {
         db_name,
         use_utf16,
         prefer_varbinary,
         avoid_decimal,
+        decimal_as_double,
+        decimal_rescales,
+        decimal_rescale_truncate,
         driver_does_support_i64,
         column_length_limit,
+        column_length_limits,
+        text_lengths,
+        timestamp_int96,
+        timestamps_as_epoch,
+        // Do not influence the type mapping
+        column_renames: _,
+        column_case: _,
+        sanitize_column_names: _,
+        guid_as_text,
+        time_as_text,
+        bit_as_int,
+        interval_as_text,
+        array_as_text,
+        json_as_text,
+        trim,
+        unknown_type,
     } = mapping_options;
 
     let is_optional = nullability.could_be_nullable();
@@ -97,6 +235,11 @@ pub fn strategy_from_column_description(
         Repetition::REQUIRED
     };
 
+    let column_length_limit = column_length_limits
+        .iter()
+        .rev()
+        .find(|(column, _)| column == name)
+        .map_or(column_length_limit, |&(_, limit)| limit);
     let apply_length_limit = |reported_length: Option<NonZeroUsize>| {
         min(
             reported_length
@@ -106,6 +249,74 @@ pub fn strategy_from_column_description(
         )
     };
 
+    // Some drivers report boolean columns as integers, yet still name their type accordingly.
+    let data_type = if !bit_as_int
+        && may_be_bit(&data_type)
+        && col_type_name(cursor, index).is_some_and(|type_name| is_bit_type_name(&type_name))
+    {
+        debug!(
+            "Column {name} is reported as {data_type:?}, but fetched as BIT, due to its type name."
+        );
+        DataType::Bit
+    } else {
+        data_type
+    };
+
+    let array_element =
+        if !array_as_text && db_name == "PostgreSQL" && is_reported_as_text(&data_type) {
+            col_type_name(cursor, index)
+                .and_then(|type_name| ArrayElement::from_type_name(&type_name))
+        } else {
+            None
+        };
+    let is_json = !json_as_text
+        && array_element.is_none()
+        && is_reported_as_text(&data_type)
+        && col_type_name(cursor, index).is_some_and(|type_name| is_json_type_name(&type_name));
+    if array_element.is_some() || is_json {
+        let length = if use_utf16 {
+            data_type.utf16_len()
+        } else {
+            data_type.utf8_len()
+        };
+        let length = match length {
+            Some(length) => Some(length),
+            None => cursor.col_display_size(index.try_into().unwrap())?,
+        };
+        let length = apply_length_limit(length);
+        if let Some(element) = array_element {
+            debug!("Column {name} is fetched as array of {element:?}, due to its type name.");
+            return Ok(Some(Box::new(Array::new(
+                repetition, element, use_utf16, length,
+            ))));
+        }
+        debug!("Column {name} is stored with the logical type JSON, due to its type name.");
+        return Ok(Some(json_strategy(use_utf16, repetition, length)));
+    }
+
+    if let Some(&(_, new_scale)) = decimal_rescales
+        .iter()
+        .rev()
+        .find(|(column, _)| column == name)
+    {
+        let (DataType::Numeric { scale, precision } | DataType::Decimal { scale, precision }) =
+            data_type
+        else {
+            bail!(
+                "Column '{name}' specified with `--decimal-rescale` is reported as {data_type:?}. \
+                Only DECIMAL and NUMERIC columns can be rescaled."
+            )
+        };
+        return Ok(Some(rescaled_decimal_strategy(
+            name,
+            is_optional,
+            scale as i32,
+            precision.try_into().unwrap(),
+            new_scale,
+            decimal_rescale_truncate,
+        )?));
+    }
+
     let strategy: Box<dyn ColumnStrategy> = match data_type {
         DataType::Float { precision: 0..=24 } | DataType::Real => {
             fetch_identical::<FloatType>(is_optional)
@@ -127,7 +338,14 @@ pub fn strategy_from_column_description(
                 is_signed: true,
             },
         ),
+        DataType::Date if timestamps_as_epoch.is_some() => {
+            date_as_epoch(repetition, timestamps_as_epoch.unwrap())
+        }
         DataType::Date => Box::new(Date::new(repetition)),
+        // Let the driver convert the decimals, by binding a double buffer.
+        DataType::Numeric { .. } | DataType::Decimal { .. } if decimal_as_double => {
+            fetch_identical::<DoubleType>(is_optional)
+        }
         DataType::Numeric { scale, precision } | DataType::Decimal { scale, precision } => {
             decimal_fetch_strategy(
                 is_optional,
@@ -137,10 +355,23 @@ pub fn strategy_from_column_description(
                 driver_does_support_i64,
             )
         }
+        DataType::Timestamp { precision: _ } if timestamps_as_epoch.is_some() => {
+            timestamp_as_epoch(repetition, timestamps_as_epoch.unwrap())
+        }
+        DataType::Timestamp { precision: _ } if timestamp_int96 => {
+            timestamp_without_tz_int96(repetition)
+        }
         DataType::Timestamp { precision } => {
             timestamp_without_tz(repetition, precision.try_into().unwrap())
         }
         DataType::BigInt => fetch_identical::<Int64Type>(is_optional),
+        DataType::Bit if bit_as_int => fetch_identical_with_logical_type::<Int32Type>(
+            is_optional,
+            LogicalType::Integer {
+                bit_width: 32,
+                is_signed: true,
+            },
+        ),
         DataType::Bit => Box::new(Boolean::new(repetition)),
         DataType::TinyInt => {
             let is_signed = !cursor.column_is_unsigned(index.try_into().unwrap())?;
@@ -179,16 +410,46 @@ pub fn strategy_from_column_description(
             } else {
                 dt.utf8_len()
             };
-            let length = apply_length_limit(len_in_chars);
-            text_strategy(use_utf16, repetition, length)
+            let is_limited_individually = column_length_limits
+                .iter()
+                .any(|(column, _)| column == name);
+            let length = match text_lengths.iter().find(|(column, _)| column == name) {
+                Some(&(_, length)) if !is_limited_individually => length,
+                _ => apply_length_limit(len_in_chars),
+            };
+            text_strategy(use_utf16, repetition, length, trim)
         }
+        // -152 is `XML` on Microsoft SQL Server. Documents are stored as text, as they are.
+        DataType::Other {
+            data_type: SqlDataType(-152),
+            ..
+        } if db_name == "Microsoft SQL Server" => text_strategy(
+            use_utf16,
+            repetition,
+            apply_length_limit(None),
+            TrimArgument::None,
+        ),
         DataType::Other {
             data_type: SqlDataType(-154),
             column_size: _,
             decimal_digits: precision,
         } => {
-            if db_name == "Microsoft SQL Server" {
-                time_from_text(repetition, precision.try_into().unwrap())
+            if db_name != "Microsoft SQL Server" {
+                let Some(strategy) = unsupported_type(
+                    name,
+                    &data_type,
+                    unknown_type,
+                    cursor,
+                    index,
+                    repetition,
+                    apply_length_limit,
+                )?
+                else {
+                    return Ok(None);
+                };
+                strategy
+            } else if !time_as_text {
+                time_from_text(name, repetition, precision.try_into().unwrap())
             } else {
                 unknown_non_char_type(&data_type, cursor, index, repetition, apply_length_limit)?
             }
@@ -207,18 +468,159 @@ pub fn strategy_from_column_description(
                 );
                 timestamp_tz(precision.try_into().unwrap(), repetition)?
             } else {
-                unknown_non_char_type(&data_type, cursor, index, repetition, apply_length_limit)?
+                let Some(strategy) = unsupported_type(
+                    name,
+                    &data_type,
+                    unknown_type,
+                    cursor,
+                    index,
+                    repetition,
+                    apply_length_limit,
+                )?
+                else {
+                    return Ok(None);
+                };
+                strategy
             }
         }
-        DataType::Unknown | DataType::Time { .. } | DataType::Other { .. } => {
+        // -11 is SQL_GUID, e.g. `UNIQUEIDENTIFIER` on Microsoft SQL Server.
+        DataType::Other {
+            data_type: SqlDataType(-11),
+            ..
+        } if !guid_as_text => Box::new(Guid::new(repetition)),
+        DataType::Other { data_type, .. }
+            if !interval_as_text && IntervalKind::from_sql_data_type(data_type).is_some() =>
+        {
+            let kind = IntervalKind::from_sql_data_type(data_type).unwrap();
+            Box::new(Interval::new(repetition, kind))
+        }
+        DataType::Time { precision } if !time_as_text => {
+            time_from_text(name, repetition, precision.try_into().unwrap())
+        }
+        // Types fetched as text on request, e.g. with `--time-as-text`.
+        DataType::Time { .. }
+        | DataType::Other {
+            data_type: SqlDataType(-11),
+            ..
+        } => unknown_non_char_type(&data_type, cursor, index, repetition, apply_length_limit)?,
+        DataType::Other {
+            data_type: sql_type,
+            ..
+        } if IntervalKind::from_sql_data_type(sql_type).is_some() => {
             unknown_non_char_type(&data_type, cursor, index, repetition, apply_length_limit)?
         }
+        DataType::Unknown | DataType::Other { .. } => {
+            let Some(strategy) = unsupported_type(
+                name,
+                &data_type,
+                unknown_type,
+                cursor,
+                index,
+                repetition,
+                apply_length_limit,
+            )?
+            else {
+                return Ok(None);
+            };
+            strategy
+        }
     };
 
     let desc = strategy.buffer_desc();
     debug!("ODBC buffer description for column at index {index}: {desc:?}",);
 
-    Ok(strategy)
+    Ok(Some(strategy))
+}
+
+/// `true` for the data types drivers are known to report `BIT` or `BOOLEAN` columns as.
+fn may_be_bit(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::TinyInt | DataType::SmallInt | DataType::Other { .. } | DataType::Unknown
+    )
+}
+
+/// `true` for the data types drivers are known to report arrays and JSON documents as.
+fn is_reported_as_text(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Char { .. }
+            | DataType::Varchar { .. }
+            | DataType::LongVarchar { .. }
+            | DataType::WChar { .. }
+            | DataType::WVarchar { .. }
+            | DataType::WLongVarchar { .. }
+            | DataType::Other { .. }
+            | DataType::Unknown
+    )
+}
+
+/// `true` if the name of the type in the data source indicates a boolean column.
+fn is_bit_type_name(type_name: &str) -> bool {
+    ["bit", "bool", "boolean"]
+        .iter()
+        .any(|bit| type_name.eq_ignore_ascii_case(bit))
+}
+
+/// `true` if the name of the type in the data source indicates JSON documents, e.g. PostgreSQL
+/// `JSONB` or MySQL `JSON`.
+fn is_json_type_name(type_name: &str) -> bool {
+    ["json", "jsonb"]
+        .iter()
+        .any(|json| type_name.eq_ignore_ascii_case(json))
+}
+
+/// Name of the type of the column in the data source, e.g. `BOOLEAN`. `None` if the driver does not
+/// report it. `odbc-api` does not offer a safe wrapper for this attribute.
+fn col_type_name(cursor: &mut impl ResultSetMetadata, index: i16) -> Option<String> {
+    let stmt = cursor.as_stmt_ref();
+    let mut buffer = [0u8; 128];
+    let mut length: i16 = 0;
+    // Safety: The buffer outlives the call and its length is passed along with it.
+    let ret = unsafe {
+        SQLColAttribute(
+            stmt.as_sys(),
+            index.try_into().unwrap(),
+            Desc::TypeName,
+            buffer.as_mut_ptr().cast(),
+            buffer.len().try_into().unwrap(),
+            &mut length,
+            null_mut(),
+        )
+    };
+    if ret != SqlReturn::SUCCESS && ret != SqlReturn::SUCCESS_WITH_INFO {
+        return None;
+    }
+    // The length excludes the terminating zero. The name is truncated, if it exceeds the buffer.
+    let length = usize::try_from(length).ok()?.min(buffer.len() - 1);
+    Some(String::from_utf8_lossy(&buffer[..length]).into_owned())
+}
+
+/// Handles columns of types, which are not mapped to a specific parquet type, as specified with
+/// `--unknown-type`.
+fn unsupported_type(
+    name: &str,
+    data_type: &DataType,
+    unknown_type: UnknownTypeArgument,
+    cursor: &mut impl ResultSetMetadata,
+    index: i16,
+    repetition: Repetition,
+    apply_length_limit: impl FnOnce(Option<NonZeroUsize>) -> usize,
+) -> Result<Option<Box<dyn ColumnStrategy>>, Error> {
+    match unknown_type {
+        UnknownTypeArgument::Error => bail!(
+            "Column '{name}' has the unsupported type {data_type:?}. Use `--unknown-type text` to \
+            fetch it as text, or `--unknown-type skip` to omit it."
+        ),
+        UnknownTypeArgument::Text => {
+            unknown_non_char_type(data_type, cursor, index, repetition, apply_length_limit)
+                .map(Some)
+        }
+        UnknownTypeArgument::Skip => {
+            warn!("Column '{name}' has the unsupported type {data_type:?} and is omitted.");
+            Ok(None)
+        }
+    }
 }
 
 fn unknown_non_char_type(
@@ -235,5 +637,38 @@ fn unknown_non_char_type(
     };
     let length = apply_length_limit(length);
     let use_utf16 = false;
-    Ok(text_strategy(use_utf16, repetition, length))
+    // Only values of character columns are trimmed.
+    let trim = TrimArgument::None;
+    Ok(text_strategy(use_utf16, repetition, length, trim))
+}
+
+#[cfg(test)]
+mod tests {
+    use odbc_api::{sys::SqlDataType, DataType};
+
+    use super::{is_bit_type_name, is_json_type_name, may_be_bit};
+
+    #[test]
+    fn detect_bit_reported_as_integer() {
+        assert!(may_be_bit(&DataType::TinyInt) && is_bit_type_name("BIT"));
+        assert!(may_be_bit(&DataType::SmallInt) && is_bit_type_name("bool"));
+        let other = DataType::Other {
+            data_type: SqlDataType(-7),
+            column_size: None,
+            decimal_digits: 0,
+        };
+        assert!(may_be_bit(&other) && is_bit_type_name("Boolean"));
+
+        assert!(!is_bit_type_name("tinyint"));
+        assert!(!may_be_bit(&DataType::Integer));
+        assert!(!may_be_bit(&DataType::Varchar { length: None }));
+    }
+
+    #[test]
+    fn detect_json_type_names() {
+        assert!(is_json_type_name("jsonb"));
+        assert!(is_json_type_name("JSON"));
+        assert!(!is_json_type_name("_json"));
+        assert!(!is_json_type_name("xml"));
+    }
 }
@@ -1,18 +1,35 @@
 use anyhow::{anyhow, bail, Context, Error};
 use log::{debug, info};
-use odbc_api::{buffers::ColumnarAnyBuffer, ResultSetMetadata};
+use odbc_api::{
+    buffers::{BufferDesc, ColumnarAnyBuffer},
+    DataType, ResultSetMetadata,
+};
 use parquet::{
-    file::writer::SerializedColumnWriter,
+    column::writer::ColumnWriter,
     schema::types::{Type, TypePtr},
 };
-use std::sync::Arc;
+use std::{
+    cmp::min,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::parquet_buffer::ParquetBuffer;
+use crate::{enum_args::DateGranularityArgument, parquet_buffer::ParquetBuffer};
 
 use super::{
+    column_names::name_columns,
     column_strategy::{strategy_from_column_description, ColumnStrategy, MappingOptions},
+    encoding_loss::{EncodingLossCheck, OnEncodingLoss},
     fetch_batch::FetchBatch,
+    interrupt::is_interrupted,
+    non_null::NonNullCheck,
     parquet_writer::ParquetOutput,
+    partition::{date_split_values, partition_dir_name, partition_values, DATE_SPLIT_NULL},
+    profile::Profile,
+    progress::Progress,
+    summary::Summary,
+    truncation::TruncationCheck,
 };
 
 /// Contains the decisions of how to fetch each columns of a table from an ODBC data source and copy
@@ -20,7 +37,22 @@ use super::{
 /// and in what these columns are transformed.
 pub struct ConversionStrategy {
     columns: Vec<ColumnInfo>,
+    /// Relational type of each column in `columns`, as reported by the driver.
+    data_types: Vec<DataType>,
+    /// One based index of each column in `columns` within the result set. Differs from the
+    /// position in `columns`, if only a subset of the columns is selected.
+    column_indices: Vec<u16>,
+    /// Indices of the columns used to partition the output. Their values are encoded in the
+    /// directory names and not written into the parquet files.
+    partition_columns: Vec<usize>,
+    /// Indices of the columns written into the parquet files, in the order of the parquet schema.
+    exported_columns: Vec<usize>,
+    /// Tuples of original and new name, for every column which has been renamed.
+    renamed_columns: Vec<(String, String)>,
     parquet_schema: TypePtr,
+    /// Index of the date or timestamp column the output is split by, specified with
+    /// `--split-by-date`.
+    date_split: Option<(usize, DateGranularityArgument)>,
 }
 
 /// Name, ColumnStrategy
@@ -30,13 +62,36 @@ impl ConversionStrategy {
     pub fn new(
         cursor: &mut impl ResultSetMetadata,
         mapping_options: MappingOptions,
+        partition_by: &[String],
+        column_select: &[String],
     ) -> Result<Self, Error> {
         let num_cols = cursor.num_result_cols()?;
 
         let mut columns = Vec::new();
+        let mut data_types = Vec::new();
+        let mut column_indices = Vec::new();
+        // Names of all columns in the result set, including the ones not selected.
+        let mut available = Vec::new();
 
         for index in 1..(num_cols + 1) {
             let name = cursor.col_name(index as u16)?;
+            // Give a generated name, should we fail to retrieve one from the ODBC data source.
+            let name = if name.is_empty() {
+                format!("Column{index}")
+            } else {
+                name
+            };
+            available.push(name.clone());
+            // Partition columns are always fetched, since we need their values to determine the
+            // directories.
+            if !column_select.is_empty()
+                && !column_select.contains(&name)
+                && !partition_by.contains(&name)
+            {
+                debug!("Column '{name}' is not selected and will not be fetched.");
+                continue;
+            }
+
             let nullability = cursor.col_nullability(index as u16)?;
             let data_type = cursor.col_data_type(index as u16)?;
 
@@ -47,31 +102,82 @@ impl ConversionStrategy {
                 name, data_type, nullability
             );
 
-            // Give a generated name, should we fail to retrieve one from the ODBC data source.
-            let name = if name.is_empty() {
-                format!("Column{index}")
-            } else {
-                name
-            };
-
-            let column_fetch_strategy = strategy_from_column_description(
+            let Some(column_fetch_strategy) = strategy_from_column_description(
                 &name,
                 data_type,
                 nullability,
                 mapping_options,
                 cursor,
                 index,
-            )?;
+            )?
+            else {
+                continue;
+            };
             columns.push((name, column_fetch_strategy));
+            data_types.push(data_type);
+            column_indices.push(index as u16);
+        }
+
+        if let Some((unknown, _)) = mapping_options
+            .decimal_rescales
+            .iter()
+            .find(|(name, _)| !available.contains(name))
+        {
+            bail!(
+                "Column '{unknown}' specified with `--decimal-rescale` is not part of the result \
+                set. Available columns are: {}",
+                available.join(", ")
+            )
+        }
+
+        if let Some(unknown) = column_select.iter().find(|name| !available.contains(name)) {
+            bail!(
+                "Selected column '{unknown}' is not part of the result set. Available columns are: \
+                {}",
+                available.join(", ")
+            )
         }
 
         if columns.is_empty() {
             bail!("Resulting parquet file would not have any columns!")
         }
 
-        let fields = columns
+        let partition_columns = partition_by
+            .iter()
+            .map(|partition_name| {
+                columns
+                    .iter()
+                    .position(|(name, _)| name == partition_name)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Partition column '{partition_name}' is not part of the result set."
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let exported_columns: Vec<_> = (0..columns.len())
+            .filter(|index| !partition_columns.contains(index))
+            .collect();
+        if exported_columns.is_empty() {
+            bail!(
+                "Resulting parquet file would not have any columns besides the partition columns!"
+            )
+        }
+
+        let renamed_columns = name_columns(
+            columns.iter_mut().map(|(name, _)| name).collect(),
+            mapping_options.column_renames,
+            mapping_options.column_case,
+            mapping_options.sanitize_column_names,
+            &available,
+        )?;
+
+        let fields = exported_columns
             .iter()
-            .map(|(name, s)| Arc::new(s.parquet_type(name)))
+            .map(|&index| {
+                let (name, s) = &columns[index];
+                Arc::new(s.parquet_type(name))
+            })
             .collect();
         let parquet_schema = Arc::new(
             Type::group_type_builder("schema")
@@ -82,10 +188,39 @@ impl ConversionStrategy {
 
         Ok(ConversionStrategy {
             columns,
+            data_types,
+            column_indices,
+            partition_columns,
+            exported_columns,
+            renamed_columns,
             parquet_schema,
+            date_split: None,
         })
     }
 
+    /// Write the rows into one file for each distinct date in the `column`, truncated to the
+    /// `granularity`.
+    pub fn split_by_date(
+        &mut self,
+        column: &str,
+        granularity: DateGranularityArgument,
+    ) -> Result<(), Error> {
+        let Some(index) = self.columns.iter().position(|(name, _)| name == column) else {
+            bail!("Column '{column}' to split the output by is not part of the fetched columns.")
+        };
+        if !matches!(
+            self.columns[index].1.buffer_desc(),
+            BufferDesc::Date { .. } | BufferDesc::Timestamp { .. }
+        ) {
+            bail!(
+                "Can not split the output by column '{column}', since it is not fetched as a date \
+                or timestamp."
+            )
+        }
+        self.date_split = Some((index, granularity));
+        Ok(())
+    }
+
     /// Size of a single fetch buffer per row
     pub fn fetch_buffer_size_per_row(&self) -> usize {
         self.columns
@@ -95,11 +230,12 @@ impl ConversionStrategy {
     }
 
     pub fn allocate_fetch_buffer(&self, batch_size_row: usize) -> ColumnarAnyBuffer {
-        ColumnarAnyBuffer::from_descs(
+        ColumnarAnyBuffer::from_descs_and_indices(
             batch_size_row,
-            self.columns
+            self.column_indices
                 .iter()
-                .map(|(_name, strategy)| strategy.buffer_desc()),
+                .zip(&self.columns)
+                .map(|(&index, (_name, strategy))| (index, strategy.buffer_desc())),
         )
     }
 
@@ -107,47 +243,135 @@ impl ConversionStrategy {
         self.parquet_schema.clone()
     }
 
+    /// Names of the columns written into the parquet files, together with their relational type as
+    /// reported by the driver.
+    pub fn exported_column_types(&self) -> impl Iterator<Item = (&str, &DataType)> {
+        self.exported_columns
+            .iter()
+            .map(|&index| (self.columns[index].0.as_str(), &self.data_types[index]))
+    }
+
+    /// One based index within the result set of each column written into the parquet files, in
+    /// the order of the parquet schema.
+    pub fn exported_column_indices(&self) -> impl Iterator<Item = u16> + '_ {
+        self.exported_columns
+            .iter()
+            .map(|&index| self.column_indices[index])
+    }
+
+    /// Tuples of original and new name, for every column which has been renamed in the parquet
+    /// schema.
+    pub fn renamed_columns(&self) -> &[(String, String)] {
+        &self.renamed_columns
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn block_cursor_to_parquet(
         &self,
         mut fetch_strategy: Box<dyn FetchBatch>,
         mut writer: Box<dyn ParquetOutput>,
+        limit: Option<usize>,
+        progress: &mut Progress,
+        error_on_truncation: bool,
+        on_encoding_loss: OnEncodingLoss,
+        require_non_null: &[String],
+        profile_output: Option<&Path>,
+        summary: bool,
     ) -> Result<(), Error> {
         let mut num_batch = 0;
         // Count the number of total rows fetched so far for logging. This should be identical to
         // `num_batch * batch_size_row + num_rows`.
         let mut total_rows_fetched = 0;
+        // Number of rows we may still write, before we reach the limit.
+        let mut remaining_rows = limit.unwrap_or(usize::MAX);
 
         let mut pb = ParquetBuffer::new(fetch_strategy.max_batch_size_in_rows());
+        let names: Vec<_> = self.columns.iter().map(|(name, _)| name.clone()).collect();
+        let mut truncation = TruncationCheck::new(names.clone(), error_on_truncation);
+        let mut encoding_loss = EncodingLossCheck::new(names.clone(), on_encoding_loss);
+        let mut non_null = NonNullCheck::new(&names, require_non_null)?;
+        let mut summary = summary.then(|| Summary::new(names.clone()));
+        let mut profile = profile_output.map(|_| Profile::new(names));
 
-        while let Some(buffer) = fetch_strategy
-            .next_batch()
-            .map_err(|e| self.translate_fetch_error(e))?
-        {
+        while remaining_rows != 0 {
+            let Some(buffer) = fetch_strategy
+                .next_batch()
+                .map_err(|e| self.translate_fetch_error(e))?
+            else {
+                break;
+            };
             num_batch += 1;
             let num_rows = buffer.num_rows();
             total_rows_fetched += num_rows;
             info!("Fetched batch {num_batch} with {num_rows} rows.");
             info!("Fetched {total_rows_fetched} rows in total.");
-            self.write_batch(&mut writer, num_batch, buffer, &mut pb)?;
+            let num_rows = min(num_rows, remaining_rows);
+            remaining_rows -= num_rows;
+            truncation.check_batch(buffer, num_rows)?;
+            encoding_loss.check_batch(buffer, num_rows)?;
+            non_null.check_batch(buffer, num_rows)?;
+            if let Some(summary) = &mut summary {
+                summary.add_batch(buffer, num_rows);
+            }
+            if let Some(profile) = &mut profile {
+                profile.add_batch(buffer, num_rows);
+            }
+            self.write_batch(&mut writer, num_batch, buffer, num_rows, &mut pb)?;
+            progress.update(
+                total_rows_fetched,
+                writer.bytes_written(),
+                writer.current_path(),
+            );
+            if is_interrupted() {
+                break;
+            }
+        }
+        progress.finish();
+        truncation.report();
+        encoding_loss.report();
+        if remaining_rows == 0 {
+            info!("Reached limit of {} rows.", limit.unwrap());
         }
         writer.close_box()?;
+        if let Some(summary) = &summary {
+            summary.print();
+        }
+        if is_interrupted() {
+            bail!(
+                "Export has been interrupted after {total_rows_fetched} rows. The output has been \
+                closed and is readable, but incomplete."
+            )
+        }
+        if let (Some(profile), Some(path)) = (profile, profile_output) {
+            profile.write(path)?;
+        }
         Ok(())
     }
 
+    /// Writes the first `num_rows` rows of the fetch buffer.
     fn write_batch(
         &self,
         writer: &mut Box<dyn ParquetOutput>,
         num_batch: u32,
         buffer: &ColumnarAnyBuffer,
+        num_rows: usize,
         pb: &mut ParquetBuffer,
     ) -> Result<(), Error> {
-        let num_rows = buffer.num_rows();
-        pb.set_num_rows_fetched(num_rows);
+        if num_rows == buffer.num_rows() {
+            pb.set_num_rows_fetched(num_rows);
+        } else {
+            // Truncate the batch, e.g. because we reached the limit of rows
+            pb.select_rows((0..num_rows).collect());
+        }
 
         let column_exporter = ColumnExporter {
             buffer,
+            num_rows,
             conversion_buffer: pb,
             columns: &self.columns,
+            exported_columns: &self.exported_columns,
+            partition_columns: &self.partition_columns,
+            date_split: self.date_split,
         };
 
         writer.write_row_group(num_batch, column_exporter)?;
@@ -167,28 +391,6 @@ impl ConversionStrategy {
                 `--driver-does-not-support-64bit-integers` flag.",
                 )
             }
-            // It is important to give good hints for truncation errors, as they are rooted in the
-            // how ODBC expects column buffers allocated, which is fair to assume few people know.
-            // At least in the context of `odbc2parquet` we can tell them which flags to set, in
-            // order to get rid of the problem.
-            odbc_api::Error::TooLargeValueForBuffer {
-                indicator,
-                buffer_index,
-            } => {
-                let indicator_msg = if let Some(length) = indicator {
-                    format!("The driver indicated an actual length of {length}.")
-                } else {
-                    "Sadly the driver did not return a length indicator for the value, so you will \
-                    have to guess its actual length."
-                        .to_owned()
-                };
-                let column_name = self.columns[buffer_index].0.as_str();
-                anyhow!(format!(
-                    "A field exceeds the maximum element length of a column buffer. You can use \
-                    the `--column-length-limit` option to increase the maximum element size of \
-                    columns. {indicator_msg} The error occurred for column {column_name}."
-                ))
-            }
             other => other.into(),
         }
     }
@@ -197,25 +399,98 @@ impl ConversionStrategy {
 /// Exposes the contents from a fetch buffer column by column to a parquet serializer
 pub struct ColumnExporter<'a> {
     buffer: &'a ColumnarAnyBuffer,
+    /// Number of rows at the beginning of the buffer which are exported.
+    num_rows: usize,
     conversion_buffer: &'a mut ParquetBuffer,
     columns: &'a [(String, Box<dyn ColumnStrategy>)],
+    exported_columns: &'a [usize],
+    partition_columns: &'a [usize],
+    date_split: Option<(usize, DateGranularityArgument)>,
 }
 
 impl ColumnExporter<'_> {
+    /// Export the column with the index `col_index` in the parquet schema.
     pub fn export_nth_column(
         &mut self,
         col_index: usize,
-        column_writer: &mut SerializedColumnWriter,
+        column_writer: &mut ColumnWriter,
     ) -> Result<(), Error> {
+        let col_index = self.exported_columns[col_index];
         let col_name = &self.columns[col_index].0;
         debug!("Writing column with index {col_index} and name '{col_name}'.");
         let odbc_column = self.buffer.column(col_index);
         self.columns[col_index]
             .1
-            .copy_odbc_to_parquet(self.conversion_buffer, column_writer.untyped(), odbc_column)
+            .copy_odbc_to_parquet(self.conversion_buffer, column_writer, odbc_column)
             .with_context(|| {
                 format!("Failed to copy column '{col_name}' from ODBC representation into Parquet.")
             })?;
         Ok::<(), Error>(())
     }
+
+    /// Groups the rows of the current batch by the values of the partition columns. Each group is
+    /// described by the relative path of the partition directory and the indices of the rows
+    /// belonging to it. The groups are ordered by their path.
+    pub fn partitions(&self) -> Vec<(PathBuf, Vec<usize>)> {
+        let values: Vec<_> = self
+            .partition_columns
+            .iter()
+            .map(|&index| partition_values(self.buffer.column(index)))
+            .collect();
+        let mut partitions: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+        for row in 0..self.num_rows {
+            let path = self
+                .partition_columns
+                .iter()
+                .zip(&values)
+                .map(|(&index, values)| {
+                    partition_dir_name(&self.columns[index].0, values[row].as_deref())
+                })
+                .collect();
+            partitions.entry(path).or_default().push(row);
+        }
+        partitions.into_iter().collect()
+    }
+
+    /// Groups the rows of the current batch by the date in the column specified with
+    /// `--split-by-date`. Each group is described by the formatted date and the indices of the rows
+    /// belonging to it. The groups are ordered by their date.
+    pub fn date_splits(&self) -> Vec<(String, Vec<usize>)> {
+        let (index, granularity) = self
+            .date_split
+            .expect("Output must be split by date in order to group rows by it.");
+        let dates = date_split_values(self.buffer.column(index), granularity);
+        let mut splits: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (row, date) in dates.into_iter().take(self.num_rows).enumerate() {
+            let date = date.unwrap_or_else(|| DATE_SPLIT_NULL.to_owned());
+            splits.entry(date).or_default().push(row);
+        }
+        splits.into_iter().collect()
+    }
+
+    /// Only export the rows with the specified indices. Indices must be in ascending order.
+    pub fn select_rows(&mut self, rows: Vec<usize>) {
+        self.conversion_buffer.select_rows(rows);
+    }
+
+    /// Indices of the rows which are exported.
+    pub fn selected_rows(&self) -> Vec<usize> {
+        match self.conversion_buffer.row_selection() {
+            Some(rows) => rows.to_vec(),
+            None => (0..self.num_rows).collect(),
+        }
+    }
+
+    /// Allows passing the exporter to several consumers in sequence, e.g. one for each partition.
+    pub fn reborrow(&mut self) -> ColumnExporter<'_> {
+        ColumnExporter {
+            buffer: self.buffer,
+            num_rows: self.num_rows,
+            conversion_buffer: self.conversion_buffer,
+            columns: self.columns,
+            exported_columns: self.exported_columns,
+            partition_columns: self.partition_columns,
+            date_split: self.date_split,
+        }
+    }
 }
@@ -0,0 +1,94 @@
+use anyhow::Error;
+use log::info;
+use odbc_api::IntoParameter;
+
+use crate::{
+    connection::open_connection,
+    enum_args::{TrimArgument, UnknownTypeArgument},
+    CountOpt,
+};
+
+use super::{
+    batch_size_limit::BatchSizeLimit,
+    column_strategy::MappingOptions,
+    conversion_strategy::ConversionStrategy,
+    fetch_batch::{fetch_strategy, FetchRetries},
+    query_statement_text,
+};
+
+/// Executes the query and prints the number of rows in its result set. Fetch buffers are sized the
+/// same way as for an export to parquet.
+pub fn count(opt: CountOpt) -> Result<(), Error> {
+    let CountOpt {
+        connect_opts,
+        batch_size_row,
+        batch_size_memory,
+        column_length_limit,
+        encoding,
+        driver_does_not_support_64bit_integers,
+        query,
+        parameters,
+    } = opt;
+
+    let batch_size = BatchSizeLimit::new(batch_size_row, batch_size_memory);
+    let query = query_statement_text(query)?;
+    let params: Vec<_> = parameters
+        .iter()
+        .map(|param| param.as_str().into_parameter())
+        .collect();
+
+    let odbc_conn = open_connection(&connect_opts)?;
+    let db_name = odbc_conn.database_management_system_name()?;
+    info!("Database Management System Name: {db_name}");
+
+    let mapping_options = MappingOptions {
+        db_name: &db_name,
+        use_utf16: encoding.use_utf16(),
+        prefer_varbinary: false,
+        avoid_decimal: false,
+        decimal_as_double: false,
+        decimal_rescales: &[],
+        decimal_rescale_truncate: false,
+        driver_does_support_i64: !driver_does_not_support_64bit_integers,
+        column_length_limit,
+        column_length_limits: &[],
+        text_lengths: &[],
+        timestamp_int96: false,
+        timestamps_as_epoch: None,
+        column_renames: &[],
+        column_case: None,
+        sanitize_column_names: false,
+        guid_as_text: false,
+        time_as_text: false,
+        bit_as_int: false,
+        interval_as_text: false,
+        array_as_text: false,
+        json_as_text: false,
+        trim: TrimArgument::None,
+        unknown_type: UnknownTypeArgument::Text,
+    };
+
+    let Some(mut cursor) = odbc_conn
+        .into_cursor(&query, params.as_slice(), None)
+        .map_err(odbc_api::Error::from)?
+    else {
+        eprintln!("Query came back empty (not even a schema has been returned).");
+        return Ok(());
+    };
+    let table_strategy = ConversionStrategy::new(&mut cursor, mapping_options, &[], &[])?;
+    let no_retries = FetchRetries {
+        max_retries: 0,
+        backoff: Default::default(),
+    };
+    // There is no parquet to write while fetching the next batch, so concurrent fetching would
+    // only cost memory.
+    let mut fetch_strategy =
+        fetch_strategy(1, cursor, &table_strategy, batch_size, no_retries, None)?;
+
+    let mut num_rows = 0;
+    while let Some(batch) = fetch_strategy.next_batch()? {
+        num_rows += batch.num_rows();
+    }
+    println!("{num_rows}");
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! Renders fetched batches as comma separated values, specified with `--format csv`.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use odbc_api::buffers::{AnySlice, ColumnarAnyBuffer};
+
+use super::ndjson::{date_to_iso_8601, timestamp_to_iso_8601, write_json_number, TextColumn};
+
+/// Controls how fields are separated and quoted.
+#[derive(Clone, Copy)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub quote: char,
+    /// Start each file with a row of column names.
+    pub header: bool,
+}
+
+impl CsvOptions {
+    /// Row containing the names of the columns, terminated by a newline.
+    pub fn header_row(&self, columns: &[TextColumn]) -> String {
+        let mut row = String::new();
+        for (index, column) in columns.iter().enumerate() {
+            if index != 0 {
+                row.push(self.delimiter);
+            }
+            self.write_field(&mut row, &column.name);
+        }
+        row.push('\n');
+        row
+    }
+
+    /// Writes the field, enclosed in quotes if it contains the delimiter, the quote character or a
+    /// line break. Quote characters within the field are doubled.
+    fn write_field(&self, out: &mut String, field: &str) {
+        let needs_quotes = field
+            .chars()
+            .any(|c| c == self.delimiter || c == self.quote || c == '\n' || c == '\r');
+        if !needs_quotes {
+            out.push_str(field);
+            return;
+        }
+        out.push(self.quote);
+        for c in field.chars() {
+            if c == self.quote {
+                out.push(self.quote);
+            }
+            out.push(c);
+        }
+        out.push(self.quote);
+    }
+}
+
+/// Renders the first `num_rows` rows of the batch. Each row is terminated by a newline. `NULL` is
+/// written as an empty field.
+pub fn batch_to_csv(
+    options: &CsvOptions,
+    columns: &[TextColumn],
+    batch: &ColumnarAnyBuffer,
+    num_rows: usize,
+) -> Vec<String> {
+    let mut rows = vec![String::new(); num_rows];
+    for (index, column) in columns.iter().enumerate() {
+        if index != 0 {
+            for row in &mut rows {
+                row.push(options.delimiter);
+            }
+        }
+        // Appends the value of the column to each row
+        let rows = rows.iter_mut();
+        let field = |row: &mut String, text: &str| options.write_field(row, text);
+        match batch.column(index) {
+            AnySlice::Text(view) if column.is_numeric_text => {
+                append_values(rows, view.iter(), write_json_number)
+            }
+            AnySlice::Text(view) => append_values(rows, view.iter(), |row, bytes| {
+                field(row, &String::from_utf8_lossy(bytes))
+            }),
+            AnySlice::WText(view) => append_values(rows, view.iter(), |row, text| {
+                field(row, &text.to_string_lossy())
+            }),
+            AnySlice::Binary(view) => append_values(rows, view.iter(), |row, bytes| {
+                row.push_str(&STANDARD.encode(bytes))
+            }),
+            AnySlice::NullableI64(values) => {
+                append_values(rows, values, |row, n| row.push_str(&n.to_string()))
+            }
+            AnySlice::NullableF64(values) => {
+                append_values(rows, values, |row, x| row.push_str(&x.to_string()))
+            }
+            AnySlice::NullableBit(values) => append_values(rows, values, |row, bit| {
+                row.push_str(&bit.as_bool().to_string())
+            }),
+            AnySlice::NullableDate(values) => append_values(rows, values, |row, date| {
+                row.push_str(&date_to_iso_8601(date))
+            }),
+            AnySlice::NullableTimestamp(values) => append_values(rows, values, |row, ts| {
+                row.push_str(&timestamp_to_iso_8601(ts))
+            }),
+            _ => unreachable!("Fetch buffers for CSV output are always nullable."),
+        }
+    }
+    for row in &mut rows {
+        row.push('\n');
+    }
+    rows
+}
+
+fn append_values<'a, T>(
+    rows: impl Iterator<Item = &'a mut String>,
+    values: impl Iterator<Item = Option<T>>,
+    render: impl Fn(&mut String, T),
+) {
+    for (row, value) in rows.zip(values) {
+        if let Some(value) = value {
+            render(row, value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvOptions;
+
+    #[test]
+    fn quote_fields_if_required() {
+        let options = CsvOptions {
+            delimiter: ';',
+            quote: '"',
+            header: true,
+        };
+        let render = |field: &str| {
+            let mut out = String::new();
+            options.write_field(&mut out, field);
+            out
+        };
+        assert_eq!("plain, text", render("plain, text"));
+        assert_eq!("\"a;b\"", render("a;b"));
+        assert_eq!("\"say \"\"hi\"\"\"", render("say \"hi\""));
+        assert_eq!("\"two\nlines\"", render("two\nlines"));
+    }
+}
@@ -1,6 +1,11 @@
-use std::{fs::File, io::Write, path::PathBuf, sync::Arc};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::Error;
+use anyhow::{Context, Error};
 use bytesize::ByteSize;
 use log::info;
 use parquet::{
@@ -9,16 +14,28 @@ use parquet::{
 };
 use tempfile::TempPath;
 
-use super::conversion_strategy::ColumnExporter;
+use super::{
+    conversion_strategy::ColumnExporter,
+    row_group_buffer::{EncodedRowGroup, RowGroupBuffer},
+};
 
 pub struct CurrentFile {
     writer: SerializedFileWriter<Box<dyn Write + Send>>,
     /// Path to the file currently being written to.
     path: TempPath,
+    /// Name the file is renamed to once it is finalized, in case of `--atomic-output`.
+    final_path: Option<PathBuf>,
     /// Keep track of current file size so we can split it, should it get too large.
     file_size: ByteSize,
     /// Keep track of the total number of rows written into the file so far.
     total_num_rows: u64,
+    /// Accumulates rows across batches, in case of `--row-group-size-rows`. Otherwise each batch is
+    /// written as one row group.
+    row_group_buffer: Option<RowGroupBuffer>,
+    /// Upper estimate of the bytes written once the file is finalized, in addition to the ones
+    /// written so far. Only accounts for the row groups written with
+    /// [`Self::write_encoded_row_group`].
+    metadata_size: u64,
 }
 
 impl CurrentFile {
@@ -26,21 +43,34 @@ impl CurrentFile {
         path: PathBuf,
         schema: Arc<Type>,
         properties: Arc<WriterProperties>,
+        atomic_output: bool,
+        row_group_size: Option<usize>,
+        file_mode: Option<u32>,
     ) -> Result<CurrentFile, Error> {
-        let output: Box<dyn Write + Send> = Box::new(File::create(&path).map_err(|io_err| {
-            Error::from(io_err).context(format!(
-                "Could not create output file '{}'",
-                path.to_string_lossy()
-            ))
-        })?);
+        let (path, final_path) = if atomic_output {
+            (temporary_sibling(&path), Some(path))
+        } else {
+            (path, None)
+        };
+        let output: Box<dyn Write + Send> =
+            Box::new(create_file(&path, file_mode).map_err(|io_err| {
+                Error::from(io_err).context(format!(
+                    "Could not create output file '{}'",
+                    path.to_string_lossy()
+                ))
+            })?);
         let path = TempPath::from_path(path);
+        let metadata_size = empty_file_metadata_size(schema.clone(), properties.clone())?;
         let writer = SerializedFileWriter::new(output, schema.clone(), properties.clone())?;
 
         Ok(Self {
             writer,
             path,
+            final_path,
             file_size: ByteSize::b(0),
             total_num_rows: 0,
+            row_group_buffer: row_group_size.map(RowGroupBuffer::new),
+            metadata_size,
         })
     }
 
@@ -48,28 +78,102 @@ impl CurrentFile {
         &mut self,
         mut column_exporter: ColumnExporter,
     ) -> Result<ByteSize, Error> {
+        if let Some(row_group_buffer) = &mut self.row_group_buffer {
+            let num_rows = column_exporter.selected_rows().len();
+            row_group_buffer.write(&mut self.writer, column_exporter)?;
+            self.update_file_size();
+            self.total_num_rows += num_rows as u64;
+            return Ok(self.file_size);
+        }
         let mut col_index = 0;
         let mut row_group_writer = self.writer.next_row_group()?;
         while let Some(mut column_writer) = row_group_writer.next_column()? {
-            column_exporter.export_nth_column(col_index, &mut column_writer)?;
+            column_exporter.export_nth_column(col_index, column_writer.untyped())?;
             column_writer.close()?;
             col_index += 1;
         }
         let metadata = row_group_writer.close()?;
-        // Of course writing a row group increases file size. We keep track of it here, so we can
-        // split on file size if we go over a threshold.
-        self.file_size += ByteSize::b(metadata.compressed_size().try_into().unwrap());
+        self.update_file_size();
         let rows_in_row_group: u64 = metadata.num_rows().try_into().unwrap();
         self.total_num_rows += rows_in_row_group;
         Ok(self.file_size)
     }
 
+    /// Encodes the selected rows as a row group with the schema of this file, without writing it.
+    pub fn encode_row_group(
+        &self,
+        column_exporter: ColumnExporter,
+    ) -> Result<EncodedRowGroup, Error> {
+        EncodedRowGroup::new(&self.writer, column_exporter)
+    }
+
+    /// Upper estimate of the size the file would have if it were finalized now.
+    pub fn finalized_size(&self) -> u64 {
+        self.writer.bytes_written() as u64 + self.metadata_size
+    }
+
+    /// Writes a row group encoded with [`Self::encode_row_group`].
+    pub fn write_encoded_row_group(
+        &mut self,
+        row_group: EncodedRowGroup,
+        num_rows: usize,
+    ) -> Result<ByteSize, Error> {
+        self.metadata_size += row_group.metadata_size();
+        row_group.write(&mut self.writer)?;
+        self.update_file_size();
+        self.total_num_rows += num_rows as u64;
+        Ok(self.file_size)
+    }
+
+    /// Total number of rows written into the file so far.
+    pub fn num_rows(&self) -> u64 {
+        self.total_num_rows
+    }
+
+    fn update_file_size(&mut self) {
+        // Of course writing a row group increases file size. We keep track of it here, so we can
+        // split on file size if we go over a threshold. We use the number of bytes actually
+        // written to the file so far, rather than summing up the sizes reported in the row group
+        // metadata. This includes any overhead, like page headers. The length of the file on disk
+        // could lag behind, since the writer buffers its output.
+        self.file_size = ByteSize::b(self.writer.bytes_written().try_into().unwrap());
+    }
+
+    /// Number of rows of the current row group, which have not yet been written to the file. Always
+    /// zero, unless `--row-group-size-rows` is specified.
+    pub fn buffered_rows(&self) -> usize {
+        self.row_group_buffer
+            .as_ref()
+            .map_or(0, RowGroupBuffer::num_rows)
+    }
+
+    /// Final path of the file, even if it is still written to a temporary sibling.
+    pub fn path(&self) -> &Path {
+        self.final_path.as_deref().unwrap_or(&self.path)
+    }
+
+    /// Bytes written to the file so far.
+    pub fn file_size(&self) -> ByteSize {
+        self.file_size
+    }
+
     /// Writes metadata at the end and persists the file. Called if we do not want to continue
     /// writing batches into this file.
-    pub fn finalize(self) -> Result<(), Error> {
+    pub fn finalize(mut self) -> Result<(), Error> {
+        // The last row group is likely incomplete.
+        if let Some(row_group_buffer) = &mut self.row_group_buffer {
+            row_group_buffer.flush(&mut self.writer)?;
+            self.update_file_size();
+        }
         self.writer.close()?;
         // Do not persist empty files
-        let path = self.path.keep()?;
+        let path = match self.final_path {
+            Some(final_path) => {
+                persist_temporary(self.path, &final_path)?;
+                final_path
+            }
+            None => self.path.keep()?,
+        };
         info!(
             "{} rows have been written to {} with a file size of {}.",
             self.total_num_rows,
@@ -79,3 +183,53 @@ impl CurrentFile {
         Ok(())
     }
 }
+
+/// Bytes written when finalizing a file with `schema` and `properties`, which does not contain any
+/// row groups. I.e. the file metadata, its length and the magic number at the end of the file.
+fn empty_file_metadata_size(
+    schema: Arc<Type>,
+    properties: Arc<WriterProperties>,
+) -> Result<u64, Error> {
+    let writer = SerializedFileWriter::new(Vec::new(), schema, properties)?;
+    let header_size = writer.bytes_written();
+    let file = writer.into_inner()?;
+    Ok((file.len() - header_size) as u64)
+}
+
+/// Sibling of `path` with the suffix `.tmp`, e.g. `out.par.tmp`. Output is written to it in case of
+/// `--atomic-output`.
+pub fn temporary_sibling(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Creates or truncates the file at `path`, like [`File::create`]. With `mode` the file has these
+/// permissions on Unix, independent of the umask and of the permissions of an existing file.
+pub fn create_file(path: &Path, mode: Option<u32>) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        // Newly created files are never readable by others, yet the umask may remove permissions
+        // and existing files keep theirs. So we set them explicitly, too.
+        options.mode(mode);
+        let file = options.open(path)?;
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+        return Ok(file);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    options.open(path)
+}
+
+/// Renames the completely written temporary file to its final name.
+pub fn persist_temporary(temporary: TempPath, final_path: &Path) -> Result<(), Error> {
+    temporary.persist(final_path).with_context(|| {
+        format!(
+            "Could not rename output file to '{}'.",
+            final_path.to_string_lossy()
+        )
+    })
+}
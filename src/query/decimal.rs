@@ -1,6 +1,6 @@
 use std::{convert::TryInto, marker::PhantomData};
 
-use anyhow::Error;
+use anyhow::{bail, Error};
 use atoi::FromRadix10Signed;
 use odbc_api::{
     buffers::{AnySlice, BufferDesc},
@@ -121,7 +121,9 @@ pub fn decimal_fetch_strategy(
                 },
             ))
         }
-        (0..=38, _) => Box::new(DecimalAsBinary::new(repetition, scale, precision)),
+        // Parquet allows for decimals of arbitrary precision stored in fixed sized byte arrays. We
+        // support up to 76 digits, which fit into 32 bytes.
+        (0..=76, _) => Box::new(DecimalAsBinary::new(repetition, scale, precision)),
         (_, _) => {
             let length = odbc_api::DataType::Decimal {
                 precision: precision as usize,
@@ -134,6 +136,139 @@ pub fn decimal_fetch_strategy(
     }
 }
 
+/// Fetches decimals as text, with the scale reported by the database, yet stores them with
+/// `new_scale`. The precision is adjusted, so the integer digits still fit. Specified with
+/// `--decimal-rescale`. Unless `truncate` is set, values which would lose non-zero fractional
+/// digits cause an error.
+pub fn rescaled_decimal_strategy(
+    name: &str,
+    is_optional: bool,
+    scale: i32,
+    precision: u8,
+    new_scale: i32,
+    truncate: bool,
+) -> Result<Box<dyn ColumnStrategy>, Error> {
+    let repetition = if is_optional {
+        Repetition::OPTIONAL
+    } else {
+        Repetition::REQUIRED
+    };
+    let integer_digits = (precision as i32 - scale).max(0);
+    let new_precision = (integer_digits + new_scale).max(1);
+    let new_precision = match u8::try_from(new_precision) {
+        Ok(new_precision @ 1..=76) => new_precision,
+        _ => bail!(
+            "Column '{name}' would have a precision of {new_precision} with a scale of \
+            {new_scale}. Decimals with a precision of more than 76 are not supported."
+        ),
+    };
+    let logical_type = LogicalType::Decimal {
+        scale: new_scale,
+        precision: new_precision as i32,
+    };
+    let target: Box<dyn ColumnStrategy> = match new_precision {
+        0..=9 => Box::new(DecimalTextToInteger::<Int32Type>::new(
+            new_precision,
+            new_scale,
+            repetition,
+            logical_type,
+        )),
+        10..=18 => Box::new(DecimalTextToInteger::<Int64Type>::new(
+            new_precision,
+            new_scale,
+            repetition,
+            logical_type,
+        )),
+        _ => Box::new(DecimalAsBinary::new(repetition, new_scale, new_precision)),
+    };
+    // The text buffer must be large enough for the values in their original scale.
+    let max_str_len = DataType::Decimal {
+        precision: precision as usize,
+        scale: scale.try_into().unwrap(),
+    }
+    .display_size()
+    .unwrap()
+    .get();
+    Ok(Box::new(RescaledDecimal {
+        name: name.to_owned(),
+        target,
+        max_str_len,
+        scale: new_scale as usize,
+        truncate,
+    }))
+}
+
+struct RescaledDecimal {
+    /// Name of the column in the result set, used in error messages.
+    name: String,
+    /// Converts the text into the new scale and writes it to parquet. Any fractional digits beyond
+    /// the new scale are truncated towards zero.
+    target: Box<dyn ColumnStrategy>,
+    max_str_len: usize,
+    /// Scale in the output.
+    scale: usize,
+    /// Allow cutting off non-zero fractional digits.
+    truncate: bool,
+}
+
+impl ColumnStrategy for RescaledDecimal {
+    fn parquet_type(&self, name: &str) -> Type {
+        self.target.parquet_type(name)
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        BufferDesc::Text {
+            max_str_len: self.max_str_len,
+        }
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        if !self.truncate {
+            let view = column_view.as_text_view().expect(
+                "Invalid Column view type. This is not supposed to happen. Please open a Bug at \
+                https://github.com/pacman82/odbc2parquet/issues.",
+            );
+            if let Some(text) = view
+                .iter()
+                .flatten()
+                .find(|text| has_digits_beyond_scale(text, self.scale))
+            {
+                bail!(
+                    "Value {} of column '{}' can not be rescaled to a scale of {} without losing \
+                    significant digits. Use `--decimal-rescale-truncate` to cut them off.",
+                    String::from_utf8_lossy(text),
+                    self.name,
+                    self.scale
+                )
+            }
+        }
+        self.target
+            .copy_odbc_to_parquet(parquet_buffer, column_writer, column_view)
+    }
+}
+
+/// `true` if the text representation of a decimal has non-zero fractional digits beyond `scale`.
+/// Like [`decimal_text_to_i128`] any non digit character is regarded as a radix character, with the
+/// exception of a leading `+` or `-`.
+fn has_digits_beyond_scale(text: &[u8], scale: usize) -> bool {
+    let text = match text.first() {
+        Some(b'-' | b'+') => &text[1..],
+        _ => text,
+    };
+    let num_digits_high = text.iter().take_while(|c| c.is_ascii_digit()).count();
+    // Skip the radix character
+    let low = text.get(num_digits_high + 1..).unwrap_or_default();
+    low.iter()
+        .take_while(|c| c.is_ascii_digit())
+        .skip(scale)
+        .any(|&c| c != b'0')
+}
+
 struct DecimalTextToInteger<Pdt> {
     precision: u8,
     scale: i32,
@@ -220,7 +355,9 @@ impl FromDecimalTextRepresentation for i64 {
     }
 }
 
-/// Strategy for fetching decimal values which can not be represented as either 32Bit or 64Bit
+/// Strategy for fetching decimal values which can not be represented as either 32Bit or 64Bit.
+/// Values with a precision of up to 38 are converted via `i128`, larger ones via a 256 Bit twos
+/// complement.
 struct DecimalAsBinary {
     repetition: Repetition,
     scale: i32,
@@ -303,12 +440,105 @@ fn write_decimal_col(
 
     let scale = scale as usize;
 
-    parquet_buffer.write_twos_complement_i128(
-        column_writer,
-        view.iter()
-            .map(|field| field.map(|text| decimal_text_to_i128(text, scale))),
-        length_in_bytes,
-    )?;
+    if length_in_bytes <= 16 {
+        parquet_buffer.write_twos_complement_i128(
+            column_writer,
+            view.iter()
+                .map(|field| field.map(|text| decimal_text_to_i128(text, scale))),
+            length_in_bytes,
+        )?;
+    } else {
+        parquet_buffer.write_twos_complement_bytes(
+            column_writer,
+            view.iter()
+                .map(|field| field.map(|text| decimal_text_to_i256_bytes(text, scale))),
+            length_in_bytes,
+        )?;
+    }
 
     Ok(())
 }
+
+/// Big endian 256 Bit twos complement of the decimal value times 10 to the power of `scale`. Like
+/// [`decimal_text_to_i128`] any non digit character is regarded as a radix character, with the
+/// exception of a leading `+` or `-`. Additional fractional digits beyond `scale` are truncated.
+fn decimal_text_to_i256_bytes(text: &[u8], scale: usize) -> [u8; 32] {
+    let (is_negative, text) = match text.first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let num_digits_high = text.iter().take_while(|c| c.is_ascii_digit()).count();
+    let (high, low) = text.split_at(num_digits_high);
+    // Skip the radix character
+    let low = low.get(1..).unwrap_or_default();
+    let low = &low[..low.iter().take_while(|c| c.is_ascii_digit()).count()];
+    let low = &low[..low.len().min(scale)];
+
+    let mut bytes = [0u8; 32];
+    let mut multiply_add = |digit: u8| {
+        let mut carry = digit as u16;
+        for byte in bytes.iter_mut().rev() {
+            let value = *byte as u16 * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+    };
+    high.iter().chain(low).for_each(|&c| multiply_add(c - b'0'));
+    // Pad fraction with zeroes to match scale
+    (low.len()..scale).for_each(|_| multiply_add(0));
+
+    if is_negative {
+        // Twos complement: Invert all bits and add one
+        let mut carry = 1;
+        for byte in bytes.iter_mut().rev() {
+            let value = (!*byte) as u16 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use odbc_api::{decimal_text_to_i32, decimal_text_to_i64};
+
+    use super::{decimal_text_to_i256_bytes, has_digits_beyond_scale};
+
+    #[test]
+    fn decimal_text_to_256_bit_twos_complement() {
+        let as_i128 = |bytes: [u8; 32]| i128::from_be_bytes(bytes[16..].try_into().unwrap());
+
+        assert_eq!(12345, as_i128(decimal_text_to_i256_bytes(b"123.45", 2)));
+        assert_eq!(123450, as_i128(decimal_text_to_i256_bytes(b"123.45", 3)));
+        assert_eq!(-1234, as_i128(decimal_text_to_i256_bytes(b"-123.45", 1)));
+        assert_eq!([0xff; 32], decimal_text_to_i256_bytes(b"-1", 0));
+        // 10^40 does not fit into 128 Bit
+        let bytes = decimal_text_to_i256_bytes(b"10000000000000000000000000000000000000000", 0);
+        assert_eq!(
+            "0000000000000000000000000000001d6329f1c35ca4bfabb9f5610000000000",
+            bytes.iter().map(|b| format!("{b:02x}")).collect::<String>()
+        );
+    }
+
+    #[test]
+    fn rescale_decimal_text() {
+        // Trailing zeroes are not significant
+        assert!(!has_digits_beyond_scale(b"1.990", 2));
+        assert!(!has_digits_beyond_scale(b"100", 0));
+        assert!(!has_digits_beyond_scale(b".50", 1));
+        assert!(!has_digits_beyond_scale(b"-12.3", 4));
+        assert!(has_digits_beyond_scale(b"1.995", 2));
+        assert!(has_digits_beyond_scale(b"-0.001", 2));
+        assert!(has_digits_beyond_scale(b"1.5", 0));
+
+        // Additional digits are truncated towards zero, rather than rounded.
+        assert_eq!(199, decimal_text_to_i32(b"1.995", 2));
+        assert_eq!(-199, decimal_text_to_i32(b"-1.995", 2));
+        assert_eq!(0, decimal_text_to_i32(b"-0.001", 2));
+        // Increasing the scale pads the fraction with zeroes.
+        assert_eq!(1500, decimal_text_to_i64(b"1.5", 3));
+        assert_eq!(-1_234_500_000_000_000, decimal_text_to_i64(b"-1234.5", 12));
+    }
+}
@@ -0,0 +1,116 @@
+//! Detects text fetched with the system encoding, which is not valid UTF-8. These values are
+//! written with replacement characters.
+
+use anyhow::{bail, Error};
+use log::{info, warn};
+use odbc_api::buffers::{AnySlice, ColumnarAnyBuffer};
+
+use crate::enum_args::EncodingArgument;
+
+/// How to react to text values which are not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnEncodingLoss {
+    /// Replace invalid characters and log a warning for each affected column.
+    Warn,
+    /// Abort the export. Specified with `--error-on-encoding-loss`.
+    Fail,
+    /// Replace invalid characters, like `Warn`, but only log at info level. Specified with
+    /// `--encoding Utf8Lossy`.
+    Replace,
+}
+
+impl OnEncodingLoss {
+    pub fn new(encoding: EncodingArgument, error_on_encoding_loss: bool) -> Result<Self, Error> {
+        match (encoding, error_on_encoding_loss) {
+            (EncodingArgument::Utf8Lossy, true) => bail!(
+                "`--encoding Utf8Lossy` replaces invalid UTF-8 on purpose and can not be combined \
+                with `--error-on-encoding-loss`."
+            ),
+            (EncodingArgument::Utf8Lossy, false) => Ok(Self::Replace),
+            (_, true) => Ok(Self::Fail),
+            (_, false) => Ok(Self::Warn),
+        }
+    }
+}
+
+/// Counts the values of each column, which are not valid UTF-8, or fails on the first one if
+/// `--error-on-encoding-loss` is set.
+pub struct EncodingLossCheck {
+    /// Names of the columns, in the order of the fetch buffers.
+    names: Vec<String>,
+    on_encoding_loss: OnEncodingLoss,
+    /// Number of invalid values and one based row of the first invalid value for each column.
+    invalid: Vec<(usize, usize)>,
+    /// Number of rows in all previous batches. Used to report the row offset of invalid values.
+    rows_before_batch: usize,
+}
+
+impl EncodingLossCheck {
+    pub fn new(names: Vec<String>, on_encoding_loss: OnEncodingLoss) -> Self {
+        let invalid = vec![(0, 0); names.len()];
+        Self {
+            names,
+            on_encoding_loss,
+            invalid,
+            rows_before_batch: 0,
+        }
+    }
+
+    /// Inspects the first `num_rows` rows of the batch. Only narrow text columns are affected,
+    /// since wide text is decoded from UTF-16 independent of the system locale.
+    pub fn check_batch(&mut self, batch: &ColumnarAnyBuffer, num_rows: usize) -> Result<(), Error> {
+        for (index, name) in self.names.iter().enumerate() {
+            let AnySlice::Text(view) = batch.column(index) else {
+                continue;
+            };
+            let mut invalid_rows = view
+                .iter()
+                .take(num_rows)
+                .enumerate()
+                .filter(|(_, value)| value.is_some_and(|bytes| std::str::from_utf8(bytes).is_err()))
+                // One based, like the rows in most database tools.
+                .map(|(row, _)| self.rows_before_batch + row + 1);
+            let Some(first_row) = invalid_rows.next() else {
+                continue;
+            };
+            if self.on_encoding_loss == OnEncodingLoss::Fail {
+                bail!(
+                    "The value of column {name} in row {first_row} is not valid UTF-8. The text \
+                    has been fetched using the encoding of the system locale. Try to execute \
+                    odbc2parquet in a shell with UTF-8 locale or specify `--encoding Utf16`."
+                )
+            }
+            let (num_invalid, first) = &mut self.invalid[index];
+            if *num_invalid == 0 {
+                *first = first_row;
+            }
+            *num_invalid += 1 + invalid_rows.count();
+        }
+        self.rows_before_batch += num_rows;
+        Ok(())
+    }
+
+    /// Logs a warning for each column with values which are not valid UTF-8. Only an info, if the
+    /// user expects invalid text with `--encoding Utf8Lossy`.
+    pub fn report(&self) {
+        for (name, &(num_invalid, first_row)) in self.names.iter().zip(&self.invalid) {
+            if num_invalid == 0 {
+                continue;
+            }
+            if self.on_encoding_loss == OnEncodingLoss::Replace {
+                info!(
+                    "{num_invalid} values of column '{name}' are not valid UTF-8, starting with \
+                    row {first_row}. Invalid characters have been replaced."
+                );
+            } else {
+                warn!(
+                    "{num_invalid} values of column '{name}' are not valid UTF-8, starting with \
+                    row {first_row}. Invalid characters have been replaced. Try to execute \
+                    odbc2parquet in a shell with UTF-8 locale or specify `--encoding Utf16`. Use \
+                    `--error-on-encoding-loss` to abort the export instead, or `--encoding \
+                    Utf8Lossy` to silence this warning."
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Stores dates and timestamps as plain integers, counting the time units since the UNIX epoch.
+//! Specified with `--timestamps-as-epoch`.
+
+use anyhow::Error;
+use chrono::{NaiveDate, NaiveDateTime};
+use odbc_api::{
+    buffers::{AnySlice, BufferDesc, Item},
+    sys::{Date, Timestamp},
+};
+use parquet::{
+    basic::Repetition,
+    column::writer::ColumnWriter,
+    data_type::{DataType, Int64Type},
+    schema::types::Type,
+};
+
+use crate::{enum_args::EpochUnitArgument, parquet_buffer::ParquetBuffer};
+
+use super::column_strategy::ColumnStrategy;
+
+pub fn timestamp_as_epoch(
+    repetition: Repetition,
+    unit: EpochUnitArgument,
+) -> Box<dyn ColumnStrategy> {
+    Box::new(EpochToI64 {
+        repetition,
+        unit,
+        is_date: false,
+    })
+}
+
+/// Dates are stored as the epoch of midnight at the beginning of the day.
+pub fn date_as_epoch(repetition: Repetition, unit: EpochUnitArgument) -> Box<dyn ColumnStrategy> {
+    Box::new(EpochToI64 {
+        repetition,
+        unit,
+        is_date: true,
+    })
+}
+
+struct EpochToI64 {
+    repetition: Repetition,
+    unit: EpochUnitArgument,
+    /// Fetch the column as date rather than as timestamp.
+    is_date: bool,
+}
+
+impl ColumnStrategy for EpochToI64 {
+    fn parquet_type(&self, name: &str) -> Type {
+        // No logical type, readers only see plain integers.
+        Type::primitive_type_builder(name, Int64Type::get_physical_type())
+            .with_repetition(self.repetition)
+            .build()
+            .unwrap()
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        if self.is_date {
+            BufferDesc::Date { nullable: true }
+        } else {
+            BufferDesc::Timestamp { nullable: true }
+        }
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        let into = Int64Type::get_column_writer_mut(column_writer).unwrap();
+        if self.is_date {
+            let from = Date::as_nullable_slice(column_view).unwrap();
+            let from = from.map(|date| date.map(|date| epoch(date_to_datetime(date), self.unit)));
+            parquet_buffer.write_optional(into, from)?;
+        } else {
+            let from = Timestamp::as_nullable_slice(column_view).unwrap();
+            let from = from.map(|ts| ts.map(|ts| epoch(timestamp_to_datetime(ts), self.unit)));
+            parquet_buffer.write_optional(into, from)?;
+        }
+        Ok(())
+    }
+}
+
+fn date_to_datetime(date: &Date) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+
+fn timestamp_to_datetime(ts: &Timestamp) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(ts.year as i32, ts.month as u32, ts.day as u32)
+        .unwrap()
+        .and_hms_nano_opt(
+            ts.hour as u32,
+            ts.minute as u32,
+            ts.second as u32,
+            ts.fraction,
+        )
+        .unwrap()
+}
+
+/// Number of units since the UNIX epoch. Timestamps without time zone are interpreted as UTC.
+/// Fractions of a unit are truncated towards negative infinity.
+fn epoch(datetime: NaiveDateTime, unit: EpochUnitArgument) -> i64 {
+    let datetime = datetime.and_utc();
+    match unit {
+        EpochUnitArgument::Seconds => datetime.timestamp(),
+        EpochUnitArgument::Millis => datetime.timestamp_millis(),
+        EpochUnitArgument::Micros => datetime.timestamp_micros(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odbc_api::sys::{Date, Timestamp};
+
+    use crate::enum_args::EpochUnitArgument;
+
+    use super::{date_to_datetime, epoch, timestamp_to_datetime};
+
+    #[test]
+    fn timestamp_to_epoch() {
+        let ts = Timestamp {
+            year: 2020,
+            month: 9,
+            day: 16,
+            hour: 3,
+            minute: 54,
+            second: 12,
+            fraction: 123_456_789,
+        };
+        let datetime = timestamp_to_datetime(&ts);
+        assert_eq!(1600228452, epoch(datetime, EpochUnitArgument::Seconds));
+        assert_eq!(1600228452123, epoch(datetime, EpochUnitArgument::Millis));
+        assert_eq!(1600228452123456, epoch(datetime, EpochUnitArgument::Micros));
+
+        let date = Date {
+            year: 1969,
+            month: 12,
+            day: 31,
+        };
+        assert_eq!(
+            -86400,
+            epoch(date_to_datetime(&date), EpochUnitArgument::Seconds)
+        );
+    }
+}
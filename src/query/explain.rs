@@ -0,0 +1,85 @@
+//! Prints the execution plan of the query instead of exporting its result set. Specified with
+//! `--explain` or `--explain-analyze`.
+
+use std::io::{stdout, Write};
+
+use anyhow::{bail, Error};
+use odbc_api::{Connection, Cursor, ParameterCollectionRef, ResultSetMetadata};
+
+use crate::enum_args::SqlDialectArgument;
+
+/// Executes the query wrapped in the `EXPLAIN` statement of `dialect` and streams the rows of the
+/// returned plan to standard output. Columns are separated by tabs, and every result set is
+/// preceded by a line with the names of its columns. With `analyze` the query is actually executed,
+/// so the plan contains the times and row counts measured by the database.
+pub fn explain_query(
+    conn: &Connection,
+    query: &str,
+    params: impl ParameterCollectionRef,
+    dialect: SqlDialectArgument,
+    analyze: bool,
+) -> Result<(), Error> {
+    let query = query.trim().trim_end_matches(';');
+    match dialect {
+        SqlDialectArgument::Postgresql | SqlDialectArgument::Mysql => {
+            let keyword = if analyze {
+                "EXPLAIN ANALYZE"
+            } else {
+                "EXPLAIN"
+            };
+            print_result_sets(conn, &format!("{keyword} {query}"), params)
+        }
+        SqlDialectArgument::Mssql => {
+            if analyze {
+                bail!(
+                    "`--explain-analyze` is not supported for Microsoft SQL Server. Use \
+                    `--explain` to print the estimated plan."
+                )
+            }
+            // While `SHOWPLAN_TEXT` is on, statements are not executed. Instead their plan is
+            // returned. It must be the only statement of its batch.
+            conn.execute("SET SHOWPLAN_TEXT ON", (), None)?;
+            let result = print_result_sets(conn, query, params);
+            conn.execute("SET SHOWPLAN_TEXT OFF", (), None)?;
+            result
+        }
+    }
+}
+
+fn print_result_sets(
+    conn: &Connection,
+    statement: &str,
+    params: impl ParameterCollectionRef,
+) -> Result<(), Error> {
+    let Some(mut cursor) = conn.execute(statement, params, None)? else {
+        bail!("The database did not return a plan for the query.")
+    };
+    let mut out = stdout().lock();
+    let mut field = Vec::new();
+    loop {
+        let num_cols = cursor.num_result_cols()?.try_into().unwrap();
+        let mut names = Vec::new();
+        for index in 1..=num_cols {
+            names.push(cursor.col_name(index)?);
+        }
+        writeln!(out, "{}", names.join("\t"))?;
+        while let Some(mut row) = cursor.next_row()? {
+            for index in 1..=num_cols {
+                if index != 1 {
+                    out.write_all(b"\t")?;
+                }
+                // `NULL` is written as an empty field.
+                if row.get_text(index, &mut field)? {
+                    out.write_all(&field)?;
+                }
+            }
+            out.write_all(b"\n")?;
+        }
+        match cursor.more_results()? {
+            Some(next) => cursor = next,
+            None => break,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -1,14 +1,21 @@
-use std::mem::swap;
+use std::{
+    mem::replace,
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    thread::{self, sleep, JoinHandle},
+    time::Duration,
+};
 
 use anyhow::Error;
-use log::info;
-use odbc_api::{
-    buffers::ColumnarAnyBuffer, BlockCursor, ConcurrentBlockCursor, Cursor, RowSetBuffer,
-};
+use bytesize::ByteSize;
+use log::{info, warn};
+use odbc_api::{buffers::ColumnarAnyBuffer, BlockCursor, Cursor};
 
 use crate::parquet_buffer::ParquetBuffer;
 
-use super::{batch_size_limit::BatchSizeLimit, conversion_strategy::ConversionStrategy};
+use super::{
+    batch_size_limit::{check_total_memory, BatchSizeLimit},
+    conversion_strategy::ConversionStrategy,
+};
 
 pub trait FetchBatch {
     /// Maximum batch size in rows. This is used to allocate the parquet buffer of correct size.
@@ -18,23 +25,57 @@ pub trait FetchBatch {
     fn next_batch(&mut self) -> Result<Option<&ColumnarAnyBuffer>, odbc_api::Error>;
 }
 
+/// Controls how often a fetch is retried, which failed due to a connection level error.
+#[derive(Clone, Copy)]
+pub struct FetchRetries {
+    /// Maximum number of retries for a single batch. `0` means fetches are not retried.
+    pub max_retries: u32,
+    /// Time to wait before the first retry. Doubled for each further retry of the same batch.
+    pub backoff: Duration,
+}
+
+/// `fetch_buffer_count` is the number of fetch buffers allocated. With more than one, batches are
+/// fetched concurrently to writing the output.
 pub fn fetch_strategy(
-    concurrent_fetching: bool,
+    fetch_buffer_count: usize,
     cursor: impl Cursor + 'static + Send,
     conversion_strategy: &ConversionStrategy,
     batch_size_limit: BatchSizeLimit,
+    retries: FetchRetries,
+    max_total_memory: Option<ByteSize>,
 ) -> Result<Box<dyn FetchBatch>, Error> {
-    if concurrent_fetching {
+    // The fetch thread of a concurrent cursor stops after the first error, so we can only retry
+    // fetching with a sequential cursor.
+    if fetch_buffer_count > 1 && retries.max_retries != 0 {
+        info!("Fetching sequentially, in order to be able to retry failed fetches.");
+    }
+    let concurrent = fetch_buffer_count > 1 && retries.max_retries == 0;
+    let num_buffers = if concurrent { fetch_buffer_count } else { 1 };
+
+    let mem_usage_odbc_buffer_per_row: usize = conversion_strategy.fetch_buffer_size_per_row();
+    let total_mem_usage_per_row =
+        mem_usage_odbc_buffer_per_row + ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW;
+    let batch_size_row = batch_size_limit.batch_size_in_rows(total_mem_usage_per_row)?;
+    check_total_memory(
+        batch_size_row,
+        total_mem_usage_per_row,
+        num_buffers,
+        max_total_memory,
+    )?;
+
+    if concurrent {
         Ok(Box::new(ConcurrentFetch::new(
             cursor,
             conversion_strategy,
-            batch_size_limit,
+            batch_size_row,
+            fetch_buffer_count,
         )?))
     } else {
         Ok(Box::new(SequentialFetch::new(
             cursor,
             conversion_strategy,
-            batch_size_limit,
+            batch_size_row,
+            retries,
         )?))
     }
 }
@@ -44,6 +85,7 @@ pub fn fetch_strategy(
 /// one fetch buffer.
 struct SequentialFetch<C: Cursor> {
     block_cursor: BlockCursor<C, ColumnarAnyBuffer>,
+    retries: FetchRetries,
 }
 
 impl<C> SequentialFetch<C>
@@ -53,25 +95,16 @@ where
     pub fn new(
         cursor: C,
         table_strategy: &ConversionStrategy,
-        batch_size_limit: BatchSizeLimit,
+        batch_size_row: usize,
+        retries: FetchRetries,
     ) -> Result<Self, Error> {
-        let mem_usage_odbc_buffer_per_row: usize = table_strategy.fetch_buffer_size_per_row();
-        let total_mem_usage_per_row =
-            mem_usage_odbc_buffer_per_row + ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW;
-        info!(
-            "Memory usage per row is {} bytes. This excludes memory directly allocated by the ODBC \
-            driver.",
-            total_mem_usage_per_row,
-        );
-
-        let batch_size_row = batch_size_limit.batch_size_in_rows(total_mem_usage_per_row)?;
-
-        info!("Batch size set to {} rows.", batch_size_row);
-
         let fetch_buffer = table_strategy.allocate_fetch_buffer(batch_size_row);
 
         let block_cursor = cursor.bind_buffer(fetch_buffer)?;
-        Ok(Self { block_cursor })
+        Ok(Self {
+            block_cursor,
+            retries,
+        })
     }
 }
 
@@ -80,8 +113,32 @@ where
     C: Cursor,
 {
     fn next_batch(&mut self) -> Result<Option<&ColumnarAnyBuffer>, odbc_api::Error> {
-        let batch = self.block_cursor.fetch_with_truncation_check(true)?;
-        Ok(batch)
+        // Rows of a batch are only written to parquet after the fetch succeeded, so retrying a
+        // failed fetch never causes rows to be written twice.
+        let block_cursor: *mut BlockCursor<C, ColumnarAnyBuffer> = &mut self.block_cursor;
+        let mut attempt = 0;
+        loop {
+            // Safety: The borrow checker does not understand that the borrow of the cursor ends
+            // when we retry in the error branch, so we reborrow it from a pointer in each
+            // iteration. At most one borrow, the one returned, is alive at any time.
+            // Truncated values are detected by `TruncationCheck`, so they can be counted instead of
+            // failing the fetch.
+            let result = unsafe { &mut *block_cursor }.fetch_with_truncation_check(false);
+            match result {
+                Err(error) if attempt < self.retries.max_retries && is_connection_error(&error) => {
+                    let backoff = self.retries.backoff * 2u32.saturating_pow(attempt);
+                    attempt += 1;
+                    warn!(
+                        "Fetching a batch failed with a connection error. Retry {attempt} of {} in \
+                        {} ms.\n{error}",
+                        self.retries.max_retries,
+                        backoff.as_millis()
+                    );
+                    sleep(backoff);
+                }
+                result => return result,
+            }
+        }
     }
 
     fn max_batch_size_in_rows(&self) -> usize {
@@ -89,64 +146,135 @@ where
     }
 }
 
-/// Use a concurrent cursor and an extra buffers. One buffers content is read and written into
-/// parquet, while the other is filled in an extra system thread.
-struct ConcurrentFetch<C: Cursor> {
+/// Fetches batches in an extra system thread, while the contents of a previously fetched batch is
+/// written into parquet. Up to `fetch_buffer_count - 1` batches are fetched ahead.
+struct ConcurrentFetch {
     // This buffer is read from and its contents is written into parquet.
-    buffer: ColumnarAnyBuffer,
-    block_cursor: ConcurrentBlockCursor<C, ColumnarAnyBuffer>,
+    buffer: Option<ColumnarAnyBuffer>,
+    /// Receives filled batches from the fetch thread. Its sender is dropped once the result set is
+    /// consumed or an error occurred.
+    receive_batch: Receiver<ColumnarAnyBuffer>,
+    /// Sends buffers, whose contents have been written, back to the fetch thread to be filled again.
+    send_buffer: SyncSender<ColumnarAnyBuffer>,
+    /// `None` once the thread has been joined.
+    fetch_thread: Option<JoinHandle<Result<(), odbc_api::Error>>>,
+    batch_size_row: usize,
 }
 
-impl<C> ConcurrentFetch<C>
-where
-    C: Cursor + Send + 'static,
-{
+impl ConcurrentFetch {
     pub fn new(
-        cursor: C,
+        cursor: impl Cursor + Send + 'static,
         table_strategy: &ConversionStrategy,
-        batch_size_limit: BatchSizeLimit,
+        batch_size_row: usize,
+        fetch_buffer_count: usize,
     ) -> Result<Self, Error> {
-        let mem_usage_odbc_buffer_per_row: usize = table_strategy.fetch_buffer_size_per_row();
-        let total_mem_usage_per_row =
-            mem_usage_odbc_buffer_per_row + ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW;
-        info!(
-            "Memory usage per row is 2x {} bytes (buffer is alloctated two times, because of \
-            concurrent fetching). This excludes memory directly allocated by the ODBC driver.",
-            total_mem_usage_per_row,
-        );
-
-        let batch_size_row = batch_size_limit.batch_size_in_rows(total_mem_usage_per_row)?;
+        let fetch_buffer = table_strategy.allocate_fetch_buffer(batch_size_row);
+        let mut block_cursor = cursor.bind_buffer(fetch_buffer)?;
 
-        info!("Batch size set to {} rows.", batch_size_row);
+        // One buffer is bound to the cursor, one is read by the application. The others are either
+        // filled batches waiting to be written, or spare buffers waiting to be filled.
+        let (send_batch, receive_batch) = sync_channel(fetch_buffer_count - 1);
+        let (send_buffer, receive_buffer) = sync_channel(fetch_buffer_count);
+        for _ in 2..fetch_buffer_count {
+            send_buffer
+                .send(table_strategy.allocate_fetch_buffer(batch_size_row))
+                .unwrap();
+        }
 
-        let fetch_buffer = table_strategy.allocate_fetch_buffer(batch_size_row);
-        let buffer = table_strategy.allocate_fetch_buffer(batch_size_row);
+        let fetch_thread = thread::spawn(move || loop {
+            // Truncated values are detected by `TruncationCheck`, so they can be counted instead of
+            // failing the fetch.
+            if block_cursor.fetch_with_truncation_check(false)?.is_none() {
+                break Ok(());
+            }
+            let (cursor, batch) = block_cursor.unbind()?;
+            // Either channel is only closed, if the application stopped reading batches, e.g.
+            // because of `--limit`.
+            if send_batch.send(batch).is_err() {
+                break Ok(());
+            }
+            let Ok(next_buffer) = receive_buffer.recv() else {
+                break Ok(());
+            };
+            block_cursor = cursor.bind_buffer(next_buffer)?;
+        });
 
-        let block_cursor = cursor.bind_buffer(fetch_buffer)?;
-        let block_cursor = ConcurrentBlockCursor::from_block_cursor(block_cursor);
         Ok(Self {
-            buffer,
-            block_cursor,
+            buffer: None,
+            receive_batch,
+            send_buffer,
+            fetch_thread: Some(fetch_thread),
+            batch_size_row,
         })
     }
 }
 
-impl<C> FetchBatch for ConcurrentFetch<C>
-where
-    C: Cursor,
-{
+impl FetchBatch for ConcurrentFetch {
     fn next_batch(&mut self) -> Result<Option<&ColumnarAnyBuffer>, odbc_api::Error> {
-        let batch = self.block_cursor.fetch()?;
-        if let Some(mut batch) = batch {
-            swap(&mut batch, &mut self.buffer);
-            self.block_cursor.fill(batch);
-            Ok(Some(&self.buffer))
-        } else {
-            Ok(None)
+        // The previous batch has been written, so it can be filled again.
+        if let Some(buffer) = self.buffer.take() {
+            let _ = self.send_buffer.send(buffer);
+        }
+        match self.receive_batch.recv() {
+            Ok(batch) => Ok(Some(self.buffer.insert(batch))),
+            // The fetch thread stopped, either because the result set is consumed or due to an
+            // error.
+            Err(_) => match self.fetch_thread.take() {
+                Some(fetch_thread) => fetch_thread.join().unwrap().map(|()| None),
+                None => Ok(None),
+            },
         }
     }
 
     fn max_batch_size_in_rows(&self) -> usize {
-        self.buffer.row_array_size()
+        self.batch_size_row
+    }
+}
+
+impl Drop for ConcurrentFetch {
+    fn drop(&mut self) {
+        if let Some(fetch_thread) = self.fetch_thread.take() {
+            // Disconnect both channels, so the fetch thread stops after the batch it is currently
+            // fetching, rather than outliving the export, e.g. after `--limit` or an error.
+            drop(replace(&mut self.send_buffer, sync_channel(0).0));
+            drop(replace(&mut self.receive_batch, sync_channel(0).1));
+            // We are already shutting down, so errors of the remaining fetch are of no interest.
+            let _ = fetch_thread.join();
+        }
+    }
+}
+
+/// `true` if the error indicates a problem with the connection, rather than with the query or the
+/// data. Only these errors are worth retrying.
+fn is_connection_error(error: &odbc_api::Error) -> bool {
+    match error {
+        // SQLSTATE class 08 indicates a connection exception. HYT01 is a connection timeout.
+        odbc_api::Error::Diagnostics { record, .. } => {
+            record.state.0.starts_with(b"08") || record.state.0 == *b"HYT01"
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odbc_api::handles::{Record, State};
+
+    use super::is_connection_error;
+
+    #[test]
+    fn only_retry_connection_errors() {
+        let error = |state: &[u8; 5]| odbc_api::Error::Diagnostics {
+            record: Record {
+                state: State(*state),
+                ..Record::default()
+            },
+            function: "SQLFetch",
+        };
+
+        assert!(is_connection_error(&error(b"08S01")));
+        assert!(is_connection_error(&error(b"HYT01")));
+        assert!(!is_connection_error(&error(b"42S02")));
+        assert!(!is_connection_error(&error(b"22003")));
     }
 }
@@ -0,0 +1,99 @@
+use anyhow::Error;
+use odbc_api::buffers::{AnySlice, BufferDesc};
+use parquet::{
+    basic::{LogicalType, Repetition, Type as PhysicalType},
+    column::writer::{get_typed_column_writer_mut, ColumnWriter},
+    data_type::{ByteArray, FixedLenByteArray, FixedLenByteArrayType},
+    schema::types::Type,
+};
+
+use crate::parquet_buffer::ParquetBuffer;
+
+use super::column_strategy::ColumnStrategy;
+
+/// Size of a GUID in bytes.
+const GUID_LENGTH: usize = 16;
+
+/// Fetches GUIDs (e.g. `UNIQUEIDENTIFIER` on Microsoft SQL Server) as binary and writes them as
+/// `FIXED_LEN_BYTE_ARRAY(16)` with logical type `UUID`.
+pub struct Guid {
+    repetition: Repetition,
+}
+
+impl Guid {
+    pub fn new(repetition: Repetition) -> Self {
+        Self { repetition }
+    }
+}
+
+impl ColumnStrategy for Guid {
+    fn parquet_type(&self, name: &str) -> Type {
+        Type::primitive_type_builder(name, PhysicalType::FIXED_LEN_BYTE_ARRAY)
+            .with_logical_type(Some(LogicalType::Uuid))
+            .with_repetition(self.repetition)
+            .with_length(GUID_LENGTH as i32)
+            .build()
+            .unwrap()
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        BufferDesc::Binary {
+            length: GUID_LENGTH,
+        }
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        let cw = get_typed_column_writer_mut::<FixedLenByteArrayType>(column_writer);
+        let view = column_view.as_bin_view().unwrap();
+        parquet_buffer.write_optional(
+            cw,
+            view.iter().map(|maybe_bytes| maybe_bytes.map(guid_to_uuid)),
+        )?;
+        Ok(())
+    }
+}
+
+/// ODBC transfers GUIDs as `SQLGUID` struct, which stores its first three groups as integers in
+/// native byte order, i.e. little endian on most platforms. Parquet expects the bytes of an UUID in
+/// big endian order, like in its text representation.
+fn guid_to_uuid(guid: &[u8]) -> FixedLenByteArray {
+    let data1 = u32::from_ne_bytes(guid[0..4].try_into().unwrap());
+    let data2 = u16::from_ne_bytes(guid[4..6].try_into().unwrap());
+    let data3 = u16::from_ne_bytes(guid[6..8].try_into().unwrap());
+    let mut uuid = Vec::with_capacity(GUID_LENGTH);
+    uuid.extend_from_slice(&data1.to_be_bytes());
+    uuid.extend_from_slice(&data2.to_be_bytes());
+    uuid.extend_from_slice(&data3.to_be_bytes());
+    uuid.extend_from_slice(&guid[8..]);
+    ByteArray::from(uuid).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::guid_to_uuid;
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn convert_guid_byte_order() {
+        // 00112233-4455-6677-8899-aabbccddeeff as SQLGUID struct on a little endian machine.
+        let guid = [
+            0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff,
+        ];
+
+        let uuid = guid_to_uuid(&guid);
+
+        assert_eq!(
+            [
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff
+            ],
+            uuid.data()
+        );
+    }
+}
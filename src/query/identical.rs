@@ -101,16 +101,17 @@ where
 
     fn copy_odbc_to_parquet(
         &self,
-        _parquet_buffer: &mut ParquetBuffer,
+        parquet_buffer: &mut ParquetBuffer,
         column_writer: &mut ColumnWriter,
         column_view: AnySlice,
     ) -> Result<(), Error> {
         // We do not require to buffer the values, as they must neither be transformed, nor contain
-        // any gaps due to null, we can use the ODBC buffer directly to write the batch.
+        // any gaps due to null, we can use the ODBC buffer directly to write the batch. Unless only
+        // a subset of the rows is selected, that is.
 
         let values = Pdt::T::as_slice(column_view).unwrap();
         let column_writer = get_typed_column_writer_mut::<Pdt>(column_writer);
-        column_writer.write_batch(values, None, None)?;
+        parquet_buffer.write_required(column_writer, values)?;
         Ok(())
     }
 }
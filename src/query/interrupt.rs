@@ -0,0 +1,44 @@
+//! Stops an export gracefully on Ctrl-C, so the output files are closed and remain readable.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the signal handler on the first SIGINT.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a handler for SIGINT. The first signal requests the export to stop after the current
+/// batch, the second one terminates the process immediately. Does nothing on platforms other than
+/// unix, where Ctrl-C keeps terminating the process right away.
+pub fn install_interrupt_handler() {
+    #[cfg(unix)]
+    // Safety: The handler only uses async signal safe operations.
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handle_sigint as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+/// `true` once Ctrl-C has been pressed.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+extern "C" fn handle_sigint(_signal: libc::c_int) {
+    // Neither the logger nor the printing functions of the standard library are async signal
+    // safe, so we write to standard error directly.
+    let write_stderr = |message: &[u8]| {
+        // Safety: `write` is async signal safe.
+        unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr().cast(), message.len()) };
+    };
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        write_stderr(b"\nInterrupted again. Exiting immediately, the output may be corrupt.\n");
+        // Safety: `_exit` is async signal safe. 130 is the conventional exit code after SIGINT.
+        unsafe { libc::_exit(130) };
+    }
+    write_stderr(
+        b"\nInterrupted. Finishing the current batch and closing the output, please wait. Press \
+        Ctrl-C again to exit immediately.\n",
+    );
+}
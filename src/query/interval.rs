@@ -0,0 +1,342 @@
+//! Stores SQL `INTERVAL` columns with the converted type `INTERVAL`.
+
+use anyhow::{anyhow, Error};
+use odbc_api::{
+    buffers::{AnySlice, BufferDesc},
+    sys::SqlDataType,
+};
+use parquet::{
+    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    column::writer::{get_typed_column_writer_mut, ColumnWriter},
+    data_type::{ByteArray, FixedLenByteArray, FixedLenByteArrayType},
+    schema::types::Type,
+};
+
+use crate::parquet_buffer::ParquetBuffer;
+
+use super::column_strategy::ColumnStrategy;
+
+/// Size of a parquet interval in bytes. Three little endian unsigned 32 Bit integers, for months,
+/// days and milliseconds.
+const INTERVAL_LENGTH: usize = 12;
+
+/// Enough to hold the text representation of any interval, e.g. `178000000 years 11 mons
+/// 2147483647 days 2562047788:00:54.775807` in the verbose format of PostgreSQL.
+const MAX_INTERVAL_TEXT_LEN: usize = 100;
+
+/// Fields of a SQL interval type, e.g. `SQL_INTERVAL_DAY_TO_SECOND`. Determines how the fields of
+/// its text representation are interpreted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IntervalKind {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    YearToMonth,
+    DayToHour,
+    DayToMinute,
+    DayToSecond,
+    HourToMinute,
+    HourToSecond,
+    MinuteToSecond,
+}
+
+impl IntervalKind {
+    /// `None` if the data type is not one of the concise interval types (`101` to `113`).
+    pub fn from_sql_data_type(data_type: SqlDataType) -> Option<Self> {
+        let kind = match data_type.0 {
+            101 => IntervalKind::Year,
+            102 => IntervalKind::Month,
+            103 => IntervalKind::Day,
+            104 => IntervalKind::Hour,
+            105 => IntervalKind::Minute,
+            106 => IntervalKind::Second,
+            107 => IntervalKind::YearToMonth,
+            108 => IntervalKind::DayToHour,
+            109 => IntervalKind::DayToMinute,
+            110 => IntervalKind::DayToSecond,
+            111 => IntervalKind::HourToMinute,
+            112 => IntervalKind::HourToSecond,
+            113 => IntervalKind::MinuteToSecond,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Unit of a number without unit, e.g. the days in `3 04:05:06` or the hours in `27`.
+    fn leading_unit(self) -> Unit {
+        match self {
+            IntervalKind::Year | IntervalKind::YearToMonth => Unit::Year,
+            IntervalKind::Month => Unit::Month,
+            IntervalKind::Hour | IntervalKind::HourToMinute | IntervalKind::HourToSecond => {
+                Unit::Hour
+            }
+            IntervalKind::Minute | IntervalKind::MinuteToSecond => Unit::Minute,
+            IntervalKind::Second => Unit::Second,
+            IntervalKind::Day
+            | IntervalKind::DayToHour
+            | IntervalKind::DayToMinute
+            | IntervalKind::DayToSecond => Unit::Day,
+        }
+    }
+}
+
+/// Fetches intervals as text, letting the driver format them, and writes them as
+/// `FIXED_LEN_BYTE_ARRAY(12)` with converted type `INTERVAL`. `odbc-api` does not offer buffers for
+/// the `SQL_INTERVAL_STRUCT`.
+pub struct Interval {
+    repetition: Repetition,
+    kind: IntervalKind,
+}
+
+impl Interval {
+    pub fn new(repetition: Repetition, kind: IntervalKind) -> Self {
+        Self { repetition, kind }
+    }
+}
+
+impl ColumnStrategy for Interval {
+    fn parquet_type(&self, name: &str) -> Type {
+        Type::primitive_type_builder(name, PhysicalType::FIXED_LEN_BYTE_ARRAY)
+            .with_converted_type(ConvertedType::INTERVAL)
+            .with_repetition(self.repetition)
+            .with_length(INTERVAL_LENGTH as i32)
+            .build()
+            .unwrap()
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        BufferDesc::Text {
+            max_str_len: MAX_INTERVAL_TEXT_LEN,
+        }
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        let cw = get_typed_column_writer_mut::<FixedLenByteArrayType>(column_writer);
+        let view = column_view.as_text_view().unwrap();
+        parquet_buffer.write_optional_fallible(
+            cw,
+            view.iter().map(|maybe_bytes| {
+                maybe_bytes
+                    .map(|bytes| {
+                        let text = String::from_utf8_lossy(bytes);
+                        let fields = parse_interval(&text, self.kind).ok_or_else(|| {
+                            anyhow!(
+                                "Can not parse interval '{text}'. Use `--interval-as-text` to \
+                                store intervals as text."
+                            )
+                        })?;
+                        fields.to_parquet().ok_or_else(|| {
+                            anyhow!(
+                                "Interval '{text}' is negative or too large, which can not be \
+                                represented by the parquet INTERVAL type. Use \
+                                `--interval-as-text` to store intervals as text."
+                            )
+                        })
+                    })
+                    .transpose()
+            }),
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl Unit {
+    /// Unit names used by PostgreSQL, e.g. `mons` in `1 year 2 mons`.
+    fn from_name(name: &str) -> Option<Self> {
+        let unit = match name {
+            "year" | "years" => Unit::Year,
+            "mon" | "mons" | "month" | "months" => Unit::Month,
+            "day" | "days" => Unit::Day,
+            "hour" | "hours" => Unit::Hour,
+            "min" | "mins" | "minute" | "minutes" => Unit::Minute,
+            "sec" | "secs" | "second" | "seconds" => Unit::Second,
+            _ => return None,
+        };
+        Some(unit)
+    }
+}
+
+/// Fields of an interval, as stored in parquet.
+#[derive(Default, Debug, PartialEq, Eq)]
+struct IntervalFields {
+    months: i64,
+    days: i64,
+    millis: i64,
+}
+
+impl IntervalFields {
+    /// Adds `number` of `unit`s. `number` may have fractional digits, if the unit is seconds.
+    fn add(&mut self, number: &str, unit: Unit) -> Option<()> {
+        match unit {
+            Unit::Year => self.months += number.parse::<i64>().ok()?.checked_mul(12)?,
+            Unit::Month => self.months += number.parse::<i64>().ok()?,
+            Unit::Day => self.days += number.parse::<i64>().ok()?,
+            Unit::Hour => self.millis += number.parse::<i64>().ok()?.checked_mul(3_600_000)?,
+            Unit::Minute => self.millis += number.parse::<i64>().ok()?.checked_mul(60_000)?,
+            Unit::Second => self.millis += seconds_to_millis(number)?,
+        }
+        Some(())
+    }
+
+    /// `None` if any field is negative or exceeds 32 Bit.
+    fn to_parquet(&self) -> Option<FixedLenByteArray> {
+        let mut bytes = Vec::with_capacity(INTERVAL_LENGTH);
+        for field in [self.months, self.days, self.millis] {
+            bytes.extend_from_slice(&u32::try_from(field).ok()?.to_le_bytes());
+        }
+        Some(ByteArray::from(bytes).into())
+    }
+}
+
+/// Parses the text representation of an interval. Understands the format specified by ODBC, e.g.
+/// `1-02` for a year to month interval or `3 04:05:06.789` for a day to second interval, as well as
+/// the default format of PostgreSQL, e.g. `1 year 2 mons 3 days 04:05:06.789`. Fractions of
+/// milliseconds are truncated.
+fn parse_interval(text: &str, kind: IntervalKind) -> Option<IntervalFields> {
+    let mut fields = IntervalFields::default();
+    let mut tokens = text.split_whitespace().peekable();
+    // Empty text is not a valid interval.
+    tokens.peek()?;
+    while let Some(token) = tokens.next() {
+        if token.contains(':') {
+            // Clock part, e.g. `04:05:06.789`
+            let (sign, clock) = split_sign(token);
+            let parts: Vec<&str> = clock.split(':').collect();
+            let units: &[Unit] = match (parts.len(), kind) {
+                (2, IntervalKind::MinuteToSecond) => &[Unit::Minute, Unit::Second],
+                (2, _) => &[Unit::Hour, Unit::Minute],
+                (3, _) => &[Unit::Hour, Unit::Minute, Unit::Second],
+                _ => return None,
+            };
+            for (part, &unit) in parts.iter().zip(units) {
+                fields.add(&format!("{sign}{part}"), unit)?;
+            }
+        } else if let Some((years, months)) = split_year_month(token) {
+            let (sign, _) = split_sign(token);
+            fields.add(years, Unit::Year)?;
+            fields.add(&format!("{sign}{months}"), Unit::Month)?;
+        } else {
+            let unit = match tokens.peek().and_then(|name| Unit::from_name(name)) {
+                Some(unit) => {
+                    tokens.next();
+                    unit
+                }
+                None => kind.leading_unit(),
+            };
+            fields.add(token, unit)?;
+        }
+    }
+    Some(fields)
+}
+
+/// Splits a leading `-` from the token.
+fn split_sign(token: &str) -> (&str, &str) {
+    match token.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", token),
+    }
+}
+
+/// Splits `Y-M`, e.g. `-1-02`, into years (including the sign) and months.
+fn split_year_month(token: &str) -> Option<(&str, &str)> {
+    let (separator, _) = token.char_indices().skip(1).find(|&(_, c)| c == '-')?;
+    Some((&token[..separator], &token[separator + 1..]))
+}
+
+/// Parses seconds with optional fractional digits, e.g. `06.789`, into milliseconds.
+fn seconds_to_millis(seconds: &str) -> Option<i64> {
+    let (sign, seconds) = split_sign(seconds);
+    let (integer, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut millis = integer.parse::<i64>().ok()?.checked_mul(1000)?;
+    // Pad to three digits, e.g. `.5` is 500 milliseconds.
+    let fraction = format!("{:0<3}", &fraction[..fraction.len().min(3)]);
+    millis += fraction.parse::<i64>().ok()?;
+    Some(if sign == "-" { -millis } else { millis })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_interval, IntervalFields, IntervalKind};
+
+    fn fields(months: i64, days: i64, millis: i64) -> Option<IntervalFields> {
+        Some(IntervalFields {
+            months,
+            days,
+            millis,
+        })
+    }
+
+    #[test]
+    fn parse_day_to_second_interval() {
+        let day_to_second = IntervalKind::DayToSecond;
+        let millis = ((4 * 60 + 5) * 60 + 6) * 1000 + 789;
+
+        assert_eq!(
+            fields(0, 3, millis),
+            parse_interval("3 04:05:06.789", day_to_second)
+        );
+        assert_eq!(
+            fields(0, 3, millis),
+            parse_interval("3 days 04:05:06.789123", day_to_second)
+        );
+        assert_eq!(
+            fields(0, 0, 500),
+            parse_interval("00:00:00.5", day_to_second)
+        );
+        assert_eq!(
+            fields(0, -1, -1000),
+            parse_interval("-1 -00:00:01", day_to_second)
+        );
+        assert_eq!(
+            fields(0, 0, 90_000),
+            parse_interval("01:30", IntervalKind::MinuteToSecond)
+        );
+        assert_eq!(None, parse_interval("", day_to_second));
+        assert_eq!(None, parse_interval("3 fortnights", day_to_second));
+    }
+
+    #[test]
+    fn parse_year_to_month_interval() {
+        let year_to_month = IntervalKind::YearToMonth;
+
+        assert_eq!(fields(14, 0, 0), parse_interval("1-02", year_to_month));
+        assert_eq!(fields(-14, 0, 0), parse_interval("-1-02", year_to_month));
+        assert_eq!(
+            fields(14, 0, 0),
+            parse_interval("1 year 2 mons", year_to_month)
+        );
+        assert_eq!(fields(24, 0, 0), parse_interval("2", year_to_month));
+        assert_eq!(
+            fields(14, 3, 0),
+            parse_interval("1 year 2 mons 3 days", IntervalKind::DayToSecond)
+        );
+    }
+
+    #[test]
+    fn interval_to_parquet_bytes() {
+        let interval = fields(14, 3, 500).unwrap().to_parquet().unwrap();
+        assert_eq!([14, 0, 0, 0, 3, 0, 0, 0, 0xf4, 1, 0, 0], interval.data());
+        assert!(fields(-1, 0, 0).unwrap().to_parquet().is_none());
+    }
+}
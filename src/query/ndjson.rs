@@ -0,0 +1,483 @@
+//! Writes the result set as newline delimited JSON, with one JSON object per row, or as CSV
+//! instead of parquet.
+
+use std::{
+    cmp::min,
+    io::{stdout, BufWriter, Write},
+    num::NonZeroUsize,
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Error};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytesize::ByteSize;
+use chrono::NaiveDate;
+use io_arg::IoArg;
+use odbc_api::{
+    buffers::{AnySlice, BufferDesc, ColumnarAnyBuffer},
+    sys::{Date, Timestamp},
+    Cursor, DataType, ResultSetMetadata,
+};
+use tempfile::TempPath;
+
+use crate::json::write_json_string;
+
+use super::{
+    batch_size_limit::{check_total_memory, BatchSizeLimit},
+    column_names::name_columns,
+    column_strategy::MappingOptions,
+    csv::{batch_to_csv, CsvOptions},
+    current_file::{create_file, persist_temporary, temporary_sibling},
+    encoding_loss::{EncodingLossCheck, OnEncodingLoss},
+    interrupt::is_interrupted,
+    non_null::NonNullCheck,
+    parquet_writer::{ParquetWriterOptions, SplitFileNaming},
+    progress::Progress,
+    summary::Summary,
+    truncation::TruncationCheck,
+};
+
+/// Text format the rows are written in.
+pub enum TextFormat {
+    Ndjson,
+    Csv(CsvOptions),
+}
+
+/// Fetches all rows of the cursor and writes them as JSON objects or CSV rows into the output.
+/// Splitting the output into several files is controlled by the same options as for parquet. The
+/// file size is measured in bytes written.
+#[allow(clippy::too_many_arguments)]
+pub fn cursor_to_text(
+    mut cursor: impl Cursor,
+    output: IoArg,
+    format: TextFormat,
+    batch_size: BatchSizeLimit,
+    mapping_options: MappingOptions,
+    options: &ParquetWriterOptions,
+    limit: Option<usize>,
+    error_on_truncation: bool,
+    on_encoding_loss: OnEncodingLoss,
+    require_non_null: &[String],
+    summary: bool,
+    bom: bool,
+    max_total_memory: Option<ByteSize>,
+    mut progress: Progress,
+) -> Result<(), Error> {
+    let columns = text_columns(&mut cursor, mapping_options)?;
+    let bytes_per_row = columns
+        .iter()
+        .map(|column| column.desc.bytes_per_row())
+        .sum();
+    let capacity = batch_size.batch_size_in_rows(bytes_per_row)?;
+    check_total_memory(capacity, bytes_per_row, 1, max_total_memory)?;
+    let buffer = ColumnarAnyBuffer::from_descs(capacity, columns.iter().map(|column| column.desc));
+    let mut block_cursor = cursor.bind_buffer(buffer)?;
+
+    let header = match &format {
+        TextFormat::Csv(csv) if csv.header => Some(csv.header_row(&columns)),
+        _ => None,
+    };
+    let mut output = TextOutput::new(output, options, bom, header);
+    let mut remaining_rows = limit.unwrap_or(usize::MAX);
+    let mut total_rows_fetched = 0;
+    let names: Vec<_> = columns.iter().map(|column| column.name.clone()).collect();
+    let mut truncation = TruncationCheck::new(names.clone(), error_on_truncation);
+    let mut encoding_loss = EncodingLossCheck::new(names.clone(), on_encoding_loss);
+    let mut non_null = NonNullCheck::new(&names, require_non_null)?;
+    let mut summary = summary.then(|| Summary::new(names));
+    while remaining_rows != 0 {
+        let Some(batch) = block_cursor.fetch_with_truncation_check(false)? else {
+            break;
+        };
+        total_rows_fetched += batch.num_rows();
+        let num_rows = min(batch.num_rows(), remaining_rows);
+        remaining_rows -= num_rows;
+        truncation.check_batch(batch, num_rows)?;
+        encoding_loss.check_batch(batch, num_rows)?;
+        non_null.check_batch(batch, num_rows)?;
+        if let Some(summary) = &mut summary {
+            summary.add_batch(batch, num_rows);
+        }
+        let rows = match &format {
+            TextFormat::Ndjson => batch_to_ndjson(&columns, batch, num_rows),
+            TextFormat::Csv(csv) => batch_to_csv(csv, &columns, batch, num_rows),
+        };
+        output.write_batch(&rows)?;
+        progress.update(
+            total_rows_fetched,
+            output.bytes_written,
+            output.current_path.as_deref(),
+        );
+        if is_interrupted() {
+            break;
+        }
+    }
+    progress.finish();
+    truncation.report();
+    encoding_loss.report();
+    output.finish()?;
+    if let Some(summary) = &summary {
+        summary.print();
+    }
+    if is_interrupted() {
+        bail!(
+            "Export has been interrupted. The output has been closed and is readable, but \
+            incomplete."
+        )
+    }
+    Ok(())
+}
+
+/// Byte order mark written at the start of each file with `--bom`.
+const UTF8_BOM: &[u8] = "\u{FEFF}".as_bytes();
+
+/// A column of the result set and the buffer it is fetched into.
+pub struct TextColumn {
+    pub name: String,
+    desc: BufferDesc,
+    /// Decimals are fetched as text, but written as numbers.
+    pub is_numeric_text: bool,
+}
+
+fn text_columns(
+    cursor: &mut impl ResultSetMetadata,
+    mapping_options: MappingOptions,
+) -> Result<Vec<TextColumn>, Error> {
+    let num_cols: u16 = cursor.num_result_cols()?.try_into().unwrap();
+    let mut columns = Vec::new();
+    let mut available = Vec::new();
+    for index in 1..(num_cols + 1) {
+        let name = cursor.col_name(index)?;
+        available.push(name.clone());
+        let data_type = cursor.col_data_type(index)?;
+        let column_length_limit = mapping_options
+            .column_length_limits
+            .iter()
+            .rev()
+            .find(|(column, _)| *column == name)
+            .map_or(mapping_options.column_length_limit, |&(_, limit)| limit);
+        let apply_length_limit = |reported_length: Option<NonZeroUsize>| {
+            min(
+                reported_length
+                    .map(NonZeroUsize::get)
+                    .unwrap_or(column_length_limit),
+                column_length_limit,
+            )
+        };
+        let mut is_numeric_text = false;
+        let desc = match data_type {
+            DataType::TinyInt | DataType::SmallInt | DataType::Integer | DataType::BigInt => {
+                BufferDesc::I64 { nullable: true }
+            }
+            DataType::Float { .. } | DataType::Real | DataType::Double => {
+                BufferDesc::F64 { nullable: true }
+            }
+            DataType::Bit => BufferDesc::Bit { nullable: true },
+            DataType::Date => BufferDesc::Date { nullable: true },
+            DataType::Timestamp { .. } => BufferDesc::Timestamp { nullable: true },
+            DataType::Numeric { .. } | DataType::Decimal { .. }
+                if mapping_options.decimal_as_double =>
+            {
+                BufferDesc::F64 { nullable: true }
+            }
+            DataType::Numeric { precision, .. } | DataType::Decimal { precision, .. } => {
+                is_numeric_text = true;
+                // Sign and decimal point
+                BufferDesc::Text {
+                    max_str_len: precision + 2,
+                }
+            }
+            DataType::Binary { length }
+            | DataType::Varbinary { length }
+            | DataType::LongVarbinary { length } => BufferDesc::Binary {
+                length: apply_length_limit(length),
+            },
+            other if mapping_options.use_utf16 => BufferDesc::WText {
+                max_str_len: apply_length_limit(other.utf16_len()),
+            },
+            other => BufferDesc::Text {
+                max_str_len: apply_length_limit(other.utf8_len()),
+            },
+        };
+        columns.push(TextColumn {
+            name,
+            desc,
+            is_numeric_text,
+        });
+    }
+    name_columns(
+        columns.iter_mut().map(|column| &mut column.name).collect(),
+        mapping_options.column_renames,
+        mapping_options.column_case,
+        mapping_options.sanitize_column_names,
+        &available,
+    )?;
+    Ok(columns)
+}
+
+/// Renders the first `num_rows` rows of the batch. Each row is terminated by a newline.
+fn batch_to_ndjson(
+    columns: &[TextColumn],
+    batch: &ColumnarAnyBuffer,
+    num_rows: usize,
+) -> Vec<String> {
+    let mut rows = vec![String::from("{"); num_rows];
+    for (index, column) in columns.iter().enumerate() {
+        let mut key = String::new();
+        if index != 0 {
+            key.push(',');
+        }
+        write_json_string(&mut key, &column.name);
+        key.push(':');
+        for row in &mut rows {
+            row.push_str(&key);
+        }
+        // Appends the value of the column to each row
+        let rows = rows.iter_mut();
+        match batch.column(index) {
+            AnySlice::Text(view) if column.is_numeric_text => {
+                append_values(rows, view.iter(), write_json_number)
+            }
+            AnySlice::Text(view) => append_values(rows, view.iter(), |row, bytes| {
+                write_json_string(row, &String::from_utf8_lossy(bytes))
+            }),
+            AnySlice::WText(view) => append_values(rows, view.iter(), |row, text| {
+                write_json_string(row, &text.to_string_lossy())
+            }),
+            AnySlice::Binary(view) => append_values(rows, view.iter(), |row, bytes| {
+                write_json_string(row, &STANDARD.encode(bytes))
+            }),
+            AnySlice::NullableI64(values) => {
+                append_values(rows, values, |row, n| row.push_str(&n.to_string()))
+            }
+            AnySlice::NullableF64(values) => append_values(rows, values, |row, x| {
+                // JSON has no representation for NaN and infinity.
+                if x.is_finite() {
+                    row.push_str(&x.to_string())
+                } else {
+                    row.push_str("null")
+                }
+            }),
+            AnySlice::NullableBit(values) => append_values(rows, values, |row, bit| {
+                row.push_str(&bit.as_bool().to_string())
+            }),
+            AnySlice::NullableDate(values) => append_values(rows, values, |row, date| {
+                write_json_string(row, &date_to_iso_8601(date))
+            }),
+            AnySlice::NullableTimestamp(values) => append_values(rows, values, |row, ts| {
+                write_json_string(row, &timestamp_to_iso_8601(ts))
+            }),
+            _ => unreachable!("Fetch buffers for JSON output are always nullable."),
+        }
+    }
+    for row in &mut rows {
+        row.push_str("}\n");
+    }
+    rows
+}
+
+fn append_values<'a, T>(
+    rows: impl Iterator<Item = &'a mut String>,
+    values: impl Iterator<Item = Option<T>>,
+    render: impl Fn(&mut String, T),
+) {
+    for (row, value) in rows.zip(values) {
+        match value {
+            Some(value) => render(row, value),
+            None => row.push_str("null"),
+        }
+    }
+}
+
+/// Writes the text representation of a decimal as JSON number. Some drivers omit the leading zero
+/// (e.g. `.5`), which is not valid JSON.
+pub fn write_json_number(out: &mut String, text: &[u8]) {
+    let text = String::from_utf8_lossy(text);
+    let text = text.trim();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text),
+    };
+    out.push_str(sign);
+    if digits.starts_with('.') {
+        out.push('0');
+    }
+    out.push_str(digits);
+}
+
+pub fn date_to_iso_8601(date: &Date) -> String {
+    format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+pub fn timestamp_to_iso_8601(ts: &Timestamp) -> String {
+    NaiveDate::from_ymd_opt(ts.year.into(), ts.month.into(), ts.day.into())
+        .and_then(|date| {
+            date.and_hms_nano_opt(
+                ts.hour.into(),
+                ts.minute.into(),
+                ts.second.into(),
+                ts.fraction,
+            )
+        })
+        .map(|datetime| datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+        .expect("Database must return valid timestamps")
+}
+
+/// Standard output or one or more files, the text is written to.
+struct TextOutput<'o> {
+    output: IoArg,
+    options: &'o ParquetWriterOptions,
+    naming: SplitFileNaming,
+    /// `None` until the first batch is written, and after a file has been completed.
+    writer: Option<Box<dyn Write>>,
+    /// Temporary file the current file is written to and its final name, in case of
+    /// `--atomic-output`.
+    pending_rename: Option<(TempPath, PathBuf)>,
+    num_file: u32,
+    num_batch: u32,
+    bytes_in_file: u64,
+    /// Total number of bytes written to all files so far. Used to report progress.
+    bytes_written: u64,
+    /// Path of the file currently written to. `None` for standard out.
+    current_path: Option<PathBuf>,
+    rows_in_file: usize,
+    /// Start each file with a UTF-8 byte order mark.
+    bom: bool,
+    /// Written at the start of each file, after the byte order mark. E.g. the column names of CSV.
+    header: Option<String>,
+}
+
+impl<'o> TextOutput<'o> {
+    fn new(
+        output: IoArg,
+        options: &'o ParquetWriterOptions,
+        bom: bool,
+        header: Option<String>,
+    ) -> Self {
+        Self {
+            output,
+            options,
+            naming: SplitFileNaming::new(options),
+            writer: None,
+            pending_rename: None,
+            num_file: 0,
+            num_batch: 0,
+            bytes_in_file: 0,
+            bytes_written: 0,
+            current_path: None,
+            rows_in_file: 0,
+            bom,
+            header,
+        }
+    }
+
+    /// Writes the rows, each terminated by a newline.
+    fn write_batch(&mut self, rows: &[String]) -> Result<(), Error> {
+        let rows_per_file = self.options.file_size.rows_per_file().unwrap_or(usize::MAX);
+        // Split the batch, so no file contains more than `rows_per_file` rows.
+        let mut rest = rows;
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(min(rest.len(), rows_per_file - self.rows_in_file));
+            self.write_rows(chunk.concat().as_bytes(), chunk.len())?;
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    fn write_rows(&mut self, text: &[u8], num_rows: usize) -> Result<(), Error> {
+        if self.writer.is_none() {
+            self.writer = Some(self.open_next()?);
+        }
+        self.writer.as_mut().unwrap().write_all(text)?;
+        self.bytes_in_file += text.len() as u64;
+        self.bytes_written += text.len() as u64;
+        self.rows_in_file += num_rows;
+        self.num_batch += 1;
+        if self.options.file_size.should_start_new_file(
+            self.num_batch,
+            ByteSize::b(self.bytes_in_file),
+            self.rows_in_file,
+        ) {
+            self.close_current()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Error> {
+        if self.writer.is_none() && self.num_file == 0 && !self.options.no_empty_file {
+            // Empty result set. Create an empty file, just like we would write a parquet file
+            // containing only the schema.
+            self.writer = Some(self.open_next()?);
+        }
+        self.close_current()
+    }
+
+    fn open_next(&mut self) -> Result<Box<dyn Write>, Error> {
+        self.num_file += 1;
+        let mut writer: Box<dyn Write> = match &self.output {
+            IoArg::StdStream => Box::new(BufWriter::new(stdout())),
+            IoArg::File(path) => {
+                let path = if self.options.file_size.output_is_splitted() {
+                    self.naming.path(path, self.num_file)?
+                } else {
+                    path.clone()
+                };
+                let (path, final_path) = if self.options.atomic_output {
+                    (temporary_sibling(&path), Some(path))
+                } else {
+                    (path, None)
+                };
+                let file = create_file(&path, self.options.file_mode).with_context(|| {
+                    format!("Could not create output file '{}'.", path.display())
+                })?;
+                self.current_path = Some(final_path.clone().unwrap_or_else(|| path.clone()));
+                if let Some(final_path) = final_path {
+                    // Removes the temporary file, should the export fail.
+                    self.pending_rename = Some((TempPath::from_path(path), final_path));
+                }
+                Box::new(BufWriter::new(file))
+            }
+        };
+        if self.bom {
+            writer.write_all(UTF8_BOM)?;
+            self.bytes_in_file += UTF8_BOM.len() as u64;
+            self.bytes_written += UTF8_BOM.len() as u64;
+        }
+        if let Some(header) = &self.header {
+            writer.write_all(header.as_bytes())?;
+            self.bytes_in_file += header.len() as u64;
+            self.bytes_written += header.len() as u64;
+        }
+        Ok(writer)
+    }
+
+    fn close_current(&mut self) -> Result<(), Error> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        if let Some((temporary, final_path)) = self.pending_rename.take() {
+            persist_temporary(temporary, &final_path)?;
+        }
+        self.bytes_in_file = 0;
+        self.rows_in_file = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_json_number;
+
+    #[test]
+    fn decimal_text_to_json_number() {
+        let render = |text: &str| {
+            let mut out = String::new();
+            write_json_number(&mut out, text.as_bytes());
+            out
+        };
+        assert_eq!("12.50", render("12.50"));
+        assert_eq!("0.5", render(".5"));
+        assert_eq!("-0.5", render("-.5"));
+        assert_eq!("-3", render(" -3 "));
+    }
+}
@@ -0,0 +1,95 @@
+//! Aborts the export if a column specified with `--require-non-null` contains NULL.
+
+use anyhow::{bail, Error};
+use odbc_api::{
+    buffers::{AnySlice, ColumnarAnyBuffer},
+    sys::NULL_DATA,
+};
+
+/// Inspects the indicators of the required columns in each fetched batch.
+pub struct NonNullCheck {
+    /// Index in the fetch buffers and name of each required column.
+    columns: Vec<(usize, String)>,
+    /// Number of batches inspected so far.
+    num_batch: usize,
+    /// Number of rows in all previous batches. Used to report the row offset of a NULL.
+    rows_before_batch: usize,
+}
+
+impl NonNullCheck {
+    /// `names` are the names of the columns, in the order of the fetch buffers. Fails if a
+    /// `required` column is not among them.
+    pub fn new(names: &[String], required: &[String]) -> Result<Self, Error> {
+        let columns = required
+            .iter()
+            .map(|required| {
+                let Some(index) = names.iter().position(|name| name == required) else {
+                    bail!(
+                        "Column '{required}' specified with `--require-non-null` is not part of \
+                        the result set. Available columns are: {}",
+                        names.join(", ")
+                    )
+                };
+                Ok((index, required.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            columns,
+            num_batch: 0,
+            rows_before_batch: 0,
+        })
+    }
+
+    /// Inspects the first `num_rows` rows of the batch.
+    pub fn check_batch(&mut self, batch: &ColumnarAnyBuffer, num_rows: usize) -> Result<(), Error> {
+        self.num_batch += 1;
+        for (index, name) in &self.columns {
+            if let Some(offset) = first_null(batch.column(*index), num_rows) {
+                // One based, like the rows in most database tools.
+                let row = self.rows_before_batch + offset + 1;
+                bail!(
+                    "Column {name} contains NULL in row {row}, but is required to be non-null. The \
+                    NULL is at offset {offset} of batch {}.",
+                    self.num_batch
+                )
+            }
+        }
+        self.rows_before_batch += num_rows;
+        Ok(())
+    }
+}
+
+/// Index of the first NULL within the first `num_rows` rows of the column.
+fn first_null(column: AnySlice, num_rows: usize) -> Option<usize> {
+    is_null(column, num_rows).position(|is_null| is_null)
+}
+
+/// Whether each of the first `num_rows` values of the column is NULL, according to its indicators.
+/// Columns fetched into buffers without indicators can not contain NULL.
+pub fn is_null<'a>(column: AnySlice<'a>, num_rows: usize) -> Box<dyn Iterator<Item = bool> + 'a> {
+    let indicators = |indicators: &'a [isize]| -> Box<dyn Iterator<Item = bool> + 'a> {
+        Box::new(
+            indicators
+                .iter()
+                .take(num_rows)
+                .map(|&indicator| indicator == NULL_DATA),
+        )
+    };
+    match column {
+        AnySlice::Text(view) => Box::new(view.iter().take(num_rows).map(|value| value.is_none())),
+        AnySlice::WText(view) => Box::new(view.iter().take(num_rows).map(|value| value.is_none())),
+        AnySlice::Binary(view) => Box::new(view.iter().take(num_rows).map(|value| value.is_none())),
+        AnySlice::NullableDate(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableTime(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableTimestamp(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableF64(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableF32(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableI8(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableI16(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableI32(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableI64(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableU8(slice) => indicators(slice.raw_values().1),
+        AnySlice::NullableBit(slice) => indicators(slice.raw_values().1),
+        _ => Box::new(std::iter::repeat_n(false, num_rows)),
+    }
+}
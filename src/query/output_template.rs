@@ -0,0 +1,156 @@
+//! Names of the files, if the output is split into multiple files.
+
+use anyhow::{bail, Error};
+use chrono::Utc;
+
+/// Template for the file names of split output, specified with `--output-template`. E.g.
+/// `export_{date}_{index:04}.par`.
+#[derive(Clone, Debug)]
+pub struct OutputTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// One based number of the file, padded with zeroes to `width` digits.
+    Index {
+        width: usize,
+    },
+    /// The date the export started, so it is identical for all files of one export.
+    Date(String),
+    /// A random UUID (version 4), which is different for each file.
+    Uuid,
+}
+
+impl OutputTemplate {
+    pub fn parse(template: &str) -> Result<Self, Error> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start != 0 {
+                segments.push(Segment::Literal(rest[..start].to_owned()));
+            }
+            let Some(len) = rest[start..].find('}') else {
+                bail!("Unclosed placeholder in output template '{template}'.")
+            };
+            let placeholder = &rest[(start + 1)..(start + len)];
+            let segment = match placeholder.split_once(':') {
+                None if placeholder == "index" => Segment::Index { width: 0 },
+                None if placeholder == "date" => {
+                    Segment::Date(Utc::now().format("%Y-%m-%d").to_string())
+                }
+                None if placeholder == "uuid" => Segment::Uuid,
+                Some(("index", width)) if width.parse::<usize>().is_ok() => Segment::Index {
+                    width: width.parse().unwrap(),
+                },
+                _ => bail!(
+                    "Unknown placeholder '{{{placeholder}}}' in output template. Supported are \
+                    '{{index}}', '{{index:WIDTH}}', '{{date}}' and '{{uuid}}'."
+                ),
+            };
+            segments.push(segment);
+            rest = &rest[(start + len + 1)..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_owned()));
+        }
+        Ok(Self { segments })
+    }
+
+    /// `true` if the template contains an `{index}` placeholder, so the names of different files
+    /// are guaranteed to differ.
+    pub fn has_index(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Index { .. }))
+    }
+
+    /// Number of the file, if `file_name` matches the template. `None` otherwise. Dates match any
+    /// date, so files of exports started on previous days are recognized, too.
+    pub fn parse_index(&self, file_name: &str) -> Option<u32> {
+        let mut rest = file_name;
+        let mut index = None;
+        for segment in &self.segments {
+            let len = match segment {
+                Segment::Literal(text) => {
+                    rest = rest.strip_prefix(text.as_str())?;
+                    continue;
+                }
+                Segment::Date(_) => "2024-01-31".len(),
+                Segment::Uuid => "00000000-0000-0000-0000-000000000000".len(),
+                Segment::Index { .. } => rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len()),
+            };
+            let value = rest.get(..len)?;
+            if let Segment::Index { .. } = segment {
+                index = Some(value.parse().ok()?);
+            }
+            rest = &rest[len..];
+        }
+        if rest.is_empty() {
+            index
+        } else {
+            None
+        }
+    }
+
+    /// File name of the `num_file`-th file of the output. `num_file` is one based.
+    pub fn render(&self, num_file: u32) -> String {
+        let mut name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) | Segment::Date(text) => name.push_str(text),
+                Segment::Index { width } => name.push_str(&format!("{num_file:0width$}")),
+                Segment::Uuid => name.push_str(&uuid_v4()),
+            }
+        }
+        name
+    }
+}
+
+/// Random UUID in its hyphenated text representation.
+fn uuid_v4() -> String {
+    let mut bytes = fastrand::u128(..).to_be_bytes();
+    // Set version (4) and variant (RFC 4122) bits
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputTemplate;
+
+    #[test]
+    fn render_output_template() {
+        let template = OutputTemplate::parse("export_{index:04}.par").unwrap();
+        assert!(template.has_index());
+        assert_eq!("export_0001.par", template.render(1));
+        assert_eq!("export_0012.par", template.render(12));
+        assert_eq!(Some(12), template.parse_index("export_0012.par"));
+        assert_eq!(Some(12345), template.parse_index("export_12345.par"));
+        assert_eq!(None, template.parse_index("export_0012.par.tmp"));
+        assert_eq!(None, template.parse_index("other_0012.par"));
+
+        let template = OutputTemplate::parse("{uuid}.par").unwrap();
+        assert!(!template.has_index());
+        let name = template.render(1);
+        assert_eq!(40, name.len());
+        assert_eq!(Some('4'), name.chars().nth(14));
+        assert_ne!(name, template.render(2));
+
+        assert!(OutputTemplate::parse("{index").is_err());
+        assert!(OutputTemplate::parse("{index:abc}").is_err());
+        assert!(OutputTemplate::parse("{name}").is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Export a query using multiple connections in parallel. Each connection fetches a different
+//! range of an integer key column and writes it into its own file.
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::{bail, Error};
+use bytesize::ByteSize;
+use io_arg::IoArg;
+use log::{info, warn};
+use odbc_api::{buffers::ColumnarAnyBuffer, Connection, Cursor, Nullable};
+use parquet::schema::types::Type;
+
+use crate::{
+    connection::{is_unsupported_attribute, open_connection, ConnectOpts},
+    enum_args::ParamTypeArgument,
+};
+
+use super::{
+    batch_size_limit::BatchSizeLimit,
+    column_strategy::MappingOptions,
+    conversion_strategy::ConversionStrategy,
+    encoding_loss::OnEncodingLoss,
+    execute_pre_sql,
+    fetch_batch::{fetch_strategy, FetchBatch, FetchRetries},
+    parameters_file::bind_parameters,
+    parquet_writer::{parquet_output, ParquetWriterOptions, SplitFileNaming},
+    progress::Progress,
+    with_encodings_for_type, with_original_column_names,
+};
+
+/// Settings of a parallel export, specified with `--parallel` and `--split-key`.
+pub struct ParallelExport<'a> {
+    /// Number of connections fetching concurrently.
+    pub num_workers: usize,
+    /// Name of the integer column, whose range is split between the workers.
+    pub split_key: &'a str,
+    /// Query text, as specified by the user.
+    pub query: &'a str,
+    /// Positional parameters of the query, as specified by the user.
+    pub parameters: &'a [String],
+    /// Types of the parameters, as specified with `--param-type`.
+    pub param_types: &'a [(usize, ParamTypeArgument)],
+    /// Indices of the parameters bound as NULL, as specified with `--null-param`.
+    pub null_params: &'a [usize],
+    pub batch_size: BatchSizeLimit,
+    /// Number of fetch buffers allocated by each worker.
+    pub fetch_buffer_count: usize,
+    pub retries: FetchRetries,
+    pub column_select: &'a [String],
+    pub error_on_truncation: bool,
+    pub on_encoding_loss: OnEncodingLoss,
+    /// Columns specified with `--require-non-null`.
+    pub require_non_null: &'a [String],
+    /// Statements executed on the connection of each worker, before the query.
+    pub pre_sql: &'a [String],
+    /// Limit for the memory of the fetch buffers of each worker.
+    pub max_total_memory: Option<ByteSize>,
+    /// Timeout of the query determining the range of the key, and of the query of each worker.
+    pub query_timeout_sec: Option<usize>,
+}
+
+impl ParallelExport<'_> {
+    /// Writes the result of the query into one file per worker. `odbc_conn` is used to determine
+    /// the range of the key column and the schema, which must be identical for every file.
+    pub fn export(
+        &self,
+        odbc_conn: &Connection,
+        connect_opts: &ConnectOpts,
+        mapping_options: MappingOptions,
+        path: &Path,
+        parquet_format_options: ParquetWriterOptions,
+    ) -> Result<(), Error> {
+        let ranges = match self.key_range(odbc_conn)? {
+            Some((min, max)) => split_range(min, max, self.num_workers),
+            // Empty result set. A single worker with an empty range still writes the schema.
+            None => vec![(1, 0)],
+        };
+        let split_query = self.split_query();
+        info!("Query executed by each worker: {split_query}");
+
+        let mut prepared = odbc_conn.prepare(&split_query)?;
+        let reference_strategy =
+            ConversionStrategy::new(&mut prepared, mapping_options, &[], self.column_select)?;
+        let reference_schema = reference_strategy.parquet_schema();
+        let naming = SplitFileNaming::new(&parquet_format_options);
+
+        // Set as soon as the first worker fails, so the others stop fetching.
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let first_error = Mutex::new(None);
+        thread::scope(|scope| {
+            for (index, range) in ranges.into_iter().enumerate() {
+                let worker_path = naming.path(path, index as u32 + 1);
+                let options = parquet_format_options.clone();
+                let (cancelled, first_error) = (&cancelled, &first_error);
+                let (split_query, reference_schema) = (&split_query, &reference_schema);
+                scope.spawn(move || {
+                    info!(
+                        "Worker {index} exports keys from {} to {}.",
+                        range.0, range.1
+                    );
+                    let result = worker_path.and_then(|worker_path| {
+                        self.export_range(
+                            connect_opts,
+                            mapping_options,
+                            split_query,
+                            reference_schema,
+                            range,
+                            &worker_path,
+                            options,
+                            cancelled,
+                        )
+                    });
+                    if let Err(error) = result {
+                        if !cancelled.swap(true, Ordering::SeqCst) {
+                            *first_error.lock().unwrap() = Some(error);
+                        }
+                    }
+                });
+            }
+        });
+
+        if let Some(error) = first_error.into_inner().unwrap() {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Minimum and maximum of the key column, or `None` if the result set is empty.
+    fn key_range(&self, odbc_conn: &Connection) -> Result<Option<(i64, i64)>, Error> {
+        let key = self.split_key;
+        let range_query = format!(
+            "SELECT MIN({key}), MAX({key}) FROM ({}) AS odbc2parquet_keys",
+            self.inner_query()
+        );
+        let params = bind_parameters(self.parameters, self.param_types, self.null_params)?;
+        let cursor =
+            match odbc_conn.execute(&range_query, params.as_slice(), self.query_timeout_sec) {
+                Err(error)
+                    if self.query_timeout_sec.is_some() && is_unsupported_attribute(&error) =>
+                {
+                    warn!(
+                    "Driver does not support a query timeout. Executing query without one.\n{error}"
+                );
+                    odbc_conn.execute(&range_query, params.as_slice(), None)?
+                }
+                result => result?,
+            };
+        let Some(mut cursor) = cursor else {
+            bail!("Query to determine the range of the split key did not return a result set.")
+        };
+        let mut row = cursor
+            .next_row()?
+            .expect("Aggregate query must return exactly one row");
+        let mut min = Nullable::<i64>::null();
+        let mut max = Nullable::<i64>::null();
+        row.get_data(1, &mut min)?;
+        row.get_data(2, &mut max)?;
+        Ok(min.into_opt().zip(max.into_opt()))
+    }
+
+    /// The user query, restricted to a range of the key column. The bounds of the range are the
+    /// last two parameters.
+    fn split_query(&self) -> String {
+        format!(
+            "SELECT * FROM ({}) AS odbc2parquet_split WHERE {} BETWEEN ? AND ?",
+            self.inner_query(),
+            self.split_key
+        )
+    }
+
+    /// Query text without a trailing semicolon, so it can be used as a subquery.
+    fn inner_query(&self) -> &str {
+        self.query.trim_end().trim_end_matches(';')
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn export_range(
+        &self,
+        connect_opts: &ConnectOpts,
+        mapping_options: MappingOptions,
+        split_query: &str,
+        reference_schema: &Type,
+        (min, max): (i64, i64),
+        path: &Path,
+        parquet_format_options: ParquetWriterOptions,
+        cancelled: &Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        let odbc_conn = open_connection(connect_opts)?;
+        execute_pre_sql(&odbc_conn, self.pre_sql)?;
+        let mut params = bind_parameters(self.parameters, self.param_types, self.null_params)?;
+        params.push(Box::new(min));
+        params.push(Box::new(max));
+        let cursor =
+            match odbc_conn.into_cursor(split_query, params.as_slice(), self.query_timeout_sec) {
+                Err(failed)
+                    if self.query_timeout_sec.is_some()
+                        && is_unsupported_attribute(&failed.error) =>
+                {
+                    warn!(
+                        "Driver does not support a query timeout. Executing query without one.\n{}",
+                        failed.error
+                    );
+                    failed
+                        .connection
+                        .into_cursor(split_query, params.as_slice(), None)
+                }
+                result => result,
+            }
+            .map_err(odbc_api::Error::from)?;
+        let Some(mut cursor) = cursor else {
+            bail!("Query came back empty (not even a schema has been returned).")
+        };
+
+        let table_strategy =
+            ConversionStrategy::new(&mut cursor, mapping_options, &[], self.column_select)?;
+        if *table_strategy.parquet_schema() != *reference_schema {
+            bail!(
+                "The schema of the rows with keys from {min} to {max} differs from the schema of \
+                the other workers."
+            )
+        }
+        let parquet_format_options =
+            with_original_column_names(parquet_format_options, &table_strategy);
+        let parquet_format_options =
+            with_encodings_for_type(parquet_format_options, &table_strategy);
+        let writer = parquet_output(
+            IoArg::File(path.to_owned()),
+            table_strategy.parquet_schema(),
+            parquet_format_options,
+        )?;
+        let fetch_strategy = fetch_strategy(
+            self.fetch_buffer_count,
+            cursor,
+            &table_strategy,
+            self.batch_size,
+            self.retries,
+            self.max_total_memory,
+        )?;
+        let fetch_strategy = Box::new(Cancellable {
+            inner: fetch_strategy,
+            cancelled: cancelled.clone(),
+        });
+        // Progress bars of concurrent workers would overwrite each other.
+        table_strategy.block_cursor_to_parquet(
+            fetch_strategy,
+            writer,
+            None,
+            &mut Progress::disabled(),
+            self.error_on_truncation,
+            self.on_encoding_loss,
+            self.require_non_null,
+            None,
+            false,
+        )?;
+        if cancelled.load(Ordering::SeqCst) {
+            bail!("Cancelled, because another worker failed.")
+        }
+        Ok(())
+    }
+}
+
+/// Stops fetching as soon as another worker failed.
+struct Cancellable {
+    inner: Box<dyn FetchBatch>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl FetchBatch for Cancellable {
+    fn max_batch_size_in_rows(&self) -> usize {
+        self.inner.max_batch_size_in_rows()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<&ColumnarAnyBuffer>, odbc_api::Error> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        self.inner.next_batch()
+    }
+}
+
+/// Splits the inclusive range from `min` to `max` into up to `num_parts` inclusive ranges of
+/// similar size.
+fn split_range(min: i64, max: i64, num_parts: usize) -> Vec<(i64, i64)> {
+    let (min, max) = (min as i128, max as i128);
+    let part_len = (max - min) / num_parts as i128 + 1;
+    (0..num_parts as i128)
+        .map(|index| min + index * part_len)
+        .take_while(|&start| start <= max)
+        .map(|start| (start as i64, (start + part_len - 1).min(max) as i64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_range;
+
+    #[test]
+    fn split_key_range() {
+        assert_eq!(vec![(1, 4), (5, 8), (9, 10)], split_range(1, 10, 3));
+        assert_eq!(vec![(5, 5)], split_range(5, 5, 4));
+        assert_eq!(vec![(-3, -2), (-1, 0)], split_range(-3, 0, 2));
+        assert_eq!(
+            vec![(i64::MIN, -1), (0, i64::MAX)],
+            split_range(i64::MIN, i64::MAX, 2)
+        );
+    }
+}
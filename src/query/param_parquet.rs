@@ -0,0 +1,223 @@
+//! Executes the query once for each row of a parquet file, binding its columns as parameters, and
+//! writes the result sets of all executions into one output. Specified with `--param-parquet`.
+
+use std::{fs::File, mem::forget, path::Path};
+
+use anyhow::{bail, Context, Error};
+use bytesize::ByteSize;
+use chrono::DateTime;
+use log::info;
+use odbc_api::{
+    buffers::ColumnarAnyBuffer, handles::AsStatementRef, parameter::InputParameter, Cursor,
+    CursorImpl, Prepared, StatementConnection,
+};
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::Field,
+};
+
+use crate::{enum_args::ParamTypeArgument, parquet_buffer::ParquetBuffer};
+
+use super::{
+    batch_size_limit::{check_total_memory, BatchSizeLimit},
+    conversion_strategy::ConversionStrategy,
+    fetch_batch::FetchBatch,
+    parameters_file::{bind_parameter, null_parameter},
+};
+
+/// Parameters of one execution of the query.
+pub type ParameterRow = Vec<Box<dyn InputParameter>>;
+
+/// Reads every row of the parquet file at `path` as parameters for one execution. Columns are
+/// bound in the order of the schema, with a type matching their parquet type.
+pub fn read_parameter_rows(path: &Path) -> Result<Vec<ParameterRow>, Error> {
+    let file = File::open(path)
+        .with_context(|| format!("Could not open parameter file '{}'.", path.display()))?;
+    let reader = SerializedFileReader::new(file)?;
+    let mut rows = Vec::new();
+    for row in reader.get_row_iter(None)? {
+        let row = row?;
+        let parameters = row
+            .get_column_iter()
+            .map(|(name, field)| {
+                let (param_type, text) = field_to_text(field).with_context(|| {
+                    format!("Can not bind column '{name}' of '{}'.", path.display())
+                })?;
+                let Some(text) = text else {
+                    return Ok(null_parameter(param_type));
+                };
+                Ok(bind_parameter(&text, param_type).unwrap_or_else(|| {
+                    unreachable!("Text representation of {field} must be a valid {param_type:?}")
+                }))
+            })
+            .collect::<Result<_, Error>>()?;
+        rows.push(parameters);
+    }
+    Ok(rows)
+}
+
+/// Type a field is bound with, and its text representation. The text is `None` for NULL.
+fn field_to_text(field: &Field) -> Result<(ParamTypeArgument, Option<String>), Error> {
+    let typed = match field {
+        Field::Null => (ParamTypeArgument::Text, None),
+        Field::Bool(b) => (ParamTypeArgument::Bool, Some(b.to_string())),
+        Field::Byte(n) => (ParamTypeArgument::I32, Some(n.to_string())),
+        Field::Short(n) => (ParamTypeArgument::I32, Some(n.to_string())),
+        Field::Int(n) => (ParamTypeArgument::I32, Some(n.to_string())),
+        Field::UByte(n) => (ParamTypeArgument::I32, Some(n.to_string())),
+        Field::UShort(n) => (ParamTypeArgument::I32, Some(n.to_string())),
+        Field::Long(n) => (ParamTypeArgument::I64, Some(n.to_string())),
+        Field::UInt(n) => (ParamTypeArgument::I64, Some(n.to_string())),
+        // Values outside of the range of `i64` are passed on as text.
+        Field::ULong(n) if i64::try_from(*n).is_ok() => {
+            (ParamTypeArgument::I64, Some(n.to_string()))
+        }
+        Field::ULong(n) => (ParamTypeArgument::Text, Some(n.to_string())),
+        Field::Float16(x) => (ParamTypeArgument::F64, Some(x.to_string())),
+        Field::Float(x) => (ParamTypeArgument::F64, Some(x.to_string())),
+        Field::Double(x) => (ParamTypeArgument::F64, Some(x.to_string())),
+        // Bound as text, so the database converts it without losing precision.
+        Field::Decimal(_) => (ParamTypeArgument::Text, Some(field.to_string())),
+        Field::Str(text) => (ParamTypeArgument::Text, Some(text.clone())),
+        // Formatted as `YYYY-MM-DD`.
+        Field::Date(_) => (ParamTypeArgument::Date, Some(field.to_string())),
+        Field::TimestampMillis(millis) => {
+            let timestamp = DateTime::from_timestamp_millis(*millis)
+                .context("Timestamp is out of range.")?
+                .naive_utc();
+            let text = timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+            (ParamTypeArgument::Timestamp, Some(text))
+        }
+        Field::TimestampMicros(micros) => {
+            let timestamp = DateTime::from_timestamp_micros(*micros)
+                .context("Timestamp is out of range.")?
+                .naive_utc();
+            let text = timestamp.format("%Y-%m-%d %H:%M:%S%.6f").to_string();
+            (ParamTypeArgument::Timestamp, Some(text))
+        }
+        Field::Bytes(_) | Field::Group(_) | Field::ListInternal(_) | Field::MapInternal(_) => {
+            bail!("Only primitive types other than binary can be bound as parameters.")
+        }
+    };
+    Ok(typed)
+}
+
+/// Executes the prepared statement once for each parameter row, and fetches the batches of all
+/// result sets one after another. The fetch buffer is bound to the statement for each batch, since
+/// it has to be unbound before the statement can be executed again.
+pub struct RepeatedFetch {
+    prepared: Prepared<StatementConnection<'static>>,
+    /// Parameters of the executions which are still pending, in reverse order.
+    parameter_rows: Vec<ParameterRow>,
+    /// Parameters of the current execution. Kept alive, as long as they are bound.
+    current_parameters: Option<ParameterRow>,
+    /// `None` only while a batch is fetched.
+    buffer: Option<ColumnarAnyBuffer>,
+    batch_size_row: usize,
+    /// `true` while the result set of the current execution has not been consumed.
+    in_result_set: bool,
+    num_executions: usize,
+}
+
+impl RepeatedFetch {
+    pub fn new(
+        prepared: Prepared<StatementConnection<'static>>,
+        mut parameter_rows: Vec<ParameterRow>,
+        table_strategy: &ConversionStrategy,
+        batch_size_limit: BatchSizeLimit,
+        max_total_memory: Option<ByteSize>,
+    ) -> Result<Self, Error> {
+        let total_mem_usage_per_row =
+            table_strategy.fetch_buffer_size_per_row() + ParquetBuffer::MEMORY_USAGE_BYTES_PER_ROW;
+        let batch_size_row = batch_size_limit.batch_size_in_rows(total_mem_usage_per_row)?;
+        check_total_memory(batch_size_row, total_mem_usage_per_row, 1, max_total_memory)?;
+        parameter_rows.reverse();
+        Ok(Self {
+            prepared,
+            parameter_rows,
+            current_parameters: None,
+            buffer: Some(table_strategy.allocate_fetch_buffer(batch_size_row)),
+            batch_size_row,
+            in_result_set: false,
+            num_executions: 0,
+        })
+    }
+}
+
+impl FetchBatch for RepeatedFetch {
+    fn next_batch(&mut self) -> Result<Option<&ColumnarAnyBuffer>, odbc_api::Error> {
+        loop {
+            if !self.in_result_set {
+                let Some(parameters) = self.parameter_rows.pop() else {
+                    return Ok(None);
+                };
+                self.num_executions += 1;
+                info!("Execute query with parameter row {}.", self.num_executions);
+                let parameters = self.current_parameters.insert(parameters);
+                match self.prepared.execute(parameters.as_slice())? {
+                    Some(cursor) => {
+                        // Dropping the cursor would close it, but we want to fetch from it.
+                        forget(cursor);
+                        self.in_result_set = true;
+                    }
+                    None => continue,
+                }
+            }
+            // Safety: The statement is in cursor state, since its last execution returned a
+            // result set, which has not been consumed yet.
+            let cursor = unsafe { CursorImpl::new(self.prepared.as_stmt_ref()) };
+            let buffer = self
+                .buffer
+                .take()
+                .expect("Fetch buffer must be returned after each batch");
+            let mut block_cursor = cursor.bind_buffer(buffer)?;
+            // Truncated values are detected by `TruncationCheck`, so they can be counted instead of
+            // failing the fetch.
+            let has_batch = block_cursor.fetch_with_truncation_check(false)?.is_some();
+            let (cursor, buffer) = block_cursor.unbind()?;
+            self.buffer = Some(buffer);
+            if has_batch {
+                forget(cursor);
+                return Ok(self.buffer.as_ref());
+            }
+            // Closes the cursor, so the statement can be executed again.
+            drop(cursor);
+            self.in_result_set = false;
+        }
+    }
+
+    fn max_batch_size_in_rows(&self) -> usize {
+        self.batch_size_row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::record::Field;
+
+    use crate::enum_args::ParamTypeArgument;
+
+    use super::field_to_text;
+
+    #[test]
+    fn text_of_fields() {
+        let text = |field| field_to_text(&field).unwrap();
+        assert_eq!(
+            (ParamTypeArgument::I32, Some("42".to_owned())),
+            text(Field::Int(42))
+        );
+        assert_eq!((ParamTypeArgument::Text, None), text(Field::Null));
+        assert_eq!(
+            (ParamTypeArgument::Date, Some("2024-01-31".to_owned())),
+            text(Field::Date(19753))
+        );
+        assert_eq!(
+            (
+                ParamTypeArgument::Timestamp,
+                Some("2024-01-31 12:30:00.000".to_owned())
+            ),
+            text(Field::TimestampMillis(1_706_704_200_000))
+        );
+        assert!(field_to_text(&Field::Bytes(vec![1u8].into())).is_err());
+    }
+}
@@ -0,0 +1,427 @@
+use std::{io::Read, iter::Peekable, num::NonZeroUsize, str::Chars};
+
+use anyhow::{anyhow, bail, Context, Error};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use io_arg::IoArg;
+use odbc_api::{
+    parameter::{InputParameter, VarCharBox, WithDataType},
+    sys::{Date, Timestamp},
+    Bit, DataType, IntoParameter, Nullable,
+};
+
+use crate::enum_args::ParamTypeArgument;
+
+/// Reads the parameters from the file specified with `--parameters-file`. The file either contains
+/// a JSON array (e.g. `["Hello", 42]`), or one parameter per line.
+pub fn read_parameters_file(path: IoArg) -> Result<Vec<String>, Error> {
+    let source = if path.is_file() {
+        "parameters file"
+    } else {
+        "standard input"
+    };
+    let mut text = String::new();
+    path.open_as_input()
+        .and_then(|mut input| input.buf_read().read_to_string(&mut text))
+        .with_context(|| format!("Could not read parameters from {source}."))?;
+    if text.trim_start().starts_with('[') {
+        parse_json_array(&text)
+            .with_context(|| format!("Could not parse parameters from {source} as JSON array."))
+    } else {
+        Ok(text.lines().map(str::to_owned).collect())
+    }
+}
+
+/// Line separating the query from its parameters, if both are read from standard input.
+pub const STDIN_PARAMETERS_DELIMITER: &str = "---";
+
+/// Splits the text read from standard input into the query and the parameters following the first
+/// line consisting of [`STDIN_PARAMETERS_DELIMITER`], one parameter per line. `None` if there is no
+/// such line.
+pub fn split_stdin_parameters(text: &str) -> Option<(&str, Vec<String>)> {
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end() == STDIN_PARAMETERS_DELIMITER {
+            let parameters = text[start + line.len()..]
+                .lines()
+                .map(str::to_owned)
+                .collect();
+            return Some((&text[..start], parameters));
+        }
+        start += line.len();
+    }
+    None
+}
+
+/// Number of placeholders (`?`) in the query text. Question marks within quoted literals or
+/// identifiers are ignored.
+pub fn count_placeholders(query: &str) -> usize {
+    let mut quote = None;
+    let mut count = 0;
+    for c in query.chars() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '?') => count += 1,
+            // Escaped quotes (e.g. `''`) close and immediately reopen the literal, so we do not
+            // need to handle them explicitly.
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    count
+}
+
+/// Query text followed by one line for each positional parameter, showing its one based index and
+/// value. Printed by `--verbose-sql`.
+pub fn describe_bound_query(query: &str, parameters: &[String], null_params: &[usize]) -> String {
+    let mut description = format!("Query: {}", query.trim_end());
+    for (index, parameter) in (1..).zip(parameters) {
+        if null_params.contains(&index) {
+            description.push_str(&format!("\nParameter {index}: NULL"));
+        } else {
+            description.push_str(&format!("\nParameter {index}: '{parameter}'"));
+        }
+    }
+    description
+}
+
+/// Converts the positional parameters into values bound with the types specified by
+/// `--param-type`. Parameters without a type are bound as text. Parameters whose index is in
+/// `null_params` are bound as NULL, regardless of their value.
+pub fn bind_parameters(
+    parameters: &[String],
+    param_types: &[(usize, ParamTypeArgument)],
+    null_params: &[usize],
+) -> Result<Vec<Box<dyn InputParameter>>, Error> {
+    if let Some((index, _)) = param_types
+        .iter()
+        .find(|(index, _)| *index > parameters.len())
+    {
+        bail!(
+            "`--param-type` refers to parameter {index}, but only {} parameters have been \
+            specified.",
+            parameters.len()
+        )
+    }
+    if let Some(index) = null_params
+        .iter()
+        .find(|&&index| index == 0 || index > parameters.len())
+    {
+        bail!(
+            "`--null-param` refers to parameter {index}, but only {} parameters have been \
+            specified. Indices start at 1.",
+            parameters.len()
+        )
+    }
+    (1..)
+        .zip(parameters)
+        .map(|(index, parameter)| {
+            // The last type specified for a parameter wins.
+            let param_type = param_types
+                .iter()
+                .rev()
+                .find(|(i, _)| *i == index)
+                .map_or(ParamTypeArgument::Text, |&(_, param_type)| param_type);
+            if null_params.contains(&index) {
+                return Ok(null_parameter(param_type));
+            }
+            bind_parameter(parameter, param_type).ok_or_else(|| {
+                anyhow!("Parameter {index} '{parameter}' is not a valid {param_type:?}.")
+            })
+        })
+        .collect()
+}
+
+/// `None` if the text can not be parsed as the specified type.
+pub fn bind_parameter(
+    text: &str,
+    param_type: ParamTypeArgument,
+) -> Option<Box<dyn InputParameter>> {
+    Some(match param_type {
+        ParamTypeArgument::Text => Box::new(text.to_owned().into_parameter()),
+        ParamTypeArgument::I32 => Box::new(text.trim().parse::<i32>().ok()?),
+        ParamTypeArgument::I64 => Box::new(text.trim().parse::<i64>().ok()?),
+        ParamTypeArgument::F64 => Box::new(text.trim().parse::<f64>().ok()?),
+        ParamTypeArgument::Bool => Box::new(Bit::from_bool(parse_bool(text)?)),
+        ParamTypeArgument::Date => Box::new(parse_date(text)?),
+        ParamTypeArgument::Timestamp => Box::new(WithDataType {
+            value: parse_timestamp(text)?,
+            // Some drivers reject fractional digits, which are not covered by the precision.
+            data_type: DataType::Timestamp {
+                precision: fractional_digits(text),
+            },
+        }),
+    })
+}
+
+/// NULL, bound with the C type and SQL type a value of `param_type` would be bound with. The
+/// indicator is set to `SQL_NULL_DATA`.
+pub fn null_parameter(param_type: ParamTypeArgument) -> Box<dyn InputParameter> {
+    match param_type {
+        // Some drivers reject text parameters with a column size of zero.
+        ParamTypeArgument::Text => Box::new(WithDataType {
+            value: VarCharBox::null(),
+            data_type: DataType::Varchar {
+                length: NonZeroUsize::new(1),
+            },
+        }),
+        ParamTypeArgument::I32 => Box::new(Nullable::<i32>::null()),
+        ParamTypeArgument::I64 => Box::new(Nullable::<i64>::null()),
+        ParamTypeArgument::F64 => Box::new(Nullable::<f64>::null()),
+        ParamTypeArgument::Bool => Box::new(Nullable::<Bit>::null()),
+        ParamTypeArgument::Date => Box::new(Nullable::<Date>::null()),
+        ParamTypeArgument::Timestamp => Box::new(WithDataType {
+            value: Nullable::<Timestamp>::null(),
+            data_type: DataType::Timestamp { precision: 0 },
+        }),
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_date(text: &str) -> Option<Date> {
+    let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?;
+    Some(Date {
+        year: date.year().try_into().ok()?,
+        month: date.month() as u16,
+        day: date.day() as u16,
+    })
+}
+
+/// Accepts both a space and `T` as separator between date and time.
+fn parse_timestamp(text: &str) -> Option<Timestamp> {
+    let text = text.trim().replacen('T', " ", 1);
+    let datetime = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+    Some(Timestamp {
+        year: datetime.year().try_into().ok()?,
+        month: datetime.month() as u16,
+        day: datetime.day() as u16,
+        hour: datetime.hour() as u16,
+        minute: datetime.minute() as u16,
+        second: datetime.second() as u16,
+        fraction: datetime.nanosecond(),
+    })
+}
+
+/// Number of digits after the decimal point of the seconds.
+fn fractional_digits(timestamp: &str) -> i16 {
+    timestamp
+        .trim()
+        .rsplit_once('.')
+        .map_or(0, |(_, fraction)| {
+            fraction.len().try_into().unwrap_or(i16::MAX)
+        })
+}
+
+/// Parses a JSON array of strings, numbers and booleans into their text representations.
+fn parse_json_array(text: &str) -> Result<Vec<String>, Error> {
+    let mut chars = text.trim().chars().peekable();
+    let mut parameters = Vec::new();
+    expect(&mut chars, '[')?;
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            parameters.push(parse_json_value(&mut chars)?);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => (),
+                Some(']') => break,
+                Some(c) => bail!("Expected ',' or ']', but found '{c}'."),
+                None => bail!("Unexpected end of input. Array is not closed."),
+            }
+        }
+    }
+    if chars.next().is_some() {
+        bail!("Unexpected text after the end of the array.")
+    }
+    Ok(parameters)
+}
+
+fn parse_json_value(chars: &mut Peekable<Chars>) -> Result<String, Error> {
+    if chars.peek() == Some(&'"') {
+        chars.next();
+        return parse_json_string(chars);
+    }
+    let mut literal = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == ',' || c == ']' || c.is_whitespace() {
+            break;
+        }
+        literal.push(c);
+        chars.next();
+    }
+    match literal.as_str() {
+        "true" | "false" => Ok(literal),
+        "null" => bail!("NULL is not supported as a parameter value."),
+        _ if literal.parse::<f64>().is_ok() => Ok(literal),
+        _ => bail!("Invalid parameter '{literal}'. Expected string, number or boolean."),
+    }
+}
+
+fn parse_json_string(chars: &mut Peekable<Chars>) -> Result<String, Error> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('/') => value.push('/'),
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('u') => value.push(parse_unicode_escape(chars)?),
+                Some(c) => bail!("Invalid escape sequence '\\{c}'."),
+                None => bail!("Unexpected end of input in string."),
+            },
+            Some(c) => value.push(c),
+            None => bail!("Unexpected end of input in string."),
+        }
+    }
+}
+
+/// Parses the four hex digits following `\u`, including a possible surrogate pair.
+fn parse_unicode_escape(chars: &mut Peekable<Chars>) -> Result<char, Error> {
+    let high = parse_hex4(chars)?;
+    let code_point = if (0xD800..0xDC00).contains(&high) {
+        expect(chars, '\\')?;
+        expect(chars, 'u')?;
+        let low = parse_hex4(chars)?;
+        0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+    } else {
+        high
+    };
+    char::from_u32(code_point).context("Invalid unicode escape sequence.")
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, Error> {
+    let digits: String = chars.take(4).collect();
+    u32::from_str_radix(&digits, 16)
+        .with_context(|| format!("Invalid unicode escape sequence '\\u{digits}'."))
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), Error> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => bail!("Expected '{expected}', but found '{c}'."),
+        None => bail!("Expected '{expected}', but found end of input."),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use odbc_api::sys::{Date, Timestamp};
+
+    use crate::enum_args::ParamTypeArgument;
+
+    use super::{
+        bind_parameters, count_placeholders, describe_bound_query, parse_date, parse_json_array,
+        parse_timestamp, split_stdin_parameters,
+    };
+
+    #[test]
+    fn query_and_parameters_from_stdin() {
+        let (query, parameters) =
+            split_stdin_parameters("SELECT a FROM t\nWHERE b = ? AND c = ?\n---\r\n42\nsecret\n")
+                .unwrap();
+        assert_eq!("SELECT a FROM t\nWHERE b = ? AND c = ?\n", query);
+        assert_eq!(vec!["42".to_owned(), "secret".to_owned()], parameters);
+
+        // Only a line consisting of the delimiter separates the parameters
+        assert_eq!(
+            None,
+            split_stdin_parameters("SELECT '---' AS a\n--- comment\n")
+        );
+        assert_eq!(
+            Some(("SELECT 42\n", Vec::new())),
+            split_stdin_parameters("SELECT 42\n---")
+        );
+    }
+
+    #[test]
+    fn parse_typed_parameters() {
+        assert_eq!(
+            Some(Date {
+                year: 2024,
+                month: 1,
+                day: 2
+            }),
+            parse_date("2024-01-02")
+        );
+        assert_eq!(
+            Some(Timestamp {
+                year: 2024,
+                month: 1,
+                day: 2,
+                hour: 3,
+                minute: 4,
+                second: 5,
+                fraction: 600_000_000
+            }),
+            parse_timestamp("2024-01-02T03:04:05.6")
+        );
+        assert_eq!(None, parse_date("02.01.2024"));
+
+        let parameters = ["42".to_owned(), "forty two".to_owned()];
+        assert!(bind_parameters(&parameters, &[(1, ParamTypeArgument::I64)], &[]).is_ok());
+        // The value of a NULL parameter is not parsed.
+        assert!(bind_parameters(&parameters, &[(2, ParamTypeArgument::I64)], &[2]).is_ok());
+        assert!(bind_parameters(&parameters, &[], &[3]).is_err());
+        let error = bind_parameters(&parameters, &[(2, ParamTypeArgument::I64)], &[])
+            .err()
+            .unwrap();
+        assert_eq!(
+            "Parameter 2 'forty two' is not a valid I64.",
+            error.to_string()
+        );
+        assert!(bind_parameters(&parameters, &[(3, ParamTypeArgument::I64)], &[]).is_err());
+    }
+
+    #[test]
+    fn parse_parameters_from_json_array() {
+        let parameters =
+            parse_json_array(r#" ["Hello, \"World\"", 42, -1.5e3, true, "ä"] "#).unwrap();
+
+        assert_eq!(
+            vec!["Hello, \"World\"", "42", "-1.5e3", "true", "ä"],
+            parameters
+        );
+        assert!(parse_json_array("[]").unwrap().is_empty());
+        assert!(parse_json_array("[null]").is_err());
+        assert!(parse_json_array(r#"["unterminated]"#).is_err());
+    }
+
+    #[test]
+    fn ignore_question_marks_in_literals() {
+        assert_eq!(
+            2,
+            count_placeholders("SELECT '?', \"a?\" FROM t WHERE a > ? AND b < ? AND c = 'it''s?'")
+        );
+    }
+
+    #[test]
+    fn describe_query_with_parameters() {
+        assert_eq!(
+            "Query: SELECT * FROM t WHERE a > ? AND b = ?\nParameter 1: '42'\nParameter 2: NULL",
+            describe_bound_query(
+                "SELECT * FROM t WHERE a > ? AND b = ?\n",
+                &["42".to_owned(), "x".to_owned()],
+                &[2]
+            )
+        );
+    }
+}
@@ -1,37 +1,92 @@
 use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read_dir, File},
     io::{stdout, Write},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use anyhow::{format_err, Error};
+use anyhow::{bail, format_err, Context, Error};
+use bytesize::ByteSize;
 use io_arg::IoArg;
+use log::info;
 use parquet::{
     basic::{Compression, Encoding},
     file::{
-        properties::{WriterProperties, WriterVersion},
+        metadata::KeyValue,
+        properties::{EnabledStatistics, WriterProperties, WriterVersion},
+        reader::{FileReader, SerializedFileReader},
         writer::SerializedFileWriter,
     },
     schema::types::{ColumnPath, Type},
 };
 
+use crate::enum_args::SqlTypeArgument;
+
 use super::{
-    batch_size_limit::FileSizeLimit, conversion_strategy::ColumnExporter, current_file::CurrentFile,
+    batch_size_limit::FileSizeLimit, conversion_strategy::ColumnExporter,
+    current_file::CurrentFile, output_template::OutputTemplate, row_group_buffer::RowGroupBuffer,
 };
 
 /// Options influencing the output parquet file independent of schema or row content.
+#[derive(Clone)]
 pub struct ParquetWriterOptions {
     /// Directly correlated to the `--column-compression-default` command line option
     pub column_compression_default: Compression,
+    /// Tuples of column name and compression, overriding the default compression for the
+    /// associated columns.
+    pub column_compressions: Vec<(String, Compression)>,
     /// Tuples of column name and encoding which control the encoding for the associated columns.
     pub column_encodings: Vec<(String, Encoding)>,
+    /// Tuples of SQL type and encoding, applied to all columns of the type. Resolved into
+    /// `column_encodings` once the types of the columns are known.
+    pub encodings_for_type: Vec<(SqlTypeArgument, Encoding)>,
+    /// Tuples of column name and false positive probability, for columns with a bloom filter.
+    pub bloom_filters: Vec<(String, f64)>,
+    /// Names of the columns for which no statistics are written.
+    pub no_statistics: Vec<String>,
+    /// Do not write statistics for any column.
+    pub no_statistics_all: bool,
+    /// Only write statistics for column chunks, not for pages, so no column index is written.
+    pub no_column_index: bool,
+    /// Names of the columns which are not dictionary encoded.
+    pub no_dictionary: Vec<String>,
+    /// Do not use dictionary encoding for any column.
+    pub no_dictionary_all: bool,
+    /// Overwrites the default data page size limit of the parquet library.
+    pub data_page_size: Option<usize>,
+    /// Overwrites the default dictionary page size limit of the parquet library.
+    pub dictionary_page_size: Option<usize>,
     /// Number of digits in the suffix, appended to the end of a file in case they are numbered.
     pub suffix_length: usize,
+    /// Replaces the suffix logic for naming numbered files, if specified.
+    pub output_template: Option<OutputTemplate>,
     /// A fuzzy limit for file size, causing the rest of the query to be written into new files if a
     /// threshold is passed.
     pub file_size: FileSizeLimit,
+    /// Number of rows in each row group, independent of the size of the fetched batches. `None`
+    /// writes each batch as one row group.
+    pub row_group_size: Option<usize>,
     /// Do not create a file if no row was in the result set.
     pub no_empty_file: bool,
+    /// Output is a directory with hive style partitions, rather than a single file.
+    pub partitioned: bool,
+    /// Number of partitions a partitioned output may write to, before the export fails.
+    pub max_open_partitions: usize,
+    /// Output is written into one file per date, specified with `--split-by-date`.
+    pub split_by_date: bool,
+    /// Additional key value pairs stored in the metadata of each file.
+    pub key_value_metadata: Vec<KeyValue>,
+    /// Version of the parquet format the files are written in.
+    pub writer_version: WriterVersion,
+    /// Overrides the `created_by` field in the footer of the files.
+    pub created_by: Option<String>,
+    /// Write each file to a temporary sibling, which is renamed once the file is complete.
+    pub atomic_output: bool,
+    /// Permissions of the created files on Unix, specified with `--file-mode`.
+    pub file_mode: Option<u32>,
+    /// Continue numbering split files after the highest number of the existing files.
+    pub append_to_dataset: bool,
 }
 
 pub fn parquet_output(
@@ -39,24 +94,126 @@ pub fn parquet_output(
     schema: Arc<Type>,
     options: ParquetWriterOptions,
 ) -> Result<Box<dyn ParquetOutput>, Error> {
-    // Write properties
+    // Fail before fetching any rows
+    let is_unknown = |column_name: &str| {
+        !schema
+            .get_fields()
+            .iter()
+            .any(|field| field.name() == column_name)
+    };
+    if let Some((unknown, _)) = options
+        .bloom_filters
+        .iter()
+        .find(|(column_name, _)| is_unknown(column_name))
+    {
+        bail!("Can not write bloom filter for column '{unknown}', since it is not part of the output.")
+    }
+    if let Some(unknown) = options
+        .no_dictionary
+        .iter()
+        .find(|column_name| is_unknown(column_name))
+    {
+        bail!(
+            "Can not disable dictionary encoding for column '{unknown}', since it is not part of \
+            the output."
+        )
+    }
+    let properties = Arc::new(writer_properties(&options));
+
+    let writer: Box<dyn ParquetOutput> = match output {
+        IoArg::StdStream => Box::new(StandardOut::new(
+            schema,
+            properties,
+            options.row_group_size,
+        )?),
+        IoArg::File(path) if options.partitioned => {
+            Box::new(PartitionedOutput::new(path, schema, options, properties)?)
+        }
+        IoArg::File(path) if options.split_by_date => {
+            Box::new(DateSplitOutput::new(path, schema, options, properties))
+        }
+        IoArg::File(path) => Box::new(FileWriter::new(path, schema, options, properties)?),
+    };
+
+    Ok(writer)
+}
+
+/// Properties used to write every parquet file of the output.
+pub fn writer_properties(options: &ParquetWriterOptions) -> WriterProperties {
     // Seems to also work fine without setting the batch size explicitly, but what the heck. Just to
     // be on the safe side.
     let mut wpb = WriterProperties::builder()
-        .set_writer_version(WriterVersion::PARQUET_2_0)
+        .set_writer_version(options.writer_version)
         .set_compression(options.column_compression_default);
     for (column_name, encoding) in options.column_encodings.clone() {
         let col = ColumnPath::new(vec![column_name]);
         wpb = wpb.set_column_encoding(col, encoding)
     }
-    let properties = Arc::new(wpb.build());
+    for (column_name, compression) in &options.column_compressions {
+        let col = ColumnPath::new(vec![column_name.clone()]);
+        wpb = wpb.set_column_compression(col, *compression)
+    }
+    for (column_name, fpp) in &options.bloom_filters {
+        let col = ColumnPath::new(vec![column_name.clone()]);
+        wpb = wpb
+            .set_column_bloom_filter_enabled(col.clone(), true)
+            .set_column_bloom_filter_fpp(col, *fpp)
+    }
+    if options.no_statistics_all {
+        wpb = wpb.set_statistics_enabled(EnabledStatistics::None);
+    } else if options.no_column_index {
+        // The column index consists of the page statistics.
+        wpb = wpb.set_statistics_enabled(EnabledStatistics::Chunk);
+    }
+    for column_name in &options.no_statistics {
+        let col = ColumnPath::new(vec![column_name.clone()]);
+        wpb = wpb.set_column_statistics_enabled(col, EnabledStatistics::None)
+    }
+    if options.no_dictionary_all {
+        wpb = wpb.set_dictionary_enabled(false);
+    }
+    for column_name in &options.no_dictionary {
+        let col = ColumnPath::new(vec![column_name.clone()]);
+        wpb = wpb.set_column_dictionary_enabled(col, false)
+    }
+    if let Some(data_page_size) = options.data_page_size {
+        wpb = wpb.set_data_page_size_limit(data_page_size);
+    }
+    if let Some(dictionary_page_size) = options.dictionary_page_size {
+        wpb = wpb.set_dictionary_page_size_limit(dictionary_page_size);
+    }
+    if !options.key_value_metadata.is_empty() {
+        wpb = wpb.set_key_value_metadata(Some(options.key_value_metadata.clone()));
+    }
+    if let Some(created_by) = &options.created_by {
+        wpb = wpb.set_created_by(created_by.clone());
+    }
+    wpb.build()
+}
 
-    let writer: Box<dyn ParquetOutput> = match output {
-        IoArg::StdStream => Box::new(StandardOut::new(schema, properties)?),
-        IoArg::File(path) => Box::new(FileWriter::new(path, schema, options, properties)?),
+/// Human readable description of the files the output will be written to.
+pub fn describe_output(output: &IoArg, options: &ParquetWriterOptions) -> Result<String, Error> {
+    let description = match output {
+        IoArg::StdStream => "standard output".to_owned(),
+        IoArg::File(path) if options.partitioned => format!(
+            "hive style partitions in directory '{}'",
+            path.to_string_lossy()
+        ),
+        IoArg::File(path) if options.split_by_date => format!(
+            "one file per date, e.g. '{}'",
+            path_with_date(path, "2024-01-31")?.to_string_lossy()
+        ),
+        IoArg::File(path) if options.file_size.output_is_splitted() => {
+            let naming = SplitFileNaming::new(options);
+            format!(
+                "files '{}', '{}', ...",
+                naming.path(path, 1)?.to_string_lossy(),
+                naming.path(path, 2)?.to_string_lossy()
+            )
+        }
+        IoArg::File(path) => format!("file '{}'", path.to_string_lossy()),
     };
-
-    Ok(writer)
+    Ok(description)
 }
 
 /// Writes row groups to the output, which could be either standard out, a single parquet file or
@@ -79,6 +236,12 @@ pub trait ParquetOutput {
     fn close(self) -> Result<(), Error>;
 
     fn close_box(self: Box<Self>) -> Result<(), Error>;
+
+    /// Total number of bytes written to the output so far. Used to report progress.
+    fn bytes_written(&self) -> u64;
+
+    /// Path of the file currently written to, if there is a single one.
+    fn current_path(&self) -> Option<&Path>;
 }
 
 /// Wraps parquet SerializedFileWriter. Handles splitting into new files after maximum amount of
@@ -89,12 +252,19 @@ struct FileWriter {
     properties: Arc<WriterProperties>,
     file_size: FileSizeLimit,
     num_file: u32,
-    /// Length of the suffix, appended to the end of a file in case they are numbered.
-    suffix_length: usize,
+    /// Names of the files in case they are numbered.
+    naming: SplitFileNaming,
     /// Current file open for writing. `None`, if we are in between files, i.e. a file has been
     /// closed, due to the size threshold, but a new row group has not yet been received from the
     /// database.
     current_file: Option<CurrentFile>,
+    /// Size of all files, which have already been finalized.
+    bytes_in_finalized_files: u64,
+    /// Number of rows written to the current file. Used to split the output by `--rows-per-file`.
+    rows_in_file: usize,
+    atomic_output: bool,
+    row_group_size: Option<usize>,
+    file_mode: Option<u32>,
 }
 
 impl FileWriter {
@@ -104,15 +274,30 @@ impl FileWriter {
         options: ParquetWriterOptions,
         properties: Arc<WriterProperties>,
     ) -> Result<Self, Error> {
-        let mut file_writer = Self {
-            base_path: path,
+        let mut file_writer = Self::without_file(
+            path,
             schema,
             properties,
-            file_size: options.file_size,
-            num_file: 0,
-            suffix_length: options.suffix_length,
-            current_file: None,
-        };
+            options.file_size,
+            SplitFileNaming::new(&options),
+            options.atomic_output,
+            options.row_group_size,
+            options.file_mode,
+        );
+
+        if options.append_to_dataset {
+            if let Some((num_file, path)) = file_writer
+                .naming
+                .highest_existing(&file_writer.base_path)?
+            {
+                check_schema_of_existing_file(&path, &file_writer.schema)?;
+                info!(
+                    "Appending to dataset after existing file '{}'.",
+                    path.display()
+                );
+                file_writer.num_file = num_file;
+            }
+        }
 
         if !options.no_empty_file {
             file_writer.next_file()?;
@@ -121,36 +306,59 @@ impl FileWriter {
         Ok(file_writer)
     }
 
+    /// Create a writer, which creates its first file only once the first row group is written.
+    #[allow(clippy::too_many_arguments)]
+    fn without_file(
+        path: PathBuf,
+        schema: Arc<Type>,
+        properties: Arc<WriterProperties>,
+        file_size: FileSizeLimit,
+        naming: SplitFileNaming,
+        atomic_output: bool,
+        row_group_size: Option<usize>,
+        file_mode: Option<u32>,
+    ) -> Self {
+        Self {
+            base_path: path,
+            schema,
+            properties,
+            file_size,
+            num_file: 0,
+            naming,
+            current_file: None,
+            bytes_in_finalized_files: 0,
+            rows_in_file: 0,
+            atomic_output,
+            row_group_size,
+            file_mode,
+        }
+    }
+
     fn next_file(&mut self) -> Result<(), Error> {
-        let suffix = self
-            .file_size
-            .output_is_splitted()
-            .then_some((self.num_file + 1, self.suffix_length));
-        let path = Self::current_path(&self.base_path, suffix)?;
+        let path = if self.file_size.output_is_splitted() {
+            self.naming.path(&self.base_path, self.num_file + 1)?
+        } else {
+            self.base_path.clone()
+        };
         self.current_file = Some(CurrentFile::new(
             path,
             self.schema.clone(),
             self.properties.clone(),
+            self.atomic_output,
+            self.row_group_size,
+            self.file_mode,
         )?);
         self.num_file += 1;
         Ok(())
     }
 
-    fn current_path(base_path: &Path, suffix: Option<(u32, usize)>) -> Result<PathBuf, Error> {
-        let path = if let Some((num_file, suffix_length)) = suffix {
-            path_with_suffix(base_path, num_file, suffix_length)?
-        } else {
-            base_path.to_owned()
-        };
-        Ok(path)
-    }
-}
-
-impl ParquetOutput for FileWriter {
-    fn write_row_group(
+    /// Writes `num_rows` rows into the current file, and finalizes it, if the size limit is
+    /// reached.
+    fn write_to_current_file(
         &mut self,
         num_batch: u32,
         column_exporter: ColumnExporter,
+        num_rows: usize,
     ) -> Result<(), Error> {
         // There is no file. Let us create one so we can write the row group.
         if self.current_file.is_none() {
@@ -164,16 +372,141 @@ impl ParquetOutput for FileWriter {
             .unwrap()
             .write_row_group(column_exporter)?;
 
+        self.rows_in_file += num_rows;
         if self
             .file_size
-            .should_start_new_file(num_batch + 1, file_size)
+            .should_start_new_file(num_batch + 1, file_size, self.rows_in_file)
         {
-            self.current_file.take().unwrap().finalize()?;
+            self.finalize_current_file()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the selected rows, so that no file becomes larger than `max_size`. Rows which do not
+    /// fit into the current file are written into a new one.
+    fn write_below_ceiling(
+        &mut self,
+        num_batch: u32,
+        mut column_exporter: ColumnExporter,
+        max_size: ByteSize,
+    ) -> Result<(), Error> {
+        let selected = column_exporter.selected_rows();
+        let mut rest = selected.as_slice();
+        while !rest.is_empty() {
+            if self.current_file.is_none() {
+                self.next_file()?
+            }
+            let current_file = self.current_file.as_mut().unwrap();
+            let available = max_size
+                .as_u64()
+                .saturating_sub(current_file.finalized_size());
+            // Shrink the row group until it fits into the current file.
+            let mut num_rows = rest.len();
+            let row_group = loop {
+                if available == 0 {
+                    break None;
+                }
+                column_exporter.select_rows(rest[..num_rows].to_vec());
+                let row_group = current_file.encode_row_group(column_exporter.reborrow())?;
+                let size = row_group.size();
+                if size <= available {
+                    break Some(row_group);
+                }
+                if num_rows == 1 {
+                    break None;
+                }
+                // Assume the rows to be of similar size
+                let estimate = (num_rows as u64 * available / size) as usize;
+                num_rows = estimate.clamp(1, num_rows - 1);
+            };
+            let Some(row_group) = row_group else {
+                if current_file.num_rows() == 0 {
+                    bail!(
+                        "A file containing a single row would be larger than the maximum file \
+                        size of {max_size}."
+                    )
+                }
+                // Not even one more row fits into the current file. Continue with a new one.
+                self.finalize_current_file()?;
+                continue;
+            };
+            let file_size = current_file.write_encoded_row_group(row_group, num_rows)?;
+            self.rows_in_file += num_rows;
+            rest = &rest[num_rows..];
+            if self
+                .file_size
+                .should_start_new_file(num_batch + 1, file_size, self.rows_in_file)
+            {
+                self.finalize_current_file()?;
+            }
         }
+        Ok(())
+    }
 
+    fn finalize_current_file(&mut self) -> Result<(), Error> {
+        let current_file = self.current_file.take().unwrap();
+        self.bytes_in_finalized_files += current_file.file_size().as_u64();
+        current_file.finalize()?;
+        self.rows_in_file = 0;
         Ok(())
     }
 
+    /// Number of rows which can be written, before the current file must be split due to
+    /// `--rows-per-file`, or the current row group is complete. We split batches at these
+    /// boundaries, so files split due to their size still end with a complete row group. `None`
+    /// if neither applies.
+    fn rows_until_boundary(&self) -> Option<usize> {
+        let buffered = self
+            .current_file
+            .as_ref()
+            .map_or(0, CurrentFile::buffered_rows);
+        let row_group = self.row_group_size.map(|size| size - buffered);
+        let file = self
+            .file_size
+            .rows_per_file()
+            .map(|rows| rows - self.rows_in_file);
+        match (row_group, file) {
+            (Some(row_group), Some(file)) => Some(row_group.min(file)),
+            (row_group, file) => row_group.or(file),
+        }
+    }
+}
+
+impl ParquetOutput for FileWriter {
+    fn write_row_group(
+        &mut self,
+        num_batch: u32,
+        mut column_exporter: ColumnExporter,
+    ) -> Result<(), Error> {
+        if let Some(max_size) = self.file_size.max_file_size() {
+            return self.write_below_ceiling(num_batch, column_exporter, max_size);
+        }
+        let selected = column_exporter.selected_rows();
+        if self.rows_until_boundary().is_none() {
+            return self.write_to_current_file(num_batch, column_exporter, selected.len());
+        }
+        // Split the batch, so no file contains more than `rows_per_file` rows, and no row group
+        // more than `row_group_size` rows.
+        let mut rest = selected.as_slice();
+        while let Some(limit) = self
+            .rows_until_boundary()
+            .filter(|&limit| rest.len() > limit)
+        {
+            let (head, tail) = rest.split_at(limit);
+            column_exporter.select_rows(head.to_vec());
+            self.write_to_current_file(num_batch, column_exporter.reborrow(), head.len())?;
+            rest = tail;
+        }
+        if rest.len() != selected.len() {
+            if rest.is_empty() {
+                return Ok(());
+            }
+            column_exporter.select_rows(rest.to_vec());
+        }
+        self.write_to_current_file(num_batch, column_exporter, rest.len())
+    }
+
     fn close(self) -> Result<(), Error> {
         // An active file might, or might not exist at this point, depending on whether the
         // file splitting due to size thresholds coincides with the data source being consumed and
@@ -189,19 +522,250 @@ impl ParquetOutput for FileWriter {
     fn close_box(self: Box<Self>) -> Result<(), Error> {
         self.close()
     }
+
+    fn bytes_written(&self) -> u64 {
+        let current = self
+            .current_file
+            .as_ref()
+            .map_or(0, |file| file.file_size().as_u64());
+        self.bytes_in_finalized_files + current
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        self.current_file.as_ref().map(CurrentFile::path)
+    }
+}
+
+/// Writes the output into a directory with hive style partitions. Each partition is written by its
+/// own [`FileWriter`] into a file named `part.par` (or `part_01.par`, ... in case the output is
+/// split) within the directory of the partition. E.g. `out/country=Germany/part.par`.
+struct PartitionedOutput {
+    base_dir: PathBuf,
+    schema: Arc<Type>,
+    properties: Arc<WriterProperties>,
+    file_size: FileSizeLimit,
+    naming: SplitFileNaming,
+    atomic_output: bool,
+    row_group_size: Option<usize>,
+    file_mode: Option<u32>,
+    /// Maximum number of entries in `partitions`. Specified with `--max-open-partitions`.
+    max_open_partitions: usize,
+    /// Writer for each partition directory we encountered so far, together with the number of row
+    /// groups written to it.
+    partitions: HashMap<PathBuf, (FileWriter, u32)>,
+}
+
+impl PartitionedOutput {
+    pub fn new(
+        base_dir: PathBuf,
+        schema: Arc<Type>,
+        options: ParquetWriterOptions,
+        properties: Arc<WriterProperties>,
+    ) -> Result<Self, Error> {
+        // Partition directories are only created once we receive rows for them. Still, create the
+        // base directory so an empty result is distinguishable from a failed query.
+        if !options.no_empty_file {
+            create_dir_all(&base_dir).map_err(|io_err| {
+                Error::from(io_err).context(format!(
+                    "Could not create output directory '{}'",
+                    base_dir.to_string_lossy()
+                ))
+            })?;
+        }
+        Ok(Self {
+            base_dir,
+            schema,
+            properties,
+            file_size: options.file_size,
+            naming: SplitFileNaming::new(&options),
+            atomic_output: options.atomic_output,
+            row_group_size: options.row_group_size,
+            file_mode: options.file_mode,
+            max_open_partitions: options.max_open_partitions,
+            partitions: HashMap::new(),
+        })
+    }
+}
+
+impl ParquetOutput for PartitionedOutput {
+    fn write_row_group(
+        &mut self,
+        _num_batch: u32,
+        mut column_exporter: ColumnExporter,
+    ) -> Result<(), Error> {
+        for (partition, rows) in column_exporter.partitions() {
+            if !self.partitions.contains_key(&partition) {
+                if self.partitions.len() == self.max_open_partitions {
+                    bail!(
+                        "Partition '{}' would exceed the limit of {} open partitions. Writers of \
+                        all partitions are kept open until the end of the export. Raise the limit \
+                        with `--max-open-partitions`, or use a partition column with fewer \
+                        distinct values.",
+                        partition.to_string_lossy(),
+                        self.max_open_partitions
+                    )
+                }
+                let dir = self.base_dir.join(&partition);
+                create_dir_all(&dir).map_err(|io_err| {
+                    Error::from(io_err).context(format!(
+                        "Could not create partition directory '{}'",
+                        dir.to_string_lossy()
+                    ))
+                })?;
+                let writer = FileWriter::without_file(
+                    dir.join("part.par"),
+                    self.schema.clone(),
+                    self.properties.clone(),
+                    self.file_size,
+                    self.naming.clone(),
+                    self.atomic_output,
+                    self.row_group_size,
+                    self.file_mode,
+                );
+                self.partitions.insert(partition.clone(), (writer, 0));
+            }
+            let (writer, num_row_groups) = self.partitions.get_mut(&partition).unwrap();
+            *num_row_groups += 1;
+            column_exporter.select_rows(rows);
+            writer.write_row_group(*num_row_groups, column_exporter.reborrow())?;
+        }
+        Ok(())
+    }
+
+    fn close(self) -> Result<(), Error> {
+        for (writer, _num_row_groups) in self.partitions.into_values() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+
+    fn close_box(self: Box<Self>) -> Result<(), Error> {
+        self.close()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.partitions
+            .values()
+            .map(|(writer, _num_row_groups)| writer.bytes_written())
+            .sum()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        // Rows are written to many files at once.
+        None
+    }
+}
+
+/// Writes the rows into one file per date, named after the base path with the date appended to
+/// its stem. E.g. `out_2024-01-31.par`. Files are created once the first row for their date is
+/// received and stay open until the end of the export, so rows need not be ordered by date.
+struct DateSplitOutput {
+    base_path: PathBuf,
+    schema: Arc<Type>,
+    properties: Arc<WriterProperties>,
+    file_size: FileSizeLimit,
+    naming: SplitFileNaming,
+    atomic_output: bool,
+    row_group_size: Option<usize>,
+    file_mode: Option<u32>,
+    /// Writer for each date we encountered so far, together with the number of row groups written
+    /// to it.
+    files: HashMap<String, (FileWriter, u32)>,
+}
+
+impl DateSplitOutput {
+    pub fn new(
+        base_path: PathBuf,
+        schema: Arc<Type>,
+        options: ParquetWriterOptions,
+        properties: Arc<WriterProperties>,
+    ) -> Self {
+        Self {
+            base_path,
+            schema,
+            properties,
+            file_size: options.file_size,
+            naming: SplitFileNaming::new(&options),
+            atomic_output: options.atomic_output,
+            row_group_size: options.row_group_size,
+            file_mode: options.file_mode,
+            files: HashMap::new(),
+        }
+    }
+}
+
+impl ParquetOutput for DateSplitOutput {
+    fn write_row_group(
+        &mut self,
+        _num_batch: u32,
+        mut column_exporter: ColumnExporter,
+    ) -> Result<(), Error> {
+        for (date, rows) in column_exporter.date_splits() {
+            if !self.files.contains_key(&date) {
+                let writer = FileWriter::without_file(
+                    path_with_date(&self.base_path, &date)?,
+                    self.schema.clone(),
+                    self.properties.clone(),
+                    self.file_size,
+                    self.naming.clone(),
+                    self.atomic_output,
+                    self.row_group_size,
+                    self.file_mode,
+                );
+                self.files.insert(date.clone(), (writer, 0));
+            }
+            let (writer, num_row_groups) = self.files.get_mut(&date).unwrap();
+            *num_row_groups += 1;
+            column_exporter.select_rows(rows);
+            writer.write_row_group(*num_row_groups, column_exporter.reborrow())?;
+        }
+        Ok(())
+    }
+
+    fn close(self) -> Result<(), Error> {
+        for (writer, _num_row_groups) in self.files.into_values() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+
+    fn close_box(self: Box<Self>) -> Result<(), Error> {
+        self.close()
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.files
+            .values()
+            .map(|(writer, _num_row_groups)| writer.bytes_written())
+            .sum()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        // Rows are written to many files at once.
+        None
+    }
 }
 
 /// Stream parquet directly to standard out
 struct StandardOut {
     writer: SerializedFileWriter<Box<dyn Write + Send>>,
+    /// Accumulates rows across batches, in case of `--row-group-size-rows`.
+    row_group_buffer: Option<RowGroupBuffer>,
 }
 
 impl StandardOut {
-    pub fn new(schema: Arc<Type>, properties: Arc<WriterProperties>) -> Result<Self, Error> {
+    pub fn new(
+        schema: Arc<Type>,
+        properties: Arc<WriterProperties>,
+        row_group_size: Option<usize>,
+    ) -> Result<Self, Error> {
         let output: Box<dyn Write + Send> = Box::new(stdout());
         let writer = SerializedFileWriter::new(output, schema.clone(), properties.clone())?;
 
-        Ok(Self { writer })
+        Ok(Self {
+            writer,
+            row_group_buffer: row_group_size.map(RowGroupBuffer::new),
+        })
     }
 }
 
@@ -211,10 +775,13 @@ impl ParquetOutput for StandardOut {
         _num_batch: u32,
         mut column_exporter: ColumnExporter,
     ) -> Result<(), Error> {
+        if let Some(row_group_buffer) = &mut self.row_group_buffer {
+            return row_group_buffer.write(&mut self.writer, column_exporter);
+        }
         let mut row_group_writer = self.writer.next_row_group()?;
         let mut col_index = 0;
         while let Some(mut column_writer) = row_group_writer.next_column()? {
-            column_exporter.export_nth_column(col_index, &mut column_writer)?;
+            column_exporter.export_nth_column(col_index, column_writer.untyped())?;
             column_writer.close()?;
             col_index += 1;
         }
@@ -222,7 +789,10 @@ impl ParquetOutput for StandardOut {
         Ok(())
     }
 
-    fn close(self) -> Result<(), Error> {
+    fn close(mut self) -> Result<(), Error> {
+        if let Some(row_group_buffer) = &mut self.row_group_buffer {
+            row_group_buffer.flush(&mut self.writer)?;
+        }
         self.writer.close()?;
         Ok(())
     }
@@ -230,10 +800,141 @@ impl ParquetOutput for StandardOut {
     fn close_box(self: Box<Self>) -> Result<(), Error> {
         self.close()
     }
+
+    fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written().try_into().unwrap()
+    }
+
+    fn current_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// How files are named, in case the output is split into multiple numbered files.
+#[derive(Clone)]
+pub struct SplitFileNaming {
+    suffix_length: usize,
+    template: Option<OutputTemplate>,
+}
+
+impl SplitFileNaming {
+    pub fn new(options: &ParquetWriterOptions) -> Self {
+        Self {
+            suffix_length: options.suffix_length,
+            template: options.output_template.clone(),
+        }
+    }
+
+    /// Path of the `num_file`-th file. The template only determines the file name, the file is
+    /// always placed in the directory of `base_path`.
+    pub fn path(&self, base_path: &Path, num_file: u32) -> Result<PathBuf, Error> {
+        match &self.template {
+            Some(template) => Ok(base_path.with_file_name(template.render(num_file))),
+            None => path_with_suffix(base_path, num_file, self.suffix_length),
+        }
+    }
+
+    /// Number of a file named like the `num_file`-th file of the output. `None` if `file_name`
+    /// does not follow the naming scheme. Numbers may have more digits than the suffix length.
+    fn num_file(&self, base_path: &Path, file_name: &str) -> Option<u32> {
+        if let Some(template) = &self.template {
+            return template.parse_index(file_name);
+        }
+        let stem = base_path.file_stem()?.to_str()?;
+        let mut rest = file_name.strip_prefix(stem)?.strip_prefix('_')?;
+        if let Some(extension) = base_path.extension() {
+            rest = rest.strip_suffix(extension.to_str()?)?.strip_suffix('.')?;
+        }
+        if rest.is_empty() || !rest.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        rest.parse().ok()
+    }
+
+    /// Number and path of the existing file in the directory of `base_path`, with the highest
+    /// number. `None` if there is none.
+    pub fn highest_existing(&self, base_path: &Path) -> Result<Option<(u32, PathBuf)>, Error> {
+        let directory = match base_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if !directory.is_dir() {
+            return Ok(None);
+        }
+        let mut highest: Option<(u32, PathBuf)> = None;
+        for entry in read_dir(directory)? {
+            let entry = entry?;
+            let Some(num_file) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| self.num_file(base_path, name))
+            else {
+                continue;
+            };
+            if highest.as_ref().is_none_or(|(max, _)| num_file > *max) {
+                highest = Some((num_file, entry.path()));
+            }
+        }
+        Ok(highest)
+    }
+}
+
+/// Fails if the columns of the parquet file at `path` differ from the columns of `schema`, in name,
+/// type or repetition.
+fn check_schema_of_existing_file(path: &Path, schema: &Type) -> Result<(), Error> {
+    let file = File::open(path)
+        .with_context(|| format!("Could not open existing file '{}'.", path.display()))?;
+    let reader = SerializedFileReader::new(file)
+        .with_context(|| format!("Could not read existing file '{}'.", path.display()))?;
+    let existing = reader.metadata().file_metadata().schema();
+    let same_columns = existing.get_fields().len() == schema.get_fields().len()
+        && existing
+            .get_fields()
+            .iter()
+            .zip(schema.get_fields())
+            .all(|(existing, new)| same_column(existing, new));
+    if !same_columns {
+        bail!(
+            "The schema of the existing file '{}' does not match the schema of the query. Can not \
+            append to the dataset.",
+            path.display()
+        )
+    }
+    Ok(())
+}
+
+fn same_column(existing: &Type, new: &Type) -> bool {
+    let (a, b) = (existing.get_basic_info(), new.get_basic_info());
+    if a.name() != b.name()
+        || a.repetition() != b.repetition()
+        || a.converted_type() != b.converted_type()
+        || existing.is_primitive() != new.is_primitive()
+    {
+        return false;
+    }
+    if existing.is_primitive() {
+        existing.get_physical_type() == new.get_physical_type()
+    } else {
+        existing.get_fields().len() == new.get_fields().len()
+            && existing
+                .get_fields()
+                .iter()
+                .zip(new.get_fields())
+                .all(|(existing, new)| same_column(existing, new))
+    }
 }
 
 fn path_with_suffix(path: &Path, num_file: u32, suffix_length: usize) -> Result<PathBuf, Error> {
     let suffix = format!("_{:0width$}", num_file, width = suffix_length);
+    path_with_stem_suffix(path, &suffix)
+}
+
+/// Path of the file holding the rows of `date`, if the output is split with `--split-by-date`.
+fn path_with_date(path: &Path, date: &str) -> Result<PathBuf, Error> {
+    path_with_stem_suffix(path, &format!("_{date}"))
+}
+
+fn path_with_stem_suffix(path: &Path, suffix: &str) -> Result<PathBuf, Error> {
     let mut stem = path
         .file_stem()
         .ok_or_else(|| format_err!("Output needs To have a file stem."))?
@@ -0,0 +1,186 @@
+//! Helpers for writing the output in hive style partitions, i.e. a directory layout like
+//! `out/country=Germany/part.par`, or into one file per date.
+
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+use odbc_api::{
+    buffers::AnySlice,
+    sys::{Date, Timestamp},
+};
+
+use crate::enum_args::DateGranularityArgument;
+
+/// Directory name used by hive for partitions where the value of the partition column is `NULL`.
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Used in place of the date in the names of files containing the rows where the column the output
+/// is split by is `NULL`.
+pub const DATE_SPLIT_NULL: &str = "null";
+
+/// Each date or timestamp in the column, truncated to the granularity and formatted as it appears
+/// in the file name. `None` represents `NULL`.
+pub fn date_split_values(
+    column: AnySlice,
+    granularity: DateGranularityArgument,
+) -> Vec<Option<String>> {
+    let format_date = |d: &Date| {
+        NaiveDate::from_ymd_opt(d.year.into(), d.month.into(), d.day.into())
+            .expect("Database must return valid dates")
+            .format(granularity.format())
+            .to_string()
+    };
+    let format_timestamp = |t: &Timestamp| {
+        NaiveDate::from_ymd_opt(t.year.into(), t.month.into(), t.day.into())
+            .and_then(|date| date.and_hms_opt(t.hour.into(), t.minute.into(), t.second.into()))
+            .expect("Database must return valid timestamps")
+            .format(granularity.format())
+            .to_string()
+    };
+    match column {
+        AnySlice::Date(values) => values.iter().map(|d| Some(format_date(d))).collect(),
+        AnySlice::NullableDate(values) => values.map(|d| d.map(format_date)).collect(),
+        AnySlice::Timestamp(values) => values.iter().map(|t| Some(format_timestamp(t))).collect(),
+        AnySlice::NullableTimestamp(values) => values.map(|t| t.map(format_timestamp)).collect(),
+        _ => unreachable!("Output can only be split by date or timestamp columns."),
+    }
+}
+
+/// Text representation of each value in the column. Used to determine the name of the directory a
+/// row is written to. `None` represents `NULL`.
+pub fn partition_values(column: AnySlice) -> Vec<Option<String>> {
+    match column {
+        AnySlice::Text(view) => view
+            .iter()
+            .map(|value| value.map(|bytes| String::from_utf8_lossy(bytes).into_owned()))
+            .collect(),
+        AnySlice::WText(view) => view
+            .iter()
+            .map(|value| value.map(|chars| String::from_utf16_lossy(chars.as_slice())))
+            .collect(),
+        AnySlice::Binary(view) => view.iter().map(|value| value.map(to_hex)).collect(),
+        AnySlice::Date(values) => values.iter().map(|d| Some(format_date(d))).collect(),
+        AnySlice::Time(values) => values.iter().map(|t| Some(format_time(t))).collect(),
+        AnySlice::Timestamp(values) => values.iter().map(|t| Some(format_timestamp(t))).collect(),
+        AnySlice::F64(values) => to_strings(values),
+        AnySlice::F32(values) => to_strings(values),
+        AnySlice::I8(values) => to_strings(values),
+        AnySlice::I16(values) => to_strings(values),
+        AnySlice::I32(values) => to_strings(values),
+        AnySlice::I64(values) => to_strings(values),
+        AnySlice::U8(values) => to_strings(values),
+        AnySlice::Bit(values) => values
+            .iter()
+            .map(|bit| Some(bit.as_bool().to_string()))
+            .collect(),
+        AnySlice::NullableDate(values) => values.map(|d| d.map(format_date)).collect(),
+        AnySlice::NullableTime(values) => values.map(|t| t.map(format_time)).collect(),
+        AnySlice::NullableTimestamp(values) => values.map(|t| t.map(format_timestamp)).collect(),
+        AnySlice::NullableF64(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableF32(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableI8(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableI16(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableI32(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableI64(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableU8(values) => values.map(|v| v.map(ToString::to_string)).collect(),
+        AnySlice::NullableBit(values) => values
+            .map(|bit| bit.map(|bit| bit.as_bool().to_string()))
+            .collect(),
+    }
+}
+
+/// Name of the directory containing the rows with `value` in the partition column `name`. E.g.
+/// `country=Germany`.
+pub fn partition_dir_name(name: &str, value: Option<&str>) -> String {
+    let value = value
+        .map(escape_path_name)
+        .unwrap_or_else(|| HIVE_DEFAULT_PARTITION.to_owned());
+    format!("{}={}", escape_path_name(name), value)
+}
+
+/// Percent encodes all characters which are not allowed in file names, or would be misinterpreted
+/// by readers of hive style partitions. This is the same set of characters, hive escapes, plus the
+/// characters forbidden in file names on windows.
+fn escape_path_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_control()
+            || matches!(
+                c,
+                '"' | '#'
+                    | '%'
+                    | '\''
+                    | '*'
+                    | '/'
+                    | ':'
+                    | '='
+                    | '?'
+                    | '\\'
+                    | '{'
+                    | '['
+                    | ']'
+                    | '^'
+                    | '<'
+                    | '>'
+                    | '|'
+            )
+        {
+            let mut utf8 = [0; 4];
+            for byte in c.encode_utf8(&mut utf8).as_bytes() {
+                write!(escaped, "%{byte:02X}").unwrap();
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn to_strings<T: ToString>(values: &[T]) -> Vec<Option<String>> {
+    values.iter().map(|v| Some(v.to_string())).collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{byte:02x}").unwrap();
+        hex
+    })
+}
+
+fn format_date(date: &odbc_api::sys::Date) -> String {
+    format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+fn format_time(time: &odbc_api::sys::Time) -> String {
+    format!("{:02}:{:02}:{:02}", time.hour, time.minute, time.second)
+}
+
+fn format_timestamp(ts: &odbc_api::sys::Timestamp) -> String {
+    let mut text = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        ts.year, ts.month, ts.day, ts.hour, ts.minute, ts.second
+    );
+    if ts.fraction != 0 {
+        write!(text, ".{:09}", ts.fraction).unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::partition_dir_name;
+
+    #[test]
+    fn hive_style_directory_names() {
+        assert_eq!(
+            "country=Germany",
+            partition_dir_name("country", Some("Germany"))
+        );
+        assert_eq!(
+            "country=__HIVE_DEFAULT_PARTITION__",
+            partition_dir_name("country", None)
+        );
+        assert_eq!("path=a%2Fb%3Dc", partition_dir_name("path", Some("a/b=c")));
+        assert_eq!("a%3Ab=%25", partition_dir_name("a:b", Some("%")));
+    }
+}
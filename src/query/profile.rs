@@ -0,0 +1,287 @@
+//! Collects basic statistics for each column while exporting, specified with `--profile-output`.
+
+use std::{collections::BTreeSet, fs, path::Path};
+
+use anyhow::{Context, Error};
+use odbc_api::{
+    buffers::{AnySlice, ColumnarAnyBuffer},
+    Bit,
+};
+
+use crate::json::write_json_string;
+
+use super::ndjson::{date_to_iso_8601, timestamp_to_iso_8601};
+
+/// Columns with more distinct values are not considered low cardinality, and no sample is written
+/// for them.
+const MAX_DISTINCT_VALUES: usize = 20;
+
+/// Statistics of all columns, accumulated from the fetched batches.
+pub struct Profile {
+    num_rows: usize,
+    columns: Vec<ColumnProfile>,
+}
+
+impl Profile {
+    /// `names` of the columns, in the order of the fetch buffers.
+    pub fn new(names: Vec<String>) -> Self {
+        let columns = names.into_iter().map(ColumnProfile::new).collect();
+        Self {
+            num_rows: 0,
+            columns,
+        }
+    }
+
+    /// Accounts for the first `num_rows` rows of the batch.
+    pub fn add_batch(&mut self, batch: &ColumnarAnyBuffer, num_rows: usize) {
+        self.num_rows += num_rows;
+        for (index, column) in self.columns.iter_mut().enumerate() {
+            column.add_slice(batch.column(index), num_rows);
+        }
+    }
+
+    /// Writes the profile as JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_json())
+            .with_context(|| format!("Could not write profile to '{}'.", path.display()))
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = format!("{{\"num_rows\":{},\"columns\":[", self.num_rows);
+        for (index, column) in self.columns.iter().enumerate() {
+            if index != 0 {
+                json.push(',');
+            }
+            column.write_json(&mut json);
+        }
+        json.push_str("]}\n");
+        json
+    }
+}
+
+struct ColumnProfile {
+    name: String,
+    null_count: usize,
+    /// Smallest and largest value of integer and floating point columns.
+    range: Option<(Number, Number)>,
+    /// `None` once the column has more than `MAX_DISTINCT_VALUES` distinct values, or if values of
+    /// its type are not sampled.
+    distinct_values: Option<BTreeSet<String>>,
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl ColumnProfile {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            null_count: 0,
+            range: None,
+            distinct_values: Some(BTreeSet::new()),
+        }
+    }
+
+    fn add_slice(&mut self, slice: AnySlice, num_rows: usize) {
+        let integer = |n: i64| Some(Number::Integer(n));
+        match slice {
+            AnySlice::Text(view) => self.add_values(view.iter().take(num_rows), |bytes| {
+                (None, Some(String::from_utf8_lossy(bytes).into_owned()))
+            }),
+            AnySlice::WText(view) => self.add_values(view.iter().take(num_rows), |text| {
+                (None, Some(text.to_string_lossy()))
+            }),
+            AnySlice::Binary(view) => {
+                self.distinct_values = None;
+                self.add_values(view.iter().take(num_rows), |_| (None, None))
+            }
+            AnySlice::NullableDate(values) => self.add_values(values.take(num_rows), |date| {
+                (None, Some(date_to_iso_8601(date)))
+            }),
+            AnySlice::NullableTimestamp(values) => self.add_values(values.take(num_rows), |ts| {
+                (None, Some(timestamp_to_iso_8601(ts)))
+            }),
+            AnySlice::NullableTime(values) => self.add_values(values.take(num_rows), |time| {
+                let text = format!("{:02}:{:02}:{:02}", time.hour, time.minute, time.second);
+                (None, Some(text))
+            }),
+            AnySlice::NullableBit(values) => self.add_values(values.take(num_rows), |bit: &Bit| {
+                (None, Some(bit.as_bool().to_string()))
+            }),
+            AnySlice::NullableF64(values) => {
+                self.add_values(values.take(num_rows), |&x| (float(x), Some(x.to_string())))
+            }
+            AnySlice::NullableF32(values) => self.add_values(values.take(num_rows), |&x| {
+                (float(x.into()), Some(x.to_string()))
+            }),
+            AnySlice::NullableI64(values) => self.add_values(values.take(num_rows), |&n| {
+                (integer(n), Some(n.to_string()))
+            }),
+            AnySlice::NullableI32(values) => self.add_values(values.take(num_rows), |&n| {
+                (integer(n.into()), Some(n.to_string()))
+            }),
+            AnySlice::NullableI16(values) => self.add_values(values.take(num_rows), |&n| {
+                (integer(n.into()), Some(n.to_string()))
+            }),
+            AnySlice::NullableI8(values) => self.add_values(values.take(num_rows), |&n| {
+                (integer(n.into()), Some(n.to_string()))
+            }),
+            AnySlice::NullableU8(values) => self.add_values(values.take(num_rows), |&n| {
+                (integer(n.into()), Some(n.to_string()))
+            }),
+            AnySlice::F64(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |&x| {
+                    (float(x), Some(x.to_string()))
+                }),
+            AnySlice::F32(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |&x| {
+                    (float(x.into()), Some(x.to_string()))
+                }),
+            AnySlice::I64(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |&n| {
+                    (integer(n), Some(n.to_string()))
+                }),
+            AnySlice::I32(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |&n| {
+                    (integer(n.into()), Some(n.to_string()))
+                }),
+            AnySlice::I16(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |&n| {
+                    (integer(n.into()), Some(n.to_string()))
+                }),
+            AnySlice::I8(values) => self.add_values(values.iter().take(num_rows).map(Some), |&n| {
+                (integer(n.into()), Some(n.to_string()))
+            }),
+            AnySlice::U8(values) => self.add_values(values.iter().take(num_rows).map(Some), |&n| {
+                (integer(n.into()), Some(n.to_string()))
+            }),
+            AnySlice::Bit(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |bit| {
+                    (None, Some(bit.as_bool().to_string()))
+                }),
+            AnySlice::Date(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |date| {
+                    (None, Some(date_to_iso_8601(date)))
+                }),
+            AnySlice::Timestamp(values) => self
+                .add_values(values.iter().take(num_rows).map(Some), |ts| {
+                    (None, Some(timestamp_to_iso_8601(ts)))
+                }),
+            AnySlice::Time(values) => {
+                self.add_values(values.iter().take(num_rows).map(Some), |time| {
+                    let text = format!("{:02}:{:02}:{:02}", time.hour, time.minute, time.second);
+                    (None, Some(text))
+                })
+            }
+        }
+    }
+
+    /// `describe` returns the value as a number, if it is part of the range, and its text for the
+    /// sample of distinct values.
+    fn add_values<T>(
+        &mut self,
+        values: impl Iterator<Item = Option<T>>,
+        describe: impl Fn(T) -> (Option<Number>, Option<String>),
+    ) {
+        for value in values {
+            let Some(value) = value else {
+                self.null_count += 1;
+                continue;
+            };
+            let (number, text) = describe(value);
+            if let Some(number) = number {
+                self.range = Some(match self.range {
+                    None => (number, number),
+                    Some((min, max)) => (
+                        if number < min { number } else { min },
+                        if number > max { number } else { max },
+                    ),
+                });
+            }
+            if let (Some(distinct), Some(text)) = (&mut self.distinct_values, text) {
+                distinct.insert(text);
+                if distinct.len() > MAX_DISTINCT_VALUES {
+                    self.distinct_values = None;
+                }
+            }
+        }
+    }
+
+    fn write_json(&self, json: &mut String) {
+        json.push_str("{\"name\":");
+        write_json_string(json, &self.name);
+        json.push_str(&format!(",\"null_count\":{}", self.null_count));
+        let (min, max) = match self.range {
+            Some((min, max)) => (min.to_json(), max.to_json()),
+            None => ("null".to_owned(), "null".to_owned()),
+        };
+        json.push_str(&format!(",\"min\":{min},\"max\":{max}"));
+        json.push_str(",\"distinct_values\":");
+        match &self.distinct_values {
+            Some(distinct) => {
+                json.push('[');
+                for (index, value) in distinct.iter().enumerate() {
+                    if index != 0 {
+                        json.push(',');
+                    }
+                    write_json_string(json, value);
+                }
+                json.push(']');
+            }
+            None => json.push_str("null"),
+        }
+        json.push('}');
+    }
+}
+
+/// NaN is neither smaller nor larger than any other value, so it is not part of the range.
+fn float(x: f64) -> Option<Number> {
+    (!x.is_nan()).then_some(Number::Float(x))
+}
+
+impl Number {
+    fn to_json(self) -> String {
+        match self {
+            Number::Integer(n) => n.to_string(),
+            // JSON has no representation for infinity.
+            Number::Float(x) if x.is_finite() => x.to_string(),
+            Number::Float(_) => "null".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnProfile, Number};
+
+    #[test]
+    fn profile_of_column() {
+        let mut column = ColumnProfile::new("a".to_owned());
+        column.add_values([Some(3), None, Some(-1), Some(3)].into_iter(), |n: i64| {
+            (Some(Number::Integer(n)), Some(n.to_string()))
+        });
+
+        let mut json = String::new();
+        column.write_json(&mut json);
+        assert_eq!(
+            r#"{"name":"a","null_count":1,"min":-1,"max":3,"distinct_values":["-1","3"]}"#,
+            json
+        );
+    }
+
+    #[test]
+    fn no_sample_for_high_cardinality() {
+        let mut column = ColumnProfile::new("a".to_owned());
+        column.add_values((0..100).map(Some), |n: i64| (None, Some(n.to_string())));
+
+        let mut json = String::new();
+        column.write_json(&mut json);
+        assert_eq!(
+            r#"{"name":"a","null_count":0,"min":null,"max":null,"distinct_values":null}"#,
+            json
+        );
+    }
+}
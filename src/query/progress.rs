@@ -0,0 +1,152 @@
+//! Reports the progress of an export, specified with `--progress`.
+
+use std::{
+    io::{stderr, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use bytesize::ByteSize;
+use log::info;
+
+use crate::enum_args::ProgressArgument;
+
+/// Reports rows fetched, bytes written and throughput after each batch. Always written to standard
+/// error, so it does not interfere with parquet written to standard out.
+pub struct Progress {
+    mode: ProgressArgument,
+    /// Minimum time between two log messages in `log` mode.
+    interval: Duration,
+    start: Instant,
+    last_report: Instant,
+    /// Allows to report a percentage and an estimate of the remaining time. Without it we can only
+    /// report the throughput, since the total number of rows is unknown.
+    limit: Option<usize>,
+}
+
+impl Progress {
+    pub fn new(mode: ProgressArgument, interval: Duration, limit: Option<usize>) -> Self {
+        let now = Instant::now();
+        Self {
+            mode,
+            interval,
+            start: now,
+            last_report: now,
+            limit,
+        }
+    }
+
+    /// Does not report anything.
+    pub fn disabled() -> Self {
+        Self::new(ProgressArgument::None, Duration::ZERO, None)
+    }
+
+    /// Called after each batch with the totals of the export so far.
+    pub fn update(&mut self, rows_fetched: usize, bytes_written: u64, current_file: Option<&Path>) {
+        match self.mode {
+            ProgressArgument::None => (),
+            ProgressArgument::Bar => {
+                let status = self.status(
+                    self.start.elapsed(),
+                    rows_fetched,
+                    bytes_written,
+                    current_file,
+                );
+                // Carriage return and clear line, so the bar is redrawn in place.
+                let mut stderr = stderr().lock();
+                let _ = write!(stderr, "\r{status}\x1b[K");
+                let _ = stderr.flush();
+            }
+            ProgressArgument::Log => {
+                if self.last_report.elapsed() >= self.interval {
+                    self.last_report = Instant::now();
+                    let status = self.status(
+                        self.start.elapsed(),
+                        rows_fetched,
+                        bytes_written,
+                        current_file,
+                    );
+                    info!("Progress: {status}");
+                }
+            }
+        }
+    }
+
+    /// Moves the cursor past the progress bar, so subsequent output starts on a new line.
+    pub fn finish(&self) {
+        if matches!(self.mode, ProgressArgument::Bar) {
+            eprintln!();
+        }
+    }
+
+    fn status(
+        &self,
+        elapsed: Duration,
+        rows_fetched: usize,
+        bytes_written: u64,
+        current_file: Option<&Path>,
+    ) -> String {
+        let seconds = elapsed.as_secs_f64();
+        let rows_per_second = if seconds > 0. {
+            rows_fetched as f64 / seconds
+        } else {
+            0.
+        };
+        let bytes_per_second = if seconds > 0. {
+            bytes_written as f64 / seconds
+        } else {
+            0.
+        };
+        let mut status = match self.limit {
+            Some(limit) => {
+                let percentage = rows_fetched as f64 / limit as f64 * 100.;
+                format!("{rows_fetched} of {limit} rows ({percentage:.0}%)")
+            }
+            None => format!("{rows_fetched} rows"),
+        };
+        status.push_str(&format!(
+            ", {} written, {rows_per_second:.0} rows/s, {}/s",
+            ByteSize::b(bytes_written),
+            ByteSize::b(bytes_per_second as u64)
+        ));
+        if let Some(limit) = self.limit {
+            if rows_per_second > 0. {
+                let remaining = limit.saturating_sub(rows_fetched) as f64 / rows_per_second;
+                status.push_str(&format!(", ETA {remaining:.0}s"));
+            }
+        }
+        if let Some(path) = current_file {
+            status.push_str(&format!(", file '{}'", path.display()));
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use crate::enum_args::ProgressArgument;
+
+    use super::Progress;
+
+    #[test]
+    fn progress_status() {
+        let progress = Progress::new(ProgressArgument::Log, Duration::ZERO, None);
+        assert_eq!(
+            "1000 rows, 2.0 KiB written, 100 rows/s, 204 B/s, file 'out_01.par'",
+            progress.status(
+                Duration::from_secs(10),
+                1000,
+                2048,
+                Some(Path::new("out_01.par"))
+            )
+        );
+
+        let progress = Progress::new(ProgressArgument::Log, Duration::ZERO, Some(4000));
+        assert_eq!(
+            "1000 of 4000 rows (25%), 2.0 KiB written, 100 rows/s, 204 B/s, ETA 30s",
+            progress.status(Duration::from_secs(10), 1000, 2048, None)
+        );
+    }
+}
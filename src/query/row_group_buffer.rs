@@ -0,0 +1,331 @@
+//! Accumulates the rows of several fetched batches into a single row group, so the size of the row
+//! groups is independent of the size of the fetch buffers. Specified with `--row-group-size-rows`.
+//! Also encodes row groups into memory, in order to learn their size before they are written to a
+//! file, in case of `--max-file-size`.
+
+use std::{
+    io::{self, Write},
+    mem::take,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Error;
+use bytes::Bytes;
+use parquet::{
+    column::{
+        page::{CompressedPage, PageWriteSpec, PageWriter},
+        writer::{get_column_writer, ColumnCloseResult, ColumnWriter},
+    },
+    errors::Result as ParquetResult,
+    file::writer::{SerializedFileWriter, SerializedPageWriter, TrackedWrite},
+    thrift::{TCompactOutputProtocol, TSerializable},
+};
+
+use super::conversion_strategy::ColumnExporter;
+
+/// Encodes the rows of the current row group into memory, until it holds `row_group_size` rows.
+/// Only then the row group is written to the file.
+pub struct RowGroupBuffer {
+    row_group_size: usize,
+    /// One writer for each column of the schema. Empty, if no rows are buffered.
+    columns: Vec<BufferedColumn>,
+    /// Number of rows in the current row group.
+    num_rows: usize,
+}
+
+impl RowGroupBuffer {
+    pub fn new(row_group_size: usize) -> Self {
+        Self {
+            row_group_size,
+            columns: Vec::new(),
+            num_rows: 0,
+        }
+    }
+
+    /// Number of rows which have not yet been written to the file.
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    /// Adds the selected rows of the batch to the current row group. Each time the row group
+    /// reaches `row_group_size` rows, it is written to `writer`.
+    pub fn write<W: Write + Send>(
+        &mut self,
+        writer: &mut SerializedFileWriter<W>,
+        mut column_exporter: ColumnExporter,
+    ) -> Result<(), Error> {
+        let selected = column_exporter.selected_rows();
+        let mut rest = selected.as_slice();
+        while !rest.is_empty() {
+            let (head, tail) = rest.split_at(rest.len().min(self.row_group_size - self.num_rows));
+            if head.len() != selected.len() {
+                column_exporter.select_rows(head.to_vec());
+            }
+            self.buffer_rows(writer, column_exporter.reborrow(), head.len())?;
+            if self.num_rows == self.row_group_size {
+                self.flush(writer)?;
+            }
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered rows to `writer` as a row group, even if it holds less than
+    /// `row_group_size` rows. Does nothing if no rows are buffered.
+    pub fn flush<W: Write + Send>(
+        &mut self,
+        writer: &mut SerializedFileWriter<W>,
+    ) -> Result<(), Error> {
+        if self.columns.is_empty() {
+            return Ok(());
+        }
+        let columns = take(&mut self.columns)
+            .into_iter()
+            .map(BufferedColumn::close)
+            .collect::<Result<Vec<_>, _>>()?;
+        append_row_group(writer, columns)?;
+        self.num_rows = 0;
+        Ok(())
+    }
+
+    fn buffer_rows<W: Write + Send>(
+        &mut self,
+        writer: &SerializedFileWriter<W>,
+        mut column_exporter: ColumnExporter,
+        num_rows: usize,
+    ) -> Result<(), Error> {
+        if self.columns.is_empty() {
+            self.columns = BufferedColumn::for_schema(writer);
+        }
+        for (col_index, column) in self.columns.iter_mut().enumerate() {
+            column_exporter.export_nth_column(col_index, &mut column.writer)?;
+        }
+        self.num_rows += num_rows;
+        Ok(())
+    }
+}
+
+/// Selected rows of one batch, encoded into memory as a complete row group. The row group is only
+/// written once we know which file it fits into.
+pub struct EncodedRowGroup {
+    /// Encoded pages and metadata of each column chunk.
+    columns: Vec<(Bytes, ColumnCloseResult)>,
+    /// See [`Self::metadata_size`].
+    metadata_size: u64,
+}
+
+impl EncodedRowGroup {
+    /// Encodes the selected rows with the schema and properties of `writer`, without writing them.
+    pub fn new<W: Write + Send>(
+        writer: &SerializedFileWriter<W>,
+        mut column_exporter: ColumnExporter,
+    ) -> Result<Self, Error> {
+        let mut columns = BufferedColumn::for_schema(writer);
+        for (col_index, column) in columns.iter_mut().enumerate() {
+            column_exporter.export_nth_column(col_index, &mut column.writer)?;
+        }
+        let columns = columns
+            .into_iter()
+            .map(BufferedColumn::close)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_columns(columns)
+    }
+
+    fn from_columns(columns: Vec<(Bytes, ColumnCloseResult)>) -> Result<Self, Error> {
+        let mut metadata_size = METADATA_RESERVE_PER_ROW_GROUP;
+        for (_, close_result) in &columns {
+            metadata_size += METADATA_RESERVE_PER_COLUMN_CHUNK
+                + thrift_size(&close_result.metadata.to_thrift())?;
+            if let Some(column_index) = &close_result.column_index {
+                metadata_size += thrift_size(column_index)?;
+            }
+            if let Some(offset_index) = &close_result.offset_index {
+                metadata_size += thrift_size(offset_index)?
+                    + METADATA_RESERVE_PER_PAGE * offset_index.page_locations.len() as u64;
+            }
+        }
+        Ok(Self {
+            columns,
+            metadata_size,
+        })
+    }
+
+    /// Upper estimate of the bytes the row group adds to a file. I.e. its encoded pages, plus its
+    /// share of the metadata at the end of the file.
+    pub fn size(&self) -> u64 {
+        let pages: u64 = self
+            .columns
+            .iter()
+            .map(|(pages, _)| pages.len() as u64)
+            .sum();
+        pages + self.metadata_size
+    }
+
+    /// Upper estimate of the bytes the row group adds to the metadata at the end of the file, i.e.
+    /// its column chunk metadata and page indices.
+    pub fn metadata_size(&self) -> u64 {
+        self.metadata_size
+    }
+
+    pub fn write<W: Write + Send>(self, writer: &mut SerializedFileWriter<W>) -> Result<(), Error> {
+        append_row_group(writer, self.columns)
+    }
+}
+
+/// Offsets in the metadata of an encoded row group are relative to its start. Within the file
+/// they are absolute, and also offsets of the page indices are added. We reserve room for these
+/// integers to grow.
+const METADATA_RESERVE_PER_COLUMN_CHUNK: u64 = 64;
+/// Room for the offset of a page in the offset index to grow, once it is absolute.
+const METADATA_RESERVE_PER_PAGE: u64 = 10;
+/// Row count, sizes and offset of the row group itself.
+const METADATA_RESERVE_PER_ROW_GROUP: u64 = 64;
+
+/// Number of bytes `value` takes up in the file metadata.
+fn thrift_size(value: &impl TSerializable) -> Result<u64, Error> {
+    let mut buffer = Vec::new();
+    let mut protocol = TCompactOutputProtocol::new(&mut buffer);
+    value.write_to_out_protocol(&mut protocol)?;
+    Ok(buffer.len() as u64)
+}
+
+/// Writes column chunks, which have been encoded into memory, as the next row group.
+fn append_row_group<W: Write + Send>(
+    writer: &mut SerializedFileWriter<W>,
+    columns: Vec<(Bytes, ColumnCloseResult)>,
+) -> Result<(), Error> {
+    let mut row_group_writer = writer.next_row_group()?;
+    for (pages, close_result) in columns {
+        row_group_writer.append_column(&pages, close_result)?;
+    }
+    row_group_writer.close()?;
+    Ok(())
+}
+
+/// Column chunk of the current row group.
+struct BufferedColumn {
+    writer: ColumnWriter<'static>,
+    /// Encoded pages written by `writer`.
+    pages: SharedBuffer,
+}
+
+impl BufferedColumn {
+    /// One column chunk for each column in the schema of `writer`.
+    fn for_schema<W: Write + Send>(writer: &SerializedFileWriter<W>) -> Vec<Self> {
+        writer
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|descr| {
+                let pages = SharedBuffer::default();
+                let page_writer = Box::new(MemoryPageWriter {
+                    sink: TrackedWrite::new(pages.clone()),
+                });
+                BufferedColumn {
+                    writer: get_column_writer(
+                        descr.clone(),
+                        writer.properties().clone(),
+                        page_writer,
+                    ),
+                    pages,
+                }
+            })
+            .collect()
+    }
+
+    /// Finishes the column chunk and returns its encoded pages along with its metadata.
+    fn close(self) -> Result<(Bytes, ColumnCloseResult), Error> {
+        let close_result = self.writer.close()?;
+        let pages = Bytes::from(take(&mut *self.pages.0.lock().unwrap()));
+        Ok((pages, close_result))
+    }
+}
+
+/// In memory sink for the pages of a column chunk. Shared, since the page writer is owned by the
+/// column writer, yet we need to access the pages once the column writer is closed.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes pages like they are serialized into a file, but into memory. Offsets of the pages
+/// are relative to the start of the column chunk.
+struct MemoryPageWriter {
+    sink: TrackedWrite<SharedBuffer>,
+}
+
+impl PageWriter for MemoryPageWriter {
+    fn write_page(&mut self, page: CompressedPage) -> ParquetResult<PageWriteSpec> {
+        SerializedPageWriter::new(&mut self.sink).write_page(page)
+    }
+
+    fn close(&mut self) -> ParquetResult<()> {
+        self.sink.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parquet::{
+        column::writer::get_typed_column_writer_mut,
+        data_type::{ByteArray, ByteArrayType, Int32Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+
+    use super::{BufferedColumn, EncodedRowGroup};
+
+    #[test]
+    fn encoded_size_is_an_upper_bound() {
+        let schema = parse_message_type(
+            "message schema { REQUIRED INT32 a; OPTIONAL BYTE_ARRAY b (UTF8); }",
+        )
+        .unwrap();
+        let properties = WriterProperties::builder()
+            .set_data_page_row_count_limit(100)
+            .build();
+        let (schema, properties) = (Arc::new(schema), Arc::new(properties));
+        let empty_file = SerializedFileWriter::new(Vec::new(), schema.clone(), properties.clone())
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let mut writer = SerializedFileWriter::new(Vec::new(), schema, properties).unwrap();
+        let mut projected_size = empty_file.len() as u64;
+        for _ in 0..3 {
+            let mut columns = BufferedColumn::for_schema(&writer);
+            let values: Vec<i32> = (0..1000).collect();
+            get_typed_column_writer_mut::<Int32Type>(&mut columns[0].writer)
+                .write_batch(&values, None, None)
+                .unwrap();
+            let texts: Vec<ByteArray> = values
+                .iter()
+                .map(|value| ByteArray::from(format!("value {value}").as_str()))
+                .collect();
+            get_typed_column_writer_mut::<ByteArrayType>(&mut columns[1].writer)
+                .write_batch(&texts, Some(&[1; 1000]), None)
+                .unwrap();
+            let columns = columns
+                .into_iter()
+                .map(|column| column.close().unwrap())
+                .collect();
+            let row_group = EncodedRowGroup::from_columns(columns).unwrap();
+            projected_size += row_group.size();
+            row_group.write(&mut writer).unwrap();
+        }
+
+        let file = writer.into_inner().unwrap();
+
+        assert!(file.len() as u64 <= projected_size);
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Error;
+use log::info;
+use odbc_api::ResultSetMetadata;
+use parquet::{
+    basic::{ConvertedType, LogicalType, TimeUnit},
+    schema::types::Type,
+};
+
+use crate::{connection::open_connection, json::write_json_string, SchemaOpt};
+
+use super::{
+    column_strategy::{individual_column_length_limits, MappingOptions},
+    conversion_strategy::ConversionStrategy,
+    query_statement_text,
+};
+
+/// Prints how the columns of the result set of a query would be mapped to parquet, without
+/// fetching any rows.
+pub fn schema(opt: SchemaOpt) -> Result<(), Error> {
+    let SchemaOpt {
+        connect_opts,
+        mapping_opts,
+        json,
+        query,
+    } = opt;
+
+    let query = query_statement_text(query)?;
+    let odbc_conn = open_connection(&connect_opts)?;
+    let db_name = odbc_conn.database_management_system_name()?;
+    info!("Database Management System Name: {db_name}");
+
+    // Same mapping as the `query` subcommand. The lengths of text columns are not determined from
+    // the data, since no rows are fetched.
+    let column_length_limits = individual_column_length_limits(&mapping_opts);
+    let mapping_options =
+        MappingOptions::from_opts(&mapping_opts, &db_name, &column_length_limits, &[]);
+
+    // Only prepare the statement, so we can inspect the result set without executing the query.
+    let mut prepared = odbc_conn.prepare(&query)?;
+    let table_strategy = ConversionStrategy::new(&mut prepared, mapping_options, &[], &[])?;
+
+    let mut columns = Vec::new();
+    let fields = table_strategy.parquet_schema();
+    for (index, field) in table_strategy
+        .exported_column_indices()
+        .zip(fields.get_fields())
+    {
+        columns.push(ColumnSchema {
+            odbc_name: prepared.col_name(index)?,
+            parquet_name: field.name().to_owned(),
+            sql_type: format!("{:?}", prepared.col_data_type(index)?),
+            nullable: format!("{:?}", prepared.col_nullability(index)?),
+            parquet_type: physical_type_text(field),
+            logical_type: logical_type_text(field),
+        });
+    }
+
+    if json {
+        println!("{}", columns_to_json(&columns));
+    } else {
+        print!("{}", columns_to_table(&columns));
+    }
+    Ok(())
+}
+
+/// Description of how a single column of the result set is mapped to parquet.
+struct ColumnSchema {
+    /// Name of the column in the result set, as reported by the driver.
+    odbc_name: String,
+    /// Name of the column in the parquet schema, after renaming, folding the case and sanitizing.
+    parquet_name: String,
+    sql_type: String,
+    nullable: String,
+    parquet_type: String,
+    logical_type: String,
+}
+
+impl ColumnSchema {
+    fn fields(&self) -> [&str; 6] {
+        [
+            &self.odbc_name,
+            &self.parquet_name,
+            &self.sql_type,
+            &self.nullable,
+            &self.parquet_type,
+            &self.logical_type,
+        ]
+    }
+}
+
+const HEADERS: [&str; 6] = [
+    "ODBC column name",
+    "parquet column name",
+    "SQL type",
+    "nullable",
+    "parquet type",
+    "logical type",
+];
+
+/// Renders the columns as a table, with one row per column. Cells are separated by `|`.
+fn columns_to_table(columns: &[ColumnSchema]) -> String {
+    let mut widths = HEADERS.map(|header| header.chars().count());
+    for column in columns {
+        for (width, field) in widths.iter_mut().zip(column.fields()) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+    let render_row = |fields: [&str; 6]| {
+        let cells: Vec<_> = fields
+            .iter()
+            .zip(widths)
+            .map(|(field, width)| format!("{field:width$}"))
+            .collect();
+        format!("{}\n", cells.join(" | ").trim_end())
+    };
+    let mut table = render_row(HEADERS);
+    for column in columns {
+        table.push_str(&render_row(column.fields()));
+    }
+    table
+}
+
+/// Renders the columns as a JSON array of objects.
+fn columns_to_json(columns: &[ColumnSchema]) -> String {
+    let keys = [
+        "odbc_name",
+        "parquet_name",
+        "sql_type",
+        "nullable",
+        "parquet_type",
+        "logical_type",
+    ];
+    let mut json = String::from("[");
+    for (index, column) in columns.iter().enumerate() {
+        if index != 0 {
+            json.push(',');
+        }
+        json.push('{');
+        for (index, (key, value)) in keys.iter().zip(column.fields()).enumerate() {
+            if index != 0 {
+                json.push(',');
+            }
+            write_json_string(&mut json, key);
+            json.push(':');
+            write_json_string(&mut json, value);
+        }
+        json.push('}');
+    }
+    json.push(']');
+    json
+}
+
+/// Physical type of a parquet column. For lists the physical type of their elements.
+fn physical_type_text(field: &Type) -> String {
+    if field.is_primitive() {
+        field.get_physical_type().to_string()
+    } else {
+        physical_type_text(&field.get_fields()[0])
+    }
+}
+
+/// Text representation of the logical type of a parquet column, formatted like the output of
+/// `parquet-schema`. Falls back to the converted type, and is empty if neither is set.
+fn logical_type_text(field: &Type) -> String {
+    let info = field.get_basic_info();
+    match info.logical_type() {
+        Some(LogicalType::Integer {
+            bit_width,
+            is_signed,
+        }) => format!("INTEGER({bit_width},{is_signed})"),
+        Some(LogicalType::Decimal { scale, precision }) => format!("DECIMAL({precision},{scale})"),
+        Some(LogicalType::Timestamp {
+            is_adjusted_to_u_t_c,
+            unit,
+        }) => format!(
+            "TIMESTAMP({},{is_adjusted_to_u_t_c})",
+            time_unit_text(&unit)
+        ),
+        Some(LogicalType::Time {
+            is_adjusted_to_u_t_c,
+            unit,
+        }) => format!("TIME({},{is_adjusted_to_u_t_c})", time_unit_text(&unit)),
+        Some(LogicalType::Date) => "DATE".to_owned(),
+        Some(LogicalType::String) => "STRING".to_owned(),
+        Some(other) => format!("{other:?}").to_uppercase(),
+        None if info.converted_type() == ConvertedType::NONE => String::new(),
+        None => info.converted_type().to_string(),
+    }
+}
+
+fn time_unit_text(unit: &TimeUnit) -> &'static str {
+    match unit {
+        TimeUnit::MILLIS(_) => "MILLIS",
+        TimeUnit::MICROS(_) => "MICROS",
+        TimeUnit::NANOS(_) => "NANOS",
+    }
+}
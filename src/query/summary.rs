@@ -0,0 +1,92 @@
+//! Counts the NULLs of each column while exporting, and prints them once the export is finished.
+//! Specified with `--summary`.
+
+use odbc_api::buffers::ColumnarAnyBuffer;
+
+use super::non_null::is_null;
+
+/// Number of rows, and number of NULLs for each column, accumulated from the fetched batches.
+pub struct Summary {
+    num_rows: usize,
+    /// Name and number of NULLs of each column, in the order of the fetch buffers.
+    columns: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// `names` of the columns, in the order of the fetch buffers.
+    pub fn new(names: Vec<String>) -> Self {
+        let columns = names.into_iter().map(|name| (name, 0)).collect();
+        Self {
+            num_rows: 0,
+            columns,
+        }
+    }
+
+    /// Accounts for the first `num_rows` rows of the batch.
+    pub fn add_batch(&mut self, batch: &ColumnarAnyBuffer, num_rows: usize) {
+        self.num_rows += num_rows;
+        for (index, (_, null_count)) in self.columns.iter_mut().enumerate() {
+            *null_count += is_null(batch.column(index), num_rows)
+                .filter(|&is_null| is_null)
+                .count();
+        }
+    }
+
+    /// Prints the summary to standard error, independent of the log level.
+    pub fn print(&self) {
+        eprint!("{}", self.to_table());
+    }
+
+    /// One line with the number of rows, followed by a table with the number and share of NULLs of
+    /// each column.
+    fn to_table(&self) -> String {
+        let rows: Vec<[String; 3]> = self
+            .columns
+            .iter()
+            .map(|(name, null_count)| {
+                let share = if self.num_rows == 0 {
+                    0.
+                } else {
+                    *null_count as f64 * 100. / self.num_rows as f64
+                };
+                [name.clone(), null_count.to_string(), format!("{share:.1}%")]
+            })
+            .collect();
+        let header = ["column", "nulls", "share"].map(str::to_owned);
+        let mut widths = [0; 3];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut table = format!("Exported {} rows.\n", self.num_rows);
+        for row in std::iter::once(&header).chain(&rows) {
+            let [name, null_count, share] = row;
+            let [name_width, null_count_width, share_width] = widths;
+            table.push_str(&format!(
+                "{name:name_width$} | {null_count:>null_count_width$} | {share:>share_width$}\n"
+            ));
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Summary;
+
+    #[test]
+    fn render_summary() {
+        let summary = Summary {
+            num_rows: 4,
+            columns: vec![("id".to_owned(), 0), ("comment".to_owned(), 3)],
+        };
+        assert_eq!(
+            "Exported 4 rows.\n\
+            column  | nulls | share\n\
+            id      |     0 |  0.0%\n\
+            comment |     3 | 75.0%\n",
+            summary.to_table()
+        );
+    }
+}
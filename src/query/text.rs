@@ -1,16 +1,13 @@
-use std::borrow::Cow;
-
 use anyhow::{anyhow, Error};
-use log::warn;
 use odbc_api::buffers::{AnySlice, BufferDesc};
 use parquet::{
-    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    basic::{ConvertedType, LogicalType, Repetition, Type as PhysicalType},
     column::writer::{get_typed_column_writer_mut, ColumnWriter},
     data_type::{ByteArray, ByteArrayType},
     schema::types::Type,
 };
 
-use crate::parquet_buffer::ParquetBuffer;
+use crate::{enum_args::TrimArgument, parquet_buffer::ParquetBuffer};
 
 use super::column_strategy::ColumnStrategy;
 
@@ -18,11 +15,58 @@ pub fn text_strategy(
     use_utf16: bool,
     repetition: Repetition,
     length: usize,
+    trim: TrimArgument,
 ) -> Box<dyn ColumnStrategy> {
     if use_utf16 {
-        Box::new(Utf16ToUtf8::new(repetition, length))
+        Box::new(Utf16ToUtf8::new(repetition, length, trim))
     } else {
-        Box::new(Utf8::with_bytes_length(repetition, length))
+        Box::new(Utf8 {
+            repetition,
+            length,
+            trim,
+        })
+    }
+}
+
+/// Text columns holding JSON documents, e.g. PostgreSQL `JSONB`. Fetched like any other text, but
+/// annotated with the logical type `JSON`.
+pub fn json_strategy(
+    use_utf16: bool,
+    repetition: Repetition,
+    length: usize,
+) -> Box<dyn ColumnStrategy> {
+    Box::new(Json {
+        repetition,
+        text: text_strategy(use_utf16, repetition, length, TrimArgument::None),
+    })
+}
+
+struct Json {
+    repetition: Repetition,
+    text: Box<dyn ColumnStrategy>,
+}
+
+impl ColumnStrategy for Json {
+    fn parquet_type(&self, name: &str) -> Type {
+        Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+            .with_logical_type(Some(LogicalType::Json))
+            .with_repetition(self.repetition)
+            .build()
+            .unwrap()
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        self.text.buffer_desc()
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        self.text
+            .copy_odbc_to_parquet(parquet_buffer, column_writer, column_view)
     }
 }
 
@@ -30,11 +74,16 @@ struct Utf16ToUtf8 {
     repetition: Repetition,
     /// Length of the column elements in `u16` (as opposed to code points).
     length: usize,
+    trim: TrimArgument,
 }
 
 impl Utf16ToUtf8 {
-    pub fn new(repetition: Repetition, length: usize) -> Self {
-        Self { repetition, length }
+    pub fn new(repetition: Repetition, length: usize, trim: TrimArgument) -> Self {
+        Self {
+            repetition,
+            length,
+            trim,
+        }
     }
 }
 
@@ -59,7 +108,7 @@ impl ColumnStrategy for Utf16ToUtf8 {
         column_writer: &mut ColumnWriter,
         column_view: AnySlice,
     ) -> Result<(), Error> {
-        write_utf16_to_utf8(parquet_buffer, column_writer, column_view)
+        write_utf16_to_utf8(parquet_buffer, column_writer, column_view, self.trim)
     }
 }
 
@@ -67,6 +116,7 @@ fn write_utf16_to_utf8(
     pb: &mut ParquetBuffer,
     column_writer: &mut ColumnWriter,
     column_reader: AnySlice,
+    trim: TrimArgument,
 ) -> Result<(), Error> {
     let cw = get_typed_column_writer_mut::<ByteArrayType>(column_writer);
     let view = column_reader.as_w_text_view().unwrap();
@@ -75,13 +125,10 @@ fn write_utf16_to_utf8(
         cw,
         view.iter().map(|item| {
             if let Some(ustr) = item {
-                let byte_array: ByteArray = ustr
-                    .to_string()
-                    .map_err(|_utf_16_error| {
-                        anyhow!("Data source must return valid UTF16 in wide character buffer")
-                    })?
-                    .into_bytes()
-                    .into();
+                let text = ustr.to_string().map_err(|_utf_16_error| {
+                    anyhow!("Data source must return valid UTF16 in wide character buffer")
+                })?;
+                let byte_array: ByteArray = trim.apply(&text).as_bytes().to_vec().into();
                 Ok(Some(byte_array))
             } else {
                 Ok(None)
@@ -95,11 +142,16 @@ pub struct Utf8 {
     repetition: Repetition,
     // Maximum string length in bytes
     length: usize,
+    trim: TrimArgument,
 }
 
 impl Utf8 {
     pub fn with_bytes_length(repetition: Repetition, length: usize) -> Self {
-        Self { repetition, length }
+        Self {
+            repetition,
+            length,
+            trim: TrimArgument::None,
+        }
     }
 }
 
@@ -124,7 +176,7 @@ impl ColumnStrategy for Utf8 {
         column_writer: &mut ColumnWriter,
         column_view: AnySlice,
     ) -> Result<(), Error> {
-        write_to_utf8(parquet_buffer, column_writer, column_view)
+        write_to_utf8(parquet_buffer, column_writer, column_view, self.trim)
     }
 }
 
@@ -132,30 +184,23 @@ fn write_to_utf8(
     pb: &mut ParquetBuffer,
     column_writer: &mut ColumnWriter,
     column_reader: AnySlice,
+    trim: TrimArgument,
 ) -> Result<(), Error> {
     let cw = get_typed_column_writer_mut::<ByteArrayType>(column_writer);
     let view = column_reader.as_text_view().unwrap();
 
     pb.write_optional(
         cw,
-        view.iter().map(|item| item.map(utf8_bytes_to_byte_array)),
+        view.iter()
+            .map(|item| item.map(|bytes| utf8_bytes_to_byte_array(bytes, trim))),
     )?;
 
     Ok(())
 }
 
-fn utf8_bytes_to_byte_array(bytes: &[u8]) -> ByteArray {
-    // Allocate string into a ByteArray and make sure it is all UTF-8 characters
-    let utf8_str = String::from_utf8_lossy(bytes);
-    // We need to allocate the string anyway to create a ByteArray (yikes!), yet if it already
-    // happened after the to_string_lossy method, it implies we had to use a replacement
-    // character!
-    if matches!(utf8_str, Cow::Owned(_)) {
-        warn!(
-            "Non UTF-8 characters found in string. Try to execute odbc2parquet in a shell with \
-            UTF-8 locale or try specifying `--encoding Utf16` on the command line. Value: {}",
-            utf8_str
-        );
-    }
-    utf8_str.into_owned().into_bytes().into()
+fn utf8_bytes_to_byte_array(bytes: &[u8], trim: TrimArgument) -> ByteArray {
+    // Allocate string into a ByteArray and make sure it is all UTF-8 characters. Invalid values
+    // are reported by `EncodingLossCheck`.
+    let text = String::from_utf8_lossy(bytes);
+    trim.apply(&text).as_bytes().to_vec().into()
 }
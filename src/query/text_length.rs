@@ -0,0 +1,222 @@
+//! Determines the length of the fetch buffers for text columns from the values in the data source,
+//! rather than from the lengths reported by the driver. Specified with `--size-text-from-data`.
+
+use anyhow::Error;
+use log::{info, warn};
+use odbc_api::{Connection, Cursor, DataType, Nullable, ParameterCollectionRef, ResultSetMetadata};
+
+use crate::enum_args::QuoteIdentifiersArgument;
+
+/// Tuples of column name and buffer length for each text column of the result set of `query`. The
+/// lengths are determined by a first pass over the data, which selects the maximum length of each
+/// column. Empty, with a warning, if the query is not a simple query of a single table, or the
+/// first pass fails.
+pub fn text_lengths_from_data(
+    conn: &Connection,
+    query: &str,
+    params: impl ParameterCollectionRef,
+    db_name: &str,
+    use_utf16: bool,
+) -> Result<Vec<(String, usize)>, Error> {
+    let Some(query) = single_table_query(query) else {
+        warn!(
+            "`--size-text-from-data` only supports simple queries of a single table, without \
+            joins, unions or subqueries. Using the lengths reported by the driver."
+        );
+        return Ok(Vec::new());
+    };
+
+    // Prepare the query to learn about the columns, without executing it.
+    let mut prepared = conn.prepare(query)?;
+    let mut text_columns = Vec::new();
+    for index in 1..=prepared.num_result_cols()? {
+        let index = index as u16;
+        if is_text(prepared.col_data_type(index)?) {
+            text_columns.push(prepared.col_name(index)?);
+        }
+    }
+    if text_columns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // `LEN` of Microsoft SQL Server ignores trailing spaces, so we use the length in bytes.
+    let is_mssql = db_name == "Microsoft SQL Server";
+    let (length_function, quote) = match db_name {
+        "Microsoft SQL Server" => ("DATALENGTH", QuoteIdentifiersArgument::Sqlserver),
+        "MySQL" | "MariaDB" => ("CHAR_LENGTH", QuoteIdentifiersArgument::Mysql),
+        _ => ("CHAR_LENGTH", QuoteIdentifiersArgument::Ansi),
+    };
+    let maxima: Vec<_> = text_columns
+        .iter()
+        .map(|name| format!("MAX({length_function}({}))", quote.quote(name)))
+        .collect();
+    let sizing_query = format!("SELECT {} FROM ({query}) AS t", maxima.join(", "));
+    info!("Determining the length of text columns with: {sizing_query}");
+
+    let mut cursor = match conn.execute(&sizing_query, params, None) {
+        Ok(Some(cursor)) => cursor,
+        Ok(None) => return Ok(Vec::new()),
+        Err(error) => {
+            warn!(
+                "Could not determine the length of text columns from the data. Using the lengths \
+                reported by the driver.\n{error}"
+            );
+            return Ok(Vec::new());
+        }
+    };
+    let Some(mut row) = cursor.next_row()? else {
+        return Ok(Vec::new());
+    };
+    let mut lengths = Vec::new();
+    for (index, name) in (1..).zip(text_columns) {
+        let mut max_length = Nullable::<i64>::null();
+        row.get_data(index, &mut max_length)?;
+        // `NULL` if the table is empty, or all values are `NULL`.
+        let measured = max_length.into_opt().unwrap_or(0).max(0) as usize;
+        let length = buffer_length(measured, is_mssql, use_utf16);
+        info!("Text column '{name}' is fetched with a buffer length of {length}.");
+        lengths.push((name, length));
+    }
+    Ok(lengths)
+}
+
+/// Length of the buffer in `u8` or `u16` elements, if the maximum length of the column is
+/// `measured`. Measured in bytes of the encoding of the data source for Microsoft SQL Server, and
+/// in characters otherwise. We can not know how many elements the characters require in the buffer,
+/// so we assume the worst case.
+fn buffer_length(measured: usize, measured_in_bytes: bool, use_utf16: bool) -> usize {
+    let factor = match (measured_in_bytes, use_utf16) {
+        // Both single byte code pages and UTF-16 require no more than one UTF-16 element per byte.
+        (true, true) => 1,
+        // A single byte may encode a character requiring three bytes in UTF-8.
+        (true, false) => 3,
+        // Characters outside of the basic multilingual plane require surrogate pairs.
+        (false, true) => 2,
+        (false, false) => 4,
+    };
+    // Buffers must not be empty.
+    (measured * factor).max(1)
+}
+
+fn is_text(data_type: DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Char { .. }
+            | DataType::Varchar { .. }
+            | DataType::LongVarchar { .. }
+            | DataType::WChar { .. }
+            | DataType::WVarchar { .. }
+            | DataType::WLongVarchar { .. }
+    )
+}
+
+/// The query without a trailing `ORDER BY` clause and `;`, if it selects from a single table
+/// without joins, unions or subqueries. `None` otherwise. Ordering does not change the maximum
+/// length, and is not allowed in derived tables by every data source.
+fn single_table_query(query: &str) -> Option<&str> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let words = words(query);
+    let count = |keyword: &str| words.iter().filter(|(_, word)| word == keyword).count();
+    if words.first().map(|(_, word)| word.as_str()) != Some("SELECT")
+        || count("SELECT") != 1
+        || count("FROM") != 1
+        || ["JOIN", "UNION", "INTERSECT", "EXCEPT"]
+            .iter()
+            .any(|keyword| count(keyword) != 0)
+    {
+        return None;
+    }
+    // A comma in the from clause would list multiple tables.
+    let from = words.iter().position(|(_, word)| word == "FROM").unwrap();
+    let end_of_from = words[from + 1..]
+        .iter()
+        .find(|(_, word)| {
+            [
+                "WHERE", "GROUP", "HAVING", "ORDER", "LIMIT", "OFFSET", "FETCH",
+            ]
+            .contains(&word.as_str())
+        })
+        .map_or(query.len(), |&(start, _)| start);
+    let from_start = words[from].0;
+    if query[from_start..end_of_from].contains([',', '(']) {
+        return None;
+    }
+    // Window functions may be ordered, too, but those are part of the select list.
+    let Some(order) = words[from..]
+        .iter()
+        .position(|(_, word)| word == "ORDER")
+        .map(|position| from + position)
+    else {
+        return Some(query);
+    };
+    // Removing the ordering would change the rows selected by a limit.
+    if words[order..]
+        .iter()
+        .any(|(_, word)| ["LIMIT", "OFFSET", "FETCH", "TOP"].contains(&word.as_str()))
+        || count("TOP") != 0
+    {
+        return None;
+    }
+    Some(query[..words[order].0].trim_end())
+}
+
+/// Upper case words of `text`, together with their byte offsets. Words consist of alphanumeric
+/// characters and `_`.
+fn words(text: &str) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        let is_word = c.is_alphanumeric() || c == '_';
+        match (start, is_word) {
+            (None, true) => start = Some(index),
+            (Some(begin), false) => {
+                words.push((begin, text[begin..index].to_uppercase()));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{buffer_length, single_table_query};
+
+    #[test]
+    fn detect_single_table_queries() {
+        assert_eq!(
+            Some("SELECT a, b FROM t WHERE a > 1"),
+            single_table_query("SELECT a, b FROM t WHERE a > 1 ORDER BY b;")
+        );
+        assert_eq!(
+            Some("select * from t"),
+            single_table_query("select * from t")
+        );
+        assert_eq!(
+            None,
+            single_table_query("SELECT * FROM t JOIN u ON t.a = u.a")
+        );
+        assert_eq!(None, single_table_query("SELECT * FROM t, u"));
+        assert_eq!(
+            None,
+            single_table_query("SELECT * FROM (SELECT a FROM t) AS s")
+        );
+        assert_eq!(
+            None,
+            single_table_query("SELECT a FROM t UNION SELECT a FROM u")
+        );
+        assert_eq!(
+            None,
+            single_table_query("SELECT a FROM t ORDER BY a LIMIT 10")
+        );
+        assert_eq!(None, single_table_query("EXEC procedure"));
+    }
+
+    #[test]
+    fn worst_case_buffer_length() {
+        assert_eq!(30, buffer_length(10, true, false));
+        assert_eq!(20, buffer_length(10, false, true));
+        assert_eq!(1, buffer_length(0, false, false));
+    }
+}
@@ -1,8 +1,4 @@
-use std::ops::{Add, Div, Mul};
-
-use anyhow::Error;
-use atoi::FromRadix10;
-use chrono::{NaiveTime, Timelike};
+use anyhow::{anyhow, Error};
 use odbc_api::buffers::{AnySlice, BufferDesc};
 use parquet::{
     basic::{LogicalType, Repetition, Type as PhysicalType},
@@ -17,18 +13,26 @@ use crate::parquet_buffer::{BufferedDataType, ParquetBuffer};
 use super::column_strategy::ColumnStrategy;
 
 /// Parse wallclock time with fractional seconds from text into time. E.g. 16:04:12.0000000
-pub fn time_from_text(repetition: Repetition, precision: u8) -> Box<dyn ColumnStrategy> {
-    Box::new(TimeFromText::new(repetition, precision))
+pub fn time_from_text(
+    name: &str,
+    repetition: Repetition,
+    precision: u8,
+) -> Box<dyn ColumnStrategy> {
+    Box::new(TimeFromText::new(name, repetition, precision))
 }
 
 struct TimeFromText {
+    /// Name of the column in the result set, to tell the user which column holds a value which can
+    /// not be parsed.
+    name: String,
     repetition: Repetition,
     precision: u8,
 }
 
 impl TimeFromText {
-    pub fn new(repetition: Repetition, precision: u8) -> Self {
+    pub fn new(name: &str, repetition: Repetition, precision: u8) -> Self {
         Self {
+            name: name.to_owned(),
             repetition,
             precision,
         }
@@ -71,9 +75,9 @@ impl ColumnStrategy for TimeFromText {
         column_view: AnySlice,
     ) -> Result<(), Error> {
         match self.precision {
-            0..=3 => write_time_ms(parquet_buffer, column_writer, column_view),
-            4..=6 => write_time_us(parquet_buffer, column_writer, column_view),
-            _ => write_time_ns(parquet_buffer, column_writer, column_view),
+            0..=3 => write_time_ms(parquet_buffer, column_writer, column_view, &self.name),
+            4..=6 => write_time_us(parquet_buffer, column_writer, column_view, &self.name),
+            _ => write_time_ns(parquet_buffer, column_writer, column_view, &self.name),
         }
     }
 }
@@ -82,108 +86,152 @@ fn write_time_ns(
     pb: &mut ParquetBuffer,
     column_writer: &mut ColumnWriter,
     column_reader: AnySlice,
+    name: &str,
 ) -> Result<(), Error> {
-    write_time_with::<Int64Type>(pb, column_writer, column_reader, 1_000_000_000, 1)
+    write_time_with::<Int64Type>(pb, column_writer, column_reader, name, 1)
 }
 
 fn write_time_us(
     pb: &mut ParquetBuffer,
     column_writer: &mut ColumnWriter,
     column_reader: AnySlice,
+    name: &str,
 ) -> Result<(), Error> {
-    write_time_with::<Int64Type>(pb, column_writer, column_reader, 1_000_000, 1_000)
+    write_time_with::<Int64Type>(pb, column_writer, column_reader, name, 1_000)
 }
 
 fn write_time_ms(
     pb: &mut ParquetBuffer,
     column_writer: &mut ColumnWriter,
     column_reader: AnySlice,
+    name: &str,
 ) -> Result<(), Error> {
-    write_time_with::<Int32Type>(pb, column_writer, column_reader, 1_000, 1_000_000)
+    write_time_with::<Int32Type>(pb, column_writer, column_reader, name, 1_000_000)
 }
 
 fn write_time_with<Pdt>(
     pb: &mut ParquetBuffer,
     column_writer: &mut ColumnWriter,
     column_reader: AnySlice,
-    s_factor: Pdt::T,
-    ns_divisor: Pdt::T,
+    name: &str,
+    ns_divisor: u64,
 ) -> Result<(), Error>
 where
     Pdt: DataType,
-    Pdt::T: BufferedDataType
-        + TryFrom<u32>
-        + Mul<Output = Pdt::T>
-        + Div<Output = Pdt::T>
-        + Add<Output = Pdt::T>
-        + Copy,
-    <Pdt::T as TryFrom<u32>>::Error: std::fmt::Debug,
+    Pdt::T: BufferedDataType + TryFrom<u64>,
+    <Pdt::T as TryFrom<u64>>::Error: std::fmt::Debug,
 {
     let from = column_reader.as_text_view().unwrap();
     let into = Pdt::get_column_writer_mut(column_writer).unwrap();
-    pb.write_optional(
+    pb.write_optional_fallible(
         into,
         from.iter().map(|field| {
-            field.map(|text| {
-                let time = parse_time(text);
-                let seconds = time.num_seconds_from_midnight();
-                let nanoseconds = time.nanosecond();
-                let seconds: Pdt::T = seconds.try_into().unwrap();
-                let nanoseconds: Pdt::T = nanoseconds.try_into().unwrap();
-                seconds * s_factor + nanoseconds as Pdt::T / ns_divisor
-            })
+            field
+                .map(|text| {
+                    let nanoseconds = parse_time(text).ok_or_else(|| {
+                        anyhow!(
+                            "Can not parse time '{}' of column '{name}'. Use `--time-as-text` to \
+                            store times as text.",
+                            String::from_utf8_lossy(text)
+                        )
+                    })?;
+                    // Even `24:00:00` in nanoseconds fits into 32 Bit once divided into
+                    // milliseconds.
+                    Ok((nanoseconds / ns_divisor).try_into().unwrap())
+                })
+                .transpose()
         }),
     )?;
     Ok(())
 }
 
-/// Parse timestamp from representation HH:MM:SS[.FFF]
-fn parse_time(bytes: &[u8]) -> NaiveTime {
-    // From radix ten also returns the number of bytes extracted. We don't care. Should always
-    // be two, for hour, min and sec.
-    let (hour, _) = u32::from_radix_10(&bytes[0..2]);
-    let (min, _) = u32::from_radix_10(&bytes[3..5]);
-    let (sec, _) = u32::from_radix_10(&bytes[6..8]);
-    // If a fractional part is present, we parse it.
-    let nano = if bytes.len() > 9 {
-        let (fraction, precision) = u32::from_radix_10(&bytes[9..]);
-        match precision {
-            0..=8 => {
-                // Pad value with `0` to represent nanoseconds
-                fraction * 10_u32.pow(9 - precision as u32)
-            }
-            9 => fraction,
-            _ => {
-                // More than nanoseconds precision. Let's just remove the additional digits at the
-                // end.
-                fraction / 10_u32.pow(precision as u32 - 9)
-            }
+/// Nanoseconds since midnight of a time in the representation `H:MM:SS[.FFF]`. The hour may have
+/// one or two digits. `24:00:00` is accepted as the end of the day, which e.g. PostgreSQL allows.
+/// `None` if the text is not a valid time.
+fn parse_time(bytes: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(bytes).ok()?.trim();
+    let (hms, fraction) = match text.split_once('.') {
+        Some((hms, fraction)) => (hms, fraction),
+        None => (text, ""),
+    };
+    let mut fields = hms.split(':');
+    let (Some(hour), Some(min), Some(sec), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return None;
+    };
+    let parse = |digits: &str, max_len: usize| -> Option<u64> {
+        if digits.is_empty()
+            || digits.len() > max_len
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
         }
-    } else {
+        digits.parse().ok()
+    };
+    let (hour, min, sec) = (parse(hour, 2)?, parse(min, 2)?, parse(sec, 2)?);
+    let nano = if fraction.is_empty() {
         0
+    } else {
+        // Digits beyond nanoseconds precision are cut off.
+        let digits = &fraction[..fraction.len().min(9)];
+        parse(digits, 9)? * 10_u64.pow(9 - digits.len() as u32)
     };
-    NaiveTime::from_hms_nano_opt(hour, min, sec, nano).unwrap()
+    // Still validate the digits which are cut off.
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) || min > 59 || sec > 59 {
+        return None;
+    }
+    if hour > 24 || (hour == 24 && (min, sec, nano) != (0, 0, 0)) {
+        return None;
+    }
+    Some(((hour * 60 + min) * 60 + sec) * 1_000_000_000 + nano)
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::NaiveTime;
+    use chrono::{NaiveTime, Timelike};
 
     use crate::query::time::parse_time;
 
+    /// Nanoseconds since midnight.
+    fn nanos(time: NaiveTime) -> Option<u64> {
+        Some(time.num_seconds_from_midnight() as u64 * 1_000_000_000 + time.nanosecond() as u64)
+    }
+
     #[test]
     fn parse_timestamps() {
         assert_eq!(
             parse_time(b"16:04:12"),
-            NaiveTime::from_hms_opt(16, 4, 12).unwrap()
+            nanos(NaiveTime::from_hms_opt(16, 4, 12).unwrap())
         );
         assert_eq!(
             parse_time(b"16:04:12.0000000"),
-            NaiveTime::from_hms_opt(16, 4, 12).unwrap()
+            nanos(NaiveTime::from_hms_opt(16, 4, 12).unwrap())
         );
         assert_eq!(
             parse_time(b"16:04:12.123456"),
-            NaiveTime::from_hms_micro_opt(16, 4, 12, 123456).unwrap()
+            nanos(NaiveTime::from_hms_micro_opt(16, 4, 12, 123456).unwrap())
         );
     }
+
+    #[test]
+    fn parse_end_of_day() {
+        assert_eq!(parse_time(b"24:00:00"), Some(86_400 * 1_000_000_000));
+        assert_eq!(parse_time(b"24:00:01"), None);
+    }
+
+    #[test]
+    fn parse_single_digit_hour() {
+        assert_eq!(
+            parse_time(b"9:05:00"),
+            nanos(NaiveTime::from_hms_opt(9, 5, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_invalid_time() {
+        assert_eq!(parse_time(b"16:04"), None);
+        assert_eq!(parse_time(b"16:60:00"), None);
+        assert_eq!(parse_time(b"ab:cd:ef"), None);
+    }
 }
@@ -6,7 +6,7 @@ use odbc_api::{
 use parquet::{
     basic::{LogicalType, Repetition},
     column::writer::ColumnWriter,
-    data_type::{DataType, Int64Type},
+    data_type::{DataType, Int64Type, Int96Type},
     schema::types::Type,
 };
 
@@ -21,6 +21,12 @@ pub fn timestamp_without_tz(repetition: Repetition, precision: u8) -> Box<dyn Co
     })
 }
 
+/// Stores timestamps using the deprecated INT96 physical type, for compatibility with legacy readers
+/// like older versions of Hive or Impala.
+pub fn timestamp_without_tz_int96(repetition: Repetition) -> Box<dyn ColumnStrategy> {
+    Box::new(TimestampToInt96 { repetition })
+}
+
 struct TimestampToI64 {
     repetition: Repetition,
     precision: TimestampPrecision,
@@ -64,3 +70,31 @@ fn write_timestamp_col(
     pb.write_optional_fallible(into, from)?;
     Ok(())
 }
+
+struct TimestampToInt96 {
+    repetition: Repetition,
+}
+
+impl ColumnStrategy for TimestampToInt96 {
+    fn parquet_type(&self, name: &str) -> Type {
+        Type::primitive_type_builder(name, Int96Type::get_physical_type())
+            .with_repetition(self.repetition)
+            .build()
+            .unwrap()
+    }
+
+    fn buffer_desc(&self) -> BufferDesc {
+        BufferDesc::Timestamp { nullable: true }
+    }
+
+    fn copy_odbc_to_parquet(
+        &self,
+        parquet_buffer: &mut ParquetBuffer,
+        column_writer: &mut ColumnWriter,
+        column_view: AnySlice,
+    ) -> Result<(), Error> {
+        let from = column_view.as_nullable_slice::<Timestamp>().unwrap();
+        let into = Int96Type::get_column_writer_mut(column_writer).unwrap();
+        parquet_buffer.write_timestamps_int96(into, from)
+    }
+}
@@ -0,0 +1,109 @@
+//! Detects values which did not fit into the fetch buffers, sized by `--column-length-limit`.
+
+use anyhow::{bail, Error};
+use log::warn;
+use odbc_api::buffers::{AnySlice, ColumnarAnyBuffer, Indicator};
+
+/// Counts the truncated values of each column, or fails on the first one if
+/// `--error-on-truncation` is set.
+pub struct TruncationCheck {
+    /// Names of the columns, in the order of the fetch buffers.
+    names: Vec<String>,
+    error_on_truncation: bool,
+    /// Number of truncated values for each column.
+    num_truncated: Vec<usize>,
+    /// Number of rows in all previous batches. Used to report the row offset of truncated values.
+    rows_before_batch: usize,
+}
+
+impl TruncationCheck {
+    pub fn new(names: Vec<String>, error_on_truncation: bool) -> Self {
+        let num_truncated = vec![0; names.len()];
+        Self {
+            names,
+            error_on_truncation,
+            num_truncated,
+            rows_before_batch: 0,
+        }
+    }
+
+    /// Inspects the first `num_rows` rows of the batch.
+    pub fn check_batch(&mut self, batch: &ColumnarAnyBuffer, num_rows: usize) -> Result<(), Error> {
+        for (index, name) in self.names.iter().enumerate() {
+            let Some((indicator, truncated_rows)) = truncated_rows(batch.column(index), num_rows)
+            else {
+                continue;
+            };
+            if self.error_on_truncation {
+                let indicator_msg = if let Indicator::Length(length) = indicator {
+                    format!("The driver indicated an actual length of {length}.")
+                } else {
+                    "Sadly the driver did not return a length indicator for the value, so you will \
+                    have to guess its actual length."
+                        .to_owned()
+                };
+                // One based, like the rows in most database tools.
+                let row = self.rows_before_batch + truncated_rows.first().copied().unwrap_or(0) + 1;
+                bail!(
+                    "A field exceeds the maximum element length of a column buffer. You can use \
+                    the `--column-length-limit` option to increase the maximum element size of \
+                    columns. {indicator_msg} The error occurred for column {name} in row {row}. \
+                    Use `--column-length-limit {name}:BYTES` to increase the limit for this column \
+                    only."
+                )
+            }
+            self.num_truncated[index] += truncated_rows.len();
+        }
+        self.rows_before_batch += num_rows;
+        Ok(())
+    }
+
+    /// Logs a warning for each column with truncated values.
+    pub fn report(&self) {
+        for (name, &num_truncated) in self.names.iter().zip(&self.num_truncated) {
+            if num_truncated != 0 {
+                warn!(
+                    "{num_truncated} values of column '{name}' have been truncated, because they \
+                    exceed the maximum element length of the column buffer. Use \
+                    `--column-length-limit {name}:BYTES` to increase the limit for this column, \
+                    or `--error-on-truncation` to abort the export instead."
+                );
+            }
+        }
+    }
+}
+
+/// Indicator of the first truncated value and the indices of all truncated values within the first
+/// `num_rows` rows of a column. `None` if no value has been truncated. Only text and binary columns
+/// can be truncated.
+///
+/// The column views do not expose the indicators of individual rows. Only if the indicators reveal
+/// at least one truncated value, we report the rows whose values fill the entire buffer, since the
+/// length of a value in the buffer is derived from its indicator.
+fn truncated_rows(column: AnySlice, num_rows: usize) -> Option<(Indicator, Vec<usize>)> {
+    let filling_buffer = |lengths: &mut dyn Iterator<Item = Option<usize>>, max_len: usize| {
+        lengths
+            .take(num_rows)
+            .enumerate()
+            .filter(|(_, length)| *length == Some(max_len))
+            .map(|(row, _)| row)
+            .collect()
+    };
+    match column {
+        AnySlice::Text(view) => view.has_truncated_values().map(|indicator| {
+            let mut lengths = (0..view.len()).map(|row| view.content_length_at(row));
+            (indicator, filling_buffer(&mut lengths, view.max_len()))
+        }),
+        AnySlice::WText(view) => view.has_truncated_values().map(|indicator| {
+            let mut lengths = (0..view.len()).map(|row| view.content_length_at(row));
+            (indicator, filling_buffer(&mut lengths, view.max_len()))
+        }),
+        AnySlice::Binary(view) => view.has_truncated_values().map(|indicator| {
+            // Binary views do not know their maximum element length, but truncated values have it.
+            let max_len = view.iter().flatten().map(<[u8]>::len).max().unwrap_or(0);
+            let mut lengths = view.iter().map(|value| value.map(<[u8]>::len));
+            (indicator, filling_buffer(&mut lengths, max_len))
+        }),
+        _ => None,
+    }
+}
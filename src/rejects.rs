@@ -0,0 +1,145 @@
+//! Writes the rows which could not be inserted into a parquet file. Specified with
+//! `--rejects-output`.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use anyhow::{Context, Error};
+use parquet::{
+    basic::Type as PhysicalType,
+    column::{
+        reader::{get_typed_column_reader, ColumnReader},
+        writer::{get_typed_column_writer_mut, ColumnWriter},
+    },
+    data_type::{
+        BoolType, ByteArrayType, DataType, DoubleType, FixedLenByteArrayType, FloatType, Int32Type,
+        Int64Type, Int96Type,
+    },
+    file::{properties::WriterProperties, reader::RowGroupReader, writer::SerializedFileWriter},
+    schema::types::{ColumnDescPtr, Type},
+};
+
+/// Copies rejected rows of the input files into an output file, with the schema of the inserted
+/// columns. Each row group with rejected rows results in one row group of the output file.
+pub struct RejectsWriter {
+    writer: SerializedFileWriter<File>,
+}
+
+impl RejectsWriter {
+    pub fn new(path: &Path, columns: &[ColumnDescPtr]) -> Result<Self, Error> {
+        let fields = columns
+            .iter()
+            .map(|col_desc| col_desc.self_type_ptr())
+            .collect();
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()?;
+        let file = File::create(path)
+            .with_context(|| format!("Could not create rejects output '{}'.", path.display()))?;
+        let writer = SerializedFileWriter::new(
+            file,
+            Arc::new(schema),
+            Arc::new(WriterProperties::builder().build()),
+        )?;
+        Ok(Self { writer })
+    }
+
+    /// Copies the `rows` of `row_group` into a new row group. Only the columns at
+    /// `column_indices` are copied. `rows` are indices within the row group in ascending order.
+    pub fn write(
+        &mut self,
+        row_group: &dyn RowGroupReader,
+        column_indices: &[usize],
+        rows: &[usize],
+    ) -> Result<(), Error> {
+        let num_rows = row_group.metadata().num_rows().try_into().unwrap();
+        let mut row_group_writer = self.writer.next_row_group()?;
+        for &column_index in column_indices {
+            let col_desc = row_group.metadata().column(column_index).column_descr_ptr();
+            let reader = row_group.get_column_reader(column_index)?;
+            let mut column = row_group_writer
+                .next_column()?
+                .expect("Rejects output must have a column for each inserted column");
+            let copy = CopyRows {
+                num_rows,
+                max_def_level: col_desc.max_def_level(),
+                rows,
+            };
+            let writer = column.untyped();
+            match col_desc.physical_type() {
+                PhysicalType::BOOLEAN => copy.copy::<BoolType>(reader, writer)?,
+                PhysicalType::INT32 => copy.copy::<Int32Type>(reader, writer)?,
+                PhysicalType::INT64 => copy.copy::<Int64Type>(reader, writer)?,
+                PhysicalType::INT96 => copy.copy::<Int96Type>(reader, writer)?,
+                PhysicalType::FLOAT => copy.copy::<FloatType>(reader, writer)?,
+                PhysicalType::DOUBLE => copy.copy::<DoubleType>(reader, writer)?,
+                PhysicalType::BYTE_ARRAY => copy.copy::<ByteArrayType>(reader, writer)?,
+                PhysicalType::FIXED_LEN_BYTE_ARRAY => {
+                    copy.copy::<FixedLenByteArrayType>(reader, writer)?
+                }
+            }
+            column.close()?;
+        }
+        row_group_writer.close()?;
+        Ok(())
+    }
+
+    pub fn close(self) -> Result<(), Error> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Selects rows of a column chunk with a flat (non repeated) column.
+struct CopyRows<'a> {
+    /// Number of rows in the row group
+    num_rows: usize,
+    /// Zero for required columns, one for optional ones.
+    max_def_level: i16,
+    rows: &'a [usize],
+}
+
+impl CopyRows<'_> {
+    fn copy<T: DataType>(
+        &self,
+        reader: ColumnReader,
+        writer: &mut ColumnWriter,
+    ) -> Result<(), Error> {
+        let mut reader = get_typed_column_reader::<T>(reader);
+        let mut values = Vec::new();
+        let mut def_levels = Vec::new();
+        let is_optional = self.max_def_level > 0;
+        reader.read_records(
+            self.num_rows,
+            is_optional.then_some(&mut def_levels),
+            None,
+            &mut values,
+        )?;
+        let writer = get_typed_column_writer_mut::<T>(writer);
+        if is_optional {
+            // Values only contain the non-NULL elements, so we need to count them in order to
+            // find the value of a row.
+            let mut selected_values = Vec::new();
+            let mut selected_def_levels = Vec::new();
+            let mut rows = self.rows.iter().peekable();
+            let mut value_index = 0;
+            for (row, &def_level) in def_levels.iter().enumerate() {
+                let is_present = def_level == self.max_def_level;
+                if rows.next_if(|&&selected| selected == row).is_some() {
+                    selected_def_levels.push(def_level);
+                    if is_present {
+                        selected_values.push(values[value_index].clone());
+                    }
+                }
+                if is_present {
+                    value_index += 1;
+                }
+            }
+            writer.write_batch(&selected_values, Some(&selected_def_levels), None)?;
+        } else {
+            let selected_values: Vec<_> =
+                self.rows.iter().map(|&row| values[row].clone()).collect();
+            writer.write_batch(&selected_values, None, None)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Enables the trace of the ODBC driver manager for a single invocation, specified with
+//! `--odbc-trace`.
+
+use std::{ffi::CString, path::Path, ptr::null_mut};
+
+use anyhow::{anyhow, bail, Error};
+use odbc_api::sys::{
+    AttrOdbcVersion, ConnectionAttribute, EnvironmentAttribute, HDbc, HEnv, Handle, HandleType,
+    SQLAllocHandle, SQLFreeHandle, SQLSetConnectAttr, SQLSetEnvAttr, SqlReturn, NTS,
+};
+
+/// `SQL_OPT_TRACE_OFF`
+const TRACE_OFF: usize = 0;
+/// `SQL_OPT_TRACE_ON`
+const TRACE_ON: usize = 1;
+
+/// Keeps the trace of the driver manager enabled, until it is dropped.
+///
+/// Tracing is a connection attribute, yet it is implemented by the driver manager and applies to
+/// all connections of the process. We set it on a connection handle of our own, which is never
+/// connected, so it does not depend on how and when the connections to the data source are opened.
+/// `odbc-api` does not offer a safe wrapper for these attributes.
+pub struct OdbcTrace {
+    environment: HEnv,
+    connection: HDbc,
+}
+
+impl OdbcTrace {
+    /// Logs all ODBC calls to the file at `path`, which is created if it does not exist.
+    pub fn enable(path: &Path) -> Result<Self, Error> {
+        let Some(path_text) = path.to_str() else {
+            bail!(
+                "The path of the ODBC trace file '{}' must be valid UTF-8.",
+                path.display()
+            )
+        };
+        let trace_file = CString::new(path_text)?;
+        let failed = || anyhow!("Could not enable the ODBC trace to '{}'.", path.display());
+
+        let mut environment: Handle = null_mut();
+        let mut connection: Handle = null_mut();
+        // Safety: Handles are only used after they have been allocated successfully, and freed by
+        // the drop handler. The trace file name outlives the call setting it, since the driver
+        // manager copies it.
+        unsafe {
+            if !succeeded(SQLAllocHandle(
+                HandleType::Env,
+                null_mut(),
+                &mut environment,
+            )) {
+                return Err(failed());
+            }
+            let mut trace = OdbcTrace {
+                environment: environment as HEnv,
+                connection: null_mut(),
+            };
+            if !succeeded(SQLSetEnvAttr(
+                trace.environment,
+                EnvironmentAttribute::OdbcVersion,
+                AttrOdbcVersion::Odbc3.into(),
+                0,
+            )) || !succeeded(SQLAllocHandle(
+                HandleType::Dbc,
+                environment,
+                &mut connection,
+            )) {
+                return Err(failed());
+            }
+            trace.connection = connection as HDbc;
+            if !succeeded(SQLSetConnectAttr(
+                trace.connection,
+                ConnectionAttribute::TraceFile,
+                trace_file.as_ptr() as _,
+                NTS as i32,
+            )) || !succeeded(SQLSetConnectAttr(
+                trace.connection,
+                ConnectionAttribute::Trace,
+                TRACE_ON as _,
+                0,
+            )) {
+                return Err(failed());
+            }
+            Ok(trace)
+        }
+    }
+}
+
+impl Drop for OdbcTrace {
+    fn drop(&mut self) {
+        // Safety: The handles have been allocated in `enable` and are not used anywhere else.
+        // There is nothing we could do about errors during cleanup, so we ignore them.
+        unsafe {
+            if !self.connection.is_null() {
+                let _ = SQLSetConnectAttr(
+                    self.connection,
+                    ConnectionAttribute::Trace,
+                    TRACE_OFF as _,
+                    0,
+                );
+                let _ = SQLFreeHandle(HandleType::Dbc, self.connection as Handle);
+            }
+            let _ = SQLFreeHandle(HandleType::Env, self.environment as Handle);
+        }
+    }
+}
+
+fn succeeded(ret: SqlReturn) -> bool {
+    ret == SqlReturn::SUCCESS || ret == SqlReturn::SUCCESS_WITH_INFO
+}
@@ -15,7 +15,8 @@ use odbc_api::{
     Connection, ConnectionOptions, Cursor, Environment, IntoParameter,
 };
 use parquet::{
-    column::writer::ColumnWriter,
+    basic::{Compression, LogicalType},
+    column::{reader::ColumnReader, writer::ColumnWriter},
     data_type::{ByteArray, FixedLenByteArray},
     file::{
         properties::WriterProperties, reader::FileReader, serialized_reader::SerializedFileReader,
@@ -23,7 +24,7 @@ use parquet::{
     },
     schema::parser::parse_message_type,
 };
-use predicates::{ord::eq, str::contains};
+use predicates::{ord::eq, prelude::PredicateBooleanExt, str::contains};
 use tempfile::{tempdir, NamedTempFile, TempDir};
 
 const MSSQL: &str = "Driver={ODBC Driver 18 for SQL Server};\
@@ -70,6 +71,100 @@ fn parquet_schema_out(file: &str) -> Assert {
     cmd.args([file]).assert().success()
 }
 
+/// Connecting still works if a login timeout is specified.
+#[test]
+fn login_timeout() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--login-timeout-sec",
+            "5",
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success();
+}
+
+/// Inspect the parquet schema resulting from a query, without fetching any rows.
+#[test]
+fn schema_as_json() {
+    // Setup table for test
+    let table_name = "SchemaAsJson";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER NOT NULL"]);
+    table.insert_rows_as_text(&[["Hello", "42"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    let expected = "[\
+        {\"odbc_name\":\"a\",\"parquet_name\":\"a\",\"sql_type\":\"Varchar { length: Some(10) }\",\
+        \"nullable\":\"Nullable\",\"parquet_type\":\"BYTE_ARRAY\",\"logical_type\":\"STRING\"},\
+        {\"odbc_name\":\"b\",\"parquet_name\":\"b\",\"sql_type\":\"Integer\",\
+        \"nullable\":\"NoNulls\",\"parquet_type\":\"INT32\",\"logical_type\":\"INTEGER(32,true)\"}\
+    ]\n";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(["schema", "--connection-string", MSSQL, "--json", &query])
+        .assert()
+        .success()
+        .stdout(eq(expected));
+}
+
+/// `schema` maps the columns like `query`, so renamed columns are reported with both names.
+#[test]
+fn schema_of_renamed_columns() {
+    // Setup table for test
+    let table_name = "SchemaOfRenamedColumns";
+    TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    let expected = "\
+        ODBC column name | parquet column name | SQL type                     | nullable | \
+        parquet type | logical type\n\
+        a                | FIRST               | Varchar { length: Some(10) } | Nullable | \
+        BYTE_ARRAY   | STRING\n\
+        b                | B                   | Integer                      | Nullable | \
+        INT32        | INTEGER(32,true)\n";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "schema",
+            "--connection-string",
+            MSSQL,
+            "--column-rename",
+            "a:first",
+            "--uppercase-columns",
+            &query,
+        ])
+        .assert()
+        .success()
+        .stdout(eq(expected));
+}
+
+/// Queries still work with connection pooling enabled.
+#[test]
+fn query_with_connection_pooling() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "--connection-pooling",
+            "driver-aware",
+            "query",
+            "--connection-string",
+            MSSQL,
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success();
+}
+
 /// Query MSSQL database, yet do not specify username and password in the connection string, but
 /// pass them as separate command line options.
 #[test]
@@ -236,6 +331,45 @@ fn parameters_in_query() {
     parquet_read_out(out_str).stdout(eq(expected));
 }
 
+/// Parameters from the command line and from the parameters file are combined.
+#[test]
+fn parameters_from_file() {
+    // Setup table for test
+    let table_name = "ParametersFromFile";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["Wrong", "5"], ["Right", "42"], ["Wrong", "42"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let parameters_path = out_dir.path().join("parameters.json");
+    std::fs::write(&parameters_path, r#"["Right"]"#).unwrap();
+
+    let query = format!("SELECT a,b FROM {table_name} WHERE b=? AND a=?");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--parameters-file",
+            parameters_path.to_str().unwrap(),
+            &query,
+            "42",
+        ])
+        .assert()
+        .success();
+
+    let expected = "\
+        {a: \"Right\", b: 42}\n\
+    ";
+    parquet_read_out(out_str).stdout(eq(expected));
+}
+
 #[test]
 fn should_error_on_truncation_utf_8() {
     // Setup table for test
@@ -265,6 +399,7 @@ fn should_error_on_truncation_utf_8() {
             MSSQL,
             "--column-length-limit",
             "5",
+            "--error-on-truncation",
             &query,
         ])
         .assert();
@@ -272,10 +407,46 @@ fn should_error_on_truncation_utf_8() {
     // For UTF-8 the MSSQL driver does return a length indicator.
     let expectation = "A field exceeds the maximum element length of a column buffer. You can use \
         the `--column-length-limit` option to increase the maximum element size of columns. The \
-        driver indicated an actual length of 10. The error occurred for column a.";
+        driver indicated an actual length of 10. The error occurred for column a in row 1.";
     assertion.failure().stderr(contains(expectation));
 }
 
+#[test]
+fn column_length_limit_for_individual_column() {
+    // Setup table for test
+    let table_name = "ColumnLengthLimitForIndividualColumn";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "VARCHAR(10)"]);
+    table.insert_rows_as_text(&[["0123456789", "x"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    // Only column `a` gets a buffer large enough for its value.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--encoding",
+            "system",
+            "--connection-string",
+            MSSQL,
+            "--column-length-limit",
+            "5",
+            "--column-length-limit",
+            "a:10",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: \"0123456789\", b: \"x\"}\n"));
+}
+
 #[test]
 fn should_error_on_truncation_utf_16() {
     // Setup table for test
@@ -305,6 +476,7 @@ fn should_error_on_truncation_utf_16() {
             MSSQL,
             "--column-length-limit",
             "5",
+            "--error-on-truncation",
             &query,
         ])
         .assert();
@@ -313,7 +485,7 @@ fn should_error_on_truncation_utf_16() {
     let expectation = "A field exceeds the maximum element length of a column buffer. You can use \
         the `--column-length-limit` option to increase the maximum element size of columns. Sadly \
         the driver did not return a length indicator for the value, so you will have to guess its \
-        actual length. The error occurred for column a.";
+        actual length. The error occurred for column a in row 1.";
     assertion.failure().stderr(contains(expectation));
 }
 
@@ -345,6 +517,7 @@ fn should_error_on_truncation_for_sequential_fetch() {
             "--sequential-fetching",
             "--column-length-limit",
             "5",
+            "--error-on-truncation",
             &query,
         ])
         .assert();
@@ -353,14 +526,126 @@ fn should_error_on_truncation_for_sequential_fetch() {
     let expectation = "A field exceeds the maximum element length of a column buffer. You can \
         use the `--column-length-limit` option to increase the maximum element size of columns. \
         Sadly the driver did not return a length indicator for the value, so you will have to \
-        guess its actual length. The error occurred for column a.";
+        guess its actual length. The error occurred for column a in row 1.";
     #[cfg(not(target_os = "windows"))]
     let expectation = "A field exceeds the maximum element length of a column buffer. You can \
         use the `--column-length-limit` option to increase the maximum element size of columns. \
-        The driver indicated an actual length of 10. The error occurred for column a.";
+        The driver indicated an actual length of 10. The error occurred for column a in row 1.";
     assertion.failure().stderr(contains(expectation));
 }
 
+#[test]
+fn should_error_on_null_in_required_column() {
+    // Setup table for test
+    let table_name = "ShouldErrorOnNullInRequiredColumn";
+    let mut table = TableMssql::new(table_name, &["INTEGER", "INTEGER"]);
+    table.insert_rows_as_text(&[[Some("1"), Some("1")], [Some("2"), None]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY id");
+
+    // Column a does not contain NULL, so it passes the check.
+    let assertion = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--require-non-null",
+            "a",
+            "--require-non-null",
+            "b",
+            &query,
+        ])
+        .assert();
+
+    assertion
+        .failure()
+        .stderr(contains("Column b contains NULL in row 2"));
+}
+
+#[test]
+fn print_null_count_summary() {
+    // Setup table for test
+    let table_name = "PrintNullCountSummary";
+    let mut table = TableMssql::new(table_name, &["INTEGER", "VARCHAR(10)"]);
+    table.insert_rows_as_text(&[[Some("1"), None], [Some("2"), None]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY id");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--summary",
+            &query,
+        ])
+        .assert()
+        .success()
+        .stderr(contains(
+            "Exported 2 rows.\n\
+            column | nulls |  share\n\
+            a      |     0 |   0.0%\n\
+            b      |     2 | 100.0%\n",
+        ));
+}
+
+#[test]
+fn execute_query_for_each_row_of_param_parquet() {
+    // Setup table for test
+    let table_name = "ExecuteQueryForEachRowOfParamParquet";
+    let mut table = TableMssql::new(table_name, &["INTEGER", "VARCHAR(10)"]);
+    table.insert_rows_as_text(&[
+        [Some("1"), Some("one")],
+        [Some("2"), Some("two")],
+        [Some("3"), Some("three")],
+    ]);
+
+    // Parameters of the two executions
+    let message_type = "
+        message schema {
+            REQUIRED INT32 a;
+        }
+    ";
+    let params = TmpParquetFile::with_1_dim(message_type, &[Some(3i32), Some(1)]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name} WHERE a = ?");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--param-parquet",
+            params.path_as_str(),
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 3, b: \"three\"}\n{a: 1, b: \"one\"}\n"));
+}
+
 #[test]
 fn should_allow_specifying_explicit_compression_level() {
     // Setup table for test
@@ -529,6 +814,89 @@ fn query_decimals() {
     ));
 }
 
+#[test]
+fn query_decimals_rescaled() {
+    // Setup table for test
+    let table_name = "QueryDecimalsRescaled";
+    let mut table = TableMssql::new(
+        table_name,
+        &["DECIMAL(3,2) NOT NULL", "DECIMAL(6,3) NOT NULL"],
+    );
+    table.insert_rows_as_text(&[["1.23", "-12.300"], ["-0.05", "0.100"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a,b FROM {table_name} ORDER BY id;");
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--decimal-rescale",
+            "a:4",
+            "--decimal-rescale",
+            "b:1",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    let expected_values = "{a: 1.2300, b: -12.3}\n{a: -0.0500, b: 0.1}\n";
+    parquet_read_out(out_str).stdout(eq(expected_values));
+    parquet_schema_out(out_str).stdout(contains(
+        "message schema {\n  REQUIRED INT32 a (DECIMAL(5,4));\n  \
+                REQUIRED INT32 b (DECIMAL(4,1));\n\
+            }",
+    ));
+}
+
+#[test]
+fn decimal_rescale_losing_digits() {
+    // Setup table for test
+    let table_name = "DecimalRescaleLosingDigits";
+    let mut table = TableMssql::new(table_name, &["DECIMAL(5,3) NOT NULL"]);
+    table.insert_rows_as_text(&[["1.990"], ["-1.995"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id;");
+    let args = [
+        "query",
+        out_str,
+        "--connection-string",
+        MSSQL,
+        "--decimal-rescale",
+        "a:2",
+        &query,
+    ];
+
+    // Trailing zeroes may be cut off, but other digits only with `--decimal-rescale-truncate`
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(args)
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Value -1.995 of column 'a' can not be rescaled to a scale of 2 without losing \
+            significant digits.",
+        ));
+
+    // Truncated towards zero, rather than rounded
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(args)
+        .arg("--decimal-rescale-truncate")
+        .assert()
+        .success();
+    parquet_read_out(out_str).stdout(eq("{a: 1.99}\n{a: -1.99}\n"));
+}
+
 /// Produce output for downstream artefacts like polars which lack support for decimal. In effect
 /// logical type decimal should not show up in the output
 #[test]
@@ -770,19 +1138,18 @@ fn query_numeric_33_3() {
 }
 
 #[test]
-fn query_timestamp_with_timezone_mssql() {
+fn query_numeric_50_2_postgres() {
     // Setup table for test
-    let table_name = "QueryTimestampWithTimezone";
+    let table_name = "QueryNumeric50_2";
     let conn = ENV
-        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .connect_with_connection_string(POSTGRES, ConnectionOptions::default())
         .unwrap();
-    // ODBC data type: SqlDataType(-155), column_size: 34, decimal_digits: 7
-    setup_empty_table_mssql(&conn, table_name, &["DATETIMEOFFSET"]).unwrap();
+    setup_empty_table_pg(&conn, table_name, &["NUMERIC(50,2) NOT NULL"]).unwrap();
     let insert = format!(
         "INSERT INTO {table_name}
         (a)
         VALUES
-        ('2022-09-07 16:04:12.1234567 +02:00');"
+        (-123456789012345678901234567890123456789012345678.91);"
     );
     conn.execute(&insert, (), None).unwrap();
     // A temporary directory, to be removed at the end of the test.
@@ -801,27 +1168,73 @@ fn query_timestamp_with_timezone_mssql() {
             "query",
             out_str,
             "--connection-string",
-            MSSQL,
+            POSTGRES,
             &query,
         ])
         .assert()
         .success();
 
-    let expected_values = "{a: 1662559452123456700}\n";
+    let expected_values = "{a: -123456789012345678901234567890123456789012345678.91}\n";
     parquet_read_out(out_str).stdout(eq(expected_values));
 
-    parquet_schema_out(out_str).stdout(contains("OPTIONAL INT64 a (TIMESTAMP(NANOS,true));"));
+    parquet_schema_out(out_str).stdout(contains(
+        "{\n  REQUIRED FIXED_LEN_BYTE_ARRAY (21) a (DECIMAL(50,2));\n}",
+    ));
 }
 
 #[test]
-fn query_timestamp_mssql_precision_7() {
+fn query_timestamp_with_timezone_mssql() {
     // Setup table for test
-    let table_name = "QueryTimestampMssqlPrecision7";
-    let mut table = TableMssql::new(table_name, &["DATETIME2(7)"]);
-    table.insert_rows_as_text(&[["2022-09-07 16:04:12.1234567"]]);
-    // A temporary directory, to be removed at the end of the test.
-    let out_dir = tempdir().unwrap();
-    // The name of the output parquet file we are going to write. Since it is in a temporary
+    let table_name = "QueryTimestampWithTimezone";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    // ODBC data type: SqlDataType(-155), column_size: 34, decimal_digits: 7
+    setup_empty_table_mssql(&conn, table_name, &["DATETIMEOFFSET"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name}
+        (a)
+        VALUES
+        ('2022-09-07 16:04:12.1234567 +02:00');"
+    );
+    conn.execute(&insert, (), None).unwrap();
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    // The name of the output parquet file we are going to write. Since it is in a temporary
+    // directory it will not outlive the end of the test.
+    let out_path = out_dir.path().join("out.par");
+    // We need to pass the output path as a string argument.
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    let expected_values = "{a: 1662559452123456700}\n";
+    parquet_read_out(out_str).stdout(eq(expected_values));
+
+    parquet_schema_out(out_str).stdout(contains("OPTIONAL INT64 a (TIMESTAMP(NANOS,true));"));
+}
+
+#[test]
+fn query_timestamp_mssql_precision_7() {
+    // Setup table for test
+    let table_name = "QueryTimestampMssqlPrecision7";
+    let mut table = TableMssql::new(table_name, &["DATETIME2(7)"]);
+    table.insert_rows_as_text(&[["2022-09-07 16:04:12.1234567"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    // The name of the output parquet file we are going to write. Since it is in a temporary
     // directory it will not outlive the end of the test.
     let out_path = out_dir.path().join("out.par");
     // We need to pass the output path as a string argument.
@@ -847,6 +1260,39 @@ fn query_timestamp_mssql_precision_7() {
     parquet_schema_out(out_str).stdout(contains("OPTIONAL INT64 a (TIMESTAMP(NANOS,false));"));
 }
 
+/// Legacy readers may require timestamps to be stored as INT96
+#[test]
+fn query_timestamp_as_int96() {
+    // Setup table for test
+    let table_name = "QueryTimestampAsInt96";
+    let mut table = TableMssql::new(table_name, &["DATETIME2(7)"]);
+    table.insert_rows_as_text(&[["2022-09-07 16:04:12.1234567"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    // The name of the output parquet file we are going to write. Since it is in a temporary
+    // directory it will not outlive the end of the test.
+    let out_path = out_dir.path().join("out.par");
+    // We need to pass the output path as a string argument.
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--timestamp-int96",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_schema_out(out_str).stdout(contains("OPTIONAL INT96 a;"));
+}
+
 #[test]
 fn query_unsigned_tinyint() {
     // Setup table for test
@@ -1117,6 +1563,74 @@ fn query_time_0_mssql() {
     parquet_schema_out(out_str).stdout(contains("OPTIONAL INT32 a (TIME(MILLIS,false));"));
 }
 
+#[test]
+fn query_time_postgres() {
+    // Setup table for test
+    let table_name = "QueryTimePostgres";
+    let conn = ENV
+        .connect_with_connection_string(POSTGRES, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_pg(&conn, table_name, &["TIME(3)"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name}
+        (a)
+        VALUES
+        ('16:04:12.123');"
+    );
+    conn.execute(&insert, (), None).unwrap();
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    // The name of the output parquet file we are going to write. Since it is in a temporary
+    // directory it will not outlive the end of the test.
+    let out_path = out_dir.path().join("out.par");
+    // We need to pass the output path as a string argument.
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_schema_out(out_str).stdout(contains("OPTIONAL INT32 a (TIME(MILLIS,false));"));
+}
+
+#[test]
+fn query_time_as_text_mssql() {
+    // Setup table for test
+    let table_name = "QueryTimeAsText";
+    let mut table = TableMssql::new(table_name, &["TIME(0)"]);
+    table.insert_rows_as_text(&[["16:04:12"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--time-as-text",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: \"16:04:12\"}\n"));
+}
+
 #[test]
 fn query_timestamp_with_timezone_postgres() {
     // Setup table for test
@@ -1499,6 +2013,109 @@ fn emit_file_despite_no_empty_file_set() {
     parquet_read_out(out_str).stdout(eq(expected));
 }
 
+/// Only the first rows should be written if `--limit` is specified. Since the batch size is smaller
+/// than the limit and each row group is written into its own file, the last batch is truncated.
+#[test]
+fn limit_number_of_rows() {
+    // Setup table for test
+    let table_name = "LimitNumberOfRows";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"], ["3"], ["4"], ["5"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "2",
+            "--row-groups-per-file",
+            "1",
+            "--limit",
+            "3",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_dir.path().join("out_01.par").to_str().unwrap())
+        .stdout(eq("{a: 1}\n{a: 2}\n"));
+    parquet_read_out(out_dir.path().join("out_02.par").to_str().unwrap()).stdout(eq("{a: 3}\n"));
+    assert!(!out_dir.path().join("out_03.par").exists());
+}
+
+/// A `_SUCCESS` marker file is created next to the parts of a split output, unless
+/// `--no-success-file` is specified.
+#[test]
+fn success_file_for_split_output() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let success_path = out_dir.path().join("_SUCCESS");
+
+    let run = |out_name: &str, extra_args: &[&str]| {
+        let out_path = out_dir.path().join(out_name);
+        let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+        Command::cargo_bin("odbc2parquet")
+            .unwrap()
+            .args([
+                "-vvvv",
+                "query",
+                "--connection-string",
+                MSSQL,
+                "--row-groups-per-file",
+                "1",
+                out_str,
+                "SELECT 42 as a",
+            ])
+            .args(extra_args)
+            .assert()
+            .success();
+    };
+
+    run("without_marker.par", &["--no-success-file"]);
+    assert!(!success_path.exists());
+
+    run("with_marker.par", &[]);
+    assert_eq!(0, std::fs::metadata(&success_path).unwrap().len());
+}
+
+/// `--limit 0` in combination with `--no-empty-file` should not create any file.
+#[test]
+fn limit_zero_and_no_empty_file() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--limit",
+            "0",
+            "--no-empty-file",
+            out_str,
+            "SELECT 42 as a",
+        ])
+        .assert()
+        .success();
+
+    assert!(!out_path.exists());
+}
+
 /// Should read query from standard input if "-" is provided as query text.
 #[test]
 fn read_query_from_stdin() {
@@ -1544,6 +2161,70 @@ fn read_query_from_stdin() {
     parquet_read_out(out_str).stdout(eq(expected_values));
 }
 
+#[test]
+fn read_query_and_parameters_from_stdin() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Tempfile path must be utf8");
+
+    // Parameters follow the query after the delimiter line
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(["query", out_str, "--connection-string", MSSQL, "-"])
+        .write_stdin("SELECT CAST(? AS INT) AS a, CAST(? AS VARCHAR(10)) AS b\n---\n42\nsecret\n")
+        .assert()
+        .success();
+    parquet_read_out(out_str).stdout(eq("{a: 42, b: \"secret\"}\n"));
+
+    // The number of parameters must match the placeholders
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(["query", out_str, "--connection-string", MSSQL, "-"])
+        .write_stdin("SELECT CAST(? AS INT) AS a\n---\n42\n43\n")
+        .assert()
+        .failure()
+        .stderr(contains("The query contains 1 placeholders, but 2 parameters"));
+}
+
+/// Should read query from a file if its path is prefixed with "@".
+#[test]
+fn read_query_from_file() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let query_path = out_dir.path().join("query.sql");
+    std::fs::write(&query_path, "SELECT\n    42 AS a").unwrap();
+    let query_arg = format!("@{}", query_path.to_str().unwrap());
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Tempfile path must be utf8");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(["query", out_str, "--connection-string", MSSQL, &query_arg])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 42}\n"));
+}
+
+#[test]
+fn read_query_from_missing_file() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "-",
+            "--connection-string",
+            MSSQL,
+            "@does_not_exist.sql",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Could not read query from file 'does_not_exist.sql'.",
+        ));
+}
+
 #[test]
 fn split_files_on_num_row_groups() {
     // Setup table for test
@@ -1589,34 +2270,174 @@ fn split_files_on_num_row_groups() {
     parquet_read_out(out_dir.path().join("out_03.par").to_str().unwrap());
 }
 
-/// Verify naming of the files is with successive numbers starting from 1 to 3 with split files and
-/// `--no-empty-file` flag set. This was messed up, with a refactoring once and file names started
-/// with `2` instead of `1``.
 #[test]
-fn file_name_no_empty_file_and_split_files() {
+fn split_files_with_output_template() {
     // Setup table for test
-    let table_name = "FileNameNoEmptyFileAndSplitFiles";
-    let conn = ENV
-        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
-        .unwrap();
-    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
-    let insert = format!("INSERT INTO {table_name} (A) VALUES(1),(2),(3)");
-    conn.execute(&insert, (), None).unwrap();
+    let table_name = "SplitFilesWithOutputTemplate";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"]]);
 
     // A temporary directory, to be removed at the end of the test.
     let out_dir = tempdir().unwrap();
-    // The name of the output parquet file we are going to write. Since it is in a temporary
-    // directory it will not outlive the end of the test.
     let out_path = out_dir.path().join("out.par");
-    // We need to pass the output path as a string argument.
     let out_str = out_path.to_str().expect("Temporary file path must be utf8");
-
     let query = format!("SELECT a FROM {table_name}");
 
     Command::cargo_bin("odbc2parquet")
         .unwrap()
         .args([
-            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "1",
+            "--row-groups-per-file",
+            "1",
+            "--output-template",
+            "export_{index:04}.par",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_dir.path().join("export_0001.par").to_str().unwrap());
+    parquet_read_out(out_dir.path().join("export_0002.par").to_str().unwrap());
+}
+
+#[test]
+fn output_template_without_index() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "out.par",
+            "--connection-string",
+            MSSQL,
+            "--row-groups-per-file",
+            "1",
+            "--output-template",
+            "export_{date}.par",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "The output template must contain an '{index}' placeholder",
+        ));
+}
+
+/// Write one directory for each distinct value of the partition column. The partition column itself
+/// is not part of the parquet files.
+#[test]
+fn partition_by_column() {
+    // Setup table for test
+    let table_name = "PartitionByColumn";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["VARCHAR(10)", "INTEGER"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name} (A, B) VALUES ('x', 1), ('a/b', 2), (NULL, 3), ('x', 4)"
+    );
+    conn.execute(&insert, (), None).unwrap();
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY id");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--partition-by",
+            "a",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_path.join("a=x/part.par").to_str().unwrap())
+        .stdout(eq("{b: 1}\n{b: 4}\n"));
+    parquet_read_out(out_path.join("a=a%2Fb/part.par").to_str().unwrap())
+        .stdout(eq("{b: 2}\n"));
+    parquet_read_out(
+        out_path
+            .join("a=__HIVE_DEFAULT_PARTITION__/part.par")
+            .to_str()
+            .unwrap(),
+    )
+    .stdout(eq("{b: 3}\n"));
+}
+
+/// Writers of all partitions are kept open, so the number of distinct partition values is limited.
+#[test]
+fn partition_by_exceeds_max_open_partitions() {
+    // Setup table for test
+    let table_name = "PartitionByExceedsMaxOpenPartitions";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["x", "1"], ["y", "2"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY id");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--partition-by",
+            "a",
+            "--max-open-partitions",
+            "1",
+            &query,
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Partition 'a=y' would exceed the limit of 1 open partitions."));
+}
+
+/// Verify naming of the files is with successive numbers starting from 1 to 3 with split files and
+/// `--no-empty-file` flag set. This was messed up, with a refactoring once and file names started
+/// with `2` instead of `1``.
+#[test]
+fn file_name_no_empty_file_and_split_files() {
+    // Setup table for test
+    let table_name = "FileNameNoEmptyFileAndSplitFiles";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+    let insert = format!("INSERT INTO {table_name} (A) VALUES(1),(2),(3)");
+    conn.execute(&insert, (), None).unwrap();
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    // The name of the output parquet file we are going to write. Since it is in a temporary
+    // directory it will not outlive the end of the test.
+    let out_path = out_dir.path().join("out.par");
+    // We need to pass the output path as a string argument.
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name}");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
             "query",
             out_str,
             "--connection-string",
@@ -1683,6 +2504,47 @@ fn split_files_on_size_limit() {
     parquet_read_out(out_dir.path().join("out_03.par").to_str().unwrap());
 }
 
+#[test]
+fn split_files_below_max_file_size() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    // Enough rows to exceed the maximum file size with a single batch
+    let query = "SELECT TOP 2000 CAST(ROW_NUMBER() OVER (ORDER BY (SELECT NULL)) AS INTEGER) AS a \
+        FROM sys.all_columns";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--column-compression-default",
+            "uncompressed",
+            "--max-file-size",
+            "4KiB",
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Each batch is split across several files, none of which exceeds the limit.
+    let mut num_files = 0;
+    let mut num_rows = 0;
+    for entry in std::fs::read_dir(out_dir.path()).unwrap() {
+        let path = entry.unwrap().path();
+        assert!(path.metadata().unwrap().len() <= 4096);
+        let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+        num_rows += reader.metadata().file_metadata().num_rows();
+        num_files += 1;
+    }
+    assert!(num_files > 1);
+    assert_eq!(2000, num_rows);
+}
+
 #[test]
 fn configurable_suffix_length() {
     // Setup table for test
@@ -2034,7 +2896,7 @@ fn nchar_not_truncated() {
 
     Command::cargo_bin("odbc2parquet")
         .unwrap()
-        .args(&[
+        .args([
             "-vvvv",
             "query",
             "--encoding",
@@ -2081,7 +2943,7 @@ fn system_encoding() {
 
     Command::cargo_bin("odbc2parquet")
         .unwrap()
-        .args(&[
+        .args([
             "-vvvv",
             "query",
             "--encoding",
@@ -2229,6 +3091,93 @@ pub fn insert_32_bit_integer() {
     assert_eq!("42\n5\n1", actual);
 }
 
+/// Row groups larger than the batch size are inserted in several chunks.
+#[test]
+pub fn insert_row_group_in_chunks() {
+    let table_name = "InsertRowGroupInChunks";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(42i32), None, Some(1)]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "2",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY Id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+    assert_eq!("42\nNULL\n1", actual);
+}
+
+/// Create the table from the parquet schema, if it does not exist yet.
+#[test]
+pub fn insert_and_create_table() {
+    let table_name = "InsertAndCreateTable";
+    let conn = ENV
+        .connect_with_connection_string(POSTGRES, ConnectionOptions::default())
+        .unwrap();
+    conn.execute(&format!("DROP TABLE IF EXISTS {table_name}"), (), None)
+        .unwrap();
+    // Prepare file
+    let message_type = "
+        message schema {
+            REQUIRED INT32 a;
+            OPTIONAL BYTE_ARRAY b (UTF8);
+        }
+    ";
+    let text: ByteArray = "Hello".into();
+    let input = TmpParquetFile::with_2_dim(
+        message_type,
+        &[Some(42i32), Some(5)],
+        &[Some(text), None],
+    );
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "insert",
+            "--connection-string",
+            POSTGRES,
+            "--create-table",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY a");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+    assert_eq!("5,NULL\n42,Hello", actual);
+}
+
 #[test]
 pub fn insert_optional_32_bit_integer() {
     let table_name = "InsertOptional32BitInteger";
@@ -2269,6 +3218,49 @@ pub fn insert_optional_32_bit_integer() {
     assert_eq!("42\nNULL\n1", actual);
 }
 
+/// Parquet column names may carry a prefix, which is not part of the name of the table column.
+#[test]
+pub fn insert_with_stripped_column_prefix() {
+    let table_name = "InsertWithStrippedColumnPrefix";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 src_a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(42i32), Some(1)]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--strip-prefix",
+            "src_",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY Id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("42\n1", actual);
+}
+
 #[test]
 pub fn insert_64_bit_integer() {
     let table_name = "Insert64BitInteger";
@@ -4008,19 +5000,139 @@ fn write_statistics_for_text_columns() {
     );
 }
 
-/// This did not work in earlier versions there we set the batch write size of the parquet writer to
-/// the ODBC batch size.
+/// Statistics are only omitted for the columns specified with `--no-statistics`.
 #[test]
-#[ignore = "Takes too long to run"]
-fn query_4097_bits() {
-    let num_bits = 4097;
-
+fn no_statistics_for_specified_column() {
     // Setup table for test
-    let table_name = "Query4097Bits";
-    let conn = ENV
-        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
-        .unwrap();
-    setup_empty_table_mssql(&conn, table_name, &["BIT"]).unwrap();
+    let table_name = "NoStatisticsForSpecifiedColumn";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["aaa", "1"], ["zzz", "2"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--no-statistics",
+            "a",
+            "-", // Use `-` to explicitly write to stdout
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let row_group = reader.metadata().row_group(0);
+    assert!(row_group.column(0).statistics().is_none());
+    assert!(row_group.column(1).statistics().is_some());
+}
+
+/// `--no-column-index` omits the column index, but keeps the offset index and the statistics of
+/// the column chunks.
+#[test]
+fn no_column_index() {
+    // Setup table for test
+    let table_name = "NoColumnIndex";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["aaa", "1"], ["zzz", "2"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--no-column-index",
+            "-", // Use `-` to explicitly write to stdout
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let row_group = reader.metadata().row_group(0);
+    for column in row_group.columns() {
+        assert!(column.column_index_offset().is_none());
+        assert!(column.offset_index_offset().is_some());
+        assert!(column.statistics().is_some());
+    }
+}
+
+/// The compression of individual columns can be overwritten.
+#[test]
+fn column_compression_overrides_default() {
+    // Setup table for test
+    let table_name = "ColumnCompressionOverridesDefault";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["aaa", "1"], ["zzz", "2"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-compression-default",
+            "zstd",
+            "--column-compression",
+            "a:uncompressed",
+            "-", // Use `-` to explicitly write to stdout
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let row_group = reader.metadata().row_group(0);
+    assert_eq!(Compression::UNCOMPRESSED, row_group.column(0).compression());
+    assert!(matches!(row_group.column(1).compression(), Compression::ZSTD(_)));
+}
+
+/// Unknown codecs are reported together with the valid ones.
+#[test]
+fn unknown_column_compression_codec() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            "FakeConnectionString",
+            "--column-compression",
+            "a:jpeg",
+            "-",
+            "SELECT a FROM FakeTableName",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Sorry, I do not know a compression codec called 'jpeg'. Valid codecs are: \
+            uncompressed, gzip, lz4, lz0, zstd, snappy, brotli.",
+        ));
+}
+
+/// This did not work in earlier versions there we set the batch write size of the parquet writer to
+/// the ODBC batch size.
+#[test]
+#[ignore = "Takes too long to run"]
+fn query_4097_bits() {
+    let num_bits = 4097;
+
+    // Setup table for test
+    let table_name = "Query4097Bits";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["BIT"]).unwrap();
 
     // Insert 4097 bits "false" (default constructed) into the table
     let insert = format!(
@@ -4124,7 +5236,7 @@ fn write_values_to_file(message_type: &str, input_path: &Path, columns: &[&dyn C
 
     for col in columns {
         let mut col_writer = row_group_writer.next_column().unwrap().unwrap();
-        col.write(&mut col_writer.untyped());
+        col.write(col_writer.untyped());
         col_writer.close().unwrap();
     }
     
@@ -4151,9 +5263,9 @@ impl<T> ColumnDataImpl<T> {
             .map(|opt| if opt.is_some() { 1i16 } else { 0 })
             .collect::<Vec<_>>();
         let values = input
-            .into_iter()
+            .iter()
+            .flatten()
             .cloned()
-            .filter_map(|opt| opt)
             .collect::<Vec<_>>();
 
         ColumnDataImpl {
@@ -4414,3 +5526,2959 @@ impl_write_to_cw!(f32, FloatColumnWriter);
 impl_write_to_cw!(f64, DoubleColumnWriter);
 impl_write_to_cw!(ByteArray, ByteArrayColumnWriter);
 impl_write_to_cw!(FixedLenByteArray, FixedLenByteArrayColumnWriter);
+
+#[test]
+fn sanitize_column_names() {
+    let query = "SELECT 1 AS [first name], 2 AS [first.name], 3 AS [SUM(a)]";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--sanitize-column-names",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    let names: Vec<_> = file_metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_owned())
+        .collect();
+    assert_eq!(["first_name", "first_name_2", "SUM_a_"].as_slice(), names);
+    let original_names = file_metadata
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .find(|kv| kv.key == "odbc2parquet.original_column_names")
+        .unwrap();
+    assert_eq!(
+        r#"{"first_name":"first name","first_name_2":"first.name","SUM_a_":"SUM(a)"}"#,
+        original_names.value.as_deref().unwrap()
+    );
+}
+
+#[test]
+fn embed_query_metadata() {
+    let query = "SELECT 42 AS a";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--embed-query-metadata",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let key_value_metadata = reader
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap();
+    let value = |key: &str| {
+        key_value_metadata
+            .iter()
+            .find(|kv| kv.key == key)
+            .and_then(|kv| kv.value.clone())
+    };
+    assert_eq!(Some(query.to_owned()), value("odbc2parquet.query"));
+    assert!(value("odbc2parquet.exported_at").is_some());
+}
+
+/// A dry run should validate the query, but not create any file.
+#[test]
+fn dry_run_does_not_write_file() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--dry-run",
+            out_str,
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success()
+        .stderr(contains("Dry run."));
+    assert!(!out_path.exists());
+
+    // Invalid queries are still reported as errors
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--dry-run",
+            out_str,
+            "SELECT a FROM TableWhichDoesNotExistForDryRun",
+        ])
+        .assert()
+        .failure();
+    assert!(!out_path.exists());
+}
+
+/// `--explain` prints the plan of the query instead of exporting it.
+#[test]
+fn explain_query_plan() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--explain",
+            out_str,
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("StmtText").and(contains("Constant Scan")));
+    assert!(!out_path.exists());
+}
+
+/// Enabling retries should not change the output of a query which succeeds right away.
+#[test]
+fn query_with_retries() {
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--max-retries",
+            "3",
+            "--retry-backoff-ms",
+            "10",
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success()
+        .stderr(contains("Fetching sequentially"));
+
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    assert_eq!(1, reader.metadata().file_metadata().num_rows());
+}
+
+/// GUIDs are stored as 16 bytes with logical type UUID, in the byte order of their text
+/// representation.
+#[test]
+fn query_guid_as_uuid() {
+    // Setup table for test
+    let table_name = "QueryGuidAsUuid";
+    let mut table = TableMssql::new(table_name, &["UNIQUEIDENTIFIER"]);
+    table.insert_rows_as_text(&[["00112233-4455-6677-8899-AABBCCDDEEFF"]]);
+    let query = format!("SELECT a FROM {table_name}");
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "-", // Use `-` to explicitly write to stdout
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let column = reader.metadata().file_metadata().schema_descr().column(0);
+    assert_eq!(Some(LogicalType::Uuid), column.logical_type());
+    let row_group = reader.get_row_group(0).unwrap();
+    let ColumnReader::FixedLenByteArrayColumnReader(mut column_reader) =
+        row_group.get_column_reader(0).unwrap()
+    else {
+        panic!("GUID must be stored as FIXED_LEN_BYTE_ARRAY")
+    };
+    let mut values = Vec::new();
+    let mut def_levels = Vec::new();
+    column_reader
+        .read_records(1, Some(&mut def_levels), None, &mut values)
+        .unwrap();
+    assert_eq!(
+        [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+            0xff
+        ],
+        values[0].data()
+    );
+}
+
+#[test]
+fn query_guid_as_text() {
+    // Setup table for test
+    let table_name = "QueryGuidAsText";
+    let mut table = TableMssql::new(table_name, &["UNIQUEIDENTIFIER"]);
+    table.insert_rows_as_text(&[["00112233-4455-6677-8899-AABBCCDDEEFF"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name}");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--guid-as-text",
+            out_str,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: \"00112233-4455-6677-8899-AABBCCDDEEFF\"}\n"));
+}
+
+#[test]
+fn count_rows() {
+    // Setup table for test
+    let table_name = "CountRows";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"], ["3"]]);
+    let query = format!("SELECT a FROM {table_name} WHERE a > ?");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "count",
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "2",
+            &query,
+            "0",
+        ])
+        .assert()
+        .success()
+        .stdout(eq("3\n"));
+}
+
+#[test]
+fn parquet_version_1() {
+    let query = "SELECT 42 AS a";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--parquet-version",
+            "1.0",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    assert_eq!(1, reader.metadata().file_metadata().version());
+}
+
+#[test]
+fn parquet_version_1_rejects_delta_encoding() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--parquet-version",
+            "1.0",
+            "--parquet-column-encoding",
+            "a:delta-binary-packed",
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Encoding DELTA_BINARY_PACKED for column 'a' requires parquet version 2.0",
+        ));
+}
+
+/// Rows already in the table are removed before inserting.
+#[test]
+pub fn insert_with_truncate_table() {
+    let table_name = "InsertWithTruncateTable";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+    conn.execute(&format!("INSERT INTO {table_name} (a) VALUES (1)"), (), None)
+        .unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(42i32)]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--truncate-table",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY Id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("42", actual);
+}
+
+#[test]
+pub fn truncate_non_existing_table() {
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(42i32)]);
+    let input_path = input.path_as_str();
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--truncate-table",
+            input_path,
+            "TruncateNonExistingTable",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Can not truncate table 'TruncateNonExistingTable', since it does not exist.",
+        ));
+}
+
+/// If inserting a later chunk fails, the earlier chunks must not be committed either.
+#[test]
+pub fn insert_in_transaction_rolls_back_on_error() {
+    let table_name = "InsertInTransactionRollsBackOnError";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER NOT NULL"]).unwrap();
+
+    // Prepare file. The second row violates the `NOT NULL` constraint.
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(42i32), None]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table, one row at a time
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--transaction",
+            "--batch-size-row",
+            "1",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .failure();
+
+    // Query table and check for expected result
+    let query = format!("SELECT COUNT(*) FROM {table_name}");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("0", actual);
+}
+
+#[test]
+fn query_timeout() {
+    let query = "WAITFOR DELAY '00:00:05'; SELECT 42 AS a";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--query-timeout-sec",
+            "1",
+            "-",
+            query,
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn query_with_packet_size() {
+    let query = "SELECT 42 AS a";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--packet-size-kb",
+            "8",
+            "-",
+            query,
+        ])
+        .assert()
+        .success();
+}
+
+#[test]
+fn column_select() {
+    let query = "SELECT 1 AS a, 2 AS b, 3 AS c";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-select",
+            "c",
+            "--column-select",
+            "a",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let names: Vec<_> = reader
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_owned())
+        .collect();
+    assert_eq!(["a", "c"].as_slice(), names);
+}
+
+#[test]
+fn column_select_unknown_column() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-select",
+            "d",
+            "-",
+            "SELECT 1 AS a, 2 AS b",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Selected column 'd' is not part of the result set. Available columns are: a, b",
+        ));
+}
+
+#[test]
+fn parallel_export_with_split_key() {
+    // Setup table for test
+    let table_name = "ParallelExportWithSplitKey";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"], ["3"], ["4"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name}");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--parallel",
+            "2",
+            "--split-key",
+            "a",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Each worker writes its own range of keys
+    parquet_read_out(out_dir.path().join("out_01.par").to_str().unwrap())
+        .stdout(eq("{a: 1}\n{a: 2}\n"));
+    parquet_read_out(out_dir.path().join("out_02.par").to_str().unwrap())
+        .stdout(eq("{a: 3}\n{a: 4}\n"));
+}
+
+#[test]
+fn parallel_requires_at_least_one_connection() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "out.par",
+            "--connection-string",
+            MSSQL,
+            "--parallel",
+            "0",
+            "--split-key",
+            "a",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("invalid value '0' for '--parallel <PARALLEL>'"));
+}
+
+#[test]
+pub fn insert_with_null_string() {
+    let table_name = "InsertWithNullString";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["VARCHAR(50)"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL BYTE_ARRAY a (UTF8);
+        }
+    ";
+    let text: ByteArray = "\\N".into();
+    let input = TmpParquetFile::with_1_dim(
+        message_type,
+        &[Some(text), Some("".into()), Some("Hello".into())],
+    );
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--null-string",
+            "\\N",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result. The empty string is not affected.
+    let query = format!("SELECT a FROM {table_name} ORDER BY Id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("NULL\n\nHello", actual);
+}
+
+#[test]
+fn query_with_isolation_level() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    // `1` means read uncommitted
+    let query = "SELECT transaction_isolation_level AS a FROM sys.dm_exec_sessions \
+        WHERE session_id = @@SPID";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--isolation-level",
+            "read-uncommitted",
+            out_str,
+            query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 1}\n"));
+}
+
+#[test]
+fn bloom_filter() {
+    let query = "SELECT 1 AS a, 2 AS b";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--bloom-filter",
+            "a:0.01",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let row_group = reader.metadata().row_group(0);
+    assert!(row_group.column(0).bloom_filter_offset().is_some());
+    assert!(row_group.column(1).bloom_filter_offset().is_none());
+}
+
+#[test]
+fn bloom_filter_unknown_column() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--bloom-filter",
+            "c",
+            "-",
+            "SELECT 1 AS a, 2 AS b",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Can not write bloom filter for column 'c', since it is not part of the output.",
+        ));
+}
+
+#[test]
+pub fn insert_empty_string_as_null() {
+    let table_name = "InsertEmptyStringAsNull";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["VARCHAR(50)"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL BYTE_ARRAY a (UTF8);
+        }
+    ";
+    let text: ByteArray = "".into();
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(text), Some("Hello".into())]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--empty-string-as-null",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY Id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("NULL\nHello", actual);
+}
+
+#[test]
+fn query_to_ndjson() {
+    let query = "SELECT 1 AS a, 'x' AS b, CAST(1.5 AS DECIMAL(3,1)) AS c, \
+        CAST('2024-01-02' AS DATE) AS d, CAST(0x0102 AS VARBINARY(2)) AS e, \
+        CAST(NULL AS INTEGER) AS f";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--format",
+            "ndjson",
+            "-",
+            query,
+        ])
+        .assert()
+        .success()
+        .stdout(eq(
+            "{\"a\":1,\"b\":\"x\",\"c\":1.5,\"d\":\"2024-01-02\",\"e\":\"AQI=\",\"f\":null}\n",
+        ));
+}
+
+#[test]
+fn progress_bar_does_not_corrupt_stdout() {
+    let query = "SELECT 42 AS a";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--progress",
+            "bar",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success()
+        .stderr(contains("1 rows"));
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    assert_eq!(1, reader.metadata().file_metadata().num_rows());
+}
+
+#[test]
+fn connection_string_from_environment_variable() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .env("ODBC2PARQUET_CONNECTION_STRING", MSSQL)
+        .args(["-vv", "query", "-", "SELECT 42 AS a"])
+        .assert()
+        .success()
+        .stderr(contains("PWD=***"));
+}
+
+#[test]
+fn column_rename() {
+    let query = "SELECT 1 AS a, 2 AS [b c]";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-rename",
+            "a:first",
+            "--column-rename",
+            "b c:second value",
+            "--sanitize-column-names",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    let names: Vec<_> = file_metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_owned())
+        .collect();
+    assert_eq!(["first", "second_value"].as_slice(), names);
+    let original_names = file_metadata
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .find(|kv| kv.key == "odbc2parquet.original_column_names")
+        .unwrap();
+    assert_eq!(
+        r#"{"first":"a","second_value":"b c"}"#,
+        original_names.value.as_deref().unwrap()
+    );
+}
+
+#[test]
+fn column_rename_errors() {
+    // Source column is not part of the result set
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-rename",
+            "c:d",
+            "-",
+            "SELECT 1 AS a, 2 AS b",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Can not rename column 'c'"));
+
+    // Two columns mapped to the same name
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-rename",
+            "a:b",
+            "-",
+            "SELECT 1 AS a, 2 AS b",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Multiple columns would be written as 'b'"));
+}
+
+#[test]
+fn lowercase_columns() {
+    let query = "SELECT 1 AS Id, 2 AS [First Name]";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--column-rename",
+            "Id:ID",
+            "--lowercase-columns",
+            "--sanitize-column-names",
+            "-", // Use `-` to explicitly write to stdout
+            query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let names: Vec<_> = reader
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_owned())
+        .collect();
+    assert_eq!(["id", "first_name"].as_slice(), names);
+}
+
+#[test]
+fn uppercase_columns_collision() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--uppercase-columns",
+            "-",
+            "SELECT 1 AS ID, 2 AS id",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Columns 'ID' and 'id' would both be written as 'ID' after folding their case.",
+        ));
+}
+
+#[test]
+fn warn_about_truncated_values() {
+    // Setup table for test
+    let table_name = "WarnAboutTruncatedValues";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)"]);
+    table.insert_rows_as_text(&[["0123456789"], ["01"], ["012345"]]);
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    // Without `--error-on-truncation` the export succeeds
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--encoding",
+            "system",
+            "--connection-string",
+            MSSQL,
+            "--column-length-limit",
+            "5",
+            "-",
+            &query,
+        ])
+        .assert()
+        .success()
+        .stderr(contains("2 values of column 'a' have been truncated"));
+}
+
+#[test]
+fn access_token_is_rejected() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--auth-mode",
+            "access-token",
+            "--access-token",
+            "my-token",
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("SQL_COPT_SS_ACCESS_TOKEN"));
+}
+
+#[test]
+fn split_files_on_num_rows() {
+    // Setup table for test
+    let table_name = "SplitFilesOnNumRows";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+    let insert = format!("INSERT INTO {table_name} (A) VALUES(1),(2),(3),(4),(5)");
+    conn.execute(&insert, (), None).unwrap();
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    // Batches of three rows are split across files of two rows
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "3",
+            "--rows-per-file",
+            "2",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_dir.path().join("out_01.par").to_str().unwrap())
+        .stdout(eq("{a: 1}\n{a: 2}\n"));
+    parquet_read_out(out_dir.path().join("out_02.par").to_str().unwrap())
+        .stdout(eq("{a: 3}\n{a: 4}\n"));
+    parquet_read_out(out_dir.path().join("out_03.par").to_str().unwrap()).stdout(eq("{a: 5}\n"));
+    assert!(!out_dir.path().join("out_04.par").exists());
+}
+
+#[test]
+fn row_group_size_independent_of_batch_size() {
+    // Setup table for test
+    let table_name = "RowGroupSizeIndependentOfBatchSize";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+    let insert = format!("INSERT INTO {table_name} (A) VALUES(1),(2),(3),(4),(5)");
+    conn.execute(&insert, (), None).unwrap();
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    // Batches of two rows are accumulated into row groups of three rows
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "2",
+            "--row-group-size-rows",
+            "3",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 1}\n{a: 2}\n{a: 3}\n{a: 4}\n{a: 5}\n"));
+    let reader = SerializedFileReader::new(File::open(&out_path).unwrap()).unwrap();
+    let rows_per_row_group: Vec<_> = reader
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    // The last row group holds the remaining rows
+    assert_eq!(vec![3, 2], rows_per_row_group);
+}
+
+#[test]
+fn query_decimals_as_double() {
+    // Setup table for test
+    let table_name = "QueryDecimalsAsDouble";
+    let mut table = TableMssql::new(table_name, &["NUMERIC(3,2) NOT NULL", "DECIMAL(10,0)"]);
+    table.insert_rows_as_text(&[["1.25", "1234567890"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a,b FROM {table_name};");
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--decimal-as-double",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 1.25, b: 1234567890.0}\n"));
+    parquet_schema_out(out_str).stdout(contains(
+        "message schema {\n  \
+            REQUIRED DOUBLE a;\n  \
+            OPTIONAL DOUBLE b;\n\
+        }",
+    ));
+}
+
+#[test]
+fn page_size_limits() {
+    // Setup table for test
+    let table_name = "PageSizeLimits";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"]]);
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--data-page-size-bytes",
+            "4",
+            "--dictionary-page-size-bytes",
+            "4",
+            &query,
+        ])
+        .assert()
+        .success();
+    parquet_read_out(out_str).stdout(eq("{a: 1}\n{a: 2}\n"));
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--data-page-size-bytes",
+            "0",
+            &query,
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("`--data-page-size-bytes` must be larger than 0."));
+}
+
+#[test]
+fn no_dictionary_for_specified_column() {
+    // Setup table for test
+    let table_name = "NoDictionaryForSpecifiedColumn";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["aaa", "1"], ["aaa", "1"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--no-dictionary",
+            "a",
+            "-", // Use `-` to explicitly write to stdout
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let row_group = reader.metadata().row_group(0);
+    assert!(row_group.column(0).dictionary_page_offset().is_none());
+    assert!(row_group.column(1).dictionary_page_offset().is_some());
+
+    // Column names are validated against the output
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--no-dictionary",
+            "c",
+            "-",
+            &query,
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Can not disable dictionary encoding for column 'c'"));
+}
+
+#[test]
+fn verbose_sql_prints_parameters() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--verbose-sql",
+            "-",
+            "SELECT CAST(? AS INTEGER) AS a",
+            "42",
+        ])
+        .assert()
+        .success()
+        .stderr(contains(
+            "Query: SELECT CAST(? AS INTEGER) AS a\nParameter 1: '42'",
+        ));
+}
+
+#[test]
+fn timestamps_as_epoch() {
+    // Setup table for test
+    let table_name = "TimestampsAsEpoch";
+    let mut table = TableMssql::new(table_name, &["DATETIME2(3) NOT NULL", "DATE"]);
+    table.insert_rows_as_text(&[["2020-09-16 03:54:12.123", "1970-01-02"]]);
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a,b FROM {table_name};");
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--timestamps-as-epoch",
+            "millis",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 1600228452123, b: 86400000}\n"));
+    parquet_schema_out(out_str).stdout(contains(
+        "message schema {\n  \
+            REQUIRED INT64 a;\n  \
+            OPTIONAL INT64 b;\n\
+        }",
+    ));
+}
+
+#[test]
+fn byte_order_mark_in_each_split_ndjson_file() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.jsonl");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = "SELECT a FROM (VALUES (1),(2)) AS t(a) ORDER BY a";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--format",
+            "ndjson",
+            "--bom",
+            "--rows-per-file",
+            "1",
+            query,
+        ])
+        .assert()
+        .success();
+
+    let first = std::fs::read(out_dir.path().join("out_01.jsonl")).unwrap();
+    assert_eq!(b"\xEF\xBB\xBF{\"a\":1}\n".as_slice(), first);
+    let second = std::fs::read(out_dir.path().join("out_02.jsonl")).unwrap();
+    assert_eq!(b"\xEF\xBB\xBF{\"a\":2}\n".as_slice(), second);
+}
+
+#[test]
+fn byte_order_mark_is_rejected_for_parquet() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--bom",
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("`--bom` only applies to text output formats"));
+}
+
+#[test]
+pub fn insert_with_on_conflict_update() {
+    let table_name = "InsertWithOnConflictUpdate";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER NOT NULL UNIQUE", "INTEGER"]).unwrap();
+    conn.execute(
+        &format!("INSERT INTO {table_name} (a, b) VALUES (1, 10)"),
+        (),
+        None,
+    )
+    .unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            REQUIRED INT32 a;
+            OPTIONAL INT32 b;
+        }
+    ";
+    let input = TmpParquetFile::with_2_dim(
+        message_type,
+        &[Some(1i32), Some(2)],
+        &[Some(100i32), Some(200)],
+    );
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--on-conflict",
+            "update",
+            "--conflict-key",
+            "a",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY a");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("1,100\n2,200", actual);
+}
+
+#[test]
+fn pre_sql_statements_are_executed_on_the_same_connection() {
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    // Temporary tables are only visible to the connection which created them.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--pre-sql",
+            "CREATE TABLE #PreSql (a INTEGER)",
+            "--pre-sql",
+            "INSERT INTO #PreSql (a) VALUES (42)",
+            "SELECT a FROM #PreSql",
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 42}\n"));
+}
+
+#[test]
+fn pre_sql_statement_must_not_return_a_result_set() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--pre-sql",
+            "SELECT 1",
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Pre SQL statement 'SELECT 1' returned a result set."));
+}
+
+#[test]
+fn write_profile_of_exported_columns() {
+    // Setup table for test
+    let table_name = "WriteProfileOfExportedColumns";
+    let mut table = TableMssql::new(table_name, &["INTEGER", "VARCHAR(10)"]);
+    table.insert_rows_as_text(&[
+        [Some("3"), Some("x")],
+        [Some("-1"), Some("y")],
+        [None, Some("x")],
+    ]);
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let profile_path = out_dir.path().join("profile.json");
+
+    let query = format!("SELECT a,b FROM {table_name} ORDER BY id;");
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--profile-output",
+            profile_path.to_str().unwrap(),
+            &query,
+        ])
+        .assert()
+        .success();
+
+    let profile = std::fs::read_to_string(profile_path).unwrap();
+    assert_eq!(
+        "{\"num_rows\":3,\"columns\":[\
+            {\"name\":\"a\",\"null_count\":1,\"min\":-1,\"max\":3,\
+            \"distinct_values\":[\"-1\",\"3\"]},\
+            {\"name\":\"b\",\"null_count\":0,\"min\":null,\"max\":null,\
+            \"distinct_values\":[\"x\",\"y\"]}\
+        ]}\n",
+        profile
+    );
+}
+
+#[test]
+fn typed_parameters() {
+    // The result type reveals that the parameters are not bound as text.
+    let query = "SELECT SQL_VARIANT_PROPERTY(?, 'BaseType') AS a, \
+        SQL_VARIANT_PROPERTY(?, 'BaseType') AS b";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--param-type",
+            "1:i64",
+            "--param-type",
+            "2:date",
+            "--format",
+            "ndjson",
+            "-",
+            query,
+            "42",
+            "2024-01-02",
+        ])
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8(command.get_output().stdout.clone()).unwrap();
+    assert_eq!("{\"a\":\"bigint\",\"b\":\"date\"}\n", stdout);
+}
+
+/// Parameters specified with `--null-param` are bound as NULL, others with their values.
+#[test]
+fn null_parameters() {
+    let query = "SELECT CASE WHEN ? IS NULL THEN 'null' ELSE 'not null' END AS a, \
+        CASE WHEN ? IS NULL THEN 'null' ELSE 'not null' END AS b, ? AS c";
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--null-param",
+            "1",
+            "--null-param",
+            "3",
+            "--param-type",
+            "3:i64",
+            "--format",
+            "ndjson",
+            "-",
+            query,
+            "",
+            "42",
+            "ignored",
+        ])
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8(command.get_output().stdout.clone()).unwrap();
+    assert_eq!("{\"a\":\"null\",\"b\":\"not null\",\"c\":null}\n", stdout);
+}
+
+#[test]
+fn invalid_typed_parameter() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--param-type",
+            "1:i64",
+            "-",
+            "SELECT ? AS a",
+            "forty two",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Parameter 1 'forty two' is not a valid I64."));
+}
+
+#[test]
+pub fn insert_with_quoted_identifiers() {
+    let table_name = "InsertWithQuotedIdentifiers";
+    // Prepare table with a column named like a reserved word
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    conn.execute(&format!("DROP TABLE IF EXISTS {table_name}"), (), None)
+        .unwrap();
+    conn.execute(
+        &format!("CREATE TABLE {table_name} ([order] INTEGER)"),
+        (),
+        None,
+    )
+    .unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 order;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(42i32)]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--quote-identifiers",
+            "sqlserver",
+            "--truncate-table",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT [order] FROM {table_name}");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("42", actual);
+}
+
+#[test]
+fn split_by_date_into_daily_files() {
+    // Setup table for test
+    let table_name = "SplitByDateIntoDailyFiles";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["DATE", "INTEGER"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name} (A, B) VALUES \
+        ('2024-01-02', 1), ('2024-01-01', 2), ('2024-01-02', 3), (NULL, 4)"
+    );
+    conn.execute(&insert, (), None).unwrap();
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY id");
+
+    // Rows of the same day are spread over multiple batches.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "2",
+            "--split-by-date",
+            "a:day",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    let out_file = |name: &str| out_dir.path().join(name).to_str().unwrap().to_owned();
+    parquet_read_out(&out_file("out_2024-01-01.par")).stdout(eq("{a: 2024-01-01, b: 2}\n"));
+    parquet_read_out(&out_file("out_2024-01-02.par"))
+        .stdout(eq("{a: 2024-01-02, b: 1}\n{a: 2024-01-02, b: 3}\n"));
+    parquet_read_out(&out_file("out_null.par")).stdout(eq("{a: null, b: 4}\n"));
+    assert!(!out_path.exists());
+}
+
+#[test]
+fn fetch_ahead_with_multiple_buffers() {
+    // Setup table for test
+    let table_name = "FetchAheadWithMultipleBuffers";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"], ["3"], ["4"], ["5"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    // More buffers than batches, so the entire result set is fetched ahead.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "2",
+            "--fetch-buffer-count",
+            "4",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 1}\n{a: 2}\n{a: 3}\n{a: 4}\n{a: 5}\n"));
+}
+
+#[test]
+fn fetch_buffer_count_conflicts_with_sequential_fetching() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "out.par",
+            "--connection-string",
+            MSSQL,
+            "--sequential-fetching",
+            "--fetch-buffer-count",
+            "3",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("cannot be used with"));
+}
+
+#[test]
+fn split_by_date_conflicts_with_max_file_size() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "out.par",
+            "--connection-string",
+            MSSQL,
+            "--split-by-date",
+            "a:day",
+            "--max-file-size",
+            "1MiB",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("cannot be used with"));
+}
+
+#[test]
+fn query_bits_as_int() {
+    // Setup table for test
+    let table_name = "QueryBitsAsInt";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["BIT"]).unwrap();
+    let insert = format!("INSERT INTO {table_name} (a) VALUES (0), (1), (NULL);");
+    conn.execute(&insert, (), None).unwrap();
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id;");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--bit-as-int",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 0}\n{a: 1}\n{a: null}\n"));
+    parquet_schema_out(out_str).stdout(contains("OPTIONAL INT32 a (INTEGER(32,true));"));
+}
+
+#[test]
+fn max_total_memory_exceeded() {
+    // Setup table for test
+    let table_name = "MaxTotalMemoryExceeded";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(1000)"]);
+    table.insert_rows_as_text(&[["a"]]);
+    let query = format!("SELECT a FROM {table_name}");
+
+    // Two buffers of 100 rows with 1000 bytes each exceed the limit.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "out.par",
+            "--connection-string",
+            MSSQL,
+            "--batch-size-row",
+            "100",
+            "--max-total-memory",
+            "100KiB",
+            &query,
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("exceeds `--max-total-memory`"));
+}
+
+#[test]
+fn query_to_csv() {
+    let query = "SELECT 1 AS a, 'x;y' AS b, CAST(1.5 AS DECIMAL(3,1)) AS c, \
+        CAST('2024-01-02' AS DATE) AS d, 'say \"hi\"' AS e, CAST(NULL AS INTEGER) AS f";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--format",
+            "csv",
+            "--csv-delimiter",
+            ";",
+            "-",
+            query,
+        ])
+        .assert()
+        .success()
+        .stdout(eq("a;b;c;d;e;f\n1;\"x;y\";1.5;2024-01-02;\"say \"\"hi\"\"\";\n"));
+}
+
+#[test]
+fn csv_header_in_each_split_file() {
+    // Setup table for test
+    let table_name = "CsvHeaderInEachSplitFile";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"], ["3"]]);
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.csv");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--format",
+            "csv",
+            "--rows-per-file",
+            "2",
+            out_str,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    let read = |name: &str| std::fs::read_to_string(out_dir.path().join(name)).unwrap();
+    assert_eq!("a\n1\n2\n", read("out_01.csv"));
+    assert_eq!("a\n3\n", read("out_02.csv"));
+}
+
+#[test]
+fn csv_header_uses_renamed_columns() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--format",
+            "csv",
+            "--column-rename",
+            "a:first",
+            "--uppercase-columns",
+            "--sanitize-column-names",
+            "--require-non-null",
+            "FIRST",
+            "-",
+            "SELECT 1 as a, 2 as \"b c\"",
+        ])
+        .assert()
+        .success()
+        .stdout("FIRST,B_C\n1,2\n");
+}
+
+#[test]
+fn csv_options_require_csv_format() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--csv-no-header",
+            "-",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("only apply to `--format csv`"));
+}
+
+#[test]
+fn text_formats_reject_retries() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--format",
+            "ndjson",
+            "--max-retries",
+            "3",
+            "-",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("always fetch sequentially and without retries"));
+}
+
+#[test]
+pub fn insert_with_column_default() {
+    let table_name = "InsertWithColumnDefault";
+    // Prepare table with a column missing in the file
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER", "VARCHAR(10) NOT NULL"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(1i32), Some(2)]);
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--column-default",
+            "b:import",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a, b FROM {table_name} ORDER BY id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("1,import\n2,import", actual);
+}
+
+#[test]
+pub fn column_default_for_column_present_in_file() {
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(1i32)]);
+    let input_path = input.path_as_str();
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--column-default",
+            "a:42",
+            input_path,
+            "ColumnDefaultForColumnPresentInFile",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Column 'a' has a `--column-default`"));
+}
+
+#[test]
+fn server_side_limit_caps_rows() {
+    // Setup table for test
+    let table_name = "ServerSideLimitCapsRows";
+    let mut table = TableMssql::new(table_name, &["INTEGER"]);
+    table.insert_rows_as_text(&[["1"], ["2"], ["3"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name} ORDER BY id;");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--server-side-limit",
+            "2",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(eq("{a: 1}\n{a: 2}\n"));
+}
+
+#[test]
+pub fn insert_multiple_files_matching_pattern() {
+    let table_name = "InsertMultipleFilesMatchingPattern";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+
+    // Prepare files. The second file is named so it sorts first.
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input_dir = tempdir().unwrap();
+    let part_1 = ColumnDataImpl::new(&[Some(3i32)]);
+    write_values_to_file(message_type, &input_dir.path().join("part-1.parquet"), &[&part_1]);
+    let part_0 = ColumnDataImpl::new(&[Some(1i32), Some(2)]);
+    write_values_to_file(message_type, &input_dir.path().join("part-0.parquet"), &[&part_0]);
+    std::fs::write(input_dir.path().join("_SUCCESS"), "").unwrap();
+    let pattern = input_dir.path().join("part-*.parquet");
+
+    // Insert files into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vv",
+            "insert",
+            "--connection-string",
+            MSSQL,
+            pattern.to_str().unwrap(),
+            table_name,
+        ])
+        .assert()
+        .success()
+        .stderr(contains("Inserted 2 rows from"));
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("1\n2\n3", actual);
+}
+
+#[test]
+pub fn insert_directory_with_mismatching_schemas() {
+    let input_dir = tempdir().unwrap();
+    let ints = ColumnDataImpl::new(&[Some(1i32)]);
+    write_values_to_file(
+        "message schema { OPTIONAL INT32 a; }",
+        &input_dir.path().join("a.parquet"),
+        &[&ints],
+    );
+    let doubles = ColumnDataImpl::new(&[Some(1f64)]);
+    write_values_to_file(
+        "message schema { OPTIONAL DOUBLE a; }",
+        &input_dir.path().join("b.parquet"),
+        &[&doubles],
+    );
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            input_dir.path().to_str().unwrap(),
+            "InsertDirectoryWithMismatchingSchemas",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("does not match the schema of the first input file"));
+}
+
+#[test]
+fn deterministic_output_is_byte_identical() {
+    let query = "SELECT 42 AS a";
+    let export = || {
+        let command = Command::cargo_bin("odbc2parquet")
+            .unwrap()
+            .args([
+                "query",
+                "--connection-string",
+                MSSQL,
+                "--embed-query-metadata",
+                "--deterministic-output",
+                "--created-by",
+                "my pipeline",
+                "-", // Use `-` to explicitly write to stdout
+                query,
+            ])
+            .assert()
+            .success();
+        command.get_output().stdout.clone()
+    };
+
+    let first = export();
+    let second = export();
+
+    // Then
+    assert_eq!(first, second);
+    let reader = SerializedFileReader::new(Bytes::from(first)).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    assert_eq!(Some("my pipeline"), file_metadata.created_by());
+    let key_value_metadata = file_metadata.key_value_metadata().unwrap();
+    assert!(!key_value_metadata
+        .iter()
+        .any(|kv| kv.key == "odbc2parquet.exported_at"));
+}
+
+#[test]
+fn query_interval_postgres() {
+    // Setup table for test
+    let table_name = "QueryIntervalPostgres";
+    let conn = ENV
+        .connect_with_connection_string(POSTGRES, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_pg(
+        &conn,
+        table_name,
+        &["INTERVAL DAY TO SECOND", "INTERVAL YEAR TO MONTH"],
+    )
+    .unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name}
+        (a, b)
+        VALUES
+        ('3 days 04:05:06.789', '1 year 2 months');"
+    );
+    conn.execute(&insert, (), None).unwrap();
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a, b FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_schema_out(out_str)
+        .stdout(contains("FIXED_LEN_BYTE_ARRAY (12) a (INTERVAL);"))
+        .stdout(contains("FIXED_LEN_BYTE_ARRAY (12) b (INTERVAL);"));
+
+    // With `--interval-as-text` the text of the driver is stored as it is.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            "--interval-as-text",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(contains("1 year 2 mons"));
+}
+
+#[test]
+pub fn insert_with_skip_columns() {
+    let table_name = "InsertWithSkipColumns";
+    // Prepare table without the bookkeeping column of the file
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+            OPTIONAL INT32 _ingested_at;
+        }
+    ";
+    let input = TmpParquetFile::with_2_dim(
+        message_type,
+        &[Some(1i32), Some(2)],
+        &[Some(42i32), Some(43)],
+    );
+    let input_path = input.path_as_str();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--skip-columns",
+            "_ingested_at",
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("1\n2", actual);
+}
+
+#[test]
+pub fn insert_continue_on_error() {
+    let table_name = "InsertContinueOnError";
+    // Prepare table which does not accept NULL
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["INTEGER NOT NULL"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(1i32), None, Some(3)]);
+    let input_path = input.path_as_str();
+    let rejects_dir = tempdir().unwrap();
+    let rejects_path = rejects_dir.path().join("rejects.par");
+    let rejects = rejects_path.to_str().unwrap();
+
+    // Insert file into table
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--continue-on-error",
+            "--rejects-output",
+            rejects,
+            input_path,
+            table_name,
+        ])
+        .assert()
+        .success()
+        .stderr(contains("Skipped 1 rows"));
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+    assert_eq!("1\n3", actual);
+
+    parquet_read_out(rejects).stdout(eq("{a: null}\n"));
+}
+
+#[test]
+pub fn skip_unknown_column() {
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(1i32)]);
+    let input_path = input.path_as_str();
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--skip-columns",
+            "b",
+            input_path,
+            "SkipUnknownColumn",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Column 'b' specified with `--skip-columns` is not part of the parquet file.",
+        ));
+}
+
+#[test]
+pub fn skip_all_columns() {
+    let message_type = "
+        message schema {
+            OPTIONAL INT32 a;
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(1i32)]);
+    let input_path = input.path_as_str();
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--skip-columns",
+            "a",
+            input_path,
+            "SkipAllColumns",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("At least one column must be inserted."));
+}
+
+#[test]
+fn integrated_security_conflicts_with_user() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--integrated-security",
+            "--user",
+            "SA",
+            "out.par",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("cannot be used with"));
+}
+
+#[test]
+fn prompt_password_requires_terminal() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--user",
+            "SA",
+            "--prompt-password",
+            "out.par",
+            "SELECT 42",
+        ])
+        .env_remove("ODBC_PASSWORD")
+        .write_stdin("secret\n")
+        .assert()
+        .failure()
+        .stderr(contains(
+            "`--prompt-password` requires standard input to be a terminal.",
+        ));
+}
+
+#[test]
+fn atomic_output_renames_split_files() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = "SELECT a FROM (VALUES (1),(2),(3)) AS t(a) ORDER BY a";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--atomic-output",
+            "--batch-size-row",
+            "2",
+            "--rows-per-file",
+            "2",
+            query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_dir.path().join("out_01.par").to_str().unwrap())
+        .stdout(eq("{a: 1}\n{a: 2}\n"));
+    parquet_read_out(out_dir.path().join("out_02.par").to_str().unwrap()).stdout(eq("{a: 3}\n"));
+    assert!(out_dir.path().join("_SUCCESS").exists());
+    let leftovers: Vec<_> = std::fs::read_dir(out_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "Temporary files left: {leftovers:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn file_mode_applies_to_split_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = "SELECT a FROM (VALUES (1),(2),(3)) AS t(a) ORDER BY a";
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            MSSQL,
+            "--atomic-output",
+            "--file-mode",
+            "0600",
+            "--batch-size-row",
+            "2",
+            "--rows-per-file",
+            "2",
+            query,
+        ])
+        .assert()
+        .success();
+
+    for file_name in ["out_01.par", "out_02.par"] {
+        let metadata = std::fs::metadata(out_dir.path().join(file_name)).unwrap();
+        assert_eq!(0o600, metadata.permissions().mode() & 0o777);
+    }
+}
+
+#[test]
+fn encoding_for_type_applies_to_all_columns_of_type() {
+    // Setup table for test
+    let table_name = "EncodingForType";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)", "VARCHAR(10)", "INTEGER"]);
+    table.insert_rows_as_text(&[["a", "b", "1"]]);
+    let query = format!("SELECT a, b, c FROM {table_name}");
+
+    let command = Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--no-dictionary-all",
+            "--encoding-for-type",
+            "varchar:delta-byte-array",
+            // Individual columns take precedence
+            "--parquet-column-encoding",
+            "b:plain",
+            "-", // Use `-` to explicitly write to stdout
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let bytes = Bytes::from(command.get_output().stdout.clone());
+    let reader = SerializedFileReader::new(bytes).unwrap();
+    let row_group = reader.metadata().row_group(0);
+    let uses_delta = |index: usize| {
+        row_group
+            .column(index)
+            .encodings()
+            .contains(&parquet::basic::Encoding::DELTA_BYTE_ARRAY)
+    };
+    assert!(uses_delta(0));
+    assert!(!uses_delta(1));
+    assert!(!uses_delta(2));
+}
+
+#[test]
+fn read_options_from_config_file() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let config_path = out_dir.path().join("odbc2parquet.toml");
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    std::fs::write(
+        &config_path,
+        format!(
+            "connection-string = \"{MSSQL}\"\n\
+            \n\
+            [query]\n\
+            # Overridden by the command line\n\
+            batch_size_row = \"invalid\"\n"
+        ),
+    )
+    .unwrap();
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "--config",
+            config_path.to_str().unwrap(),
+            "query",
+            "--batch-size-row",
+            "10",
+            out_str,
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success();
+
+    let expected_values = "{a: 42}\n";
+    parquet_read_out(out_str).stdout(eq(expected_values));
+}
+
+#[test]
+fn unknown_key_in_config_file() {
+    let out_dir = tempdir().unwrap();
+    let config_path = out_dir.path().join("odbc2parquet.toml");
+    std::fs::write(&config_path, "[query]\nno_such_option = 1\n").unwrap();
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "--config",
+            config_path.to_str().unwrap(),
+            "query",
+            "--connection-string",
+            MSSQL,
+            "out.par",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Unknown key 'no_such_option'"));
+}
+
+#[test]
+fn trim_trailing_spaces_of_char_columns() {
+    // Setup table for test
+    let table_name = "TrimTrailingSpacesOfCharColumns";
+    let mut table = TableMssql::new(table_name, &["CHAR(5)", "NCHAR(5)"]);
+    table.insert_rows_as_text(&[[" ab", "cd"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+
+    for encoding in ["system", "utf16"] {
+        // A temporary directory, to be removed at the end of the test.
+        let out_dir = tempdir().unwrap();
+        let out_path = out_dir.path().join("out.par");
+        let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+        Command::cargo_bin("odbc2parquet")
+            .unwrap()
+            .args([
+                "query",
+                "--connection-string",
+                MSSQL,
+                "--encoding",
+                encoding,
+                "--trim",
+                "trailing",
+                out_str,
+                &query,
+            ])
+            .assert()
+            .success();
+
+        let expected_values = "{a: \" ab\", b: \"cd\"}\n";
+        parquet_read_out(out_str).stdout(eq(expected_values));
+    }
+}
+
+#[test]
+fn query_arrays_postgres() {
+    // Setup table for test
+    let table_name = "QueryArraysPostgres";
+    let conn = ENV
+        .connect_with_connection_string(POSTGRES, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_pg(&conn, table_name, &["INTEGER", "INTEGER[]", "TEXT[]"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name}
+        (a, b, c)
+        VALUES
+        (1, '{{1,NULL,3}}', '{{\"a b\",NULL}}'),
+        (2, '{{}}', '{{}}'),
+        (3, NULL, NULL);"
+    );
+    conn.execute(&insert, (), None).unwrap();
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT b, c FROM {table_name} ORDER BY a;");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_schema_out(out_str)
+        .stdout(contains("OPTIONAL group b (LIST)"))
+        .stdout(contains("OPTIONAL INT32 element;"));
+    let expected_values = "\
+        {b: [1, null, 3], c: [\"a b\", null]}\n\
+        {b: [], c: []}\n\
+        {b: null, c: null}\n";
+    parquet_read_out(out_str).stdout(eq(expected_values));
+
+    // With `--array-as-text` the text of the driver is stored as it is.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            "--array-as-text",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(contains("{1,NULL,3}"));
+}
+
+#[test]
+fn query_json_postgres() {
+    // Setup table for test
+    let table_name = "QueryJsonPostgres";
+    let conn = ENV
+        .connect_with_connection_string(POSTGRES, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_pg(&conn, table_name, &["JSON", "JSONB"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name}
+        (a, b)
+        VALUES
+        ('{{\"x\": 1}}', '[1, 2]');"
+    );
+    conn.execute(&insert, (), None).unwrap();
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+    let query = format!("SELECT a, b FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            &query,
+        ])
+        .assert()
+        .success();
+
+    // The logical type is part of the schema in the footer
+    let reader = SerializedFileReader::new(File::open(&out_path).unwrap()).unwrap();
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+    assert_eq!(Some(LogicalType::Json), schema.column(0).logical_type());
+    assert_eq!(Some(LogicalType::Json), schema.column(1).logical_type());
+    parquet_read_out(out_str).stdout(contains("[1, 2]"));
+
+    // With `--json-as-text` the documents are plain strings.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            out_str,
+            "--connection-string",
+            POSTGRES,
+            "--json-as-text",
+            &query,
+        ])
+        .assert()
+        .success();
+
+    let reader = SerializedFileReader::new(File::open(&out_path).unwrap()).unwrap();
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+    assert_ne!(Some(LogicalType::Json), schema.column(0).logical_type());
+}
+
+#[test]
+fn tls_ca_cert_must_exist() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            POSTGRES,
+            "--tls-ca-cert",
+            "no_such_ca.pem",
+            "out.par",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "The CA certificate 'no_such_ca.pem' specified with `--tls-ca-cert` does not exist.",
+        ));
+}
+
+#[test]
+fn append_to_dataset_continues_numbering() {
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let export = |query: &str| {
+        Command::cargo_bin("odbc2parquet")
+            .unwrap()
+            .args([
+                "query",
+                "--connection-string",
+                MSSQL,
+                "--batch-size-row",
+                "1",
+                "--row-groups-per-file",
+                "1",
+                "--append-to-dataset",
+                out_str,
+                query,
+            ])
+            .assert()
+    };
+
+    export("SELECT a FROM (VALUES (1),(2)) AS t(a) ORDER BY a").success();
+    export("SELECT a FROM (VALUES (3),(4)) AS t(a) ORDER BY a").success();
+
+    let file = |num: u32| out_dir.path().join(format!("out_{num:02}.par"));
+    parquet_read_out(file(1).to_str().unwrap()).stdout(eq("{a: 1}\n"));
+    parquet_read_out(file(4).to_str().unwrap()).stdout(eq("{a: 4}\n"));
+
+    // Different schema
+    export("SELECT 'text' AS a")
+        .failure()
+        .stderr(contains("does not match the schema of the query"));
+    assert!(!file(5).exists());
+}
+
+#[test]
+fn size_text_from_data_avoids_truncation() {
+    // Setup table for test
+    let table_name = "SizeTextFromDataAvoidsTruncation";
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["VARCHAR(MAX)"]).unwrap();
+    let insert = format!(
+        "INSERT INTO {table_name} (a) VALUES (REPLICATE(CAST('x' AS VARCHAR(MAX)), 5000));"
+    );
+    conn.execute(&insert, (), None).unwrap();
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    // The default limit of 4096 would truncate the value.
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--size-text-from-data",
+            "--error-on-truncation",
+            out_str,
+            &format!("SELECT a FROM {table_name} ORDER BY a"),
+        ])
+        .assert()
+        .success();
+
+    parquet_read_out(out_str).stdout(contains("x".repeat(5000)));
+}
+
+/// Only drivers matching the filter are listed.
+#[test]
+fn list_drivers_matching_filter() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(["list-drivers", "SQL SERVER"])
+        .assert()
+        .success()
+        .stdout(contains("ODBC Driver 18 for SQL Server"))
+        .stdout(contains("PostgreSQL").not());
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args(["list-drivers", "--json", "no such driver"])
+        .assert()
+        .success()
+        .stdout(eq("[]\n"));
+}
+
+/// `--odbc-trace` logs the ODBC calls to the specified file.
+#[test]
+fn odbc_trace_to_file() {
+    // Setup
+    let dir = tempdir().unwrap();
+    let trace_path = dir.path().join("odbc.log");
+    let trace_str = trace_path.to_str().unwrap();
+
+    // When
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "--odbc-trace",
+            trace_str,
+            "query",
+            "--connection-string",
+            MSSQL,
+            "-",
+            "SELECT 42 AS a",
+        ])
+        .assert()
+        .success();
+
+    // Then
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    assert!(trace.contains("SQLExecDirect"));
+}
+
+/// `--unknown-type` controls what happens to columns of types, which are not mapped to a specific
+/// parquet type. `XML` is reported as the driver specific type `SQL_SS_XML`.
+#[test]
+fn skip_or_reject_columns_of_unknown_type() {
+    // Setup table for test
+    let table_name = "SkipOrRejectColumnsOfUnknownType";
+    let mut table = TableMssql::new(table_name, &["INTEGER NOT NULL", "XML"]);
+    table.insert_rows_as_text(&[["42", "<a/>"]]);
+    let query = format!("SELECT a, b FROM {table_name}");
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().unwrap();
+
+    // Skipping the column still exports the others
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--unknown-type",
+            "skip",
+            out_str,
+            &query,
+        ])
+        .assert()
+        .success()
+        .stderr(contains("Column 'b' has the unsupported type"));
+    parquet_schema_out(out_str).stdout(contains("message schema {\n  REQUIRED INT32 a"));
+    parquet_schema_out(out_str).stdout(contains(" b").not());
+
+    // Rejecting the column aborts the export
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--connection-string",
+            MSSQL,
+            "--unknown-type",
+            "error",
+            out_str,
+            &query,
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("Use `--unknown-type text`"));
+}
+
+/// Decimals with more integer digits than the target column are clamped with
+/// `--numeric-overflow clamp`, and rejected by default.
+#[test]
+pub fn insert_decimal_overflow() {
+    let table_name = "InsertDecimalOverflow";
+    // Prepare table
+    let conn = ENV
+        .connect_with_connection_string(MSSQL, ConnectionOptions::default())
+        .unwrap();
+    setup_empty_table_mssql(&conn, table_name, &["DECIMAL(5,2)"]).unwrap();
+
+    // Prepare file
+    let message_type = "
+        message schema {
+            REQUIRED INT32 a (DECIMAL(9,2));
+        }
+    ";
+    let input = TmpParquetFile::with_1_dim(message_type, &[Some(12345i32), Some(123456789), Some(-1000000)]);
+    let input_path = input.path.clone();
+
+    // Rejected by default
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            input_path.to_str().unwrap(),
+            table_name,
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "Value 1234567.89 of column 'a' does not fit into the target column of type \
+            DECIMAL(5,2).",
+        ));
+
+    // Clamped to the largest and smallest value of the column
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "insert",
+            "--connection-string",
+            MSSQL,
+            "--numeric-overflow",
+            "clamp",
+            input_path.to_str().unwrap(),
+            table_name,
+        ])
+        .assert()
+        .success();
+
+    // Query table and check for expected result
+    let query = format!("SELECT a FROM {table_name} ORDER BY Id");
+    let cursor = conn.execute(&query, (), None).unwrap().unwrap();
+    let actual = cursor_to_string(cursor);
+
+    assert_eq!("123.45\n999.99\n-999.99", actual);
+}
+
+/// Valid text is fetched like with `--encoding system`, and invalid text would be replaced without
+/// a warning.
+#[test]
+#[cfg(not(target_os = "windows"))] // Windows does not use UTF-8 as default system encoding
+fn utf8_lossy_encoding() {
+    let table_name = "Utf8LossyEncoding";
+    let mut table = TableMssql::new(table_name, &["VARCHAR(10)"]);
+    table.insert_rows_as_text(&[["Ü"]]);
+
+    // A temporary directory, to be removed at the end of the test.
+    let out_dir = tempdir().unwrap();
+    let out_path = out_dir.path().join("out.par");
+    let out_str = out_path.to_str().expect("Temporary file path must be utf8");
+
+    let query = format!("SELECT a FROM {table_name};");
+
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "-vvvv",
+            "query",
+            "--encoding",
+            "Utf8Lossy",
+            "--connection-string",
+            MSSQL,
+            out_str,
+            &query,
+        ])
+        .assert()
+        .success()
+        .stderr(contains("not valid UTF-8").not());
+
+    parquet_read_out(out_str).stdout(eq("{a: \"Ü\"}\n"));
+}
+
+#[test]
+fn utf8_lossy_encoding_conflicts_with_error_on_encoding_loss() {
+    Command::cargo_bin("odbc2parquet")
+        .unwrap()
+        .args([
+            "query",
+            "--encoding",
+            "utf8-lossy",
+            "--error-on-encoding-loss",
+            "--connection-string",
+            MSSQL,
+            "out.par",
+            "SELECT 42",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains(
+            "can not be combined with `--error-on-encoding-loss`",
+        ));
+}